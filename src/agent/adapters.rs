@@ -1,15 +1,31 @@
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 
 use crate::client::{DefaultLlmClientFactory, LlmClientFactory};
-use crate::exec;
-use crate::planner::{self, Plan};
-use crate::validator::{self, ValidationResult};
+use crate::config::ExecutionSettings;
+use crate::exec::{
+    self, Executor, PendingStep, PreviewReport, StepChoice, StepEvent, StepGate, StepPhase,
+    StepStatus,
+};
+use crate::history::PlanHistory;
+use crate::planner::{self, Plan, PlanExample, PlannerGuidance, PlannerRules};
+use crate::recovery::{
+    self, InstallationInstruction, RecoveryContext, RecoveryEngine, RecoveryResult,
+    RecoveryStrategy,
+};
+use crate::reviewer::{self, PlanReview};
+use crate::undo::{self, UndoStore};
+use crate::validator::{self, MissingCommand, ValidationResult};
 
+use super::approval::ApprovalDecision;
 use super::context::AgentContext;
+use super::goal::{self, GoalAssessment};
 use super::outcome::{ExecutionReport, RecoveryOutcome};
+use super::recovery::{InteractiveChooser, RecoveryChooser};
 
 #[async_trait]
 pub trait PlanningAdapter {
@@ -21,6 +37,16 @@ pub trait ValidationAdapter {
     async fn validate(&self, context: &mut AgentContext, plan: &Plan) -> Result<ValidationResult>;
 }
 
+#[async_trait]
+pub trait ReviewAdapter {
+    async fn review(&self, context: &mut AgentContext, plan: &Plan) -> Result<PlanReview>;
+}
+
+#[async_trait]
+pub trait PreviewAdapter {
+    async fn preview(&self, context: &mut AgentContext, plan: &Plan) -> Result<PreviewReport>;
+}
+
 #[async_trait]
 pub trait ExecutionAdapter {
     async fn execute(&self, context: &mut AgentContext, plan: &Plan) -> Result<ExecutionReport>;
@@ -31,6 +57,16 @@ pub trait RecoveryAdapter {
     async fn recover(&self, context: &mut AgentContext) -> Result<RecoveryOutcome>;
 }
 
+#[async_trait]
+pub trait GoalAdapter {
+    async fn assess(
+        &self,
+        context: &mut AgentContext,
+        plan: &Plan,
+        execution: &ExecutionReport,
+    ) -> Result<GoalAssessment>;
+}
+
 /// Adapter that invokes the existing planner module.
 pub struct DirectPlanningAdapter {
     factory: Arc<dyn LlmClientFactory>,
@@ -44,7 +80,7 @@ impl DirectPlanningAdapter {
 
 impl Default for DirectPlanningAdapter {
     fn default() -> Self {
-        Self::new(Arc::new(DefaultLlmClientFactory::default()))
+        Self::new(Arc::new(DefaultLlmClientFactory))
     }
 }
 
@@ -52,13 +88,64 @@ impl Default for DirectPlanningAdapter {
 impl PlanningAdapter for DirectPlanningAdapter {
     async fn plan(&self, context: &mut AgentContext) -> Result<Plan> {
         let client = context.llm_client(self.factory.as_ref())?;
-        planner::plan(
-            client.as_ref(),
-            &context.request.task,
-            &context.config.models.planner,
-            context.config.models.max_tokens,
-        )
-        .await
+        let history = &context.config.history;
+        let examples = if history.enabled {
+            similar_plans(
+                PlanHistory::open_default(),
+                &context.request.task,
+                history.max_examples,
+            )
+        } else {
+            Vec::new()
+        };
+        let rules = PlannerRules::load().unwrap_or_else(|err| {
+            eprintln!("⚠️  Could not read planning rules: {err}");
+            PlannerRules::default()
+        });
+        let guidance = PlannerGuidance {
+            rules,
+            examples,
+            shell: context.config.execution.shell,
+            persistent_shell: context.config.execution.persistent_shell,
+        };
+
+        let request = context.planning_request();
+        let models = &context.config.models;
+        if context.request.interactive {
+            planner::plan(
+                client.as_ref(),
+                &request,
+                &models.planner,
+                models.max_tokens,
+                &guidance,
+            )
+            .await
+        } else {
+            planner::plan_unattended(
+                client.as_ref(),
+                &request,
+                &models.planner,
+                models.max_tokens,
+                &guidance,
+            )
+            .await
+        }
+    }
+}
+
+/// Past plans similar to `task`, or none with a warning when the history
+/// cannot be read; a broken history should not stop planning.
+pub(super) fn similar_plans(
+    history: Result<PlanHistory>,
+    task: &str,
+    limit: usize,
+) -> Vec<PlanExample> {
+    match history.and_then(|history| history.similar(task, limit)) {
+        Ok(examples) => examples,
+        Err(err) => {
+            eprintln!("⚠️  Could not read plan history: {err}");
+            Vec::new()
+        }
     }
 }
 
@@ -67,13 +154,115 @@ pub struct CommandValidationAdapter;
 
 #[async_trait]
 impl ValidationAdapter for CommandValidationAdapter {
-    async fn validate(&self, _context: &mut AgentContext, plan: &Plan) -> Result<ValidationResult> {
-        let mut validator = validator::CommandValidator::new();
+    async fn validate(&self, context: &mut AgentContext, plan: &Plan) -> Result<ValidationResult> {
+        let mut validator =
+            validator::CommandValidator::new().with_shell(context.config.execution.shell);
         validator.validate_plan(plan).await
     }
 }
 
+/// Adapter that asks the configured reviewer model to assess the plan.
+pub struct ModelReviewAdapter {
+    factory: Arc<dyn LlmClientFactory>,
+    model: Option<String>,
+}
+
+impl ModelReviewAdapter {
+    pub fn new(factory: Arc<dyn LlmClientFactory>) -> Self {
+        Self {
+            factory,
+            model: None,
+        }
+    }
+
+    /// Review with `model` instead of the configured reviewer model.
+    pub fn with_model(mut self, model: Option<String>) -> Self {
+        self.model = model;
+        self
+    }
+}
+
+impl Default for ModelReviewAdapter {
+    fn default() -> Self {
+        Self::new(Arc::new(DefaultLlmClientFactory))
+    }
+}
+
+#[async_trait]
+impl ReviewAdapter for ModelReviewAdapter {
+    async fn review(&self, context: &mut AgentContext, plan: &Plan) -> Result<PlanReview> {
+        let client = context.llm_client(self.factory.as_ref())?;
+        let config = &context.config;
+
+        reviewer::review(
+            client.as_ref(),
+            &context.request.task,
+            plan,
+            self.model
+                .as_deref()
+                .unwrap_or_else(|| config.review.model_or(&config.models.planner)),
+            config.models.max_tokens,
+            config.execution.shell,
+        )
+        .await
+    }
+}
+
+/// Adapter that asks the planner model whether the goal has been reached.
+pub struct ModelGoalAdapter {
+    factory: Arc<dyn LlmClientFactory>,
+}
+
+impl ModelGoalAdapter {
+    pub fn new(factory: Arc<dyn LlmClientFactory>) -> Self {
+        Self { factory }
+    }
+}
+
+impl Default for ModelGoalAdapter {
+    fn default() -> Self {
+        Self::new(Arc::new(DefaultLlmClientFactory))
+    }
+}
+
+#[async_trait]
+impl GoalAdapter for ModelGoalAdapter {
+    async fn assess(
+        &self,
+        context: &mut AgentContext,
+        plan: &Plan,
+        execution: &ExecutionReport,
+    ) -> Result<GoalAssessment> {
+        let client = context.llm_client(self.factory.as_ref())?;
+        let models = &context.config.models;
+
+        goal::assess(
+            client.as_ref(),
+            &context.request.task,
+            &context.iterations,
+            plan,
+            execution,
+            &models.planner,
+            models.max_tokens,
+        )
+        .await
+    }
+}
+
+/// Adapter that runs the execute commands in a sandbox over the working directory.
+pub struct SandboxPreviewAdapter;
+
+#[async_trait]
+impl PreviewAdapter for SandboxPreviewAdapter {
+    async fn preview(&self, context: &mut AgentContext, plan: &Plan) -> Result<PreviewReport> {
+        let dir = std::env::current_dir()?;
+        let executor = plan_executor(&context.config.execution);
+        exec::preview(executor, &dir, plan, &context.cancel).await
+    }
+}
+
 /// Placeholder execution adapter that captures the intent without running commands.
+#[allow(dead_code)]
 pub struct NoopExecutionAdapter;
 
 #[async_trait]
@@ -85,6 +274,8 @@ impl ExecutionAdapter for NoopExecutionAdapter {
             stdout: None,
             stderr: None,
             notes: vec!["Execution adapter not configured".to_string()],
+            steps: Vec::new(),
+            undo: None,
         })
     }
 }
@@ -92,17 +283,177 @@ impl ExecutionAdapter for NoopExecutionAdapter {
 /// Execution adapter that delegates to the shared plan executor when permitted.
 pub struct PlanExecutionAdapter {
     assume_yes: bool,
+    echo: bool,
+    terminal: bool,
+    undo: Option<UndoStore>,
+    gate: Option<Arc<dyn StepGate>>,
 }
 
 impl PlanExecutionAdapter {
     pub fn new() -> Self {
-        Self { assume_yes: false }
+        Self {
+            assume_yes: false,
+            echo: true,
+            terminal: false,
+            undo: None,
+            gate: None,
+        }
     }
 
     pub fn with_assume_yes(mut self, assume_yes: bool) -> Self {
         self.assume_yes = assume_yes;
         self
     }
+
+    /// Disable terminal output; command output is captured into the report instead.
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// li runs in an interactive terminal, so commands can read from it, or be
+    /// given a terminal of their own (`execution.pty` or `--pty`).
+    pub fn with_terminal(mut self, terminal: bool) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    /// Snapshot the paths the plan is about to change into `store` before running it.
+    pub fn with_undo(mut self, store: UndoStore) -> Self {
+        self.undo = Some(store);
+        self
+    }
+
+    /// Ask `gate` before each command when the run is step by step (`--step`, or
+    /// `s` at the approval prompt).
+    pub fn with_step_gate(mut self, gate: impl StepGate + 'static) -> Self {
+        self.gate = Some(Arc::new(gate));
+        self
+    }
+
+    /// Take the undo snapshot; a failure is reported but does not stop the plan.
+    fn snapshot(&self, context: &mut AgentContext, commands: &[String]) -> Option<UndoRun> {
+        let store = self.undo.as_ref()?;
+        if commands.is_empty() {
+            return None;
+        }
+
+        // Resumed runs and later goal-loop batches add to the same snapshot.
+        let id = context
+            .session_id()
+            .map_or_else(undo::new_run_id, str::to_string);
+        let captured = std::env::current_dir()
+            .map_err(anyhow::Error::from)
+            .and_then(|cwd| {
+                let mut run = UndoRun {
+                    store: store
+                        .clone()
+                        .with_persistent_shell(context.config.execution.persistent_shell),
+                    id,
+                    task: context.request.task.clone(),
+                    cwd,
+                    saved: false,
+                };
+                run.saved = run.capture(commands)?;
+                Ok(run)
+            });
+        match captured {
+            Ok(run) => Some(run),
+            Err(err) => {
+                context.record_message(format!("Could not save undo snapshot: {err:#}"));
+                None
+            }
+        }
+    }
+}
+
+/// The undo snapshot of one execution.
+#[derive(Clone)]
+struct UndoRun {
+    store: UndoStore,
+    id: String,
+    task: String,
+    cwd: PathBuf,
+    /// The snapshot records something worth undoing.
+    saved: bool,
+}
+
+impl UndoRun {
+    /// Record what `commands`, run in order from the run's directory, are about
+    /// to change. Returns whether the snapshot records anything.
+    fn capture(&self, commands: &[String]) -> Result<bool> {
+        let snapshot = self
+            .store
+            .capture(&self.id, &self.task, &self.cwd, commands)?;
+        Ok(!snapshot.is_empty())
+    }
+}
+
+/// Passes step decisions through from the user's gate. The snapshot is taken
+/// from the planned commands, so when one is edited, what the new command is
+/// about to change is added before it runs.
+struct UndoGate {
+    inner: Arc<dyn StepGate>,
+    run: UndoRun,
+    state: Mutex<UndoGateState>,
+}
+
+struct UndoGateState {
+    /// Execute commands chosen so far, so a persistent shell's `cd`s are followed.
+    commands: Vec<String>,
+    saved: bool,
+    errors: Vec<String>,
+}
+
+impl UndoGate {
+    fn new(inner: Arc<dyn StepGate>, run: UndoRun) -> Self {
+        let state = UndoGateState {
+            commands: Vec::new(),
+            saved: run.saved,
+            errors: Vec::new(),
+        };
+        Self {
+            inner,
+            run,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Report snapshot failures and return the snapshot id if it records anything.
+    fn finish(&self, context: &mut AgentContext) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        for error in &state.errors {
+            context.record_message(error.clone());
+        }
+        state.saved.then(|| self.run.id.clone())
+    }
+}
+
+#[async_trait]
+impl StepGate for UndoGate {
+    async fn decide(&self, step: PendingStep<'_>) -> Result<StepChoice> {
+        let choice = self.inner.decide(step).await?;
+        if step.phase != StepPhase::Execute {
+            return Ok(choice);
+        }
+        let mut state = self.state.lock().unwrap();
+        match &choice {
+            StepChoice::Run => state.commands.push(step.command.to_string()),
+            StepChoice::Edit(edited) => {
+                state.commands.push(edited.clone());
+                if edited.trim() != step.command.trim() {
+                    match self.run.capture(&state.commands) {
+                        Ok(saved) => state.saved |= saved,
+                        Err(err) => state.errors.push(format!(
+                            "Could not save undo snapshot for edited command '{edited}': {err:#}"
+                        )),
+                    }
+                }
+            }
+            StepChoice::Skip | StepChoice::Abort => {}
+        }
+        Ok(choice)
+    }
 }
 
 impl Default for PlanExecutionAdapter {
@@ -114,23 +465,151 @@ impl Default for PlanExecutionAdapter {
 #[async_trait]
 impl ExecutionAdapter for PlanExecutionAdapter {
     async fn execute(&self, context: &mut AgentContext, plan: &Plan) -> Result<ExecutionReport> {
-        if let Some(validation) = &context.validation {
-            if !validation.plan_can_continue {
-                return Ok(ExecutionReport::skipped(
-                    "Execution blocked: validator reported missing commands",
-                ));
+        if let Some(validation) = &context.validation
+            && !validation.plan_can_continue
+        {
+            return Ok(ExecutionReport::skipped(
+                "Execution blocked: validator reported missing commands",
+            ));
+        }
+
+        if context
+            .decision
+            .is_some_and(|decision| decision.blocks_execution())
+        {
+            return Ok(ExecutionReport::skipped(
+                "Execution blocked: plan confidence is below the refusal threshold (use --force)",
+            ));
+        }
+
+        match &context.approval {
+            Some(ApprovalDecision::Rejected { reason }) => {
+                return Ok(ExecutionReport::skipped(format!(
+                    "Execution rejected: {reason}"
+                )));
+            }
+            Some(_) => {}
+            None => {
+                if let Some(review) = &context.review
+                    && review.blocks_execution()
+                {
+                    return Ok(ExecutionReport::skipped(
+                        "Execution blocked: reviewer reported high-severity findings",
+                    ));
+                }
+
+                if !self.assume_yes && !context.request.assume_yes {
+                    return Ok(ExecutionReport::skipped("Execution requires user approval"));
+                }
             }
         }
 
-        if !self.assume_yes && !context.request.assume_yes {
-            return Ok(ExecutionReport::skipped("Execution requires user approval"));
+        let step_by_step =
+            context.request.step || context.approval == Some(ApprovalDecision::ApprovedStepByStep);
+        if step_by_step && self.gate.is_none() {
+            return Ok(ExecutionReport::skipped(
+                "Step-by-step execution needs an interactive terminal",
+            ));
         }
 
-        exec::execution_report(plan).await
+        let mut remaining = if context
+            .decision
+            .is_some_and(|decision| decision.skips_dry_run())
+        {
+            Plan {
+                dry_run_commands: Vec::new(),
+                ..plan.clone()
+            }
+        } else {
+            plan.clone()
+        };
+
+        // A resumed session does not repeat steps that already succeeded; once
+        // execution has started the dry-run checks are not repeated either.
+        let done = context.progress;
+        if done.execute > 0 && context.config.execution.persistent_shell {
+            // The shell the earlier steps ran in is gone, along with any
+            // directory, variables or functions they set up.
+            return Ok(ExecutionReport::skipped(format!(
+                "Cannot resume after {} completed step(s): they ran in a persistent shell whose state is lost; run the request again",
+                done.execute
+            )));
+        }
+        let skipped_checks = if done.execute > 0 {
+            remaining.dry_run_commands.len()
+        } else {
+            done.dry_run.min(remaining.dry_run_commands.len())
+        };
+        let skipped_steps = done.execute.min(remaining.execute_commands.len());
+        remaining.dry_run_commands.drain(..skipped_checks);
+        remaining.execute_commands.drain(..skipped_steps);
+
+        let undo = self.snapshot(context, &remaining.execute_commands);
+        let gate = self.gate.clone().filter(|_| step_by_step);
+        let undo_gate = undo
+            .clone()
+            .zip(gate.clone())
+            .map(|(run, gate)| Arc::new(UndoGate::new(gate, run)));
+        let executor = plan_executor(&context.config.execution)
+            .with_echo(self.echo)
+            .with_terminal(self.echo && self.terminal)
+            .with_pty(
+                self.echo && self.terminal && (context.config.execution.pty || context.request.pty),
+            )
+            .with_step_gate(match &undo_gate {
+                Some(undo_gate) => Some(undo_gate.clone()),
+                None => gate,
+            });
+        let cancel = context.cancel.clone();
+        let mut on_step = |step: StepEvent<'_>| {
+            let offset = match step.phase() {
+                StepPhase::DryRun => skipped_checks,
+                StepPhase::Execute => skipped_steps,
+            };
+            context.record_step(step.offset(offset));
+        };
+        let mut report = executor.run(&remaining, &cancel, &mut on_step).await;
+
+        for step in &mut report.steps {
+            step.index += match step.phase {
+                StepPhase::DryRun => skipped_checks,
+                StepPhase::Execute => skipped_steps,
+            };
+        }
+        report.commands = plan.execute_commands.clone();
+        report.undo = match &undo_gate {
+            Some(undo_gate) => undo_gate.finish(context),
+            None => undo.filter(|run| run.saved).map(|run| run.id),
+        };
+        if skipped_checks + skipped_steps > 0 {
+            report.notes.insert(
+                0,
+                format!(
+                    "Resumed after {} completed step(s)",
+                    skipped_checks + skipped_steps
+                ),
+            );
+        }
+        Ok(report)
     }
 }
 
+/// Executor for plan commands with the configured shell and time limits.
+fn plan_executor(settings: &ExecutionSettings) -> Executor {
+    Executor::new(settings.shell)
+        .with_step_timeout(seconds(settings.step_timeout_secs))
+        .with_plan_timeout(seconds(settings.plan_timeout_secs))
+        .with_idle_warning(seconds(settings.idle_warning_secs))
+        .with_persistent_shell(settings.persistent_shell)
+}
+
+/// A limit configured in seconds, where 0 means none.
+fn seconds(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
 /// Placeholder recovery adapter that marks the stage as skipped.
+#[allow(dead_code)]
 pub struct NoopRecoveryAdapter;
 
 #[async_trait]
@@ -139,3 +618,233 @@ impl RecoveryAdapter for NoopRecoveryAdapter {
         Ok(RecoveryOutcome::Skipped)
     }
 }
+
+/// Recovery attempts per missing command before giving up on it.
+const MAX_RECOVERY_ATTEMPTS: usize = 3;
+
+/// Recovery adapter that drives `RecoveryEngine` options for commands the
+/// validator reported as missing, letting a [`RecoveryChooser`] pick among them.
+pub struct EngineRecoveryAdapter {
+    factory: Arc<dyn LlmClientFactory>,
+    chooser: Box<dyn RecoveryChooser>,
+}
+
+impl EngineRecoveryAdapter {
+    pub fn new(chooser: Box<dyn RecoveryChooser>) -> Self {
+        Self {
+            factory: Arc::new(DefaultLlmClientFactory),
+            chooser,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_factory(mut self, factory: Arc<dyn LlmClientFactory>) -> Self {
+        self.factory = factory;
+        self
+    }
+
+    async fn recover_command(
+        &self,
+        engine: &mut RecoveryEngine,
+        missing: &MissingCommand,
+        plan: &Plan,
+        goal: &str,
+    ) -> Result<RecoveryOutcome> {
+        let mut attempted: Vec<String> = Vec::new();
+
+        for _ in 0..MAX_RECOVERY_ATTEMPTS {
+            let strategy = self.chooser.strategy(missing)?;
+            if matches!(strategy, RecoveryStrategy::NeverRecover) {
+                println!(
+                    "Recovery cancelled. Resolve missing tools manually and rerun the command."
+                );
+                return Ok(RecoveryOutcome::Cancelled);
+            }
+
+            let mut options = engine
+                .generate_recovery_options(strategy, missing, plan, goal)
+                .await?;
+            options
+                .command_alternatives
+                .retain(|alt| !attempted.contains(&alt.command));
+            options
+                .installation_instructions
+                .retain(|inst| !attempted.contains(install_command(inst)));
+
+            if options.command_alternatives.is_empty()
+                && options.installation_instructions.is_empty()
+                && !options.can_skip_step
+            {
+                println!(
+                    "No automated recovery options available for '{}'.",
+                    missing.command
+                );
+                continue;
+            }
+
+            let choice = self.chooser.choose(engine, &options, missing).await?;
+            let recovery_context = RecoveryContext {
+                missing_command: missing.clone(),
+                original_plan: plan.clone(),
+                original_goal: goal.to_string(),
+            };
+
+            let missing_name = missing.command.clone();
+            match engine
+                .execute_recovery(choice, &recovery_context, &options)
+                .await?
+            {
+                RecoveryResult::AlternativeSucceeded(alt) => {
+                    println!("✅ Alternative executed: {}", alt.command);
+                    return Ok(RecoveryOutcome::AlternativeApplied {
+                        missing: missing_name,
+                        command: alt.command,
+                    });
+                }
+                RecoveryResult::InstallationSucceeded(inst) => {
+                    println!("✅ Installation succeeded: {}", inst.command);
+                    return Ok(RecoveryOutcome::Installed {
+                        missing: missing_name,
+                        command: install_command(&inst).clone(),
+                    });
+                }
+                RecoveryResult::InstallationCancelled => {
+                    println!("Installation cancelled. Re-run the command when ready.");
+                    return Ok(RecoveryOutcome::Cancelled);
+                }
+                RecoveryResult::PlanAborted(reason) => {
+                    println!("Plan aborted: {}", reason);
+                    return Ok(RecoveryOutcome::Aborted { reason });
+                }
+                RecoveryResult::AlternativeFailed(alt) => {
+                    println!("Recovery attempt did not succeed. Trying another option.");
+                    attempted.push(alt.command);
+                }
+                RecoveryResult::InstallationFailed(inst) => {
+                    println!("Recovery attempt did not succeed. Trying another option.");
+                    attempted.push(install_command(&inst).clone());
+                }
+                RecoveryResult::StepSkipped => {
+                    println!("Recovery step skipped.");
+                    return Ok(RecoveryOutcome::StepSkipped {
+                        missing: missing_name,
+                    });
+                }
+                RecoveryResult::RetryRequested | RecoveryResult::RetryWithDifferentApproach => {
+                    println!("Retry requested. Re-run the command after addressing the prompt.");
+                    return Ok(RecoveryOutcome::RetryRequested);
+                }
+            }
+        }
+
+        Ok(RecoveryOutcome::Unrecovered {
+            missing: missing.command.clone(),
+            attempted,
+        })
+    }
+}
+
+impl Default for EngineRecoveryAdapter {
+    fn default() -> Self {
+        Self::new(Box::new(InteractiveChooser))
+    }
+}
+
+/// The command a step reported as not found (exit status 127). The shell's
+/// message is looked for in stdout as well, where it ends up on a terminal.
+pub(super) fn missing_at_run_time(report: &ExecutionReport) -> Option<MissingCommand> {
+    let step = report.failed_step()?;
+    if step.status != (StepStatus::Exited { code: 127 }) {
+        return None;
+    }
+
+    // bash: "bash: line 1: foo: command not found"; dash: "sh: 1: foo: not found"
+    let reported = step
+        .stderr
+        .lines()
+        .chain(step.stdout.lines())
+        .find_map(|line| {
+            let (head, _) = line
+                .rsplit_once(": command not found")
+                .or_else(|| line.rsplit_once(": not found"))?;
+            head.rsplit(": ").next().map(str::trim).map(str::to_string)
+        });
+    let command = reported
+        .filter(|name| !name.is_empty())
+        .or_else(|| step.command.split_whitespace().next().map(str::to_string))?;
+
+    Some(MissingCommand {
+        command,
+        failed_command_line: step.command.clone(),
+        plan_step: step.index - 1,
+        is_dry_run: step.phase == StepPhase::DryRun,
+    })
+}
+
+/// The command line an installation instruction actually runs.
+fn install_command(instruction: &InstallationInstruction) -> &String {
+    instruction
+        .install_commands
+        .first()
+        .unwrap_or(&instruction.command)
+}
+
+#[async_trait]
+impl RecoveryAdapter for EngineRecoveryAdapter {
+    async fn recover(&self, context: &mut AgentContext) -> Result<RecoveryOutcome> {
+        let Some(plan) = context.plan.clone() else {
+            return Ok(RecoveryOutcome::Skipped);
+        };
+        let missing_commands = match &context.validation {
+            Some(validation)
+                if !validation.plan_can_continue && !validation.missing_commands.is_empty() =>
+            {
+                validation.missing_commands.clone()
+            }
+            // The validator found every command, but a step still failed because
+            // one was missing at run time.
+            _ => match context.execution.as_ref().and_then(missing_at_run_time) {
+                Some(missing) => vec![missing],
+                None => return Ok(RecoveryOutcome::Skipped),
+            },
+        };
+
+        recovery::print_missing_commands(&missing_commands);
+        println!("Plan cannot continue until the missing commands are addressed.");
+
+        if !context.config.recovery.enabled {
+            println!(
+                "Recovery is disabled in your configuration. Enable it to receive guided fixes."
+            );
+            return Ok(RecoveryOutcome::Skipped);
+        }
+
+        let goal = context.request.task.clone();
+        let client = context.llm_client(self.factory.as_ref())?;
+        let mut engine = RecoveryEngine::with_client(&context.config, client);
+        engine.confirm_installs = self.chooser.confirms_installs();
+        engine.set_available_tools().await?;
+        let mut outcome = RecoveryOutcome::Skipped;
+
+        for missing in &missing_commands {
+            match self
+                .recover_command(&mut engine, missing, &plan, &goal)
+                .await?
+            {
+                applied @ (RecoveryOutcome::AlternativeApplied { .. }
+                | RecoveryOutcome::Installed { .. }
+                | RecoveryOutcome::StepSkipped { .. }) => outcome = applied,
+                stopped => return Ok(stopped),
+            }
+        }
+
+        if matches!(
+            outcome,
+            RecoveryOutcome::AlternativeApplied { .. } | RecoveryOutcome::Installed { .. }
+        ) {
+            println!("Re-run your original command to take advantage of the recovery steps.");
+        }
+
+        Ok(outcome)
+    }
+}
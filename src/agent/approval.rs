@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::planner::Plan;
+use crate::validator::{CommandValidator, PartKind, is_assignment};
+
+use super::context::AgentContext;
+use super::stages::{AgentStage, StageOutcome};
+use super::types::StageKind;
+
+/// Verdict returned by an approver for a plan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approved,
+    /// Approved, and the captured output should be explained afterwards.
+    ApprovedWithExplanation,
+    /// Approved, asking before each command whether to run it.
+    ApprovedStepByStep,
+    Rejected {
+        reason: String,
+    },
+}
+
+impl ApprovalDecision {
+    pub fn rejected(reason: impl Into<String>) -> Self {
+        Self::Rejected {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Decides whether a validated and reviewed plan may be executed.
+#[async_trait]
+pub trait Approver {
+    async fn approve(&self, context: &AgentContext, plan: &Plan) -> Result<ApprovalDecision>;
+}
+
+/// Approves every plan that the reviewer and confidence policy allow to run unattended.
+pub struct AutoApprover;
+
+#[async_trait]
+impl Approver for AutoApprover {
+    async fn approve(&self, context: &AgentContext, _plan: &Plan) -> Result<ApprovalDecision> {
+        Ok(match unattended_block(context) {
+            Some(reason) => ApprovalDecision::rejected(reason),
+            None => ApprovalDecision::Approved,
+        })
+    }
+}
+
+/// Approves plans whose programs are all listed in `approval.allowed_commands`.
+pub struct PolicyApprover;
+
+#[async_trait]
+impl Approver for PolicyApprover {
+    async fn approve(&self, context: &AgentContext, plan: &Plan) -> Result<ApprovalDecision> {
+        if let Some(reason) = unattended_block(context) {
+            return Ok(ApprovalDecision::rejected(reason));
+        }
+
+        let allowed = &context.config.approval.allowed_commands;
+        let commands = plan.dry_run_commands.iter().chain(&plan.execute_commands);
+        for command in commands {
+            if let Some(reason) = policy_violation(command, allowed) {
+                return Ok(ApprovalDecision::rejected(reason));
+            }
+        }
+
+        Ok(ApprovalDecision::Approved)
+    }
+}
+
+/// Programs that run a command given to them as an argument.
+const WRAPPERS: [&str; 20] = [
+    "sh", "bash", "zsh", "dash", "ksh", "fish", "eval", "exec", "command", "builtin", "xargs",
+    "env", "sudo", "doas", "su", "nohup", "nice", "timeout", "time", "watch",
+];
+
+/// `find` actions that run a command for each match.
+const FIND_EXEC: [&str; 4] = ["-exec", "-execdir", "-ok", "-okdir"];
+
+/// Why `command` may not run under `approval.mode = "policy"`, if it may not.
+///
+/// Every program must be listed in `allowed`. Substitutions are refused outright
+/// because the commands inside them are never split out, and so are programs
+/// that run other commands (`sh -c`, `sudo`, `xargs`, `find -exec`, ...) unless
+/// the wrapper itself is listed. `NAME=value` prefixes can change what a program
+/// does (`LD_PRELOAD`, `GIT_SSH_COMMAND`) and need a `NAME=` entry, and output
+/// redirections other than to `/dev/null` need a `>` entry.
+pub(crate) fn policy_violation(command: &str, allowed: &[String]) -> Option<String> {
+    let is_allowed = |program: &str| allowed.iter().any(|name| name == program);
+    if ["$(", "`", "<(", ">("]
+        .iter()
+        .any(|marker| command.contains(marker))
+    {
+        return Some("command substitutions are not allowed under the approval policy".to_string());
+    }
+
+    let parts = CommandValidator::split_command(command);
+    for (index, part) in parts.iter().enumerate() {
+        match part.kind {
+            PartKind::Program if is_allowed(&part.text) => {}
+            PartKind::Program if WRAPPERS.contains(&part.text.as_str()) => {
+                return Some(format!(
+                    "'{}' runs other commands and is not in approval.allowed_commands",
+                    part.text
+                ));
+            }
+            PartKind::Program => {
+                return Some(format!(
+                    "'{}' is not in approval.allowed_commands",
+                    part.text
+                ));
+            }
+            PartKind::Argument if is_assignment(&part.text) => {
+                let assigned = parts[..index].iter().all(|earlier| {
+                    earlier.stage != part.stage || earlier.kind != PartKind::Program
+                });
+                let name = part.text.split_once('=').map_or("", |(name, _)| name);
+                if assigned && !is_allowed(&format!("{name}=")) {
+                    return Some(format!(
+                        "setting '{name}' is not allowed unless approval.allowed_commands lists \"{name}=\""
+                    ));
+                }
+            }
+            PartKind::Redirection if writes_file(&part.text) && !is_allowed(">") => {
+                return Some(format!(
+                    "output redirection '{}' is not allowed unless approval.allowed_commands lists \">\"",
+                    part.text
+                ));
+            }
+            PartKind::Flag if FIND_EXEC.contains(&part.text.as_str()) => {
+                let program = parts[..index].iter().rev().find(|earlier| {
+                    earlier.stage == part.stage && earlier.kind == PartKind::Program
+                });
+                let wrapper = format!("find {}", part.text);
+                if program.is_some_and(|program| program.text == "find") && !is_allowed(&wrapper) {
+                    return Some(format!(
+                        "'{wrapper}' runs other commands and is not in approval.allowed_commands"
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether a redirection writes somewhere other than `/dev/null` or another
+/// file descriptor.
+fn writes_file(redirection: &str) -> bool {
+    let rest = redirection.trim_start_matches(|c: char| c.is_ascii_digit());
+    let split = rest.find(|c| !"<>&".contains(c)).unwrap_or(rest.len());
+    let (operator, target) = rest.split_at(split);
+    let target = target.trim();
+    if !operator.contains('>') {
+        return false;
+    }
+    let duplicates = operator.ends_with('&')
+        && (target == "-" || !target.is_empty() && target.chars().all(|c| c.is_ascii_digit()));
+    !duplicates && target != "/dev/null"
+}
+
+/// Reasons a plan must never run without a human explicitly approving it.
+fn unattended_block(context: &AgentContext) -> Option<String> {
+    if context
+        .review
+        .as_ref()
+        .is_some_and(|review| review.blocks_execution())
+    {
+        return Some("the reviewer reported high-severity findings".to_string());
+    }
+    None
+}
+
+/// Reasons a plan must not run even if an approver says yes.
+fn hard_block(context: &AgentContext) -> Option<String> {
+    if context
+        .decision
+        .is_some_and(|decision| decision.blocks_execution())
+    {
+        return Some(
+            "plan confidence is below the refusal threshold; re-run with --force to execute it"
+                .to_string(),
+        );
+    }
+    None
+}
+
+pub struct ApprovalStage<A> {
+    approver: Arc<A>,
+}
+
+impl<A> ApprovalStage<A> {
+    pub fn new(approver: A) -> Self {
+        Self {
+            approver: Arc::new(approver),
+        }
+    }
+}
+
+#[async_trait]
+impl<A> AgentStage for ApprovalStage<A>
+where
+    A: Approver + Send + Sync + 'static,
+{
+    fn kind(&self) -> StageKind {
+        StageKind::Approval
+    }
+
+    async fn execute(&self, context: &mut AgentContext) -> Result<StageOutcome> {
+        let Some(plan) = context.plan.clone() else {
+            context.record_stage_skip(self.kind(), "no plan available for approval");
+            return Ok(StageOutcome::Continue);
+        };
+
+        if let Some(validation) = &context.validation
+            && !validation.plan_can_continue
+        {
+            context.record_stage_skip(self.kind(), "validation blocked the plan");
+            return Ok(StageOutcome::Continue);
+        }
+
+        if let Some(ApprovalDecision::Rejected { .. }) = &context.approval {
+            context.record_stage_skip(self.kind(), "an earlier stage rejected the plan");
+            return Ok(StageOutcome::Continue);
+        }
+
+        let mut decision = self.approver.approve(context, &plan).await?;
+        if let Some(reason) = hard_block(context) {
+            decision = ApprovalDecision::rejected(reason);
+        }
+
+        // A rejected plan stays in the run so callers can still inspect it;
+        // the execution stage skips it.
+        context.record_approval(decision);
+        Ok(StageOutcome::Continue)
+    }
+}
@@ -2,22 +2,47 @@ use std::fmt;
 use std::sync::Arc;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-use crate::client::{DynLlmClient, LlmClientFactory};
+use crate::cancel::CancelToken;
+use crate::client::{DynLlmClient, LlmClientFactory, MeteredClient, UsageMeter};
 use crate::config::Config;
+use crate::exec::{PreviewReport, StepEvent, StepPhase};
 use crate::planner::Plan;
+use crate::reviewer::PlanReview;
 use crate::validator::ValidationResult;
 
+use super::approval::ApprovalDecision;
+use super::goal::{self, IterationRecord, LoopStop, LoopSummary};
+use super::hooks::{HookPoint, HookVerdict};
+use super::metrics::{StageMetrics, UsageSnapshot};
+use super::observer::AgentObserver;
 use super::outcome::{AgentOutcome, ExecutionReport, RecoveryOutcome};
+use super::policy::{self, ConfidenceDecision};
+use super::session::{Clarification, SessionRecorder, SessionState, StepProgress};
 use super::types::StageKind;
 
 /// Immutable request passed into the agent pipeline.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AgentRequest {
     pub task: String,
     pub intelligence: bool,
     pub intelligence_question: Option<String>,
     pub assume_yes: bool,
+    pub force: bool,
+    /// Whether stages may prompt on the terminal.
+    pub interactive: bool,
+    /// Keep planning and executing until the goal is assessed as achieved.
+    pub goal_loop: bool,
+    /// Preview the plan's file changes before approval even if `execution.preview` is off.
+    #[serde(default)]
+    pub preview: bool,
+    /// Ask before every dry-run and execute command whether to run it.
+    #[serde(default)]
+    pub step: bool,
+    /// Give commands a pseudo-terminal even if `execution.pty` is off.
+    #[serde(default)]
+    pub pty: bool,
 }
 
 impl AgentRequest {
@@ -27,24 +52,66 @@ impl AgentRequest {
             intelligence: false,
             intelligence_question: None,
             assume_yes: false,
+            force: false,
+            interactive: true,
+            goal_loop: false,
+            preview: false,
+            step: false,
+            pty: false,
         }
     }
 
+    #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
         self.task.trim().is_empty()
     }
 }
 
+/// Serializable view of the run so far, handed to hooks and plugin stages.
+#[derive(Debug, Serialize)]
+pub struct ContextSnapshot<'a> {
+    pub task: &'a str,
+    pub iteration: usize,
+    pub interactive: bool,
+    pub plan: Option<&'a Plan>,
+    pub validation: Option<&'a ValidationResult>,
+    pub review: Option<&'a PlanReview>,
+    pub preview: Option<&'a PreviewReport>,
+    pub approval: Option<&'a ApprovalDecision>,
+    pub execution: Option<&'a ExecutionReport>,
+    pub recovery: Option<&'a RecoveryOutcome>,
+}
+
 /// Mutable context threaded through the agent stages.
 #[derive(Clone)]
 pub struct AgentContext {
     pub config: Config,
     pub request: AgentRequest,
     pub plan: Option<Plan>,
+    pub replan_context: Option<String>,
+    pub decision: Option<ConfidenceDecision>,
     pub validation: Option<ValidationResult>,
+    pub review: Option<PlanReview>,
+    pub preview: Option<PreviewReport>,
+    pub approval: Option<ApprovalDecision>,
     pub execution: Option<ExecutionReport>,
     pub recovery: Option<RecoveryOutcome>,
+    /// Finished rounds of the goal loop, oldest first.
+    pub iterations: Vec<IterationRecord>,
+    pub loop_stop: Option<LoopStop>,
+    /// Ctrl-C and SIGTERM state for this run.
+    pub cancel: CancelToken,
+    /// Planner questions answered when the run was resumed.
+    pub clarifications: Vec<Clarification>,
+    /// Steps of the current plan that already ran successfully.
+    pub progress: StepProgress,
+    /// Index of the first pipeline stage that has not completed yet.
+    pub next_stage: usize,
+    session: Option<Arc<SessionRecorder>>,
     events: Vec<AgentEvent>,
+    metrics: Vec<StageMetrics>,
+    observers: Vec<Arc<dyn AgentObserver>>,
+    usage: Arc<UsageMeter>,
     llm_client: Option<Arc<DynLlmClient>>,
 }
 
@@ -53,9 +120,18 @@ impl fmt::Debug for AgentContext {
         f.debug_struct("AgentContext")
             .field("request", &self.request)
             .field("plan", &self.plan)
+            .field("decision", &self.decision)
             .field("validation", &self.validation)
+            .field("review", &self.review)
+            .field("preview", &self.preview)
+            .field("approval", &self.approval)
             .field("execution", &self.execution)
             .field("recovery", &self.recovery)
+            .field("iterations", &self.iterations)
+            .field("loop_stop", &self.loop_stop)
+            .field("clarifications", &self.clarifications)
+            .field("progress", &self.progress)
+            .field("next_stage", &self.next_stage)
             .field("events", &self.events)
             .finish()
     }
@@ -67,15 +143,130 @@ impl AgentContext {
             config,
             request,
             plan: None,
+            replan_context: None,
+            decision: None,
             validation: None,
+            review: None,
+            preview: None,
+            approval: None,
             execution: None,
             recovery: None,
+            iterations: Vec::new(),
+            loop_stop: None,
+            cancel: CancelToken::new(),
+            clarifications: Vec::new(),
+            progress: StepProgress::default(),
+            next_stage: 0,
+            session: None,
             events: Vec::new(),
+            metrics: Vec::new(),
+            observers: Vec::new(),
+            usage: Arc::new(UsageMeter::default()),
             llm_client: None,
         }
     }
 
+    /// Rebuild the context of a saved session.
+    pub fn restore(config: Config, state: SessionState) -> Self {
+        let mut context = Self::new(config, state.request);
+        context.next_stage = state.next_stage;
+        context.plan = state.plan;
+        context.decision = state.decision;
+        context.validation = state.validation;
+        context.review = state.review;
+        context.preview = state.preview;
+        context.approval = state.approval;
+        context.execution = state.execution;
+        context.recovery = state.recovery;
+        context.iterations = state.iterations;
+        context.loop_stop = state.loop_stop;
+        context.clarifications = state.clarifications;
+        context.progress = state.progress;
+        context.events = state.events;
+        context.metrics = state.metrics;
+        context
+    }
+
+    pub fn session_state(&self) -> SessionState {
+        SessionState {
+            request: self.request.clone(),
+            next_stage: self.next_stage,
+            plan: self.plan.clone(),
+            decision: self.decision,
+            validation: self.validation.clone(),
+            review: self.review.clone(),
+            preview: self.preview.clone(),
+            approval: self.approval.clone(),
+            execution: self.execution.clone(),
+            recovery: self.recovery.clone(),
+            iterations: self.iterations.clone(),
+            loop_stop: self.loop_stop,
+            clarifications: self.clarifications.clone(),
+            progress: self.progress,
+            events: self.events.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Save the run to its session file after every stage and step.
+    pub fn with_session(mut self, session: Option<Arc<SessionRecorder>>) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Id of the session file this run is saved to.
+    pub fn session_id(&self) -> Option<&str> {
+        self.session.as_deref().map(SessionRecorder::id)
+    }
+
+    /// Write the session file, if this run has one. A failed save is reported once
+    /// and does not stop the run.
+    pub fn save_session(&mut self) {
+        let Some(session) = self.session.clone() else {
+            return;
+        };
+        if let Err(err) = session.save(self.session_state())
+            && session.first_failure()
+        {
+            self.record_message(format!("Could not save session: {err:#}"));
+        }
+    }
+
+    pub fn with_observers(mut self, observers: Vec<Arc<dyn AgentObserver>>) -> Self {
+        self.observers = observers;
+        self
+    }
+
+    pub fn snapshot(&self) -> ContextSnapshot<'_> {
+        ContextSnapshot {
+            task: &self.request.task,
+            iteration: self.iterations.len() + 1,
+            interactive: self.request.interactive,
+            plan: self.plan.as_ref(),
+            validation: self.validation.as_ref(),
+            review: self.review.as_ref(),
+            preview: self.preview.as_ref(),
+            approval: self.approval.as_ref(),
+            execution: self.execution.as_ref(),
+            recovery: self.recovery.as_ref(),
+        }
+    }
+
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Count LLM calls in `usage`, which may be shared with clients outside the pipeline.
+    pub fn with_usage_meter(mut self, usage: Arc<UsageMeter>) -> Self {
+        self.usage = usage;
+        self
+    }
+
     pub fn record_event(&mut self, event: AgentEvent) {
+        for observer in &self.observers {
+            observer.on_event(&event);
+        }
         self.events.push(event);
     }
 
@@ -91,11 +282,31 @@ impl AgentContext {
             return Ok(client.clone());
         }
 
-        let client = factory.build(&self.config.llm)?;
+        let inner = factory.build(&self.config.llm)?;
+        let client: Arc<DynLlmClient> = Arc::new(
+            MeteredClient::new(inner, self.usage.clone()).with_cancel_token(self.cancel.clone()),
+        );
         self.llm_client = Some(client.clone());
         Ok(client)
     }
 
+    /// Tokens used by LLM calls made through [`Self::llm_client`] so far.
+    pub fn tokens_used(&self) -> u64 {
+        self.usage.tokens()
+    }
+
+    pub fn llm_calls(&self) -> u64 {
+        self.usage.calls()
+    }
+
+    pub fn usage_snapshot(&self) -> UsageSnapshot {
+        UsageSnapshot::of(&self.usage)
+    }
+
+    pub fn record_stage_metrics(&mut self, metrics: StageMetrics) {
+        self.metrics.push(metrics);
+    }
+
     pub fn record_stage_start(&mut self, stage: StageKind) {
         self.record_event(AgentEvent::StageStarted(stage));
     }
@@ -121,9 +332,54 @@ impl AgentContext {
     pub fn record_plan(&mut self, plan: Plan) {
         let confidence = plan.confidence;
         self.plan = Some(plan);
+        self.progress = StepProgress::default();
         self.record_event(AgentEvent::PlanReady { confidence });
     }
 
+    /// Replace the planner's plan, e.g. with one from a hook or plugin. The
+    /// confidence gate runs again, so the replacement does not inherit the
+    /// decision made for the plan it replaces.
+    pub fn replace_plan(&mut self, plan: Plan) {
+        let confidence = plan.confidence;
+        self.record_plan(plan);
+        let decision = policy::decide(&self.config.confidence, confidence, self.request.force);
+        self.record_confidence_decision(confidence, decision);
+    }
+
+    pub fn record_confidence_decision(&mut self, confidence: f32, decision: ConfidenceDecision) {
+        if !matches!(decision, ConfidenceDecision::Replan { .. }) {
+            self.decision = Some(decision);
+        }
+        self.record_event(AgentEvent::ConfidenceDecided {
+            confidence,
+            decision,
+        });
+    }
+
+    /// Task text sent to the planner, including answered questions, goal-loop
+    /// progress and any re-plan context.
+    pub fn planning_request(&self) -> String {
+        let mut request = self.request.task.clone();
+        if !self.clarifications.is_empty() {
+            request.push_str("\n\nAnswers to your earlier questions:");
+            for clarification in &self.clarifications {
+                request.push_str(&format!(
+                    "\n- Q: {}\n  A: {}",
+                    clarification.question, clarification.answer
+                ));
+            }
+        }
+        if !self.iterations.is_empty() {
+            request.push_str("\n\n");
+            request.push_str(&goal::progress_context(&self.iterations));
+        }
+        if let Some(extra) = &self.replan_context {
+            request.push_str("\n\n");
+            request.push_str(extra);
+        }
+        request
+    }
+
     pub fn record_validation(&mut self, validation: ValidationResult) {
         let missing = validation.missing_commands.len();
         self.validation = Some(validation.clone());
@@ -133,6 +389,73 @@ impl AgentContext {
         });
     }
 
+    pub fn record_review(&mut self, review: PlanReview) {
+        let findings = review.findings.len();
+        let blocking = review.blocks_execution();
+        self.review = Some(review);
+        self.record_event(AgentEvent::ReviewFinished { findings, blocking });
+    }
+
+    pub fn record_preview(&mut self, preview: PreviewReport) {
+        let changes = preview.changes.len();
+        let success = preview.execution.success;
+        self.preview = Some(preview);
+        self.record_event(AgentEvent::PreviewFinished { changes, success });
+    }
+
+    pub fn record_approval(&mut self, decision: ApprovalDecision) {
+        self.approval = Some(decision.clone());
+        self.record_event(AgentEvent::ApprovalDecided(decision));
+    }
+
+    pub fn record_step(&mut self, step: StepEvent<'_>) {
+        let event = match step {
+            StepEvent::Started {
+                phase,
+                index,
+                total,
+                command,
+            } => AgentEvent::StepStarted {
+                phase,
+                index,
+                total,
+                command: command.to_string(),
+            },
+            StepEvent::Finished {
+                phase,
+                index,
+                success,
+            } => {
+                if success {
+                    let done = match phase {
+                        StepPhase::DryRun => &mut self.progress.dry_run,
+                        StepPhase::Execute => &mut self.progress.execute,
+                    };
+                    *done = (*done).max(index);
+                }
+                AgentEvent::StepFinished {
+                    phase,
+                    index,
+                    success,
+                }
+            }
+            StepEvent::Idle {
+                phase,
+                index,
+                seconds,
+            } => AgentEvent::StepIdle {
+                phase,
+                index,
+                seconds,
+            },
+        };
+        let finished = matches!(event, AgentEvent::StepFinished { .. });
+        self.record_event(event);
+        if finished {
+            self.save_session();
+        }
+    }
+
     pub fn record_execution(&mut self, report: ExecutionReport) {
         let success = report.success;
         self.execution = Some(report);
@@ -144,48 +467,200 @@ impl AgentContext {
         self.record_event(AgentEvent::RecoveryFinished { outcome });
     }
 
+    pub fn record_iteration(&mut self, record: IterationRecord) {
+        self.record_event(AgentEvent::GoalAssessed {
+            iteration: record.iteration,
+            achieved: record.assessment.achieved,
+            summary: record.assessment.summary.clone(),
+        });
+        self.iterations.push(record);
+    }
+
+    pub fn record_cancellation(&mut self, reason: &str) {
+        self.record_event(AgentEvent::RunCancelled {
+            reason: reason.to_string(),
+        });
+    }
+
+    pub fn record_hook(&mut self, hook: HookPoint, verdict: HookVerdict) {
+        self.record_event(AgentEvent::HookFinished { hook, verdict });
+    }
+
+    /// Clear per-iteration results before the pipeline runs again.
+    pub fn start_next_iteration(&mut self) {
+        self.plan = None;
+        self.replan_context = None;
+        self.decision = None;
+        self.validation = None;
+        self.review = None;
+        self.preview = None;
+        self.approval = None;
+        self.execution = None;
+        self.recovery = None;
+        self.progress = StepProgress::default();
+        let iteration = self.iterations.len() + 1;
+        self.record_event(AgentEvent::IterationStarted { iteration });
+    }
+
+    fn loop_summary(&self) -> Option<LoopSummary> {
+        if !self.request.goal_loop {
+            return None;
+        }
+        Some(LoopSummary {
+            stop: self.loop_stop,
+            iterations: self.iterations.clone(),
+            tokens_used: self.tokens_used(),
+            llm_calls: self.llm_calls(),
+        })
+    }
+
     pub fn into_run(self) -> AgentRun {
+        let goal_loop = self.loop_summary();
         let AgentContext {
             plan,
+            decision,
             validation,
+            review,
+            preview,
+            approval,
             execution,
             recovery,
             events,
+            metrics,
             ..
         } = self;
 
         let outcome = AgentOutcome::Planned {
             plan,
+            decision,
             validation,
+            review,
+            preview,
+            approval,
             execution,
             recovery,
         };
 
-        AgentRun { outcome, events }
+        AgentRun {
+            outcome,
+            events,
+            metrics,
+            goal_loop,
+        }
+    }
+
+    /// End the run as cancelled, keeping the plan and any execution so far.
+    pub fn into_cancelled_run(mut self, reason: impl Into<String>) -> AgentRun {
+        let outcome = AgentOutcome::Cancelled {
+            reason: reason.into(),
+            plan: self.plan.take(),
+            execution: self.execution.take(),
+        };
+        self.into_run_with_outcome(outcome)
     }
 
     pub fn into_run_with_outcome(self, outcome: AgentOutcome) -> AgentRun {
-        let AgentContext { events, .. } = self;
-        AgentRun { outcome, events }
+        let goal_loop = self.loop_summary();
+        let AgentContext {
+            events, metrics, ..
+        } = self;
+        AgentRun {
+            outcome,
+            events,
+            metrics,
+            goal_loop,
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct AgentRun {
     pub outcome: AgentOutcome,
     pub events: Vec<AgentEvent>,
+    /// One entry per stage pass, in the order the stages ran.
+    pub metrics: Vec<StageMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal_loop: Option<LoopSummary>,
 }
 
 /// Structured audit events emitted while progressing through the pipeline.
-#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
 pub enum AgentEvent {
     StageStarted(StageKind),
     StageCompleted(StageKind),
-    StageSkipped { stage: StageKind, reason: String },
-    StageFailed { stage: StageKind, error: String },
-    PlanReady { confidence: f32 },
-    ValidationFinished { missing: usize, can_continue: bool },
-    ExecutionFinished { success: bool },
-    RecoveryFinished { outcome: RecoveryOutcome },
+    StageSkipped {
+        stage: StageKind,
+        reason: String,
+    },
+    StageFailed {
+        stage: StageKind,
+        error: String,
+    },
+    PlanReady {
+        confidence: f32,
+    },
+    ConfidenceDecided {
+        confidence: f32,
+        decision: ConfidenceDecision,
+    },
+    ValidationFinished {
+        missing: usize,
+        can_continue: bool,
+    },
+    ReviewFinished {
+        findings: usize,
+        blocking: bool,
+    },
+    /// The execute commands ran in a sandbox and changed `changes` paths.
+    PreviewFinished {
+        changes: usize,
+        success: bool,
+    },
+    ApprovalDecided(ApprovalDecision),
+    StepStarted {
+        phase: StepPhase,
+        index: usize,
+        total: usize,
+        command: String,
+    },
+    StepFinished {
+        phase: StepPhase,
+        index: usize,
+        success: bool,
+    },
+    /// A running plan command has printed nothing for `seconds`.
+    StepIdle {
+        phase: StepPhase,
+        index: usize,
+        seconds: u64,
+    },
+    ExecutionFinished {
+        success: bool,
+    },
+    RecoveryFinished {
+        outcome: RecoveryOutcome,
+    },
+    GoalAssessed {
+        iteration: usize,
+        achieved: bool,
+        summary: String,
+    },
+    IterationStarted {
+        iteration: usize,
+    },
+    HookFinished {
+        hook: HookPoint,
+        verdict: HookVerdict,
+    },
+    RunCancelled {
+        reason: String,
+    },
+    /// A saved session continued at pipeline stage `stage` (zero-based).
+    SessionResumed {
+        session: String,
+        stage: usize,
+    },
     Message(String),
 }
@@ -0,0 +1,217 @@
+//! Goal-driven iteration: after each execution the model sees what happened and
+//! decides whether the task is done or what to try next.
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::client::{ChatCompletionRequest, ChatMessage, ChatMessageRole, DynLlmClient};
+use crate::planner::{Plan, extract_json_object};
+use crate::tokens::compute_completion_token_budget;
+
+use super::outcome::ExecutionReport;
+
+/// Captured output beyond this many characters per stream is cut from the front.
+const OBSERVATION_OUTPUT_LIMIT: usize = 2000;
+
+const GOAL_SYSTEM_PROMPT: &str = r#"You are a STRICT JSON judge of whether a shell task has been completed.
+
+OBJECTIVE
+- You see the user's goal, every plan that has been run so far, and the captured output and status of each run.
+- Decide whether the goal is now achieved. If it is not, say briefly what the next batch of commands should accomplish.
+- Judge only from the evidence shown. A failed command or empty output is not proof of success.
+
+OUTPUT FORMAT (STRICT JSON ONLY)
+- Return exactly one JSON object on a single line. No prose, no markdown, no code fences.
+{
+  "achieved": <true|false>,
+  "summary": "<one sentence describing the current state>",
+  "next": "<what the next iteration should do, or an empty string when achieved>"
+}"#;
+
+/// The model's verdict after one iteration of the loop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoalAssessment {
+    pub achieved: bool,
+    pub summary: String,
+    #[serde(default)]
+    pub next: String,
+}
+
+/// One plan/execute/assess round of the goal loop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IterationRecord {
+    pub iteration: usize,
+    pub plan: Plan,
+    pub execution: ExecutionReport,
+    pub assessment: GoalAssessment,
+}
+
+/// Why the goal loop stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopStop {
+    GoalAchieved,
+    MaxIterations,
+    TokenBudget,
+    NotExecuted,
+}
+
+impl LoopStop {
+    pub fn goal_achieved(self) -> bool {
+        matches!(self, LoopStop::GoalAchieved)
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            LoopStop::GoalAchieved => "goal achieved",
+            LoopStop::MaxIterations => "iteration limit reached",
+            LoopStop::TokenBudget => "token budget exhausted",
+            LoopStop::NotExecuted => "plan was not executed",
+        }
+    }
+}
+
+/// Loop bookkeeping attached to an [`AgentRun`](super::AgentRun).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoopSummary {
+    /// `None` when a stage ended the run before the loop decided to stop.
+    pub stop: Option<LoopStop>,
+    pub iterations: Vec<IterationRecord>,
+    pub tokens_used: u64,
+    pub llm_calls: u64,
+}
+
+/// Ask the model whether `task` is achieved, given every finished iteration.
+pub async fn assess(
+    client: &DynLlmClient,
+    task: &str,
+    history: &[IterationRecord],
+    plan: &Plan,
+    execution: &ExecutionReport,
+    model: &str,
+    max_tokens: u32,
+) -> Result<GoalAssessment> {
+    let messages = vec![
+        ChatMessage {
+            role: ChatMessageRole::System,
+            content: GOAL_SYSTEM_PROMPT.to_string(),
+        },
+        ChatMessage {
+            role: ChatMessageRole::User,
+            content: assessment_request(task, history, plan, execution),
+        },
+    ];
+
+    let completion_budget = compute_completion_token_budget(max_tokens, &messages);
+
+    let request = ChatCompletionRequest {
+        model: model.to_string(),
+        messages,
+        max_tokens: Some(completion_budget),
+        temperature: Some(0.0),
+    };
+
+    let response = client
+        .chat_completion(request)
+        .await
+        .context("Goal assessment call failed")?;
+
+    let choice = response
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Goal assessment returned no choices"))?;
+
+    let content = choice.message.content.trim();
+    let json_fragment = extract_json_object(content)
+        .ok_or_else(|| anyhow!("Goal assessment response did not contain JSON object"))?;
+
+    serde_json::from_str(&json_fragment)
+        .with_context(|| format!("Failed to parse goal assessment JSON: {content}"))
+}
+
+/// Context appended to the planner request so the next plan builds on earlier runs.
+pub fn progress_context(history: &[IterationRecord]) -> String {
+    let mut context =
+        String::from("Progress so far (continue from here, do not repeat finished work):\n");
+    for record in history {
+        context.push_str(&format!(
+            "\nIteration {}: {}\n",
+            record.iteration, record.assessment.summary
+        ));
+        push_commands(&mut context, &record.execution.commands);
+        if !record.assessment.next.trim().is_empty() {
+            context.push_str(&format!("Next: {}\n", record.assessment.next.trim()));
+        }
+    }
+    context
+}
+
+fn assessment_request(
+    task: &str,
+    history: &[IterationRecord],
+    plan: &Plan,
+    execution: &ExecutionReport,
+) -> String {
+    let mut request = format!("Goal: {task}\n");
+
+    for record in history {
+        request.push_str(&format!("\nIteration {}\n", record.iteration));
+        push_observation(&mut request, &record.plan, &record.execution);
+        request.push_str(&format!("Verdict: {}\n", record.assessment.summary));
+    }
+
+    request.push_str(&format!("\nIteration {} (latest)\n", history.len() + 1));
+    push_observation(&mut request, plan, execution);
+    request
+}
+
+fn push_observation(request: &mut String, plan: &Plan, execution: &ExecutionReport) {
+    request.push_str("Commands:\n");
+    push_commands(request, &plan.execute_commands);
+    let status = if execution.success {
+        "succeeded"
+    } else {
+        "failed"
+    };
+    request.push_str(&format!("Status: {status}\n"));
+    if let Some(step) = execution.failed_step() {
+        request.push_str(&format!("Failed at: {}\n", step.describe()));
+    }
+    for note in &execution.notes {
+        request.push_str(&format!("Note: {note}\n"));
+    }
+    if let Some(stdout) = &execution.stdout {
+        request.push_str(&format!("Stdout:\n{}\n", tail(stdout)));
+    }
+    if let Some(stderr) = &execution.stderr
+        && !stderr.trim().is_empty()
+    {
+        request.push_str(&format!("Stderr:\n{}\n", tail(stderr)));
+    }
+}
+
+fn push_commands(request: &mut String, commands: &[String]) {
+    if commands.is_empty() {
+        request.push_str("  (none)\n");
+    }
+    for (idx, cmd) in commands.iter().enumerate() {
+        request.push_str(&format!("  {}. {}\n", idx + 1, cmd));
+    }
+}
+
+fn tail(output: &str) -> String {
+    let count = output.chars().count();
+    if count <= OBSERVATION_OUTPUT_LIMIT {
+        return output.trim_end().to_string();
+    }
+    let kept: String = output
+        .chars()
+        .skip(count - OBSERVATION_OUTPUT_LIMIT)
+        .collect();
+    format!(
+        "[... {} characters truncated]\n{}",
+        count - OBSERVATION_OUTPUT_LIMIT,
+        kept.trim_end()
+    )
+}
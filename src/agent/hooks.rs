@@ -0,0 +1,310 @@
+//! User lifecycle hooks: shell commands from the `hooks` config section that run
+//! around planning and execution.
+//!
+//! Each hook receives the run so far as JSON on stdin. Exiting non-zero vetoes
+//! the run, and so does a hook that outlives `hooks.timeout_secs`. `post_plan`
+//! may also print a replacement plan, which is confidence-checked, reviewed and
+//! approved like the planner's.
+
+use std::fmt;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::cancel::{Signal, signal_group};
+use crate::config::HooksSettings;
+use crate::planner::{Plan, extract_json_object};
+
+use super::approval::ApprovalDecision;
+use super::context::{AgentContext, ContextSnapshot};
+use super::types::StageKind;
+
+/// Point in the pipeline at which a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookPoint {
+    PrePlan,
+    PostPlan,
+    PreExecute,
+    PostExecute,
+    OnFailure,
+}
+
+impl HookPoint {
+    pub fn command(self, hooks: &HooksSettings) -> Option<&str> {
+        let command = match self {
+            HookPoint::PrePlan => &hooks.pre_plan,
+            HookPoint::PostPlan => &hooks.post_plan,
+            HookPoint::PreExecute => &hooks.pre_execute,
+            HookPoint::PostExecute => &hooks.post_execute,
+            HookPoint::OnFailure => &hooks.on_failure,
+        };
+        command
+            .as_deref()
+            .filter(|command| !command.trim().is_empty())
+    }
+
+    /// Whether JSON printed by the hook replaces the current plan. Only
+    /// `post_plan` runs early enough for the replacement to be validated and
+    /// approved.
+    fn replaces_plan(self) -> bool {
+        matches!(self, HookPoint::PostPlan)
+    }
+}
+
+impl fmt::Display for HookPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            HookPoint::PrePlan => "pre_plan",
+            HookPoint::PostPlan => "post_plan",
+            HookPoint::PreExecute => "pre_execute",
+            HookPoint::PostExecute => "post_execute",
+            HookPoint::OnFailure => "on_failure",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// What a hook decided, as recorded in the event log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum HookVerdict {
+    Passed,
+    Vetoed { reason: String },
+    ReplacedPlan,
+}
+
+/// Document written to a hook's stdin.
+#[derive(Debug, Serialize)]
+struct HookPayload<'a> {
+    hook: HookPoint,
+    #[serde(flatten)]
+    context: ContextSnapshot<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+/// Run the hook configured for `point`, if any, applying a replacement plan to
+/// `context`. Returns `None` when no hook is configured.
+pub async fn run(
+    context: &mut AgentContext,
+    point: HookPoint,
+    error: Option<&str>,
+) -> Result<Option<HookVerdict>> {
+    let Some(command) = point.command(&context.config.hooks) else {
+        return Ok(None);
+    };
+    let command = command.to_string();
+
+    let payload = HookPayload {
+        hook: point,
+        context: context.snapshot(),
+        error,
+    };
+    let input = serde_json::to_vec(&payload).context("Failed to serialize hook input")?;
+
+    let timeout_secs = context.config.hooks.timeout_secs;
+    let output = spawn(
+        &command,
+        point,
+        context.config.execution.shell.program(),
+        &input,
+        (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs)),
+    )
+    .await
+    .with_context(|| format!("{point} hook '{command}' could not be run"))?;
+
+    let verdict = if let Some(output) = output {
+        verdict_for(context, point, output)?
+    } else {
+        HookVerdict::Vetoed {
+            reason: format!("{point} hook timed out after {timeout_secs}s"),
+        }
+    };
+
+    context.record_hook(point, verdict.clone());
+    Ok(Some(verdict))
+}
+
+fn verdict_for(
+    context: &mut AgentContext,
+    point: HookPoint,
+    output: HookOutput,
+) -> Result<HookVerdict> {
+    let verdict = if !output.status.success() {
+        HookVerdict::Vetoed {
+            reason: veto_reason(point, &output),
+        }
+    } else if point.replaces_plan() && !output.stdout.trim().is_empty() {
+        let json = extract_json_object(&output.stdout)
+            .ok_or_else(|| anyhow!("{point} hook printed output that is not a plan JSON object"))?;
+        let plan: Plan = serde_json::from_str(&json)
+            .with_context(|| format!("{point} hook printed an invalid plan"))?;
+        context.replace_plan(plan);
+        HookVerdict::ReplacedPlan
+    } else {
+        HookVerdict::Passed
+    };
+    Ok(verdict)
+}
+
+struct HookOutput {
+    status: std::process::ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+/// Run the hook in its own process group. Returns `None` when it is still
+/// running after `timeout`, in which case the whole group has been killed.
+async fn spawn(
+    command: &str,
+    point: HookPoint,
+    shell: &str,
+    input: &[u8],
+    timeout: Option<Duration>,
+) -> Result<Option<HookOutput>> {
+    let mut child = Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        .env("LI_HOOK", point.to_string())
+        .process_group(0)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut group = GroupGuard(child.id());
+
+    let stdin = child.stdin.take();
+    let write = async move {
+        if let Some(mut stdin) = stdin {
+            // Hooks that do not read their input close stdin early; that is not an error.
+            let _ = stdin.write_all(input).await;
+        }
+    };
+    // Collect output while writing, so a hook that prints before it reads
+    // cannot fill its stdout pipe while li waits for it to take its input.
+    let finished = async move {
+        let ((), output) = tokio::join!(write, child.wait_with_output());
+        output
+    };
+    let output = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, finished).await {
+            Ok(output) => output?,
+            Err(_) => return Ok(None),
+        },
+        None => finished.await?,
+    };
+    group.0 = None;
+
+    Ok(Some(HookOutput {
+        status: output.status,
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }))
+}
+
+/// Kills a hook's or plugin's process group, including anything it started in
+/// the background, unless it exited on its own first.
+pub(super) struct GroupGuard(pub(super) Option<u32>);
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        if let Some(group) = self.0 {
+            signal_group(group, Signal::Kill);
+        }
+    }
+}
+
+/// The last line the hook wrote to stderr, or its exit status.
+fn veto_reason(point: HookPoint, output: &HookOutput) -> String {
+    let detail = output
+        .stderr
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| match output.status.code() {
+            Some(code) => format!("exited with status {code}"),
+            None => "terminated by a signal".to_string(),
+        });
+    format!("{point} hook vetoed the run: {detail}")
+}
+
+/// How the orchestrator continues after the hooks around a stage.
+pub(crate) enum HookFlow {
+    Proceed,
+    /// End the run with the results gathered so far.
+    Stop,
+    /// End the run as cancelled with this reason.
+    Cancel(String),
+}
+
+/// Run the hooks that precede `stage`.
+pub(crate) async fn before_stage(context: &mut AgentContext, stage: StageKind) -> Result<HookFlow> {
+    match stage {
+        StageKind::Planning => {
+            if let Some(HookVerdict::Vetoed { reason }) =
+                run(context, HookPoint::PrePlan, None).await?
+            {
+                return Ok(HookFlow::Cancel(reason));
+            }
+        }
+        StageKind::Execution => {
+            let rejected = matches!(context.approval, Some(ApprovalDecision::Rejected { .. }));
+            if context.plan.is_some()
+                && !rejected
+                && let Some(HookVerdict::Vetoed { reason }) =
+                    run(context, HookPoint::PreExecute, None).await?
+            {
+                context.record_approval(ApprovalDecision::rejected(reason));
+                return Ok(HookFlow::Stop);
+            }
+        }
+        _ => {}
+    }
+    Ok(HookFlow::Proceed)
+}
+
+/// Run the hooks that follow a stage that completed normally.
+pub(crate) async fn after_stage(context: &mut AgentContext, stage: StageKind) -> Result<HookFlow> {
+    match stage {
+        StageKind::Planning if context.plan.is_some() => {
+            if let Some(HookVerdict::Vetoed { reason }) =
+                run(context, HookPoint::PostPlan, None).await?
+            {
+                context.record_approval(ApprovalDecision::rejected(reason));
+                return Ok(HookFlow::Stop);
+            }
+        }
+        StageKind::Execution => {
+            let Some(report) = &context.execution else {
+                return Ok(HookFlow::Proceed);
+            };
+            let failure = (!report.success).then(|| report.notes.join("; "));
+            let verdict = run(context, HookPoint::PostExecute, None).await?;
+            if let Some(error) = failure {
+                on_failure(context, &error).await;
+            }
+            if let Some(HookVerdict::Vetoed { reason }) = verdict {
+                if let Some(report) = context.execution.as_mut() {
+                    report.notes.push(reason);
+                }
+                return Ok(HookFlow::Stop);
+            }
+        }
+        _ => {}
+    }
+    Ok(HookFlow::Proceed)
+}
+
+/// Notify the `on_failure` hook; its exit status and output are ignored.
+pub(crate) async fn on_failure(context: &mut AgentContext, error: &str) {
+    if let Err(err) = run(context, HookPoint::OnFailure, Some(error)).await {
+        context.record_message(format!("{err:#}"));
+    }
+}
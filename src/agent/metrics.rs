@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::client::UsageMeter;
+use crate::config::{Config, LlmProvider};
+
+use super::types::StageKind;
+
+/// LLM usage counters at one point in a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageSnapshot {
+    pub llm_calls: u64,
+    pub tokens: u64,
+    pub retries: u64,
+}
+
+impl UsageSnapshot {
+    pub fn of(meter: &UsageMeter) -> Self {
+        Self {
+            llm_calls: meter.calls(),
+            tokens: meter.tokens(),
+            retries: meter.retries(),
+        }
+    }
+}
+
+/// Wall-clock time and LLM usage for one pass through one stage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageMetrics {
+    pub stage: StageKind,
+    pub duration_ms: u64,
+    pub llm_calls: u64,
+    pub tokens: u64,
+    pub retries: u64,
+}
+
+impl StageMetrics {
+    pub fn between(
+        stage: StageKind,
+        elapsed: Duration,
+        before: UsageSnapshot,
+        after: UsageSnapshot,
+    ) -> Self {
+        Self {
+            stage,
+            duration_ms: u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX),
+            llm_calls: after.llm_calls.saturating_sub(before.llm_calls),
+            tokens: after.tokens.saturating_sub(before.tokens),
+            retries: after.retries.saturating_sub(before.retries),
+        }
+    }
+}
+
+/// Total duration, LLM calls, tokens and retries across `metrics`.
+pub fn totals(metrics: &[StageMetrics]) -> (u64, u64, u64, u64) {
+    metrics.iter().fold((0, 0, 0, 0), |acc, stage| {
+        (
+            acc.0 + stage.duration_ms,
+            acc.1 + stage.llm_calls,
+            acc.2 + stage.tokens,
+            acc.3 + stage.retries,
+        )
+    })
+}
+
+/// One line of the metrics file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsRecord {
+    pub recorded_at: u64,
+    pub provider: LlmProvider,
+    pub model: String,
+    pub status: String,
+    pub duration_ms: u64,
+    pub llm_calls: u64,
+    pub tokens: u64,
+    pub retries: u64,
+    pub stages: Vec<StageMetrics>,
+}
+
+impl MetricsRecord {
+    pub fn new(config: &Config, status: &str, stages: &[StageMetrics]) -> Self {
+        let (duration_ms, llm_calls, tokens, retries) = totals(stages);
+        Self {
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            provider: config.llm.provider,
+            model: config.models.planner.clone(),
+            status: status.to_string(),
+            duration_ms,
+            llm_calls,
+            tokens,
+            retries,
+            stages: stages.to_vec(),
+        }
+    }
+}
+
+/// Append-only JSON-lines file of per-run metrics.
+#[derive(Debug, Clone)]
+pub struct MetricsLog {
+    path: PathBuf,
+}
+
+impl MetricsLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn default_path() -> Result<PathBuf> {
+        let mut path = home_dir().context("Could not determine home directory")?;
+        path.push(".li/metrics.jsonl");
+        Ok(path)
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(Self::default_path()?))
+    }
+
+    pub fn append(&self, record: &MetricsRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Unable to create metrics directory {}", parent.display())
+            })?;
+        }
+
+        let line = serde_json::to_string(record).context("Failed to serialize metrics record")?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        writeln!(file, "{line}").with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
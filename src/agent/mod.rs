@@ -1,24 +1,57 @@
 pub mod adapters;
+pub mod approval;
 pub mod context;
+pub mod goal;
+pub mod hooks;
+pub mod metrics;
+pub mod observer;
 pub mod orchestrator;
 pub mod outcome;
+pub mod plugin;
+pub mod policy;
+pub mod recovery;
+pub mod session;
 pub mod stages;
 pub mod types;
 
 #[allow(unused_imports)]
 pub use adapters::{
-    CommandValidationAdapter, DirectPlanningAdapter, NoopExecutionAdapter, NoopRecoveryAdapter,
-    PlanExecutionAdapter,
+    CommandValidationAdapter, DirectPlanningAdapter, EngineRecoveryAdapter, ModelGoalAdapter,
+    ModelReviewAdapter, NoopExecutionAdapter, NoopRecoveryAdapter, PlanExecutionAdapter,
+    SandboxPreviewAdapter,
 };
 #[allow(unused_imports)]
-pub use context::{AgentContext, AgentEvent, AgentRequest, AgentRun};
+pub use approval::{ApprovalDecision, ApprovalStage, Approver, AutoApprover, PolicyApprover};
+#[allow(unused_imports)]
+pub use context::{AgentContext, AgentEvent, AgentRequest, AgentRun, ContextSnapshot};
+#[allow(unused_imports)]
+pub use goal::{GoalAssessment, IterationRecord, LoopStop, LoopSummary};
+#[allow(unused_imports)]
+pub use hooks::{HookPoint, HookVerdict};
+#[allow(unused_imports)]
+pub use metrics::{MetricsLog, MetricsRecord, StageMetrics};
+#[allow(unused_imports)]
+pub use observer::{AgentObserver, JsonLinesObserver, LogObserver, TerminalProgressObserver};
 #[allow(unused_imports)]
 pub use orchestrator::{AgentOrchestrator, AgentPipelineBuilder};
 #[allow(unused_imports)]
 pub use outcome::{AgentOutcome, ExecutionReport, RecoveryOutcome};
 #[allow(unused_imports)]
+pub use plugin::{PLUGIN_PROTOCOL_VERSION, PluginOutcome, PluginResponse, PluginStage};
+#[allow(unused_imports)]
+pub use policy::ConfidenceDecision;
+#[allow(unused_imports)]
+pub use recovery::{
+    BestConfidenceChooser, InteractiveChooser, PolicyChooser, RecoveryChooser, chooser_for,
+};
+#[allow(unused_imports)]
+pub use session::{
+    Clarification, Session, SessionRecorder, SessionState, SessionStore, StepProgress,
+};
+#[allow(unused_imports)]
 pub use stages::{
-    AgentStage, ExecutionStage, PlanningStage, RecoveryStage, StageOutcome, ValidationStage,
+    AgentStage, AssessmentStage, ExecutionStage, PlanningStage, PreviewStage, RecoveryStage,
+    ReviewStage, StageOutcome, ValidationStage,
 };
 #[allow(unused_imports)]
 pub use types::StageKind;
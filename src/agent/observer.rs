@@ -0,0 +1,109 @@
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+use crate::client::verbose_log;
+
+use super::context::AgentEvent;
+use super::policy::ConfidenceDecision;
+use super::types::StageKind;
+
+/// Receives every agent event synchronously, as it is recorded.
+pub trait AgentObserver: Send + Sync {
+    fn on_event(&self, event: &AgentEvent);
+}
+
+/// Single-line progress indicator on stderr ("planning…", "validating…").
+///
+/// Execution is left alone because the executor streams its own output.
+pub struct TerminalProgressObserver;
+
+impl TerminalProgressObserver {
+    /// Returns the observer only when stderr is an interactive terminal.
+    pub fn for_stderr() -> Option<Self> {
+        io::stderr().is_terminal().then_some(Self)
+    }
+
+    fn show(status: &str) {
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[K⏳ {status}");
+        let _ = stderr.flush();
+    }
+
+    fn clear() {
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[K");
+        let _ = stderr.flush();
+    }
+}
+
+impl AgentObserver for TerminalProgressObserver {
+    fn on_event(&self, event: &AgentEvent) {
+        match event {
+            AgentEvent::StageStarted(stage) => match stage {
+                StageKind::Planning => Self::show("planning…"),
+                StageKind::Validation => Self::show("validating…"),
+                StageKind::Review => Self::show("reviewing…"),
+                StageKind::Preview => Self::show("previewing file changes…"),
+                StageKind::Assessment => Self::show("assessing goal…"),
+                StageKind::Plugin => Self::show("running plugin…"),
+                StageKind::Approval | StageKind::Execution | StageKind::Recovery => {}
+            },
+            AgentEvent::ConfidenceDecided {
+                decision: ConfidenceDecision::Replan { attempt },
+                ..
+            } => Self::show(&format!("re-planning (attempt {attempt})…")),
+            AgentEvent::StageCompleted(_)
+            | AgentEvent::StageSkipped { .. }
+            | AgentEvent::StageFailed { .. } => Self::clear(),
+            _ => {}
+        }
+    }
+}
+
+/// Writes one JSON object per event to a file or file descriptor.
+pub struct JsonLinesObserver {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesObserver {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Stream events to an already-open file descriptor inherited from the parent process.
+    pub fn open_fd(fd: u32) -> Result<Self> {
+        let path = format!("/dev/fd/{fd}");
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open event stream file descriptor {fd}"))?;
+        Ok(Self::new(file))
+    }
+}
+
+impl AgentObserver for JsonLinesObserver {
+    fn on_event(&self, event: &AgentEvent) {
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+        let Ok(mut writer) = self.writer.lock() else {
+            return;
+        };
+        // A reader that went away must not abort the run.
+        let _ = writeln!(writer, "{line}").and_then(|()| writer.flush());
+    }
+}
+
+/// Forwards events to the verbose log (`li -v`).
+pub struct LogObserver;
+
+impl AgentObserver for LogObserver {
+    fn on_event(&self, event: &AgentEvent) {
+        verbose_log(format!("agent event: {event:?}"));
+    }
+}
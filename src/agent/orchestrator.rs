@@ -1,69 +1,248 @@
+use std::sync::Arc;
+use std::time::Instant;
+
 use anyhow::Result;
 
+use crate::cancel::{CancelToken, INTERRUPTED};
+use crate::client::UsageMeter;
 use crate::config::Config;
 
 use super::adapters::{
-    CommandValidationAdapter, DirectPlanningAdapter, NoopExecutionAdapter, NoopRecoveryAdapter,
+    CommandValidationAdapter, DirectPlanningAdapter, EngineRecoveryAdapter, ModelReviewAdapter,
+    PlanExecutionAdapter, SandboxPreviewAdapter,
 };
-use super::context::{AgentContext, AgentRequest, AgentRun};
+use super::approval::{ApprovalStage, Approver, PolicyApprover};
+use super::context::{AgentContext, AgentEvent, AgentRequest, AgentRun};
+use super::hooks::{self, HookFlow};
+use super::metrics::StageMetrics;
+use super::observer::AgentObserver;
 use super::outcome::AgentOutcome;
+use super::session::{SessionRecorder, SessionState};
 use super::stages::{
-    AgentStage, ExecutionStage, PlanningStage, RecoveryStage, StageOutcome, ValidationStage,
+    AgentStage, AssessmentStage, ExecutionStage, PlanningStage, PreviewStage, RecoveryStage,
+    ReviewStage, StageOutcome, ValidationStage,
 };
+use super::types::StageKind;
 
 pub struct AgentOrchestrator {
     stages: Vec<Box<dyn AgentStage>>,
+    observers: Vec<Arc<dyn AgentObserver>>,
+    cancel: CancelToken,
+    session: Option<Arc<SessionRecorder>>,
+    usage: Option<Arc<UsageMeter>>,
 }
 
 impl AgentOrchestrator {
     pub fn new(stages: Vec<Box<dyn AgentStage>>) -> Self {
-        Self { stages }
+        Self {
+            stages,
+            observers: Vec::new(),
+            cancel: CancelToken::new(),
+            session: None,
+            usage: None,
+        }
     }
 
+    #[allow(dead_code)]
     pub fn builder() -> AgentPipelineBuilder {
         AgentPipelineBuilder::new()
     }
 
+    #[allow(dead_code)]
     pub fn stage_count(&self) -> usize {
         self.stages.len()
     }
 
     pub async fn run(&self, config: Config, request: AgentRequest) -> Result<AgentRun> {
-        let mut context = AgentContext::new(config, request);
-
-        for stage in &self.stages {
-            let kind = stage.kind();
-            context.record_stage_start(kind);
-            match stage.execute(&mut context).await {
-                Ok(StageOutcome::Continue) => {
-                    context.record_stage_end(kind);
+        let context = self.context(AgentContext::new(config, request));
+        self.drive(context, 0).await
+    }
+
+    /// Continue a saved session at its first unfinished stage.
+    pub async fn resume(&self, config: Config, state: SessionState) -> Result<AgentRun> {
+        let start = state.next_stage;
+        let mut context = self.context(AgentContext::restore(config, state));
+        if let Some(session) = &self.session {
+            context.record_event(AgentEvent::SessionResumed {
+                session: session.id().to_string(),
+                stage: start,
+            });
+        }
+        self.drive(context, start).await
+    }
+
+    fn context(&self, context: AgentContext) -> AgentContext {
+        let context = context
+            .with_observers(self.observers.clone())
+            .with_cancel_token(self.cancel.clone())
+            .with_session(self.session.clone());
+        match &self.usage {
+            Some(usage) => context.with_usage_meter(usage.clone()),
+            None => context,
+        }
+    }
+
+    async fn drive(&self, mut context: AgentContext, start: usize) -> Result<AgentRun> {
+        context.save_session();
+        let mut run = self.run_stages(context, start).await?;
+        if let Some(session) = &self.session
+            && let Err(err) = session.finish(&run.outcome)
+        {
+            run.events.push(AgentEvent::Message(format!(
+                "Could not save session: {err:#}"
+            )));
+        }
+        Ok(run)
+    }
+
+    async fn run_stages(&self, mut context: AgentContext, mut start: usize) -> Result<AgentRun> {
+        'iterations: loop {
+            for (index, stage) in self.stages.iter().enumerate().skip(start) {
+                // An interrupt that arrived while nothing could act on it (e.g. during
+                // a terminal prompt) ends the run before the next stage starts.
+                if context.cancel.cancel_if_interrupted() {
+                    return Ok(cancelled(context));
                 }
-                Ok(StageOutcome::Finished(outcome)) => {
-                    context.record_stage_end(kind);
-                    return Ok(context.into_run_with_outcome(outcome));
+
+                let kind = stage.kind();
+                let flow = hooks::before_stage(&mut context, kind).await;
+                context = match follow_hooks(context, kind, flow).await {
+                    Ok(context) => context,
+                    Err(run) => return Ok(run),
+                };
+
+                context.record_stage_start(kind);
+                let started = Instant::now();
+                let usage_before = context.usage_snapshot();
+                let result = stage.execute(&mut context).await;
+                context.record_stage_metrics(StageMetrics::between(
+                    kind,
+                    started.elapsed(),
+                    usage_before,
+                    context.usage_snapshot(),
+                ));
+                if context.cancel.is_cancelled() {
+                    return Ok(cancelled(context));
                 }
-                Err(error) => {
-                    let message = error.to_string();
-                    context.record_stage_failure(kind, &message);
-                    let outcome = AgentOutcome::failed(kind, message);
-                    return Ok(context.into_run_with_outcome(outcome));
+                match result {
+                    Ok(StageOutcome::Continue) => {
+                        context.record_stage_end(kind);
+                        context.next_stage = index + 1;
+                        context.save_session();
+                        let flow = hooks::after_stage(&mut context, kind).await;
+                        context = match follow_hooks(context, kind, flow).await {
+                            Ok(context) => context,
+                            Err(run) => return Ok(run),
+                        };
+                    }
+                    Ok(StageOutcome::Repeat) => {
+                        context.record_stage_end(kind);
+                        context.start_next_iteration();
+                        context.next_stage = 0;
+                        context.save_session();
+                        start = 0;
+                        continue 'iterations;
+                    }
+                    Ok(StageOutcome::Finished(outcome)) => {
+                        context.record_stage_end(kind);
+                        return Ok(context.into_run_with_outcome(outcome));
+                    }
+                    Err(error) => return Ok(stage_failed(context, kind, error).await),
                 }
             }
+
+            return Ok(context.into_run());
         }
+    }
+}
+
+/// End the run as cancelled, keeping whatever the stages produced so far.
+fn cancelled(mut context: AgentContext) -> AgentRun {
+    let reason = context
+        .cancel
+        .reason()
+        .unwrap_or_else(|| INTERRUPTED.to_string());
+    context.record_cancellation(&reason);
+    context.into_cancelled_run(reason)
+}
 
-        Ok(context.into_run())
+/// Hand the context back when the hooks around `kind` let the run continue, or
+/// the finished run when they ended it.
+async fn follow_hooks(
+    context: AgentContext,
+    kind: StageKind,
+    flow: Result<HookFlow>,
+) -> std::result::Result<AgentContext, AgentRun> {
+    match flow {
+        Ok(HookFlow::Proceed) => Ok(context),
+        Ok(HookFlow::Stop) => Err(context.into_run()),
+        Ok(HookFlow::Cancel(reason)) => {
+            Err(context.into_run_with_outcome(AgentOutcome::cancelled(reason)))
+        }
+        Err(error) => Err(stage_failed(context, kind, error).await),
     }
 }
 
+/// Record a stage failure, tell the `on_failure` hook and end the run.
+async fn stage_failed(
+    mut context: AgentContext,
+    kind: StageKind,
+    error: anyhow::Error,
+) -> AgentRun {
+    let message = error.to_string();
+    context.record_stage_failure(kind, &message);
+    hooks::on_failure(&mut context, &message).await;
+    context.into_run_with_outcome(AgentOutcome::failed(kind, message))
+}
+
 pub struct AgentPipelineBuilder {
     stages: Vec<Box<dyn AgentStage>>,
+    observers: Vec<Arc<dyn AgentObserver>>,
+    cancel: CancelToken,
+    session: Option<Arc<SessionRecorder>>,
+    usage: Option<Arc<UsageMeter>>,
 }
 
 impl AgentPipelineBuilder {
     pub fn new() -> Self {
-        Self { stages: Vec::new() }
+        Self {
+            stages: Vec::new(),
+            observers: Vec::new(),
+            cancel: CancelToken::new(),
+            session: None,
+            usage: None,
+        }
+    }
+
+    /// Save the run to `session` after every stage and step so it can be resumed.
+    pub fn with_session(mut self, session: SessionRecorder) -> Self {
+        self.session = Some(Arc::new(session));
+        self
+    }
+
+    /// Stop LLM calls and plan commands when `cancel` is interrupted or cancelled.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Count the run's LLM calls in `usage`, so calls made through other clients
+    /// sharing it, such as a step gate's, show up in the stage metrics.
+    pub fn with_usage_meter(mut self, usage: Arc<UsageMeter>) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Notify `observer` of every event while the pipeline runs.
+    pub fn with_observer<O>(mut self, observer: O) -> Self
+    where
+        O: AgentObserver + 'static,
+    {
+        self.observers.push(Arc::new(observer));
+        self
     }
 
+    #[allow(dead_code)]
     pub fn add_stage<S>(mut self, stage: S) -> Self
     where
         S: AgentStage + 'static,
@@ -88,6 +267,30 @@ impl AgentPipelineBuilder {
         self
     }
 
+    pub fn with_review_adapter<R>(mut self, adapter: R) -> Self
+    where
+        R: super::adapters::ReviewAdapter + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(ReviewStage::new(adapter)));
+        self
+    }
+
+    pub fn with_preview_adapter<P>(mut self, adapter: P) -> Self
+    where
+        P: super::adapters::PreviewAdapter + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(PreviewStage::new(adapter)));
+        self
+    }
+
+    pub fn with_approver<A>(mut self, approver: A) -> Self
+    where
+        A: Approver + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(ApprovalStage::new(approver)));
+        self
+    }
+
     pub fn with_execution_adapter<E>(mut self, adapter: E) -> Self
     where
         E: super::adapters::ExecutionAdapter + Send + Sync + 'static,
@@ -104,15 +307,41 @@ impl AgentPipelineBuilder {
         self
     }
 
-    pub fn with_default_adapters(self) -> Self {
+    /// Assess each execution against the goal, looping back to planning until it is reached.
+    pub fn with_goal_adapter<G>(mut self, adapter: G) -> Self
+    where
+        G: super::adapters::GoalAdapter + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(AssessmentStage::new(adapter)));
+        self
+    }
+
+    /// Planning, validation, review, preview, approval, execution and recovery,
+    /// gated by `approver`.
+    pub fn with_standard_stages<A>(self, approver: A) -> Self
+    where
+        A: Approver + Send + Sync + 'static,
+    {
         self.with_planning_adapter(DirectPlanningAdapter::default())
             .with_validation_adapter(CommandValidationAdapter)
-            .with_execution_adapter(NoopExecutionAdapter)
-            .with_recovery_adapter(NoopRecoveryAdapter)
+            .with_review_adapter(ModelReviewAdapter::default())
+            .with_preview_adapter(SandboxPreviewAdapter)
+            .with_approver(approver)
+            .with_execution_adapter(PlanExecutionAdapter::default())
+            .with_recovery_adapter(EngineRecoveryAdapter::default())
+    }
+
+    pub fn with_default_adapters(self) -> Self {
+        self.with_standard_stages(PolicyApprover)
     }
 
     pub fn build(self) -> AgentOrchestrator {
-        AgentOrchestrator::new(self.stages)
+        let mut orchestrator = AgentOrchestrator::new(self.stages);
+        orchestrator.observers = self.observers;
+        orchestrator.cancel = self.cancel;
+        orchestrator.session = self.session;
+        orchestrator.usage = self.usage;
+        orchestrator
     }
 }
 
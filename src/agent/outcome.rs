@@ -1,45 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+use crate::exec::{PreviewReport, StepDecision, StepResult, StepStatus};
 use crate::planner::Plan;
+use crate::reviewer::PlanReview;
 use crate::validator::ValidationResult;
 
+use super::approval::ApprovalDecision;
+use super::policy::ConfidenceDecision;
 use super::types::StageKind;
 
 /// Summary of command execution produced by the executor stage.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ExecutionReport {
     pub commands: Vec<String>,
     pub success: bool,
+    /// Stdout of every step, each under a `$ command` line.
     pub stdout: Option<String>,
+    /// Stderr of every step, each under a `$ command` line.
     pub stderr: Option<String>,
     pub notes: Vec<String>,
+    /// One entry per step that ran, in order.
+    #[serde(default)]
+    pub steps: Vec<StepResult>,
+    /// Snapshot taken before the plan ran, restored with `li undo <id>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undo: Option<String>,
 }
 
 impl ExecutionReport {
     pub fn skipped(note: impl Into<String>) -> Self {
+        Self::failed(Vec::new(), note)
+    }
+
+    pub fn failed(commands: Vec<String>, error: impl Into<String>) -> Self {
         Self {
-            commands: Vec::new(),
+            commands,
             success: false,
             stdout: None,
             stderr: None,
-            notes: vec![note.into()],
+            notes: vec![error.into()],
+            steps: Vec::new(),
+            undo: None,
         }
     }
+
+    /// Report for a plan whose steps ran as recorded in `steps`.
+    pub fn from_steps(commands: Vec<String>, steps: Vec<StepResult>, notes: Vec<String>) -> Self {
+        Self {
+            commands,
+            success: steps.iter().all(StepResult::allows_next),
+            stdout: labelled(&steps, |step| &step.stdout),
+            stderr: labelled(&steps, |step| &step.stderr),
+            notes,
+            steps,
+            undo: None,
+        }
+    }
+
+    /// Whether every command ran as planned, with none skipped (in step-by-step
+    /// mode or after Ctrl-C) or edited.
+    pub fn ran_as_planned(&self) -> bool {
+        !self.steps.iter().any(|step| {
+            step.status == StepStatus::Skipped
+                || matches!(
+                    step.decision,
+                    Some(StepDecision::Skipped | StepDecision::Edited)
+                )
+        })
+    }
+
+    /// The step that stopped the plan, if one did.
+    pub fn failed_step(&self) -> Option<&StepResult> {
+        self.steps.iter().find(|step| !step.allows_next())
+    }
+
+    /// Every step with how it ended and its stdout and stderr, for prompts.
+    pub fn transcript(&self) -> String {
+        let mut transcript = String::new();
+        for step in &self.steps {
+            transcript.push_str(&format!("$ {}\n", step.command));
+            for output in [&step.stdout, &step.stderr] {
+                if !output.trim().is_empty() {
+                    transcript.push_str(output.trim_end());
+                    transcript.push('\n');
+                }
+            }
+            if !step.succeeded() {
+                transcript.push_str(&format!("[{}]\n", step.status));
+            }
+        }
+        transcript
+    }
+}
+
+fn labelled(steps: &[StepResult], output: impl Fn(&StepResult) -> &String) -> Option<String> {
+    let mut combined = String::new();
+    for step in steps {
+        let text = output(step);
+        if text.trim().is_empty() {
+            continue;
+        }
+        combined.push_str(&format!("$ {}\n{}", step.command, text));
+        if !text.ends_with('\n') {
+            combined.push('\n');
+        }
+    }
+    (!combined.is_empty()).then_some(combined)
 }
 
 /// High-level recovery outcome used to log recovery attempts.
-#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
 pub enum RecoveryOutcome {
+    /// Nothing needed recovering, or recovery is disabled.
     Skipped,
-    AlternativeApplied { command: String },
-    Installed { command: String },
+    AlternativeApplied {
+        missing: String,
+        command: String,
+    },
+    Installed {
+        missing: String,
+        command: String,
+    },
+    StepSkipped {
+        missing: String,
+    },
+    /// Every option tried for `missing` failed or none was offered.
+    Unrecovered {
+        missing: String,
+        attempted: Vec<String>,
+    },
+    RetryRequested,
+    Aborted {
+        reason: String,
+    },
     Cancelled,
 }
 
 /// Terminal result returned by the agent orchestrator.
-#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant, dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
 pub enum AgentOutcome {
     Planned {
         plan: Option<Plan>,
+        decision: Option<ConfidenceDecision>,
         validation: Option<ValidationResult>,
+        review: Option<PlanReview>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preview: Option<PreviewReport>,
+        approval: Option<ApprovalDecision>,
         execution: Option<ExecutionReport>,
         recovery: Option<RecoveryOutcome>,
     },
@@ -49,6 +160,11 @@ pub enum AgentOutcome {
     },
     Cancelled {
         reason: String,
+        /// Plan and execution results gathered before the run was cancelled.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        plan: Option<Plan>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        execution: Option<ExecutionReport>,
     },
     Failed {
         stage: StageKind,
@@ -57,6 +173,24 @@ pub enum AgentOutcome {
 }
 
 impl AgentOutcome {
+    /// The `status` tag this outcome serializes with.
+    pub fn status(&self) -> &'static str {
+        match self {
+            AgentOutcome::Planned { .. } => "planned",
+            AgentOutcome::AwaitingClarification { .. } => "awaiting_clarification",
+            AgentOutcome::Cancelled { .. } => "cancelled",
+            AgentOutcome::Failed { .. } => "failed",
+        }
+    }
+
+    pub fn cancelled(reason: impl Into<String>) -> Self {
+        Self::Cancelled {
+            reason: reason.into(),
+            plan: None,
+            execution: None,
+        }
+    }
+
     pub fn failed(stage: StageKind, error: impl Into<String>) -> Self {
         Self::Failed {
             stage,
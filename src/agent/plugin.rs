@@ -0,0 +1,228 @@
+//! Stages implemented by external executables.
+//!
+//! Protocol version 1: li starts the plugin once per stage pass, writes one
+//! [`PluginRequest`] JSON object to its stdin and closes it, then reads one
+//! [`PluginResponse`] JSON object from its stdout. Stderr is passed through to
+//! the user. A non-zero exit fails the stage, and so does a plugin that outlives
+//! its `timeout_secs`; it is then killed together with anything it started.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::cancel::CancelToken;
+use crate::config::DEFAULT_PLUGIN_TIMEOUT_SECS;
+use crate::planner::{Plan, extract_json_object};
+
+use super::approval::ApprovalDecision;
+use super::context::{AgentContext, ContextSnapshot};
+use super::hooks::GroupGuard;
+use super::outcome::AgentOutcome;
+use super::stages::{AgentStage, StageOutcome};
+use super::types::StageKind;
+
+/// Version of the plugin protocol this build speaks.
+pub const PLUGIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Document written to a plugin's stdin.
+#[derive(Debug, Serialize)]
+pub struct PluginRequest<'a> {
+    pub protocol: u32,
+    /// The plugin's name from the pipeline config.
+    pub stage: &'a str,
+    pub context: ContextSnapshot<'a>,
+}
+
+/// What the plugin wants the pipeline to do next.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum PluginOutcome {
+    Continue,
+    /// Reject the plan: later stages run, but execution is skipped.
+    Reject {
+        reason: String,
+    },
+    /// End the run without executing anything.
+    Cancel {
+        reason: String,
+    },
+    /// Fail the stage.
+    Fail {
+        error: String,
+    },
+}
+
+/// Document a plugin prints on stdout.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PluginResponse {
+    pub protocol: u32,
+    #[serde(flatten)]
+    pub outcome: PluginOutcome,
+    /// Replacement for the current plan.
+    #[serde(default)]
+    pub plan: Option<Plan>,
+    /// Messages recorded in the run's event log.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl PluginResponse {
+    pub fn parse(name: &str, stdout: &str) -> Result<Self> {
+        let json = extract_json_object(stdout)
+            .ok_or_else(|| anyhow!("Plugin '{name}' did not print a JSON response"))?;
+        let response: PluginResponse = serde_json::from_str(&json)
+            .with_context(|| format!("Plugin '{name}' printed an invalid response"))?;
+        if response.protocol != PLUGIN_PROTOCOL_VERSION {
+            bail!(
+                "Plugin '{name}' speaks protocol version {}, but li supports version {PLUGIN_PROTOCOL_VERSION}",
+                response.protocol
+            );
+        }
+        Ok(response)
+    }
+}
+
+/// Runs an external executable as a pipeline stage.
+pub struct PluginStage {
+    name: String,
+    command: String,
+    args: Vec<String>,
+    timeout: Option<Duration>,
+}
+
+impl PluginStage {
+    pub fn new(name: impl Into<String>, command: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            command: command.into(),
+            args,
+            timeout: Some(Duration::from_secs(DEFAULT_PLUGIN_TIMEOUT_SECS)),
+        }
+    }
+
+    /// Kill the plugin and fail the stage when it runs longer than `timeout`.
+    /// `None` lets it run until it exits or the run is interrupted.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the plugin in its own process group, which is killed when the run
+    /// is interrupted or the plugin times out.
+    async fn call(&self, request: &PluginRequest<'_>, cancel: &CancelToken) -> Result<String> {
+        let input = serde_json::to_vec(request).context("Failed to serialize plugin request")?;
+
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .process_group(0)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| {
+                format!("Failed to start plugin '{}' ({})", self.name, self.command)
+            })?;
+        let mut group = GroupGuard(child.id());
+
+        let stdin = child.stdin.take();
+        let write = async move {
+            if let Some(mut stdin) = stdin {
+                // A plugin that ignores its input may exit before reading it.
+                let _ = stdin.write_all(&input).await;
+            }
+        };
+        // Read while writing, so a plugin that answers before it has read the
+        // whole request cannot block on a full stdout pipe.
+        let finished = async move {
+            let ((), output) = tokio::join!(write, child.wait_with_output());
+            output
+        };
+        let limited = async {
+            match self.timeout {
+                Some(limit) => tokio::time::timeout(limit, finished).await.ok(),
+                None => Some(finished.await),
+            }
+        };
+
+        let output = tokio::select! {
+            output = limited => match output {
+                Some(output) => {
+                    output.with_context(|| format!("Plugin '{}' did not finish", self.name))?
+                }
+                None => bail!(
+                    "Plugin '{}' timed out after {}s",
+                    self.name,
+                    self.timeout.unwrap_or_default().as_secs()
+                ),
+            },
+            () = cancel.interrupted() => {
+                cancel.cancel_if_interrupted();
+                bail!("Plugin '{}' cancelled", self.name);
+            }
+        };
+        group.0 = None;
+
+        if !output.status.success() {
+            bail!("Plugin '{}' exited with {}", self.name, output.status);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl AgentStage for PluginStage {
+    fn kind(&self) -> StageKind {
+        StageKind::Plugin
+    }
+
+    async fn execute(&self, context: &mut AgentContext) -> Result<StageOutcome> {
+        let request = PluginRequest {
+            protocol: PLUGIN_PROTOCOL_VERSION,
+            stage: &self.name,
+            context: context.snapshot(),
+        };
+        let stdout = self.call(&request, &context.cancel).await?;
+        let response = PluginResponse::parse(&self.name, &stdout)?;
+
+        for message in response.events {
+            context.record_message(format!("{}: {message}", self.name));
+        }
+        if let Some(plan) = response.plan {
+            // Only the stages after this one see a replacement, so it would run
+            // unchecked once validation, review, preview or approval has passed.
+            if context.validation.is_some()
+                || context.review.is_some()
+                || context.preview.is_some()
+                || context.approval.is_some()
+                || context.execution.is_some()
+            {
+                bail!(
+                    "Plugin '{}' returned a plan after the current plan was checked; \
+                     plugins that replace the plan must come before validation, review, preview and approval",
+                    self.name
+                );
+            }
+            context.replace_plan(plan);
+        }
+
+        match response.outcome {
+            PluginOutcome::Continue => Ok(StageOutcome::Continue),
+            PluginOutcome::Reject { reason } => {
+                context.record_approval(ApprovalDecision::rejected(format!(
+                    "{}: {reason}",
+                    self.name
+                )));
+                Ok(StageOutcome::Continue)
+            }
+            PluginOutcome::Cancel { reason } => Ok(StageOutcome::Finished(
+                AgentOutcome::cancelled(format!("{}: {reason}", self.name)),
+            )),
+            PluginOutcome::Fail { error } => Err(anyhow!("{}: {error}", self.name)),
+        }
+    }
+}
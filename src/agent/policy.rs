@@ -0,0 +1,111 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfidenceSettings;
+use crate::planner::Plan;
+
+const CONTEXT_ENTRY_LIMIT: usize = 40;
+
+/// Action taken by the confidence policy for a produced plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfidenceDecision {
+    /// Confidence was below the re-plan threshold; planning is retried with more context.
+    Replan { attempt: usize },
+    /// Confidence is acceptable; the plan runs with its dry-run phase.
+    Proceed,
+    /// Confidence is high enough to run the execute commands without the dry-run phase.
+    SkipDryRun,
+    /// Confidence is below the refusal threshold; execution requires `--force`.
+    Refuse,
+    /// Confidence is below the refusal threshold but the user passed `--force`.
+    Forced,
+}
+
+impl ConfidenceDecision {
+    pub fn blocks_execution(self) -> bool {
+        matches!(self, ConfidenceDecision::Refuse)
+    }
+
+    pub fn skips_dry_run(self) -> bool {
+        matches!(self, ConfidenceDecision::SkipDryRun)
+    }
+}
+
+impl fmt::Display for ConfidenceDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfidenceDecision::Replan { attempt } => write!(f, "re-plan (attempt {attempt})"),
+            ConfidenceDecision::Proceed => write!(f, "proceed"),
+            ConfidenceDecision::SkipDryRun => write!(f, "skip dry-run"),
+            ConfidenceDecision::Refuse => write!(f, "refuse"),
+            ConfidenceDecision::Forced => write!(f, "forced"),
+        }
+    }
+}
+
+/// Whether a plan with `confidence` should be re-planned after `attempts` re-plans so far.
+pub fn should_replan(settings: &ConfidenceSettings, confidence: f32, attempts: usize) -> bool {
+    confidence < settings.replan_below && attempts < settings.max_replans
+}
+
+/// Final decision for a plan once re-planning is exhausted.
+pub fn decide(settings: &ConfidenceSettings, confidence: f32, force: bool) -> ConfidenceDecision {
+    if confidence < settings.refuse_below {
+        if force {
+            ConfidenceDecision::Forced
+        } else {
+            ConfidenceDecision::Refuse
+        }
+    } else if settings
+        .skip_dry_run_at
+        .is_some_and(|threshold| confidence >= threshold)
+    {
+        ConfidenceDecision::SkipDryRun
+    } else {
+        ConfidenceDecision::Proceed
+    }
+}
+
+/// Extra context appended to the task when a low-confidence plan is re-planned.
+pub fn replan_context(previous: &Plan) -> String {
+    let mut context = format!(
+        "Your previous plan had low confidence ({:.2}).",
+        previous.confidence
+    );
+    if !previous.notes.trim().is_empty() {
+        context.push_str(&format!(" Its notes were: {}", previous.notes.trim()));
+    }
+    context.push_str(
+        "\nIf essential information is missing, ask one clarifying question. \
+         Otherwise return a better-grounded plan that uses dry-run commands to discover what you are unsure about.",
+    );
+
+    context.push_str(&format!("\n\nEnvironment:\n- OS: {}", std::env::consts::OS));
+    if let Ok(cwd) = std::env::current_dir() {
+        context.push_str(&format!("\n- Working directory: {}", cwd.display()));
+        if let Ok(entries) = std::fs::read_dir(&cwd) {
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if entry.path().is_dir() {
+                        format!("{name}/")
+                    } else {
+                        name
+                    }
+                })
+                .collect();
+            names.sort();
+            let total = names.len();
+            names.truncate(CONTEXT_ENTRY_LIMIT);
+            context.push_str(&format!("\n- Entries: {}", names.join(", ")));
+            if total > CONTEXT_ENTRY_LIMIT {
+                context.push_str(&format!(" (and {} more)", total - CONTEXT_ENTRY_LIMIT));
+            }
+        }
+    }
+
+    context
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::config::RecoveryChooserKind;
+use crate::recovery::{self, RecoveryChoice, RecoveryEngine, RecoveryOptions, RecoveryStrategy};
+use crate::validator::MissingCommand;
+
+use super::approval::policy_violation;
+
+/// Picks how to recover from a missing command and which offered option to use.
+#[async_trait]
+pub trait RecoveryChooser: Send + Sync {
+    /// How recovery options should be generated for `missing`.
+    fn strategy(&self, missing: &MissingCommand) -> Result<RecoveryStrategy>;
+
+    async fn choose(
+        &self,
+        engine: &RecoveryEngine,
+        options: &RecoveryOptions,
+        missing: &MissingCommand,
+    ) -> Result<RecoveryChoice>;
+
+    /// Whether installations still need a terminal confirmation once chosen.
+    fn confirms_installs(&self) -> bool {
+        false
+    }
+}
+
+/// Build the chooser selected by `recovery.chooser`.
+pub fn chooser_for(kind: RecoveryChooserKind) -> Box<dyn RecoveryChooser> {
+    match kind {
+        RecoveryChooserKind::Interactive => Box::new(InteractiveChooser),
+        RecoveryChooserKind::Policy => Box::new(PolicyChooser),
+        RecoveryChooserKind::BestConfidence => Box::new(BestConfidenceChooser),
+    }
+}
+
+/// Asks the user for a strategy and shows the recovery menu.
+pub struct InteractiveChooser;
+
+#[async_trait]
+impl RecoveryChooser for InteractiveChooser {
+    fn strategy(&self, _missing: &MissingCommand) -> Result<RecoveryStrategy> {
+        recovery::prompt_recovery_strategy()
+    }
+
+    async fn choose(
+        &self,
+        engine: &RecoveryEngine,
+        options: &RecoveryOptions,
+        missing: &MissingCommand,
+    ) -> Result<RecoveryChoice> {
+        engine.present_recovery_menu(options, missing).await
+    }
+
+    fn confirms_installs(&self) -> bool {
+        true
+    }
+}
+
+/// Takes the alternative or installation the model is most confident in.
+pub struct BestConfidenceChooser;
+
+#[async_trait]
+impl RecoveryChooser for BestConfidenceChooser {
+    fn strategy(&self, _missing: &MissingCommand) -> Result<RecoveryStrategy> {
+        Ok(RecoveryStrategy::InstallationFirst)
+    }
+
+    async fn choose(
+        &self,
+        _engine: &RecoveryEngine,
+        options: &RecoveryOptions,
+        _missing: &MissingCommand,
+    ) -> Result<RecoveryChoice> {
+        Ok(best_choice(options, |_| true))
+    }
+}
+
+/// Like [`BestConfidenceChooser`], but only considers options whose programs
+/// are all listed in `approval.allowed_commands`.
+pub struct PolicyChooser;
+
+#[async_trait]
+impl RecoveryChooser for PolicyChooser {
+    fn strategy(&self, _missing: &MissingCommand) -> Result<RecoveryStrategy> {
+        Ok(RecoveryStrategy::InstallationFirst)
+    }
+
+    async fn choose(
+        &self,
+        engine: &RecoveryEngine,
+        options: &RecoveryOptions,
+        _missing: &MissingCommand,
+    ) -> Result<RecoveryChoice> {
+        let allowed = &engine.config.approval.allowed_commands;
+        Ok(best_choice(options, |command| {
+            policy_violation(command, allowed).is_none()
+        }))
+    }
+}
+
+/// Highest-confidence option accepted by `permitted`, preferring alternatives on
+/// ties; skips the step when nothing qualifies and skipping is allowed.
+fn best_choice(options: &RecoveryOptions, permitted: impl Fn(&str) -> bool) -> RecoveryChoice {
+    let alternatives = options
+        .command_alternatives
+        .iter()
+        .enumerate()
+        .filter(|(_, alt)| permitted(&alt.command))
+        .map(|(index, alt)| (alt.confidence, RecoveryChoice::UseAlternative(index)));
+    let installs = options
+        .installation_instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, inst)| inst.install_commands.iter().all(|cmd| permitted(cmd)))
+        .map(|(index, inst)| (inst.confidence, RecoveryChoice::InstallCommand(index)));
+
+    let mut best: Option<(f32, RecoveryChoice)> = None;
+    for (confidence, choice) in alternatives.chain(installs) {
+        if best.as_ref().is_none_or(|(top, _)| confidence > *top) {
+            best = Some((confidence, choice));
+        }
+    }
+
+    match best {
+        Some((_, choice)) => choice,
+        None if options.can_skip_step => RecoveryChoice::SkipStep,
+        None => RecoveryChoice::AbortPlan,
+    }
+}
@@ -0,0 +1,270 @@
+//! Saved agent runs under `~/.li/sessions`, one JSON file per run.
+//!
+//! The file is rewritten after every stage and plan step, so a run that was
+//! cancelled, lost its network connection or died with the machine can pick up
+//! at the next unfinished stage or step with `li resume`. Runs that finish
+//! normally remove their file.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::config::StageSpec;
+use crate::exec::PreviewReport;
+use crate::planner::Plan;
+use crate::reviewer::PlanReview;
+use crate::validator::ValidationResult;
+
+use super::approval::ApprovalDecision;
+use super::context::{AgentEvent, AgentRequest};
+use super::goal::{IterationRecord, LoopStop};
+use super::metrics::StageMetrics;
+use super::outcome::{AgentOutcome, ExecutionReport, RecoveryOutcome};
+use super::policy::ConfidenceDecision;
+
+/// A planner question answered with `li resume --answer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clarification {
+    pub question: String,
+    pub answer: String,
+}
+
+/// Plan steps of the current plan that have already completed successfully.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepProgress {
+    pub dry_run: usize,
+    pub execute: usize,
+}
+
+/// The parts of an agent context that survive a restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub request: AgentRequest,
+    /// Index of the first pipeline stage that has not completed yet.
+    pub next_stage: usize,
+    pub plan: Option<Plan>,
+    pub decision: Option<ConfidenceDecision>,
+    pub validation: Option<ValidationResult>,
+    pub review: Option<PlanReview>,
+    #[serde(default)]
+    pub preview: Option<PreviewReport>,
+    pub approval: Option<ApprovalDecision>,
+    pub execution: Option<ExecutionReport>,
+    pub recovery: Option<RecoveryOutcome>,
+    #[serde(default)]
+    pub iterations: Vec<IterationRecord>,
+    pub loop_stop: Option<LoopStop>,
+    #[serde(default)]
+    pub clarifications: Vec<Clarification>,
+    #[serde(default)]
+    pub progress: StepProgress,
+    #[serde(default)]
+    pub events: Vec<AgentEvent>,
+    #[serde(default)]
+    pub metrics: Vec<StageMetrics>,
+}
+
+/// One saved run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Working directory the run was started in.
+    pub cwd: PathBuf,
+    /// Pipeline the run was started with.
+    pub stages: Vec<StageSpec>,
+    /// Whether `stages` came from the config file rather than the built-in pipeline.
+    #[serde(default)]
+    pub configured: bool,
+    pub state: SessionState,
+    /// How the last attempt ended; `None` while it runs or when `li` died.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<AgentOutcome>,
+}
+
+impl Session {
+    /// Short description of where the run stopped.
+    pub fn status(&self) -> &'static str {
+        match &self.outcome {
+            Some(outcome) => outcome.status(),
+            None => "interrupted",
+        }
+    }
+}
+
+/// Directory of saved sessions.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn default_dir() -> Result<PathBuf> {
+        let mut path = home_dir().context("Could not determine home directory")?;
+        path.push(".li/sessions");
+        Ok(path)
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(Self::default_dir()?))
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    /// Write `session`, replacing any earlier copy in one step.
+    pub fn save(&self, session: &Session) -> Result<()> {
+        fs::create_dir_all(&self.dir).with_context(|| {
+            format!("Unable to create session directory {}", self.dir.display())
+        })?;
+
+        let json = serde_json::to_vec_pretty(session).context("Failed to serialize session")?;
+        let path = self.path(&session.id);
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, json)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn load(&self, id: &str) -> Result<Session> {
+        let path = self.path(id);
+        if !path.exists() {
+            bail!("No saved session '{id}' in {}", self.dir.display());
+        }
+        let contents =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("Session file {} is not valid", path.display()))
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let path = self.path(id);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err).with_context(|| format!("Failed to remove {}", path.display())),
+        }
+    }
+
+    /// Saved sessions, most recently updated first. Unreadable files are skipped.
+    pub fn list(&self) -> Result<Vec<Session>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", self.dir.display()));
+            }
+        };
+
+        let mut sessions = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| fs::read(&path).ok())
+            .filter_map(|contents| serde_json::from_slice::<Session>(&contents).ok())
+            .collect::<Vec<_>>();
+        sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+        Ok(sessions)
+    }
+
+    pub fn latest(&self) -> Result<Option<Session>> {
+        Ok(self.list()?.into_iter().next())
+    }
+}
+
+/// Keeps the session file of one run up to date.
+#[derive(Debug)]
+pub struct SessionRecorder {
+    store: SessionStore,
+    id: String,
+    created_at: u64,
+    cwd: PathBuf,
+    stages: Vec<StageSpec>,
+    configured: bool,
+    /// Set once a save has failed, so the warning is only recorded once.
+    warned: AtomicBool,
+}
+
+impl SessionRecorder {
+    /// Record a new run of `stages`.
+    pub fn start(store: SessionStore, stages: Vec<StageSpec>, configured: bool) -> Self {
+        let created_at = now();
+        Self {
+            store,
+            id: format!("{created_at}-{}", process::id()),
+            created_at,
+            cwd: std::env::current_dir().unwrap_or_default(),
+            stages,
+            configured,
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Keep recording into an existing session.
+    pub fn resume(store: SessionStore, session: &Session) -> Self {
+        Self {
+            store,
+            id: session.id.clone(),
+            created_at: session.created_at,
+            cwd: session.cwd.clone(),
+            stages: session.stages.clone(),
+            configured: session.configured,
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn save(&self, state: SessionState) -> Result<()> {
+        self.write(state, None)
+    }
+
+    /// Remove the session when the run finished normally, otherwise keep it
+    /// with the outcome so `li resume` can continue it.
+    pub fn finish(&self, outcome: &AgentOutcome) -> Result<()> {
+        if matches!(outcome, AgentOutcome::Planned { .. }) {
+            return self.store.remove(&self.id);
+        }
+        let session = self.store.load(&self.id)?;
+        self.write(session.state, Some(outcome.clone()))
+    }
+
+    fn write(&self, state: SessionState, outcome: Option<AgentOutcome>) -> Result<()> {
+        self.store.save(&Session {
+            id: self.id.clone(),
+            created_at: self.created_at,
+            updated_at: now(),
+            cwd: self.cwd.clone(),
+            stages: self.stages.clone(),
+            configured: self.configured,
+            state,
+            outcome,
+        })
+    }
+
+    /// Returns `true` the first time it is called.
+    pub(crate) fn first_failure(&self) -> bool {
+        !self.warned.swap(true, Ordering::Relaxed)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
@@ -3,15 +3,26 @@ use std::sync::Arc;
 use anyhow::Result;
 use async_trait::async_trait;
 
-use super::adapters::{ExecutionAdapter, PlanningAdapter, RecoveryAdapter, ValidationAdapter};
+use crate::planner::ClarificationNeeded;
+
+use super::adapters::{
+    ExecutionAdapter, GoalAdapter, PlanningAdapter, PreviewAdapter, RecoveryAdapter, ReviewAdapter,
+    ValidationAdapter,
+};
+use super::approval::ApprovalDecision;
 use super::context::AgentContext;
+use super::goal::{IterationRecord, LoopStop};
 use super::outcome::AgentOutcome;
+use super::policy::{self, ConfidenceDecision};
 use super::types::StageKind;
 
 /// Control flow instruction returned by stage execution.
+#[allow(clippy::large_enum_variant, dead_code)]
 #[derive(Debug)]
 pub enum StageOutcome {
     Continue,
+    /// Start another pass through the pipeline from the first stage.
+    Repeat,
     Finished(AgentOutcome),
 }
 
@@ -44,12 +55,48 @@ where
     }
 
     async fn execute(&self, context: &mut AgentContext) -> Result<StageOutcome> {
-        let plan = self.adapter.plan(context).await?;
-        context.record_plan(plan);
+        let settings = context.config.confidence.clone();
+        let mut plan = match self.adapter.plan(context).await {
+            Ok(plan) => plan,
+            Err(error) => return awaiting_clarification(error),
+        };
+        context.record_plan(plan.clone());
+
+        let mut attempts = 0;
+        while policy::should_replan(&settings, plan.confidence, attempts) {
+            attempts += 1;
+            context.record_confidence_decision(
+                plan.confidence,
+                ConfidenceDecision::Replan { attempt: attempts },
+            );
+            context.replan_context = Some(policy::replan_context(&plan));
+            plan = match self.adapter.plan(context).await {
+                Ok(plan) => plan,
+                Err(error) => return awaiting_clarification(error),
+            };
+            context.record_plan(plan.clone());
+        }
+        context.replan_context = None;
+
+        let decision = policy::decide(&settings, plan.confidence, context.request.force);
+        context.record_confidence_decision(plan.confidence, decision);
         Ok(StageOutcome::Continue)
     }
 }
 
+/// Turn an unanswered planner question into a terminal outcome; other errors pass through.
+fn awaiting_clarification(error: anyhow::Error) -> Result<StageOutcome> {
+    match error.downcast::<ClarificationNeeded>() {
+        Ok(needed) => Ok(StageOutcome::Finished(
+            AgentOutcome::AwaitingClarification {
+                question: needed.question,
+                context: needed.context,
+            },
+        )),
+        Err(error) => Err(error),
+    }
+}
+
 pub struct ValidationStage<V> {
     adapter: Arc<V>,
 }
@@ -83,6 +130,117 @@ where
     }
 }
 
+pub struct ReviewStage<R> {
+    adapter: Arc<R>,
+    always: bool,
+}
+
+impl<R> ReviewStage<R> {
+    pub fn new(adapter: R) -> Self {
+        Self {
+            adapter: Arc::new(adapter),
+            always: false,
+        }
+    }
+
+    /// Review even when `review.enabled` is off, e.g. when a configured pipeline lists this stage.
+    pub fn always(mut self) -> Self {
+        self.always = true;
+        self
+    }
+}
+
+#[async_trait]
+impl<R> AgentStage for ReviewStage<R>
+where
+    R: ReviewAdapter + Send + Sync + 'static,
+{
+    fn kind(&self) -> StageKind {
+        StageKind::Review
+    }
+
+    async fn execute(&self, context: &mut AgentContext) -> Result<StageOutcome> {
+        if !self.always && !context.config.review.enabled {
+            context.record_stage_skip(self.kind(), "plan review disabled");
+            return Ok(StageOutcome::Continue);
+        }
+
+        let Some(plan) = context.plan.clone() else {
+            context.record_stage_skip(self.kind(), "no plan available for review");
+            return Ok(StageOutcome::Continue);
+        };
+
+        let review = self.adapter.review(context, &plan).await?;
+        context.record_review(review);
+        Ok(StageOutcome::Continue)
+    }
+}
+
+pub struct PreviewStage<P> {
+    adapter: Arc<P>,
+    always: bool,
+}
+
+impl<P> PreviewStage<P> {
+    pub fn new(adapter: P) -> Self {
+        Self {
+            adapter: Arc::new(adapter),
+            always: false,
+        }
+    }
+
+    /// Preview even when neither `execution.preview` nor `--preview` asks for it.
+    pub fn always(mut self) -> Self {
+        self.always = true;
+        self
+    }
+}
+
+#[async_trait]
+impl<P> AgentStage for PreviewStage<P>
+where
+    P: PreviewAdapter + Send + Sync + 'static,
+{
+    fn kind(&self) -> StageKind {
+        StageKind::Preview
+    }
+
+    async fn execute(&self, context: &mut AgentContext) -> Result<StageOutcome> {
+        if !self.always && !context.config.execution.preview && !context.request.preview {
+            context.record_stage_skip(self.kind(), "file change preview not requested");
+            return Ok(StageOutcome::Continue);
+        }
+
+        let Some(plan) = context.plan.clone() else {
+            context.record_stage_skip(self.kind(), "no plan available for preview");
+            return Ok(StageOutcome::Continue);
+        };
+
+        let blocked = context
+            .validation
+            .as_ref()
+            .is_some_and(|validation| !validation.plan_can_continue)
+            || context
+                .decision
+                .is_some_and(|decision| decision.blocks_execution())
+            || matches!(context.approval, Some(ApprovalDecision::Rejected { .. }));
+        if blocked || plan.execute_commands.is_empty() {
+            context.record_stage_skip(self.kind(), "the plan will not run");
+            return Ok(StageOutcome::Continue);
+        }
+
+        // The preview only informs approval, so a sandbox that cannot be set up
+        // does not stop the run.
+        match self.adapter.preview(context, &plan).await {
+            Ok(preview) => context.record_preview(preview),
+            Err(error) => {
+                context.record_stage_skip(self.kind(), format!("preview unavailable: {error:#}"))
+            }
+        }
+        Ok(StageOutcome::Continue)
+    }
+}
+
 pub struct ExecutionStage<E> {
     adapter: Arc<E>,
 }
@@ -110,6 +268,12 @@ where
             return Ok(StageOutcome::Continue);
         };
 
+        if let Some(ApprovalDecision::Rejected { reason }) = &context.approval {
+            let reason = format!("plan was not approved: {reason}");
+            context.record_stage_skip(self.kind(), reason);
+            return Ok(StageOutcome::Continue);
+        }
+
         let report = self.adapter.execute(context, &plan).await?;
         context.record_execution(report);
         Ok(StageOutcome::Continue)
@@ -153,3 +317,68 @@ where
         Ok(StageOutcome::Continue)
     }
 }
+
+pub struct AssessmentStage<G> {
+    adapter: Arc<G>,
+}
+
+impl<G> AssessmentStage<G> {
+    pub fn new(adapter: G) -> Self {
+        Self {
+            adapter: Arc::new(adapter),
+        }
+    }
+}
+
+#[async_trait]
+impl<G> AgentStage for AssessmentStage<G>
+where
+    G: GoalAdapter + Send + Sync + 'static,
+{
+    fn kind(&self) -> StageKind {
+        StageKind::Assessment
+    }
+
+    async fn execute(&self, context: &mut AgentContext) -> Result<StageOutcome> {
+        if !context.request.goal_loop {
+            context.record_stage_skip(self.kind(), "goal loop not requested");
+            return Ok(StageOutcome::Continue);
+        }
+
+        let (Some(plan), Some(execution)) = (context.plan.clone(), context.execution.clone())
+        else {
+            context.record_stage_skip(self.kind(), "plan was not executed");
+            context.loop_stop = Some(LoopStop::NotExecuted);
+            return Ok(StageOutcome::Continue);
+        };
+
+        if execution.commands.is_empty() && !execution.success {
+            context.record_stage_skip(self.kind(), "plan was not executed");
+            context.loop_stop = Some(LoopStop::NotExecuted);
+            return Ok(StageOutcome::Continue);
+        }
+
+        let assessment = self.adapter.assess(context, &plan, &execution).await?;
+        let achieved = assessment.achieved;
+        context.record_iteration(IterationRecord {
+            iteration: context.iterations.len() + 1,
+            plan,
+            execution,
+            assessment,
+        });
+
+        let settings = &context.config.iteration;
+        let stop = if achieved {
+            LoopStop::GoalAchieved
+        } else if context.iterations.len() >= settings.max_iterations {
+            LoopStop::MaxIterations
+        } else if context.tokens_used() >= settings.token_budget {
+            LoopStop::TokenBudget
+        } else {
+            return Ok(StageOutcome::Repeat);
+        };
+
+        context.loop_stop = Some(stop);
+        Ok(StageOutcome::Continue)
+    }
+}
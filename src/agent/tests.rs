@@ -1,18 +1,47 @@
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use httpmock::prelude::*;
+use serde_json::json;
 
-use crate::config::{Config, LlmProvider, LlmSettings, ModelSettings, RecoverySettings};
-use crate::planner::Plan;
+use crate::cancel::{CancelToken, INTERRUPTED};
+use crate::config::{
+    ApprovalSettings, ConfidenceSettings, Config, ExecutionSettings, HistorySettings,
+    HooksSettings, IterationSettings, LlmProvider, LlmSettings, MetricsSettings, ModelSettings,
+    PipelineSettings, RecoverySettings, ReviewSettings, ShellDialect, UndoSettings,
+};
+use crate::exec::{
+    ChangeKind, Executor, FileChange, PendingStep, PreviewMode, PreviewReport, StepChoice,
+    StepDecision, StepGate, StepPhase, StepResult, StepStatus,
+};
+use crate::history::PlanHistory;
+use crate::planner::{ClarificationNeeded, Plan};
+use crate::reviewer::PlanReview;
+use crate::undo::UndoStore;
 use crate::validator::{MissingCommand, ValidationResult};
 
-use super::adapters::{ExecutionAdapter, PlanExecutionAdapter};
+use super::adapters::{
+    EngineRecoveryAdapter, ExecutionAdapter, GoalAdapter, ModelReviewAdapter, PlanExecutionAdapter,
+    PlanningAdapter, PreviewAdapter, ValidationAdapter, missing_at_run_time, similar_plans,
+};
+use super::approval::{ApprovalDecision, Approver, AutoApprover, PolicyApprover, policy_violation};
 use super::context::{AgentEvent, AgentRequest};
-use super::outcome::AgentOutcome;
-use super::stages::{AgentStage, StageOutcome};
+use super::goal::{GoalAssessment, LoopStop};
+use super::hooks::{HookPoint, HookVerdict};
+use super::metrics::{MetricsLog, MetricsRecord, StageMetrics};
+use super::observer::{AgentObserver, JsonLinesObserver};
+use super::outcome::ExecutionReport;
+use super::outcome::{AgentOutcome, RecoveryOutcome};
+use super::plugin::PluginStage;
+use super::policy::ConfidenceDecision;
+use super::recovery::{BestConfidenceChooser, PolicyChooser};
+use super::session::{Clarification, SessionRecorder, SessionStore};
+use super::stages::{AgentStage, ReviewStage, StageOutcome};
 use super::types::StageKind;
-use super::{AgentContext, AgentOrchestrator};
+use super::{AgentContext, AgentOrchestrator, AgentRun};
 
 fn sample_config() -> Config {
     Config {
@@ -28,6 +57,16 @@ fn sample_config() -> Config {
             max_tokens: 512,
         },
         recovery: RecoverySettings::default(),
+        history: HistorySettings::default(),
+        execution: ExecutionSettings::default(),
+        review: ReviewSettings::default(),
+        confidence: ConfidenceSettings::default(),
+        approval: ApprovalSettings::default(),
+        hooks: HooksSettings::default(),
+        pipeline: PipelineSettings::default(),
+        metrics: MetricsSettings::default(),
+        undo: UndoSettings::default(),
+        iteration: IterationSettings::default(),
     }
 }
 
@@ -69,8 +108,13 @@ impl AgentStage for FinishStage {
                 dry_run_commands: vec![],
                 execute_commands: vec![],
                 notes: String::new(),
+                timeouts: Default::default(),
             }),
+            decision: None,
             validation: None,
+            review: None,
+            preview: None,
+            approval: None,
             execution: None,
             recovery: None,
         }))
@@ -162,7 +206,7 @@ async fn orchestrator_reports_failures() {
 #[test]
 fn default_orchestrator_has_standard_stages() {
     let orchestrator = AgentOrchestrator::default();
-    assert_eq!(orchestrator.stage_count(), 4);
+    assert_eq!(orchestrator.stage_count(), 7);
 }
 
 #[tokio::test]
@@ -175,6 +219,7 @@ async fn plan_execution_adapter_skips_when_validation_blocks() {
             plan_step: 0,
             is_dry_run: false,
         }],
+        syntax_issues: Vec::new(),
         plan_can_continue: false,
     });
     let adapter = PlanExecutionAdapter::default();
@@ -187,6 +232,7 @@ async fn plan_execution_adapter_skips_when_validation_blocks() {
                 dry_run_commands: vec![],
                 execute_commands: vec!["foo".to_string()],
                 notes: String::new(),
+                timeouts: Default::default(),
             },
         )
         .await
@@ -195,3 +241,2045 @@ async fn plan_execution_adapter_skips_when_validation_blocks() {
     assert!(!report.success);
     assert!(report.notes.iter().any(|note| note.contains("blocked")));
 }
+
+#[tokio::test]
+async fn review_stage_skips_when_disabled() {
+    let orchestrator = AgentOrchestrator::builder()
+        .with_review_adapter(ModelReviewAdapter::default())
+        .build();
+
+    let run = orchestrator
+        .run(sample_config(), AgentRequest::new("list files"))
+        .await
+        .expect("orchestrator should succeed");
+
+    assert!(run.events.iter().any(|event| matches!(
+        event,
+        AgentEvent::StageSkipped {
+            stage: StageKind::Review,
+            ..
+        }
+    )));
+}
+
+#[tokio::test]
+async fn always_review_stage_runs_with_its_own_model_when_review_is_disabled() {
+    let server = MockServer::start_async().await;
+    let review = json!({ "achieves_goal": true, "summary": "fine", "findings": [] }).to_string();
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/v1/chat/completions")
+                .json_body_partial(json!({ "model": "big/reviewer" }).to_string());
+            then.status(200).json_body(json!({
+                "choices": [
+                    {
+                        "index": 0,
+                        "finish_reason": "stop",
+                        "message": { "role": "assistant", "content": review }
+                    }
+                ]
+            }));
+        })
+        .await;
+    let mut config = sample_config();
+    config.llm.base_url = server.url("/v1");
+
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["ls"]))
+        .add_stage(
+            ReviewStage::new(
+                ModelReviewAdapter::default().with_model(Some("big/reviewer".to_string())),
+            )
+            .always(),
+        )
+        .build();
+
+    let run = orchestrator
+        .run(config, AgentRequest::new("list files"))
+        .await
+        .expect("orchestrator should succeed");
+
+    mock.assert_async().await;
+    match run.outcome {
+        AgentOutcome::Planned { review, .. } => assert!(review.is_some()),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
+
+#[test]
+fn unreadable_plan_history_leaves_the_planner_without_examples() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("plans.jsonl");
+    std::fs::write(&path, b"{\"task\": \"list files\xff\xfe\n").unwrap();
+
+    assert!(PlanHistory::new(&path).similar("list files", 3).is_err());
+    assert!(similar_plans(Ok(PlanHistory::new(&path)), "list files", 3).is_empty());
+    assert!(
+        similar_plans(
+            Err(anyhow!("Could not determine home directory")),
+            "list files",
+            3
+        )
+        .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn plan_execution_adapter_skips_when_review_blocks() {
+    let mut request = AgentRequest::new("clean the build directory");
+    request.assume_yes = true;
+    let mut context = AgentContext::new(sample_config(), request);
+    context.record_review(
+        serde_json::from_str::<PlanReview>(
+            r#"{"achieves_goal":true,"summary":"","findings":[{"severity":"high","category":"safety","message":"deletes too much"}]}"#,
+        )
+        .unwrap(),
+    );
+    let adapter = PlanExecutionAdapter::default();
+
+    let report = adapter
+        .execute(
+            &mut context,
+            &Plan {
+                confidence: 0.9,
+                dry_run_commands: vec![],
+                execute_commands: vec!["rm -rf build".to_string()],
+                notes: String::new(),
+                timeouts: Default::default(),
+            },
+        )
+        .await
+        .expect("execution should succeed");
+
+    assert!(!report.success);
+    assert!(report.notes.iter().any(|note| note.contains("reviewer")));
+    assert!(context.into_run().events.iter().any(|event| matches!(
+        event,
+        AgentEvent::ReviewFinished {
+            findings: 1,
+            blocking: true
+        }
+    )));
+}
+
+struct ScriptedPlanningAdapter {
+    confidences: Mutex<Vec<f32>>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptedPlanningAdapter {
+    fn new(confidences: &[f32], requests: Arc<Mutex<Vec<String>>>) -> Self {
+        Self {
+            confidences: Mutex::new(confidences.iter().rev().copied().collect()),
+            requests,
+        }
+    }
+}
+
+#[async_trait]
+impl PlanningAdapter for ScriptedPlanningAdapter {
+    async fn plan(&self, context: &mut AgentContext) -> Result<Plan> {
+        self.requests
+            .lock()
+            .unwrap()
+            .push(context.planning_request());
+        let confidence = self
+            .confidences
+            .lock()
+            .unwrap()
+            .pop()
+            .expect("planner called too many times");
+        Ok(Plan {
+            confidence,
+            dry_run_commands: vec!["ls".to_string()],
+            execute_commands: vec!["touch notes.txt".to_string()],
+            notes: "assumed current directory".to_string(),
+            timeouts: Default::default(),
+        })
+    }
+}
+
+fn confidence_decisions(events: &[AgentEvent]) -> Vec<ConfidenceDecision> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            AgentEvent::ConfidenceDecided { decision, .. } => Some(*decision),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn planning_stage_replans_low_confidence_plans_with_context() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(ScriptedPlanningAdapter::new(&[0.3, 0.8], requests.clone()))
+        .build();
+
+    let run = orchestrator
+        .run(sample_config(), AgentRequest::new("create notes file"))
+        .await
+        .expect("orchestrator should succeed");
+
+    assert_eq!(
+        confidence_decisions(&run.events),
+        vec![
+            ConfidenceDecision::Replan { attempt: 1 },
+            ConfidenceDecision::Proceed
+        ]
+    );
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[0], "create notes file");
+    assert!(requests[1].starts_with("create notes file\n\n"));
+    assert!(requests[1].contains("low confidence (0.30)"));
+    assert!(requests[1].contains("Working directory:"));
+    match run.outcome {
+        AgentOutcome::Planned { plan, decision, .. } => {
+            assert!((plan.unwrap().confidence - 0.8).abs() < f32::EPSILON);
+            assert_eq!(decision, Some(ConfidenceDecision::Proceed));
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn planning_stage_refuses_very_low_confidence_unless_forced() {
+    let mut config = sample_config();
+    config.confidence.max_replans = 0;
+
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(ScriptedPlanningAdapter::new(
+            &[0.1],
+            Arc::new(Mutex::new(Vec::new())),
+        ))
+        .build();
+    let run = orchestrator
+        .run(config.clone(), AgentRequest::new("do something vague"))
+        .await
+        .expect("orchestrator should succeed");
+    assert_eq!(
+        confidence_decisions(&run.events),
+        vec![ConfidenceDecision::Refuse]
+    );
+
+    let mut request = AgentRequest::new("do something vague");
+    request.force = true;
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(ScriptedPlanningAdapter::new(
+            &[0.1],
+            Arc::new(Mutex::new(Vec::new())),
+        ))
+        .build();
+    let run = orchestrator
+        .run(config, request)
+        .await
+        .expect("orchestrator should succeed");
+    assert_eq!(
+        confidence_decisions(&run.events),
+        vec![ConfidenceDecision::Forced]
+    );
+}
+
+#[tokio::test]
+async fn planning_stage_skips_dry_run_for_high_confidence() {
+    let mut config = sample_config();
+    config.confidence.skip_dry_run_at = Some(0.9);
+
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(ScriptedPlanningAdapter::new(
+            &[0.95],
+            Arc::new(Mutex::new(Vec::new())),
+        ))
+        .build();
+    let run = orchestrator
+        .run(config, AgentRequest::new("create notes file"))
+        .await
+        .expect("orchestrator should succeed");
+
+    assert_eq!(
+        confidence_decisions(&run.events),
+        vec![ConfidenceDecision::SkipDryRun]
+    );
+}
+
+struct FixedPlanningAdapter {
+    checks: Vec<String>,
+    commands: Vec<String>,
+    timeouts: BTreeMap<String, u64>,
+}
+
+impl FixedPlanningAdapter {
+    fn new(commands: &[&str]) -> Self {
+        Self {
+            checks: Vec::new(),
+            commands: commands.iter().map(|cmd| cmd.to_string()).collect(),
+            timeouts: BTreeMap::new(),
+        }
+    }
+
+    fn with_checks(mut self, checks: &[&str]) -> Self {
+        self.checks = checks.iter().map(|cmd| cmd.to_string()).collect();
+        self
+    }
+
+    fn with_timeout(mut self, command: &str, secs: u64) -> Self {
+        self.timeouts.insert(command.to_string(), secs);
+        self
+    }
+}
+
+#[async_trait]
+impl PlanningAdapter for FixedPlanningAdapter {
+    async fn plan(&self, _context: &mut AgentContext) -> Result<Plan> {
+        Ok(Plan {
+            confidence: 0.9,
+            dry_run_commands: self.checks.clone(),
+            execute_commands: self.commands.clone(),
+            notes: String::new(),
+            timeouts: self.timeouts.clone(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn auto_approver_lets_execution_run() {
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo approved"]))
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .build();
+
+    let run = orchestrator
+        .run(sample_config(), AgentRequest::new("say approved"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match run.outcome {
+        AgentOutcome::Planned {
+            approval,
+            execution,
+            ..
+        } => {
+            assert_eq!(approval, Some(ApprovalDecision::Approved));
+            assert!(execution.expect("execution should run").success);
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn policy_approver_rejects_commands_outside_allowlist() {
+    let mut config = sample_config();
+    config.approval.allowed_commands = vec!["ls".to_string()];
+
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["ls build", "rm -rf build"]))
+        .with_approver(PolicyApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .build();
+
+    let run = orchestrator
+        .run(config, AgentRequest::new("clean the build directory"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match run.outcome {
+        AgentOutcome::Planned {
+            plan,
+            approval: Some(ApprovalDecision::Rejected { reason }),
+            execution: None,
+            ..
+        } => {
+            assert!(plan.is_some());
+            assert!(reason.contains("'rm'"));
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(
+        !run.events
+            .iter()
+            .any(|event| matches!(event, AgentEvent::ExecutionFinished { .. }))
+    );
+}
+
+#[tokio::test]
+async fn missing_commands_are_found_in_a_terminal_transcript() {
+    let plan = Plan {
+        confidence: 0.9,
+        dry_run_commands: vec![],
+        execute_commands: vec!["cd . && frobnicate_li_test --all".to_string()],
+        notes: String::new(),
+        timeouts: BTreeMap::new(),
+    };
+    let report = Executor::new(ShellDialect::Sh)
+        .with_echo(false)
+        .with_pty(true)
+        .run(&plan, &CancelToken::new(), &mut |_| {})
+        .await;
+
+    let missing = missing_at_run_time(&report).expect("missing command should be reported");
+    assert_eq!(missing.command, "frobnicate_li_test");
+    assert_eq!(
+        missing.failed_command_line,
+        "cd . && frobnicate_li_test --all"
+    );
+    assert!(!missing.is_dry_run);
+}
+
+#[test]
+fn policy_rejects_programs_hidden_from_the_allowlist() {
+    let allowed = ["ls", "echo", "find", "sudo"].map(String::from);
+    let violation = |command: &str| policy_violation(command, &allowed);
+
+    assert_eq!(
+        violation("ls\nrm -rf ~").as_deref(),
+        Some("'rm' is not in approval.allowed_commands")
+    );
+    for command in [
+        "echo $(rm -rf ~)",
+        "echo `rm -rf ~`",
+        "diff <(ls a) <(ls b)",
+    ] {
+        assert_eq!(
+            violation(command).as_deref(),
+            Some("command substitutions are not allowed under the approval policy"),
+            "{command}"
+        );
+    }
+    assert_eq!(
+        violation("sh -c 'rm -rf ~'").as_deref(),
+        Some("'sh' runs other commands and is not in approval.allowed_commands")
+    );
+    assert_eq!(
+        violation("ls | xargs rm").as_deref(),
+        Some("'xargs' runs other commands and is not in approval.allowed_commands")
+    );
+    assert_eq!(
+        violation("find . -name '*.tmp' -exec rm {} \\;").as_deref(),
+        Some("'find -exec' runs other commands and is not in approval.allowed_commands")
+    );
+
+    assert_eq!(
+        violation("GIT_SSH_COMMAND='rm -rf ~' git fetch").as_deref(),
+        Some(
+            "setting 'GIT_SSH_COMMAND' is not allowed unless approval.allowed_commands lists \"GIT_SSH_COMMAND=\""
+        )
+    );
+    assert_eq!(
+        violation("LD_PRELOAD=/tmp/x.so ls").as_deref(),
+        Some(
+            "setting 'LD_PRELOAD' is not allowed unless approval.allowed_commands lists \"LD_PRELOAD=\""
+        )
+    );
+    assert_eq!(
+        violation("ls && BASH_ENV=/tmp/x echo hi").as_deref(),
+        Some(
+            "setting 'BASH_ENV' is not allowed unless approval.allowed_commands lists \"BASH_ENV=\""
+        )
+    );
+    for command in [
+        "echo 'rm -rf ~' >> ~/.bashrc",
+        "ls >out.txt",
+        "ls 2> errors.log",
+        "ls &> all.log",
+    ] {
+        assert!(
+            violation(command).is_some_and(|reason| reason.starts_with("output redirection")),
+            "{command}"
+        );
+    }
+
+    assert_eq!(violation("find . -name '*.tmp'\nls -la"), None);
+    assert_eq!(violation("sudo ls /root"), None);
+    assert_eq!(violation("ls 2>/dev/null > /dev/null 2>&1 < input"), None);
+    assert_eq!(violation("echo LD_PRELOAD=/tmp/x.so"), None);
+
+    let allowed = ["ls", "echo", "LC_ALL=", ">"].map(String::from);
+    assert_eq!(
+        policy_violation("LC_ALL=C ls > listing.txt", &allowed),
+        None
+    );
+}
+
+#[tokio::test]
+async fn refused_plans_are_rejected_even_when_auto_approved() {
+    let mut config = sample_config();
+    config.confidence.max_replans = 0;
+
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(ScriptedPlanningAdapter::new(
+            &[0.1],
+            Arc::new(Mutex::new(Vec::new())),
+        ))
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .build();
+
+    let run = orchestrator
+        .run(config, AgentRequest::new("do something vague"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match run.outcome {
+        AgentOutcome::Planned {
+            approval: Some(ApprovalDecision::Rejected { reason }),
+            execution: None,
+            ..
+        } => assert!(reason.contains("--force")),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
+
+struct CollectingObserver {
+    events: Arc<Mutex<Vec<AgentEvent>>>,
+}
+
+impl AgentObserver for CollectingObserver {
+    fn on_event(&self, event: &AgentEvent) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
+
+#[tokio::test]
+async fn observers_see_events_as_they_are_recorded() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let orchestrator = AgentOrchestrator::builder()
+        .with_observer(CollectingObserver {
+            events: seen.clone(),
+        })
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo one", "echo two"]))
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .build();
+
+    let run = orchestrator
+        .run(sample_config(), AgentRequest::new("echo twice"))
+        .await
+        .expect("orchestrator should succeed");
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(*seen, run.events);
+    assert!(seen.contains(&AgentEvent::StepStarted {
+        phase: StepPhase::Execute,
+        index: 2,
+        total: 2,
+        command: "echo two".to_string(),
+    }));
+}
+
+#[test]
+fn json_lines_observer_writes_one_event_per_line() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let observer = JsonLinesObserver::new(file.reopen().unwrap());
+
+    observer.on_event(&AgentEvent::StageStarted(StageKind::Execution));
+    observer.on_event(&AgentEvent::StepFinished {
+        phase: StepPhase::DryRun,
+        index: 1,
+        success: true,
+    });
+
+    let written = std::fs::read_to_string(file.path()).unwrap();
+    let lines: Vec<&str> = written.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            r#"{"event":"stage_started","data":"execution"}"#,
+            r#"{"event":"step_finished","data":{"phase":"dry_run","index":1,"success":true}}"#,
+        ]
+    );
+}
+
+struct QuestioningPlanningAdapter;
+
+#[async_trait]
+impl PlanningAdapter for QuestioningPlanningAdapter {
+    async fn plan(&self, _context: &mut AgentContext) -> Result<Plan> {
+        Err(ClarificationNeeded {
+            question: "Which directory?".to_string(),
+            context: "several candidates".to_string(),
+        }
+        .into())
+    }
+}
+
+#[tokio::test]
+async fn unanswered_planner_questions_end_the_run() {
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(QuestioningPlanningAdapter)
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .build();
+
+    let run = orchestrator
+        .run(sample_config(), AgentRequest::new("clean up"))
+        .await
+        .expect("orchestrator should succeed");
+
+    assert_eq!(
+        run.outcome,
+        AgentOutcome::AwaitingClarification {
+            question: "Which directory?".to_string(),
+            context: "several candidates".to_string(),
+        }
+    );
+}
+
+#[tokio::test]
+async fn agent_run_serializes_to_json() {
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo json"]))
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .build();
+
+    let run = orchestrator
+        .run(sample_config(), AgentRequest::new("print json"))
+        .await
+        .expect("orchestrator should succeed");
+    let value = serde_json::to_value(&run).unwrap();
+
+    assert_eq!(value["outcome"]["status"], "planned");
+    assert_eq!(value["outcome"]["plan"]["execute_commands"][0], "echo json");
+    assert_eq!(value["outcome"]["approval"]["decision"], "approved");
+    assert_eq!(value["outcome"]["execution"]["success"], true);
+    assert!(
+        value["outcome"]["execution"]["stdout"]
+            .as_str()
+            .unwrap()
+            .contains("json\n")
+    );
+    assert_eq!(value["events"][0]["event"], "stage_started");
+    assert_eq!(value["events"][0]["data"], "planning");
+}
+
+/// Reports the goal as achieved once `achieved_at` iterations have run, and
+/// remembers the output it was shown.
+struct ScriptedGoalAdapter {
+    achieved_at: usize,
+    observed: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl GoalAdapter for ScriptedGoalAdapter {
+    async fn assess(
+        &self,
+        context: &mut AgentContext,
+        _plan: &Plan,
+        execution: &ExecutionReport,
+    ) -> Result<GoalAssessment> {
+        self.observed
+            .lock()
+            .unwrap()
+            .push(execution.stdout.clone().unwrap_or_default());
+        let iteration = context.iterations.len() + 1;
+        Ok(GoalAssessment {
+            achieved: iteration >= self.achieved_at,
+            summary: format!("after iteration {iteration}"),
+            next: "keep going".to_string(),
+        })
+    }
+}
+
+/// Plans `echo plan-N` on the Nth call and records each planning request.
+struct CountingPlanningAdapter {
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl PlanningAdapter for CountingPlanningAdapter {
+    async fn plan(&self, context: &mut AgentContext) -> Result<Plan> {
+        let mut requests = self.requests.lock().unwrap();
+        requests.push(context.planning_request());
+        Ok(Plan {
+            confidence: 0.9,
+            dry_run_commands: vec![],
+            execute_commands: vec![format!("echo plan-{}", requests.len())],
+            notes: String::new(),
+            timeouts: Default::default(),
+        })
+    }
+}
+
+fn goal_loop_request(task: &str) -> AgentRequest {
+    let mut request = AgentRequest::new(task);
+    request.goal_loop = true;
+    request
+}
+
+#[tokio::test]
+async fn goal_loop_replans_with_progress_until_goal_is_achieved() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(CountingPlanningAdapter {
+            requests: requests.clone(),
+        })
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .with_goal_adapter(ScriptedGoalAdapter {
+            achieved_at: 2,
+            observed: observed.clone(),
+        })
+        .build();
+
+    let run = orchestrator
+        .run(sample_config(), goal_loop_request("finish the job"))
+        .await
+        .expect("orchestrator should succeed");
+
+    let summary = run.goal_loop.expect("goal loop summary");
+    assert_eq!(summary.stop, Some(LoopStop::GoalAchieved));
+    assert_eq!(summary.iterations.len(), 2);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0], "finish the job");
+    assert!(requests[1].contains("Iteration 1: after iteration 1"));
+    assert!(requests[1].contains("Next: keep going"));
+
+    let observed = observed.lock().unwrap();
+    assert!(observed[0].contains("plan-1"));
+    assert!(observed[1].contains("plan-2"));
+    assert!(
+        run.events
+            .contains(&AgentEvent::IterationStarted { iteration: 2 })
+    );
+}
+
+#[tokio::test]
+async fn goal_loop_stops_at_the_iteration_limit() {
+    let mut config = sample_config();
+    config.iteration.max_iterations = 2;
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["true"]))
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .with_goal_adapter(ScriptedGoalAdapter {
+            achieved_at: usize::MAX,
+            observed: Arc::new(Mutex::new(Vec::new())),
+        })
+        .build();
+
+    let run = orchestrator
+        .run(config, goal_loop_request("never done"))
+        .await
+        .expect("orchestrator should succeed");
+
+    let summary = run.goal_loop.expect("goal loop summary");
+    assert_eq!(summary.stop, Some(LoopStop::MaxIterations));
+    assert_eq!(summary.iterations.len(), 2);
+}
+
+/// Reports the plan's first program as missing.
+struct MissingToolValidationAdapter;
+
+#[async_trait]
+impl ValidationAdapter for MissingToolValidationAdapter {
+    async fn validate(&self, _context: &mut AgentContext, plan: &Plan) -> Result<ValidationResult> {
+        let line = plan.execute_commands[0].clone();
+        Ok(ValidationResult {
+            missing_commands: vec![MissingCommand {
+                command: line.split_whitespace().next().unwrap().to_string(),
+                failed_command_line: line,
+                plan_step: 0,
+                is_dry_run: false,
+            }],
+            syntax_issues: Vec::new(),
+            plan_can_continue: false,
+        })
+    }
+}
+
+async fn mock_recovery_options(server: &MockServer, alternatives: serde_json::Value) {
+    let content = json!({
+        "alternatives": alternatives,
+        "installation_instructions": [
+            { "command": "false", "description": "pretend install", "platform": "linux", "confidence": 0.5 }
+        ],
+        "can_skip": false,
+        "original_goal_achievable": true
+    })
+    .to_string();
+    server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/v1/chat/completions")
+                .body_contains("lizard-tool");
+            then.status(200).json_body(json!({
+                "choices": [
+                    {
+                        "index": 0,
+                        "finish_reason": "stop",
+                        "message": { "role": "assistant", "content": content }
+                    }
+                ]
+            }));
+        })
+        .await;
+}
+
+async fn run_recovery(config: Config, adapter: EngineRecoveryAdapter) -> Option<RecoveryOutcome> {
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["lizard-tool --scan"]))
+        .with_validation_adapter(MissingToolValidationAdapter)
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .with_recovery_adapter(adapter)
+        .build();
+
+    let run = orchestrator
+        .run(config, AgentRequest::new("scan for lizards"))
+        .await
+        .expect("orchestrator should succeed");
+    match run.outcome {
+        AgentOutcome::Planned { recovery, .. } => recovery,
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn best_confidence_recovery_falls_back_after_a_failed_alternative() {
+    let server = MockServer::start_async().await;
+    mock_recovery_options(
+        &server,
+        json!([
+            { "command": "false", "description": "broken", "confidence": 0.9 },
+            { "command": "echo lizards", "description": "works", "confidence": 0.6 }
+        ]),
+    )
+    .await;
+    let mut config = sample_config();
+    config.llm.base_url = server.url("/v1");
+
+    let recovery = run_recovery(
+        config,
+        EngineRecoveryAdapter::new(Box::new(BestConfidenceChooser)),
+    )
+    .await;
+
+    assert_eq!(
+        recovery,
+        Some(RecoveryOutcome::AlternativeApplied {
+            missing: "lizard-tool".to_string(),
+            command: "echo lizards".to_string(),
+        })
+    );
+}
+
+#[tokio::test]
+async fn policy_recovery_only_uses_allowed_programs() {
+    let server = MockServer::start_async().await;
+    mock_recovery_options(
+        &server,
+        json!([
+            { "command": "echo lizards", "description": "not allowed", "confidence": 0.9 },
+            { "command": "printf lizards", "description": "allowed", "confidence": 0.3 }
+        ]),
+    )
+    .await;
+    let mut config = sample_config();
+    config.llm.base_url = server.url("/v1");
+    config.approval.allowed_commands = vec!["printf".to_string()];
+
+    let recovery = run_recovery(config, EngineRecoveryAdapter::new(Box::new(PolicyChooser))).await;
+
+    assert_eq!(
+        recovery,
+        Some(RecoveryOutcome::AlternativeApplied {
+            missing: "lizard-tool".to_string(),
+            command: "printf lizards".to_string(),
+        })
+    );
+}
+
+#[tokio::test]
+async fn stage_metrics_attribute_llm_usage_to_the_stage_that_made_the_call() {
+    let server = MockServer::start_async().await;
+    let review = json!({ "achieves_goal": true, "summary": "fine", "findings": [] }).to_string();
+    server
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/chat/completions");
+            then.status(200).json_body(json!({
+                "choices": [
+                    {
+                        "index": 0,
+                        "finish_reason": "stop",
+                        "message": { "role": "assistant", "content": review }
+                    }
+                ],
+                "usage": { "prompt_tokens": 100, "completion_tokens": 23, "total_tokens": 123 }
+            }));
+        })
+        .await;
+    let mut config = sample_config();
+    config.llm.base_url = server.url("/v1");
+    config.review.enabled = true;
+
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["ls"]))
+        .with_review_adapter(ModelReviewAdapter::default())
+        .add_stage(ErrorStage::new(StageKind::Execution))
+        .build();
+
+    let run = orchestrator
+        .run(config, AgentRequest::new("list files"))
+        .await
+        .expect("orchestrator should succeed");
+
+    let stages: Vec<_> = run.metrics.iter().map(|metrics| metrics.stage).collect();
+    assert_eq!(
+        stages,
+        vec![StageKind::Planning, StageKind::Review, StageKind::Execution]
+    );
+    assert_eq!(run.metrics[0].llm_calls, 0);
+    assert_eq!(run.metrics[1].llm_calls, 1);
+    assert_eq!(run.metrics[1].tokens, 123);
+    assert_eq!(run.metrics[1].retries, 0);
+}
+
+#[test]
+fn metrics_log_appends_one_record_per_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = MetricsLog::new(dir.path().join("metrics.jsonl"));
+    let stages = vec![StageMetrics {
+        stage: StageKind::Planning,
+        duration_ms: 1500,
+        llm_calls: 1,
+        tokens: 800,
+        retries: 1,
+    }];
+
+    let record = MetricsRecord::new(&sample_config(), "planned", &stages);
+    log.append(&record).unwrap();
+    log.append(&record).unwrap();
+
+    let contents = std::fs::read_to_string(dir.path().join("metrics.jsonl")).unwrap();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines.len(), 2);
+    let parsed: MetricsRecord = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(parsed.model, "planner/model");
+    assert_eq!(parsed.tokens, 800);
+    assert_eq!(parsed.stages, stages);
+}
+
+fn auto_pipeline(commands: &[&str]) -> AgentOrchestrator {
+    AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(commands))
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .build()
+}
+
+#[tokio::test]
+async fn pre_execute_hook_vetoes_plans_it_rejects() {
+    let mut config = sample_config();
+    config.hooks.pre_execute = Some(
+        "if grep -q prod-db; then echo 'prod hosts are off limits' >&2; exit 1; fi".to_string(),
+    );
+
+    let run = auto_pipeline(&["ssh prod-db uptime"])
+        .run(config, AgentRequest::new("check the database host"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match run.outcome {
+        AgentOutcome::Planned {
+            approval: Some(ApprovalDecision::Rejected { reason }),
+            execution: None,
+            ..
+        } => {
+            assert_eq!(
+                reason,
+                "pre_execute hook vetoed the run: prod hosts are off limits"
+            );
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn hooks_can_replace_the_plan_and_observe_the_result() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("post_execute.json");
+    let mut config = sample_config();
+    config.hooks.post_plan = Some(
+        r#"cat > /dev/null; echo '{"confidence":0.9,"dry_run_commands":[],"execute_commands":["echo replaced"],"notes":""}'"#
+            .to_string(),
+    );
+    config.hooks.post_execute = Some(format!("cat > '{}'", log.display()));
+
+    let run = auto_pipeline(&["echo original"])
+        .run(config, AgentRequest::new("say something"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match &run.outcome {
+        AgentOutcome::Planned {
+            plan: Some(plan),
+            execution: Some(execution),
+            ..
+        } => {
+            assert_eq!(plan.execute_commands, vec!["echo replaced".to_string()]);
+            assert!(execution.success);
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(run.events.contains(&AgentEvent::HookFinished {
+        hook: HookPoint::PostPlan,
+        verdict: HookVerdict::ReplacedPlan,
+    }));
+
+    let payload: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&log).unwrap()).unwrap();
+    assert_eq!(payload["hook"], "post_execute");
+    assert_eq!(payload["task"], "say something");
+    assert_eq!(payload["execution"]["commands"][0], "echo replaced");
+}
+
+#[tokio::test]
+async fn replacement_plans_from_hooks_go_through_the_confidence_gate() {
+    let mut config = sample_config();
+    config.confidence.skip_dry_run_at = Some(0.9);
+    config.hooks.post_plan = Some(
+        r#"cat > /dev/null; echo '{"confidence":0.0,"dry_run_commands":["echo check"],"execute_commands":["echo replaced"],"notes":""}'"#
+            .to_string(),
+    );
+
+    let run = auto_pipeline(&["echo original"])
+        .run(config.clone(), AgentRequest::new("say something"))
+        .await
+        .expect("orchestrator should succeed");
+    match &run.outcome {
+        AgentOutcome::Planned {
+            approval: Some(ApprovalDecision::Rejected { reason }),
+            execution: None,
+            ..
+        } => assert!(reason.contains("--force")),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert_eq!(
+        confidence_decisions(&run.events),
+        [ConfidenceDecision::SkipDryRun, ConfidenceDecision::Refuse]
+    );
+
+    // Forced, the replacement runs with its own dry-run checks.
+    let mut request = AgentRequest::new("say something");
+    request.force = true;
+    let run = auto_pipeline(&["echo original"])
+        .run(config, request)
+        .await
+        .expect("orchestrator should succeed");
+    let report = execution_report(&run);
+    assert!(report.success);
+    let commands = report
+        .steps
+        .iter()
+        .map(|step| (step.phase, step.command.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        [
+            (StepPhase::DryRun, "echo check"),
+            (StepPhase::Execute, "echo replaced")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn pre_execute_hook_output_does_not_replace_the_approved_plan() {
+    let mut config = sample_config();
+    config.hooks.pre_execute = Some(
+        r#"cat > /dev/null; echo '{"confidence":0.9,"dry_run_commands":[],"execute_commands":["echo unapproved"],"notes":""}'"#
+            .to_string(),
+    );
+
+    let run = auto_pipeline(&["echo approved"])
+        .run(config, AgentRequest::new("say something"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match &run.outcome {
+        AgentOutcome::Planned {
+            plan: Some(plan),
+            execution: Some(execution),
+            ..
+        } => {
+            assert_eq!(plan.execute_commands, vec!["echo approved".to_string()]);
+            assert_eq!(execution.commands, vec!["echo approved".to_string()]);
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(run.events.contains(&AgentEvent::HookFinished {
+        hook: HookPoint::PreExecute,
+        verdict: HookVerdict::Passed,
+    }));
+}
+
+#[tokio::test]
+async fn hooks_that_time_out_are_killed_and_veto_the_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("still-running");
+    let mut config = sample_config();
+    config.hooks.timeout_secs = 1;
+    config.hooks.pre_plan = Some(format!(
+        "(sleep 2; touch '{}') & sleep 30",
+        marker.display()
+    ));
+
+    let started = Instant::now();
+    let run = auto_pipeline(&["echo never"])
+        .run(config, AgentRequest::new("anything"))
+        .await
+        .expect("orchestrator should succeed");
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(
+        run.outcome,
+        AgentOutcome::cancelled("pre_plan hook timed out after 1s")
+    );
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(!marker.exists(), "the hook's background job kept running");
+}
+
+#[tokio::test]
+async fn hooks_that_print_before_reading_their_input_do_not_stall() {
+    let mut config = sample_config();
+    config.hooks.timeout_secs = 5;
+    config.hooks.pre_plan = Some("head -c 200000 /dev/zero; cat > /dev/null".to_string());
+
+    let run = auto_pipeline(&["echo done"])
+        .run(config, AgentRequest::new("x".repeat(200_000)))
+        .await
+        .expect("orchestrator should succeed");
+
+    assert!(matches!(
+        run.outcome,
+        AgentOutcome::Planned {
+            execution: Some(_),
+            ..
+        }
+    ));
+    assert!(run.events.contains(&AgentEvent::HookFinished {
+        hook: HookPoint::PrePlan,
+        verdict: HookVerdict::Passed,
+    }));
+}
+
+#[tokio::test]
+async fn pre_plan_veto_cancels_the_run_and_stage_failures_reach_on_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("on_failure.json");
+    let mut config = sample_config();
+    config.hooks.pre_plan = Some("exit 3".to_string());
+
+    let run = auto_pipeline(&["echo never"])
+        .run(config.clone(), AgentRequest::new("anything"))
+        .await
+        .expect("orchestrator should succeed");
+    assert_eq!(
+        run.outcome,
+        AgentOutcome::cancelled("pre_plan hook vetoed the run: exited with status 3")
+    );
+
+    config.hooks.pre_plan = None;
+    config.hooks.on_failure = Some(format!("cat > '{}'", log.display()));
+    let run = AgentOrchestrator::builder()
+        .add_stage(ErrorStage::new(StageKind::Validation))
+        .build()
+        .run(config, AgentRequest::new("anything"))
+        .await
+        .expect("orchestrator should succeed");
+    assert!(matches!(run.outcome, AgentOutcome::Failed { .. }));
+
+    let payload: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&log).unwrap()).unwrap();
+    assert_eq!(payload["hook"], "on_failure");
+    assert_eq!(payload["error"], "stage failure");
+}
+
+fn shell_plugin(name: &str, script: &str) -> PluginStage {
+    PluginStage::new(name, "sh", vec!["-c".to_string(), script.to_string()])
+}
+
+#[tokio::test]
+async fn plugin_stages_edit_the_plan_and_gate_approval() {
+    let enricher = shell_plugin(
+        "enricher",
+        r#"grep -q '"protocol":1' || exit 9
+echo '{"protocol":1,"outcome":"continue","events":["added cleanup"],"plan":{"confidence":0.9,"dry_run_commands":[],"execute_commands":["echo build","rm -rf target"],"notes":""}}'"#,
+    );
+    let gate = shell_plugin(
+        "prod-gate",
+        r#"if grep -q 'rm -rf'; then echo '{"protocol":1,"outcome":"reject","reason":"destructive command"}'; else echo '{"protocol":1,"outcome":"continue"}'; fi"#,
+    );
+
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo build"]))
+        .add_stage(enricher)
+        .add_stage(gate)
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .build()
+        .run(sample_config(), AgentRequest::new("build it"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match &run.outcome {
+        AgentOutcome::Planned {
+            plan: Some(plan),
+            approval: Some(ApprovalDecision::Rejected { reason }),
+            execution: None,
+            ..
+        } => {
+            assert_eq!(plan.execute_commands.len(), 2);
+            assert_eq!(reason, "prod-gate: destructive command");
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(
+        run.events
+            .contains(&AgentEvent::Message("enricher: added cleanup".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn plugins_after_approval_cannot_replace_the_plan() {
+    let swap = shell_plugin(
+        "swap",
+        r#"cat > /dev/null; echo '{"protocol":1,"outcome":"continue","plan":{"confidence":0.9,"dry_run_commands":[],"execute_commands":["echo unapproved"],"notes":""}}'"#,
+    );
+
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo approved"]))
+        .with_approver(AutoApprover)
+        .add_stage(swap)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .build()
+        .run(sample_config(), AgentRequest::new("say something"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match &run.outcome {
+        AgentOutcome::Failed { stage, error } => {
+            assert_eq!(*stage, StageKind::Plugin);
+            assert!(error.contains("after the current plan was checked"));
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(
+        !run.events
+            .iter()
+            .any(|event| matches!(event, AgentEvent::StepFinished { .. }))
+    );
+}
+
+#[tokio::test]
+async fn plugin_plans_go_through_the_confidence_gate() {
+    let swap = shell_plugin(
+        "swap",
+        r#"cat > /dev/null; echo '{"protocol":1,"outcome":"continue","plan":{"confidence":0.0,"dry_run_commands":[],"execute_commands":["echo unsure"],"notes":""}}'"#,
+    );
+
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo sure"]))
+        .add_stage(swap)
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .build()
+        .run(sample_config(), AgentRequest::new("say something"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match &run.outcome {
+        AgentOutcome::Planned {
+            approval: Some(ApprovalDecision::Rejected { reason }),
+            execution: None,
+            ..
+        } => assert!(reason.contains("--force")),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert_eq!(
+        confidence_decisions(&run.events).last(),
+        Some(&ConfidenceDecision::Refuse)
+    );
+}
+
+#[tokio::test]
+async fn plugin_stages_reject_unknown_protocol_versions() {
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo build"]))
+        .add_stage(shell_plugin(
+            "future",
+            r#"cat > /dev/null; echo '{"protocol":2,"outcome":"continue"}'"#,
+        ))
+        .build()
+        .run(sample_config(), AgentRequest::new("build it"))
+        .await
+        .expect("orchestrator should succeed");
+
+    match run.outcome {
+        AgentOutcome::Failed { stage, error } => {
+            assert_eq!(stage, StageKind::Plugin);
+            assert!(error.contains("protocol version 2"));
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn plugins_that_time_out_are_killed_and_fail_the_stage() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("still-running");
+    let plugin = shell_plugin(
+        "slow",
+        &format!("(sleep 2; touch '{}') & sleep 30", marker.display()),
+    )
+    .with_timeout(Some(Duration::from_secs(1)));
+
+    let started = Instant::now();
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo build"]))
+        .add_stage(plugin)
+        .build()
+        .run(sample_config(), AgentRequest::new("build it"))
+        .await
+        .expect("orchestrator should succeed");
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    match run.outcome {
+        AgentOutcome::Failed { stage, error } => {
+            assert_eq!(stage, StageKind::Plugin);
+            assert!(error.contains("timed out after 1s"));
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(!marker.exists(), "the plugin's background job kept running");
+}
+
+fn interrupt_after(cancel: &CancelToken, delay: Duration) {
+    let cancel = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        cancel.interrupt();
+    });
+}
+
+fn process_alive(pid: &str) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .is_ok_and(|stat| !stat.split(") ").nth(1).is_some_and(|s| s.starts_with('Z')))
+}
+
+#[tokio::test]
+async fn interrupting_execution_cancels_with_partial_results_and_stops_the_process_group() {
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("background.pid");
+    let command = format!("sleep 30 & echo $! > '{}'; wait", pid_file.display());
+    let cancel = CancelToken::new();
+    interrupt_after(&cancel, Duration::from_millis(300));
+
+    let started = std::time::Instant::now();
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo first", &command]))
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .with_cancel_token(cancel)
+        .build()
+        .run(sample_config(), AgentRequest::new("wait a while"))
+        .await
+        .expect("orchestrator should succeed");
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    match &run.outcome {
+        AgentOutcome::Cancelled {
+            reason,
+            plan: Some(plan),
+            execution: Some(execution),
+        } => {
+            assert_eq!(reason, INTERRUPTED);
+            assert_eq!(plan.execute_commands.len(), 2);
+            assert!(!execution.success);
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(run.events.contains(&AgentEvent::StepFinished {
+        phase: StepPhase::Execute,
+        index: 1,
+        success: true,
+    }));
+
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    assert!(!process_alive(pid.trim()));
+}
+
+#[tokio::test]
+async fn interrupting_a_plugin_cancels_the_run_and_stops_its_process_group() {
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("plugin.pid");
+    let cancel = CancelToken::new();
+    let interrupter = cancel.clone();
+    let started_file = pid_file.clone();
+    tokio::spawn(async move {
+        while !started_file.exists() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        interrupter.interrupt();
+    });
+
+    let started = Instant::now();
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo build"]))
+        .add_stage(
+            shell_plugin(
+                "stuck",
+                &format!("sleep 30 & echo $! > '{}'; wait", pid_file.display()),
+            )
+            .with_timeout(None),
+        )
+        .with_cancel_token(cancel)
+        .build()
+        .run(sample_config(), AgentRequest::new("build it"))
+        .await
+        .expect("orchestrator should succeed");
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(matches!(
+        run.outcome,
+        AgentOutcome::Cancelled { ref reason, .. } if reason == INTERRUPTED
+    ));
+
+    // The group is killed with SIGKILL, which takes effect asynchronously.
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while process_alive(pid.trim()) && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(!process_alive(pid.trim()));
+}
+
+#[tokio::test]
+async fn interrupting_an_llm_call_cancels_the_run() {
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/chat/completions");
+            then.status(200)
+                .delay(Duration::from_secs(10))
+                .json_body(json!({ "choices": [] }));
+        })
+        .await;
+    let mut config = sample_config();
+    config.llm.base_url = server.url("/v1");
+    config.review.enabled = true;
+    let cancel = CancelToken::new();
+    interrupt_after(&cancel, Duration::from_millis(200));
+
+    let started = std::time::Instant::now();
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["ls"]))
+        .with_review_adapter(ModelReviewAdapter::default())
+        .with_execution_adapter(PlanExecutionAdapter::default())
+        .with_cancel_token(cancel)
+        .build()
+        .run(config, AgentRequest::new("list files"))
+        .await
+        .expect("orchestrator should succeed");
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    match &run.outcome {
+        AgentOutcome::Cancelled {
+            reason,
+            plan: Some(_),
+            execution: None,
+        } => assert_eq!(reason, INTERRUPTED),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(
+        !run.events
+            .iter()
+            .any(|event| matches!(event, AgentEvent::StageStarted(StageKind::Execution)))
+    );
+}
+
+#[tokio::test]
+async fn interrupted_sessions_resume_at_the_next_unfinished_step() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::new(dir.path().join("sessions"));
+    let log = dir.path().join("steps.log");
+    let marker = dir.path().join("network-back");
+    let first = format!("echo one >> '{}'", log.display());
+    let second = format!(
+        "test -f '{}' || sleep 30; echo two >> '{}'",
+        marker.display(),
+        log.display()
+    );
+    let pipeline = |cancel: CancelToken, session: SessionRecorder| {
+        AgentOrchestrator::builder()
+            .with_planning_adapter(FixedPlanningAdapter::new(&[&first, &second]))
+            .with_approver(AutoApprover)
+            .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+            .with_cancel_token(cancel)
+            .with_session(session)
+            .build()
+    };
+
+    let cancel = CancelToken::new();
+    interrupt_after(&cancel, Duration::from_millis(300));
+    let run = pipeline(
+        cancel,
+        SessionRecorder::start(store.clone(), Vec::new(), false),
+    )
+    .run(sample_config(), AgentRequest::new("write two lines"))
+    .await
+    .expect("orchestrator should succeed");
+    assert_eq!(run.outcome.status(), "cancelled");
+
+    let sessions = store.list().unwrap();
+    assert_eq!(sessions.len(), 1);
+    let session = sessions.into_iter().next().unwrap();
+    assert_eq!(session.status(), "cancelled");
+    assert_eq!(session.state.next_stage, 2);
+    assert_eq!(session.state.progress.execute, 1);
+    assert_eq!(
+        session.state.approval,
+        Some(ApprovalDecision::Approved),
+        "approval is not asked for again"
+    );
+
+    std::fs::write(&marker, "").unwrap();
+    let resumed = pipeline(
+        CancelToken::new(),
+        SessionRecorder::resume(store.clone(), &session),
+    )
+    .resume(sample_config(), session.state.clone())
+    .await
+    .expect("resumed run should succeed");
+
+    match &resumed.outcome {
+        AgentOutcome::Planned {
+            execution: Some(report),
+            ..
+        } => {
+            assert!(report.success);
+            assert_eq!(report.commands, vec![first.clone(), second.clone()]);
+            assert_eq!(report.notes, vec!["Resumed after 1 completed step(s)"]);
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert!(resumed.events.contains(&AgentEvent::SessionResumed {
+        session: session.id.clone(),
+        stage: 2,
+    }));
+    assert!(resumed.events.contains(&AgentEvent::StepStarted {
+        phase: StepPhase::Execute,
+        index: 2,
+        total: 2,
+        command: second.clone(),
+    }));
+    assert_eq!(std::fs::read_to_string(&log).unwrap(), "one\ntwo\n");
+    assert!(store.list().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sessions_that_ran_in_a_persistent_shell_are_not_resumed_mid_plan() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::new(dir.path().join("sessions"));
+    std::fs::create_dir(dir.path().join("build")).unwrap();
+    let marker = dir.path().join("network-back");
+    let first = format!("cd '{}'", dir.path().join("build").display());
+    let second = format!("test -f '{}' || sleep 30; touch output", marker.display());
+    let mut config = sample_config();
+    config.execution.persistent_shell = true;
+    let pipeline = |cancel: CancelToken, session: SessionRecorder| {
+        AgentOrchestrator::builder()
+            .with_planning_adapter(FixedPlanningAdapter::new(&[&first, &second]))
+            .with_approver(AutoApprover)
+            .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+            .with_cancel_token(cancel)
+            .with_session(session)
+            .build()
+    };
+
+    let cancel = CancelToken::new();
+    interrupt_after(&cancel, Duration::from_millis(300));
+    let run = pipeline(
+        cancel,
+        SessionRecorder::start(store.clone(), Vec::new(), false),
+    )
+    .run(config.clone(), AgentRequest::new("build in a subdirectory"))
+    .await
+    .expect("orchestrator should succeed");
+    assert_eq!(run.outcome.status(), "cancelled");
+    let session = store.list().unwrap().into_iter().next().unwrap();
+    assert_eq!(session.state.progress.execute, 1);
+
+    std::fs::write(&marker, "").unwrap();
+    let resumed = pipeline(
+        CancelToken::new(),
+        SessionRecorder::resume(store.clone(), &session),
+    )
+    .resume(config, session.state.clone())
+    .await
+    .expect("resumed run should succeed");
+
+    let report = execution_report(&resumed);
+    assert!(!report.success);
+    assert!(report.steps.is_empty());
+    assert!(
+        report.notes[0].contains("persistent shell"),
+        "{:?}",
+        report.notes
+    );
+    assert!(!dir.path().join("build/output").exists());
+}
+
+struct ClarifyingPlanningAdapter {
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl PlanningAdapter for ClarifyingPlanningAdapter {
+    async fn plan(&self, context: &mut AgentContext) -> Result<Plan> {
+        if context.clarifications.is_empty() {
+            return Err(ClarificationNeeded {
+                question: "Which directory?".to_string(),
+                context: String::new(),
+            }
+            .into());
+        }
+        self.requests
+            .lock()
+            .unwrap()
+            .push(context.planning_request());
+        Ok(Plan {
+            confidence: 0.9,
+            dry_run_commands: vec![],
+            execute_commands: vec!["ls /tmp".to_string()],
+            notes: String::new(),
+            timeouts: Default::default(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn answered_clarifications_resume_planning() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SessionStore::new(dir.path());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let pipeline = |session: SessionRecorder| {
+        AgentOrchestrator::builder()
+            .with_planning_adapter(ClarifyingPlanningAdapter {
+                requests: requests.clone(),
+            })
+            .with_session(session)
+            .build()
+    };
+
+    let run = pipeline(SessionRecorder::start(store.clone(), Vec::new(), false))
+        .run(sample_config(), AgentRequest::new("clean up"))
+        .await
+        .expect("orchestrator should succeed");
+    assert_eq!(run.outcome.status(), "awaiting_clarification");
+
+    let mut session = store.latest().unwrap().expect("session should be kept");
+    assert_eq!(session.state.next_stage, 0);
+    session.state.clarifications.push(Clarification {
+        question: "Which directory?".to_string(),
+        answer: "/tmp".to_string(),
+    });
+
+    let resumed = pipeline(SessionRecorder::resume(store.clone(), &session))
+        .resume(sample_config(), session.state)
+        .await
+        .expect("resumed run should succeed");
+    assert_eq!(resumed.outcome.status(), "planned");
+    let requests = requests.lock().unwrap();
+    assert!(requests[0].starts_with("clean up"));
+    assert!(requests[0].contains("Q: Which directory?\n  A: /tmp"));
+    assert!(store.latest().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn execution_reports_record_every_step_with_separate_output() {
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(
+            FixedPlanningAdapter::new(&[
+                "echo out; echo err >&2",
+                "head -c 100000 /dev/zero | tr '\\0' a",
+                "echo failing >&2; exit 3",
+                "echo never",
+            ])
+            .with_checks(&["echo checking"]),
+        )
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .build()
+        .run(sample_config(), AgentRequest::new("report steps"))
+        .await
+        .expect("orchestrator should succeed");
+
+    let AgentOutcome::Planned {
+        execution: Some(report),
+        ..
+    } = run.outcome
+    else {
+        panic!("execution should run");
+    };
+    assert!(!report.success);
+    assert_eq!(
+        report.notes,
+        vec!["Command failed (exit code 3): echo failing >&2; exit 3"]
+    );
+    assert_eq!(report.steps.len(), 4);
+
+    let check = &report.steps[0];
+    assert_eq!((check.phase, check.index), (StepPhase::DryRun, 1));
+    assert_eq!(check.stdout, "checking\n");
+
+    let split = &report.steps[1];
+    assert_eq!(split.status, StepStatus::Exited { code: 0 });
+    assert_eq!(
+        (split.stdout.as_str(), split.stderr.as_str()),
+        ("out\n", "err\n")
+    );
+
+    let large = &report.steps[2];
+    assert!(large.stdout_truncated);
+    assert!(large.stdout.len() <= 64 * 1024);
+    assert!(!large.stderr_truncated);
+
+    let failed = report.failed_step().expect("a step should fail");
+    assert_eq!((failed.phase, failed.index), (StepPhase::Execute, 3));
+    assert_eq!(failed.status, StepStatus::Exited { code: 3 });
+    assert_eq!(failed.stderr, "failing\n");
+
+    let stderr = report.stderr.as_deref().expect("stderr should be captured");
+    assert!(stderr.contains("$ echo out; echo err >&2\nerr\n"));
+    assert!(!report.stdout.as_deref().unwrap().contains("\nerr\n"));
+    assert!(report.transcript().ends_with("failing\n[exit code 3]\n"));
+}
+
+async fn run_with_limits(adapter: FixedPlanningAdapter, config: Config) -> AgentRun {
+    AgentOrchestrator::builder()
+        .with_planning_adapter(adapter)
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .build()
+        .run(config, AgentRequest::new("run with limits"))
+        .await
+        .expect("orchestrator should succeed")
+}
+
+fn execution_report(run: &AgentRun) -> &ExecutionReport {
+    match &run.outcome {
+        AgentOutcome::Planned {
+            execution: Some(report),
+            ..
+        } => report,
+        other => panic!("execution should run, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn steps_past_their_time_limit_are_terminated_then_killed() {
+    let stubborn = "trap '' TERM; sleep 30";
+    let started = Instant::now();
+    let run = run_with_limits(
+        FixedPlanningAdapter::new(&[stubborn, "echo never"]).with_timeout(stubborn, 1),
+        sample_config(),
+    )
+    .await;
+
+    assert!(started.elapsed() < Duration::from_secs(15));
+    let report = execution_report(&run);
+    assert!(!report.success);
+    assert_eq!(report.steps.len(), 1);
+    assert_eq!(
+        report.steps[0].status,
+        StepStatus::TimedOut { limit_secs: 1 }
+    );
+    assert_eq!(
+        report.notes,
+        vec![format!(
+            "Stopped execute command after its 1s time limit: {stubborn}"
+        )]
+    );
+}
+
+#[tokio::test]
+async fn the_plan_deadline_stops_the_running_step() {
+    let mut config = sample_config();
+    config.execution.plan_timeout_secs = 1;
+    let started = Instant::now();
+    let run = run_with_limits(
+        FixedPlanningAdapter::new(&["echo first", "sleep 30", "echo never"]),
+        config,
+    )
+    .await;
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let report = execution_report(&run);
+    assert_eq!(report.steps.len(), 2);
+    assert!(report.steps[0].succeeded());
+    assert_eq!(
+        report.failed_step().map(|step| step.status),
+        Some(StepStatus::DeadlineExceeded { limit_secs: 1 })
+    );
+    assert_eq!(
+        report.notes,
+        vec!["Plan time limit of 1s reached while running: sleep 30"]
+    );
+}
+
+#[tokio::test]
+async fn quiet_commands_are_flagged_without_being_stopped() {
+    let mut config = sample_config();
+    config.execution.idle_warning_secs = 1;
+    let run = run_with_limits(
+        FixedPlanningAdapter::new(&["sleep 2; echo done", "echo chatty"]),
+        config,
+    )
+    .await;
+
+    let report = execution_report(&run);
+    assert!(report.success);
+    assert!(report.steps[0].stalled);
+    assert_eq!(report.steps[0].stdout, "done\n");
+    assert!(!report.steps[1].stalled);
+    let idle = run
+        .events
+        .iter()
+        .filter(|event| matches!(event, AgentEvent::StepIdle { .. }))
+        .collect::<Vec<_>>();
+    assert!(matches!(
+        idle.as_slice(),
+        [AgentEvent::StepIdle {
+            phase: StepPhase::Execute,
+            index: 1,
+            ..
+        }]
+    ));
+}
+
+struct StaticPreviewAdapter;
+
+#[async_trait]
+impl PreviewAdapter for StaticPreviewAdapter {
+    async fn preview(&self, _context: &mut AgentContext, plan: &Plan) -> Result<PreviewReport> {
+        Ok(PreviewReport {
+            mode: PreviewMode::Overlay,
+            changes: vec![FileChange {
+                path: "notes.txt".into(),
+                kind: ChangeKind::Added,
+                dir: false,
+            }],
+            execution: ExecutionReport::from_steps(
+                plan.execute_commands.clone(),
+                Vec::new(),
+                Vec::new(),
+            ),
+            notes: Vec::new(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn file_change_preview_runs_before_approval_only_when_requested() {
+    let orchestrator = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["echo hi > notes.txt"]))
+        .with_preview_adapter(StaticPreviewAdapter)
+        .with_approver(AutoApprover)
+        .build();
+
+    let plain = orchestrator
+        .run(sample_config(), AgentRequest::new("write notes"))
+        .await
+        .expect("orchestrator should succeed");
+    assert!(matches!(
+        plain.outcome,
+        AgentOutcome::Planned { preview: None, .. }
+    ));
+    assert!(plain.events.contains(&AgentEvent::StageSkipped {
+        stage: StageKind::Preview,
+        reason: "file change preview not requested".to_string(),
+    }));
+
+    let mut request = AgentRequest::new("write notes");
+    request.preview = true;
+    let previewed = orchestrator
+        .run(sample_config(), request)
+        .await
+        .expect("orchestrator should succeed");
+    let AgentOutcome::Planned {
+        preview: Some(preview),
+        approval: Some(ApprovalDecision::Approved),
+        ..
+    } = &previewed.outcome
+    else {
+        panic!("preview should be recorded before approval");
+    };
+    assert_eq!(preview.changes[0].to_string(), "+ notes.txt");
+    assert!(previewed.events.contains(&AgentEvent::PreviewFinished {
+        changes: 1,
+        success: true,
+    }));
+}
+
+struct StepByStepApprover;
+
+#[async_trait]
+impl Approver for StepByStepApprover {
+    async fn approve(&self, _context: &AgentContext, _plan: &Plan) -> Result<ApprovalDecision> {
+        Ok(ApprovalDecision::ApprovedStepByStep)
+    }
+}
+
+/// Skips the first execute command and runs everything else.
+struct SkipFirstStepGate;
+
+#[async_trait]
+impl StepGate for SkipFirstStepGate {
+    async fn decide(&self, step: PendingStep<'_>) -> Result<StepChoice> {
+        Ok(match (step.phase, step.index) {
+            (StepPhase::Execute, 1) => StepChoice::Skip,
+            _ => StepChoice::Run,
+        })
+    }
+}
+
+#[tokio::test]
+async fn step_by_step_approval_asks_the_gate_before_each_command() {
+    let planning = || FixedPlanningAdapter::new(&["echo one", "echo two"]).with_checks(&["true"]);
+
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(planning())
+        .with_approver(StepByStepApprover)
+        .with_execution_adapter(
+            PlanExecutionAdapter::default()
+                .with_echo(false)
+                .with_step_gate(SkipFirstStepGate),
+        )
+        .build()
+        .run(sample_config(), AgentRequest::new("step through"))
+        .await
+        .expect("orchestrator should succeed");
+    let report = execution_report(&run);
+    assert!(report.success);
+    let decisions = report
+        .steps
+        .iter()
+        .map(|step| step.decision)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        decisions,
+        [
+            Some(StepDecision::Run),
+            Some(StepDecision::Skipped),
+            Some(StepDecision::Run)
+        ]
+    );
+    assert_eq!(report.steps[1].status, StepStatus::Skipped);
+    // A plan that only worked with a step left out is no example to learn from.
+    assert!(!report.ran_as_planned());
+
+    // Without a terminal to ask on, a step-by-step run does not execute at all.
+    let mut request = AgentRequest::new("step through");
+    request.step = true;
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(planning())
+        .with_approver(AutoApprover)
+        .with_execution_adapter(PlanExecutionAdapter::default().with_echo(false))
+        .build()
+        .run(sample_config(), request)
+        .await
+        .expect("orchestrator should succeed");
+    let report = execution_report(&run);
+    assert!(report.steps.is_empty());
+    assert_eq!(
+        report.notes,
+        ["Step-by-step execution needs an interactive terminal"]
+    );
+}
+
+#[test]
+fn steps_skipped_after_an_interrupt_do_not_count_as_planned() {
+    let step = |command: &str, status: StepStatus| StepResult {
+        phase: StepPhase::Execute,
+        index: 1,
+        command: command.to_string(),
+        status,
+        duration_ms: 0,
+        stdout: String::new(),
+        stderr: String::new(),
+        stdout_truncated: false,
+        stderr_truncated: false,
+        stalled: false,
+        decision: None,
+        original_command: None,
+    };
+    let mut report = ExecutionReport {
+        commands: vec!["sleep 60".to_string(), "echo done".to_string()],
+        success: true,
+        stdout: None,
+        stderr: None,
+        notes: Vec::new(),
+        steps: vec![
+            step("sleep 60", StepStatus::Exited { code: 0 }),
+            step("echo done", StepStatus::Exited { code: 0 }),
+        ],
+        undo: None,
+    };
+    assert!(report.ran_as_planned());
+
+    // Ctrl-C and "skip" leave no step-by-step decision behind, only the status.
+    report.steps[0].status = StepStatus::Skipped;
+    assert!(!report.ran_as_planned());
+}
+
+/// Replaces the first execute command with another one.
+struct EditFirstStepGate(String);
+
+#[async_trait]
+impl StepGate for EditFirstStepGate {
+    async fn decide(&self, step: PendingStep<'_>) -> Result<StepChoice> {
+        Ok(match (step.phase, step.index) {
+            (StepPhase::Execute, 1) => StepChoice::Edit(self.0.clone()),
+            _ => StepChoice::Run,
+        })
+    }
+}
+
+#[tokio::test]
+async fn edited_steps_add_their_targets_to_the_undo_snapshot() {
+    let home = tempfile::tempdir().unwrap();
+    let work = tempfile::tempdir().unwrap();
+    let kept = work.path().join("kept.txt");
+    std::fs::write(&kept, "keep me").unwrap();
+    let store = UndoStore::new(home.path());
+
+    let run = AgentOrchestrator::builder()
+        .with_planning_adapter(FixedPlanningAdapter::new(&["true"]))
+        .with_approver(StepByStepApprover)
+        .with_execution_adapter(
+            PlanExecutionAdapter::default()
+                .with_echo(false)
+                .with_undo(store.clone())
+                .with_step_gate(EditFirstStepGate(format!("rm {}", kept.display()))),
+        )
+        .build()
+        .run(sample_config(), AgentRequest::new("edit a step"))
+        .await
+        .expect("orchestrator should succeed");
+    let report = execution_report(&run);
+    assert!(report.success);
+    assert!(!kept.exists());
+    assert!(!report.ran_as_planned());
+
+    let id = report
+        .undo
+        .as_deref()
+        .expect("the edited step should be snapshotted");
+    store.restore(id).unwrap();
+    assert_eq!(std::fs::read_to_string(&kept).unwrap(), "keep me");
+}
@@ -1,12 +1,21 @@
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 /// Logical stages in the agent pipeline.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum StageKind {
     Planning,
     Validation,
+    Review,
+    Preview,
+    Approval,
     Execution,
     Recovery,
+    Assessment,
+    /// An external stage run over the plugin protocol.
+    Plugin,
 }
 
 impl fmt::Display for StageKind {
@@ -14,8 +23,13 @@ impl fmt::Display for StageKind {
         let label = match self {
             StageKind::Planning => "planning",
             StageKind::Validation => "validation",
+            StageKind::Review => "review",
+            StageKind::Preview => "preview",
+            StageKind::Approval => "approval",
             StageKind::Execution => "execution",
             StageKind::Recovery => "recovery",
+            StageKind::Assessment => "assessment",
+            StageKind::Plugin => "plugin",
         };
         write!(f, "{label}")
     }
@@ -0,0 +1,225 @@
+//! Ctrl-C and SIGTERM handling for a task run.
+//!
+//! Signals are turned into state on a [`CancelToken`] instead of killing `li`.
+//! Whoever is waiting at the time decides what an interrupt means: the executor
+//! asks whether to skip the running step, abort the plan or carry on, and an
+//! in-flight LLM call gives up. Plan commands run in their own process group, so
+//! they only receive the signals `li` forwards to them.
+
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+/// Grace period between forwarding a signal to a command and killing it.
+pub const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// Reason recorded when the user interrupts an LLM call or a stage boundary.
+pub const INTERRUPTED: &str = "interrupted by Ctrl-C";
+
+/// Signal forwarded to a running command's process group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+    Kill,
+}
+
+impl Signal {
+    fn raw(self) -> libc::c_int {
+        match self {
+            Signal::Interrupt => libc::SIGINT,
+            Signal::Terminate => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        }
+    }
+}
+
+/// Send `signal` to every process in group `pgid`.
+pub fn signal_group(pgid: u32, signal: Signal) {
+    let Ok(pgid) = libc::pid_t::try_from(pgid) else {
+        return;
+    };
+    // SAFETY: killpg has no memory-safety preconditions; a stale group id only
+    // makes the call fail with ESRCH, which is ignored.
+    unsafe {
+        libc::killpg(pgid, signal.raw());
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct CancelState {
+    /// Interrupts nobody has acted on yet.
+    pending: u32,
+    cancelled: Option<String>,
+    /// Signal to forward when the run is cancelled.
+    signal: Option<Signal>,
+    /// Process group of the command currently running, if any.
+    group: Option<u32>,
+}
+
+/// Shared cancellation state for one task run. Cloning shares the state.
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    state: Arc<watch::Sender<CancelState>>,
+    prompt: bool,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(watch::Sender::new(CancelState::default())),
+            prompt: false,
+        }
+    }
+
+    /// Ask "skip step / abort plan / continue" on the terminal when a command is
+    /// interrupted. Without it, an interrupt aborts the plan.
+    pub fn with_prompt(mut self, prompt: bool) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    pub fn prompts(&self) -> bool {
+        self.prompt
+    }
+
+    /// Record a Ctrl-C. Returns `false` when an earlier interrupt is still
+    /// unhandled or the run is already cancelled, i.e. `li` appears stuck.
+    pub fn interrupt(&self) -> bool {
+        let mut first = true;
+        self.state.send_modify(|state| {
+            first = state.pending == 0 && state.cancelled.is_none();
+            state.pending += 1;
+        });
+        first
+    }
+
+    /// Cancel the run because of SIGTERM and pass the signal on to the running command.
+    pub fn terminate(&self) {
+        self.cancel_with("terminated by SIGTERM", Signal::Terminate);
+        if let Some(group) = self.group() {
+            signal_group(group, Signal::Terminate);
+        }
+    }
+
+    pub fn cancel(&self, reason: impl Into<String>) {
+        self.cancel_with(reason, Signal::Interrupt);
+    }
+
+    fn cancel_with(&self, reason: impl Into<String>, signal: Signal) {
+        let reason = reason.into();
+        self.state.send_modify(|state| {
+            if state.cancelled.is_none() {
+                state.cancelled = Some(reason);
+                state.signal = Some(signal);
+            }
+        });
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.borrow().cancelled.is_some()
+    }
+
+    pub fn reason(&self) -> Option<String> {
+        self.state.borrow().cancelled.clone()
+    }
+
+    /// Signal to forward to a command that is stopped because the run was cancelled.
+    pub fn signal(&self) -> Signal {
+        self.state.borrow().signal.unwrap_or(Signal::Interrupt)
+    }
+
+    /// Consume unhandled interrupts, returning whether there were any.
+    pub fn take_interrupt(&self) -> bool {
+        let mut taken = false;
+        self.state.send_if_modified(|state| {
+            taken = state.pending > 0;
+            state.pending = 0;
+            taken
+        });
+        taken
+    }
+
+    /// Cancel the run if an interrupt is waiting. Returns whether the run is cancelled.
+    pub fn cancel_if_interrupted(&self) -> bool {
+        if self.take_interrupt() {
+            self.cancel(INTERRUPTED);
+        }
+        self.is_cancelled()
+    }
+
+    /// Resolves once an interrupt is waiting or the run is cancelled.
+    pub async fn interrupted(&self) {
+        let mut receiver = self.state.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail.
+        let _ = receiver
+            .wait_for(|state| state.pending > 0 || state.cancelled.is_some())
+            .await;
+    }
+
+    /// Register the process group of the command that is about to be waited on.
+    pub fn track_group(&self, pgid: u32) -> GroupGuard {
+        self.state.send_modify(|state| state.group = Some(pgid));
+        GroupGuard {
+            token: self.clone(),
+        }
+    }
+
+    fn group(&self) -> Option<u32> {
+        self.state.borrow().group
+    }
+}
+
+/// Clears the tracked process group when the command has been reaped.
+pub struct GroupGuard {
+    token: CancelToken,
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        self.token.state.send_modify(|state| state.group = None);
+    }
+}
+
+/// Route SIGINT and SIGTERM to `token` for the rest of the process.
+///
+/// A Ctrl-C while the previous one is still unhandled (for example while a
+/// terminal prompt is blocking) exits immediately with status 130 after
+/// interrupting the running command, if any.
+pub fn install_signal_handlers(token: &CancelToken) {
+    let interrupts = token.clone();
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if !interrupts.interrupt() {
+                if let Some(group) = interrupts.group() {
+                    signal_group(group, Signal::Interrupt);
+                }
+                eprintln!("\nInterrupted.");
+                process::exit(130);
+            }
+        }
+    });
+
+    let terminations = token.clone();
+    tokio::spawn(async move {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let Ok(mut sigterm) = signal(SignalKind::terminate()) else {
+            return;
+        };
+        while sigterm.recv().await.is_some() {
+            if terminations.is_cancelled() {
+                process::exit(143);
+            }
+            terminations.terminate();
+        }
+    });
+}
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::io::{self, Write};
+
+use crate::agent::{AgentContext, ApprovalDecision, Approver, ConfidenceDecision};
+use crate::config::Config;
+use crate::exec::{ChangeKind, PreviewReport};
+use crate::planner::Plan;
+use crate::reviewer::PlanReview;
+use crate::validator::ValidationResult;
+
+/// Approver that renders the plan and asks the user on the terminal.
+pub(crate) struct InteractiveApprover;
+
+#[async_trait]
+impl Approver for InteractiveApprover {
+    async fn approve(&self, context: &AgentContext, plan: &Plan) -> Result<ApprovalDecision> {
+        let config = &context.config;
+        if let Some(validation) = &context.validation {
+            render_validation_warnings(validation);
+        }
+
+        if context.request.goal_loop {
+            println!(
+                "\n=== Iteration {} of {} ===",
+                context.iterations.len() + 1,
+                config.iteration.max_iterations
+            );
+        }
+        render_plan(plan, context.review.as_ref(), config);
+        if let Some(preview) = &context.preview {
+            render_preview(preview);
+        }
+
+        if let Some(decision) = context.decision {
+            render_confidence_decision(decision, plan.confidence, config);
+            if decision.blocks_execution() {
+                return Ok(ApprovalDecision::rejected(
+                    "plan confidence is below the refusal threshold",
+                ));
+            }
+        }
+
+        let blocked = context
+            .review
+            .as_ref()
+            .is_some_and(PlanReview::blocks_execution);
+        prompt_for_approval(blocked)
+    }
+}
+
+/// Approver used when nobody can answer a prompt; the plan is reported but not run.
+pub(crate) struct PlanOnlyApprover;
+
+#[async_trait]
+impl Approver for PlanOnlyApprover {
+    async fn approve(&self, _context: &AgentContext, _plan: &Plan) -> Result<ApprovalDecision> {
+        Ok(ApprovalDecision::rejected(
+            "interactive approval is unavailable; pass --yes or set approval.mode to auto or policy",
+        ))
+    }
+}
+
+/// Warn about missing commands that do not stop the plan from running.
+fn render_validation_warnings(validation: &ValidationResult) {
+    if validation.missing_commands.is_empty() || !validation.plan_can_continue {
+        return;
+    }
+
+    crate::recovery::print_missing_commands(&validation.missing_commands);
+    let count = validation.missing_commands.len();
+    println!(
+        "Plan can continue, but results may be degraded until the missing tool{} {} installed.",
+        if count == 1 { "" } else { "s are" },
+        if count == 1 { "is" } else { "are" }
+    );
+}
+
+pub(crate) fn render_plan(plan: &Plan, review: Option<&PlanReview>, config: &Config) {
+    println!("\n=== Proposed Plan ===");
+    println!("Provider: {}", config.llm.provider.display_name());
+    println!("Planner Model: {}", config.models.planner);
+    println!("Plan confidence: {:.2}", plan.confidence);
+
+    if !plan.dry_run_commands.is_empty() {
+        println!("\nDry-run Commands:");
+        for (idx, cmd) in plan.dry_run_commands.iter().enumerate() {
+            println!("  {}. {}", idx + 1, cmd);
+        }
+    }
+
+    if !plan.execute_commands.is_empty() {
+        println!("\nExecute Commands:");
+        for (idx, cmd) in plan.execute_commands.iter().enumerate() {
+            println!("  {}. {}", idx + 1, cmd);
+        }
+    }
+
+    if !plan.notes.trim().is_empty() {
+        println!("\nNotes: {}", plan.notes.trim());
+    }
+
+    if let Some(review) = review {
+        render_review(review, config);
+    }
+}
+
+/// Changed paths listed before the rest are summarized.
+const PREVIEW_LISTED_CHANGES: usize = 40;
+
+fn render_preview(preview: &PreviewReport) {
+    println!("\n=== File Changes (preview, {}) ===", preview.mode);
+    for note in &preview.notes {
+        println!("Note: {}", note);
+    }
+    if preview.changes.is_empty() {
+        println!("No files in the working directory would change.");
+    } else {
+        for change in preview.changes.iter().take(PREVIEW_LISTED_CHANGES) {
+            println!("  {}", change);
+        }
+        if preview.changes.len() > PREVIEW_LISTED_CHANGES {
+            println!(
+                "  … and {} more",
+                preview.changes.len() - PREVIEW_LISTED_CHANGES
+            );
+        }
+        println!(
+            "{} added, {} modified, {} deleted.",
+            preview.count(ChangeKind::Added),
+            preview.count(ChangeKind::Modified),
+            preview.count(ChangeKind::Deleted)
+        );
+    }
+    if !preview.execution.success {
+        println!(
+            "⚠️  The plan did not finish in the preview: {}",
+            preview.execution.notes.join("; ")
+        );
+    }
+}
+
+fn render_confidence_decision(decision: ConfidenceDecision, confidence: f32, config: &Config) {
+    let thresholds = &config.confidence;
+    match decision {
+        ConfidenceDecision::Refuse => println!(
+            "\n⛔ Plan confidence {:.2} is below the refusal threshold {:.2}. Re-run with --force to execute it anyway.",
+            confidence, thresholds.refuse_below
+        ),
+        ConfidenceDecision::Forced => println!(
+            "\n⚠️  Plan confidence {:.2} is below the refusal threshold {:.2}; continuing because --force was given.",
+            confidence, thresholds.refuse_below
+        ),
+        ConfidenceDecision::SkipDryRun => {
+            if let Some(threshold) = thresholds.skip_dry_run_at {
+                println!(
+                    "\nPlan confidence {:.2} is at or above {:.2}; the dry-run phase will be skipped.",
+                    confidence, threshold
+                );
+            }
+        }
+        ConfidenceDecision::Proceed | ConfidenceDecision::Replan { .. } => {}
+    }
+}
+
+fn render_review(review: &PlanReview, config: &Config) {
+    println!(
+        "\n=== Review ({}) ===",
+        config.review.model_or(&config.models.planner)
+    );
+    println!(
+        "Achieves goal: {}",
+        if review.achieves_goal { "yes" } else { "no" }
+    );
+    if !review.summary.trim().is_empty() {
+        println!("Summary: {}", review.summary.trim());
+    }
+
+    if review.findings.is_empty() {
+        println!("No findings.");
+        return;
+    }
+
+    println!("\nFindings:");
+    for finding in &review.findings {
+        println!(
+            "  [{}] {}: {}",
+            finding.severity.to_string().to_uppercase(),
+            finding.category,
+            finding.message
+        );
+        if let Some(command) = finding.command.as_deref().filter(|c| !c.trim().is_empty()) {
+            println!("      command:   {}", command);
+        }
+        if let Some(edit) = finding
+            .suggested_edit
+            .as_deref()
+            .filter(|e| !e.trim().is_empty())
+        {
+            println!("      suggested: {}", edit);
+        }
+    }
+}
+
+fn prompt_for_approval(blocked: bool) -> Result<ApprovalDecision> {
+    if blocked {
+        println!(
+            "\n⛔ The reviewer reported high-severity findings; this plan will not run automatically."
+        );
+        print!("Execute this plan anyway? [y/N/i/s]: ");
+    } else {
+        print!("\nExecute this plan? [y/N/i/s]: ");
+    }
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    let answer = input.trim().to_lowercase();
+    Ok(match answer.as_str() {
+        "y" | "yes" => ApprovalDecision::Approved,
+        "i" | "intelligence" => ApprovalDecision::ApprovedWithExplanation,
+        "s" | "step" => ApprovalDecision::ApprovedStepByStep,
+        _ => ApprovalDecision::rejected("declined at the approval prompt"),
+    })
+}
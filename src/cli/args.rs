@@ -1,7 +1,8 @@
 use anyhow::Result;
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 use crate::config::Config;
+use crate::planner::ScriptFormat;
 
 use super::commands;
 
@@ -46,6 +47,55 @@ pub struct Cli {
     #[arg(short = 'q', long = "question")]
     pub question: Option<String>,
 
+    /// Execute the plan even if its confidence is below the refusal threshold
+    #[arg(long)]
+    pub force: bool,
+
+    /// Approve the plan without prompting (still stops on blocking review findings)
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// Keep planning and running batches until the model judges the goal achieved
+    #[arg(long = "loop")]
+    pub goal_loop: bool,
+
+    /// Run the plan in a sandbox first and show the files it would change
+    #[arg(long)]
+    pub preview: bool,
+
+    /// Ask before each command whether to run, skip, edit or explain it
+    #[arg(long)]
+    pub step: bool,
+
+    /// Give each command a terminal of its own; its stderr is recorded as stdout
+    #[arg(long)]
+    pub pty: bool,
+
+    /// Print the plan as a bash script, Makefile or YAML step list instead of running it
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = ["goal_loop", "preview", "step", "pty", "output"]
+    )]
+    pub emit: Option<EmitFormat>,
+
+    /// Output format for task runs; `json` prints the whole run as one JSON object
+    #[arg(long = "output", value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Run the named pipeline from `pipeline.profiles` in the config
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Print per-stage durations, LLM calls, tokens and retries after the run
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Stream agent events as JSON lines to this already-open file descriptor
+    #[arg(long = "events-fd", value_name = "FD")]
+    pub events_fd: Option<u32>,
+
     /// Configure li settings
     #[arg(long)]
     pub config: bool,
@@ -71,10 +121,78 @@ pub struct Cli {
     pub task: Vec<String>,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// File formats for `--emit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EmitFormat {
+    Bash,
+    Make,
+    Yaml,
+}
+
+impl From<EmitFormat> for ScriptFormat {
+    fn from(format: EmitFormat) -> Self {
+        match format {
+            EmitFormat::Bash => ScriptFormat::Bash,
+            EmitFormat::Make => ScriptFormat::Make,
+            EmitFormat::Yaml => ScriptFormat::Yaml,
+        }
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// Directly invoke the chat completion API.
     Chat(ChatArgs),
+    /// Show planning rules and the merged planner system prompt.
+    Rules,
+    /// Explain a shell command piece by piece without running it.
+    Explain(ExplainArgs),
+    /// Continue a saved task run from its next unfinished stage or step.
+    Resume(ResumeArgs),
+    /// Put back the files a plan changed, from the snapshot taken before it ran.
+    Undo(UndoArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct UndoArgs {
+    /// Run whose changes to revert (defaults to the most recent one not yet undone).
+    pub id: Option<String>,
+
+    /// Restore without asking for confirmation.
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// List saved snapshots instead of restoring one.
+    #[arg(long, conflicts_with_all = ["id", "yes"])]
+    pub list: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ResumeArgs {
+    /// Session to resume (defaults to the most recent one).
+    pub id: Option<String>,
+
+    /// Answer to the question the planner asked before the run stopped.
+    #[arg(long)]
+    pub answer: Option<String>,
+
+    /// List saved sessions instead of resuming one.
+    #[arg(long, conflicts_with_all = ["id", "answer"])]
+    pub list: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExplainArgs {
+    /// Command to explain; quote it so pipes and redirections reach li intact.
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub command: Vec<String>,
 }
 
 #[derive(Debug, Args)]
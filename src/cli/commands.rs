@@ -7,11 +7,14 @@ use crate::config::{Config, LlmProvider};
 use super::args::{Cli, Command};
 use super::chat;
 use super::config_cmd;
+use super::explain;
 use super::intelligence;
 use super::models;
 use super::providers;
+use super::rules;
 use super::setup;
 use super::task;
+use super::undo;
 use super::util;
 
 pub(crate) async fn run_setup(cli: Cli) -> Result<()> {
@@ -93,12 +96,37 @@ pub(crate) async fn run(cli: Cli, mut config: Config) -> Result<()> {
 
     match cli.command {
         Some(Command::Chat(args)) => chat::handle_chat(args, &config).await?,
-        None => task::handle_task(cli.task, &config).await?,
+        Some(Command::Rules) => rules::handle_rules(&config)?,
+        Some(Command::Explain(args)) => explain::handle_explain(args, &config).await?,
+        Some(Command::Resume(ref args)) => {
+            task::handle_resume(args, task_options(&cli), &config).await?
+        }
+        Some(Command::Undo(ref args)) => undo::handle_undo(args)?,
+        None => {
+            let options = task_options(&cli);
+            task::handle_task(cli.task, options, &config).await?
+        }
     }
 
     Ok(())
 }
 
+fn task_options(cli: &Cli) -> task::TaskOptions {
+    task::TaskOptions {
+        force: cli.force,
+        assume_yes: cli.yes,
+        goal_loop: cli.goal_loop,
+        preview: cli.preview,
+        step: cli.step,
+        pty: cli.pty,
+        emit: cli.emit,
+        timings: cli.timings,
+        profile: cli.profile.clone(),
+        events_fd: cli.events_fd,
+        output: cli.output,
+    }
+}
+
 fn show_welcome_message() -> Result<()> {
     let config_path = Config::config_path()?;
     let config_exists = config_path.exists();
@@ -146,6 +174,12 @@ fn show_welcome_message() -> Result<()> {
     );
     println!("   li --provider list                                 # Show supported providers");
     println!("   li --provider cerebras                             # Switch provider");
+    println!(
+        "   li rules                                           # Show planning rules and prompt"
+    );
+    println!(
+        "   li explain \"tar -xzvf app.tgz -C /opt\"            # Explain a command without running it"
+    );
     println!("   li --config                                        # Show current configuration");
     println!("   li --config --api-key YOUR_KEY                     # Set API key manually");
     println!("   li --config --timeout 60                           # Set timeout (seconds)");
@@ -32,11 +32,9 @@ pub(crate) async fn handle_config_direct(args: &Cli, config: &mut Config) -> Res
         if args.max_tokens.is_none() {
             if existing_config.llm.provider == LlmProvider::OpenRouter {
                 if let Ok(models) = fetch_openrouter_free_models(&existing_config.llm.api_key).await
+                    && let Some(selected) = models.into_iter().find(|m| m.id == *planner_model)
                 {
-                    if let Some(selected) = models.into_iter().find(|m| m.id == *planner_model) {
-                        planner_model_context_tokens =
-                            Some(derive_max_tokens(selected.context_length));
-                    }
+                    planner_model_context_tokens = Some(derive_max_tokens(selected.context_length));
                 }
             } else {
                 bail!("Model selection currently supported only for OpenRouter provider.");
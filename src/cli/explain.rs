@@ -0,0 +1,157 @@
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+
+use crate::client::{AIClient, ChatCompletionRequest, ChatMessage, ChatMessageRole, DynLlmClient};
+use crate::config::Config;
+use crate::planner::extract_json_object;
+use crate::tokens::compute_completion_token_budget;
+use crate::validator::{CommandPart, CommandValidator, PartKind};
+
+use super::args::ExplainArgs;
+
+const EXPLAIN_SYSTEM_PROMPT: &str = r#"You explain shell commands piece by piece without running them.
+
+You receive a command and a numbered list of its parts (program, flag, argument, redirect, operator).
+Return exactly one JSON object on a single line, no prose or code fences:
+{"summary": "<one or two sentences on what the whole command does, including anything destructive>", "annotations": ["<explanation of part 1>", "<explanation of part 2>", ...]}
+
+- `annotations` MUST have one entry per numbered part, in the same order.
+- Keep each annotation short (under 80 characters) and specific to this command.
+- For flags, explain what the flag does for this program; for arguments, explain the role they play."#;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ExplainResponse {
+    pub(super) summary: String,
+    pub(super) annotations: Vec<String>,
+}
+
+pub(crate) async fn handle_explain(args: ExplainArgs, config: &Config) -> Result<()> {
+    let command = args.command.join(" ").trim().to_owned();
+    if command.is_empty() {
+        bail!("Nothing to explain. Usage: li explain \"<command>\"");
+    }
+
+    let client = AIClient::new(&config.llm)?;
+    explain_command(&client, config, &command).await
+}
+
+/// Print `command` broken into its parts, each with a short explanation.
+pub(crate) async fn explain_command(
+    client: &DynLlmClient,
+    config: &Config,
+    command: &str,
+) -> Result<()> {
+    let parts = CommandValidator::split_command(command);
+    if parts.is_empty() {
+        bail!("Could not split '{}' into parts", command);
+    }
+
+    let explanation = annotate_parts(client, config, command, &parts).await?;
+
+    println!("\n{}", command);
+    println!("{}", "─".repeat(command.chars().count().min(100)));
+    print!("{}", render_breakdown(&parts, &explanation.annotations));
+
+    if !explanation.summary.trim().is_empty() {
+        println!("\nSummary: {}", explanation.summary.trim());
+    }
+    println!();
+
+    Ok(())
+}
+
+pub(super) async fn annotate_parts(
+    client: &DynLlmClient,
+    config: &Config,
+    command: &str,
+    parts: &[CommandPart],
+) -> Result<ExplainResponse> {
+    let mut request = format!(
+        "Command ({}): {}\n\nParts:\n",
+        config.execution.shell.display_name(),
+        command
+    );
+    for (idx, part) in parts.iter().enumerate() {
+        request.push_str(&format!("{}. [{}] {}\n", idx + 1, part.kind, part.text));
+    }
+
+    let messages = vec![
+        ChatMessage {
+            role: ChatMessageRole::System,
+            content: EXPLAIN_SYSTEM_PROMPT.to_string(),
+        },
+        ChatMessage {
+            role: ChatMessageRole::User,
+            content: request,
+        },
+    ];
+
+    let completion_budget = compute_completion_token_budget(config.models.max_tokens, &messages);
+
+    let response = client
+        .chat_completion(ChatCompletionRequest {
+            model: config.models.planner.clone(),
+            messages,
+            max_tokens: Some(completion_budget),
+            temperature: Some(0.0),
+        })
+        .await
+        .context("Failed to get command explanation")?;
+
+    let content = response
+        .choices
+        .first()
+        .map(|choice| choice.message.content.trim().to_owned())
+        .ok_or_else(|| anyhow!("Explanation response contained no choices"))?;
+
+    let json_fragment = extract_json_object(&content)
+        .ok_or_else(|| anyhow!("Explanation response did not contain JSON object"))?;
+
+    serde_json::from_str(&json_fragment)
+        .with_context(|| format!("Failed to parse explanation JSON: {content}"))
+}
+
+/// Each part indented by its role, with kinds and annotations in aligned columns.
+///
+/// Parts the model returned no annotation for are listed without one.
+pub(super) fn render_breakdown(parts: &[CommandPart], annotations: &[String]) -> String {
+    let labels: Vec<String> = parts
+        .iter()
+        .map(|part| match part.kind {
+            PartKind::Program | PartKind::Operator => part.text.clone(),
+            _ => format!("  {}", part.text),
+        })
+        .collect();
+
+    let text_width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(48);
+    let kind_width = parts
+        .iter()
+        .map(|part| part.kind.to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    for (idx, (part, label)) in parts.iter().zip(&labels).enumerate() {
+        let annotation = annotations.get(idx).map(|a| a.trim()).unwrap_or("");
+        let label = if label.chars().count() > text_width {
+            out.push_str(&format!("{}\n", label));
+            ""
+        } else {
+            label.as_str()
+        };
+        let row = format!(
+            "{:text_width$}  {:kind_width$}  {}",
+            label,
+            part.kind.to_string(),
+            annotation
+        );
+        out.push_str(row.trim_end());
+        out.push('\n');
+    }
+    out
+}
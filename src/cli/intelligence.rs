@@ -31,13 +31,10 @@ pub(crate) async fn handle_intelligence(
             let looks_like_question =
                 potential_question.ends_with('?') || potential_question.contains('?');
             let command_has_whitespace = potential_command.contains(char::is_whitespace);
-            let command_starts_with_flag = potential_command.starts_with('-');
 
             if looks_like_question || command_has_whitespace {
                 question = Some(potential_question);
                 potential_command
-            } else if command_starts_with_flag {
-                task.join(" ").trim().to_owned()
             } else {
                 task.join(" ").trim().to_owned()
             }
@@ -46,10 +43,10 @@ pub(crate) async fn handle_intelligence(
 
     let mut piped_input = piped_input;
     let piped_was_present = piped_input.is_some();
-    if let Some(ref data) = piped_input {
-        if data.trim().is_empty() {
-            piped_input = None;
-        }
+    if let Some(ref data) = piped_input
+        && data.trim().is_empty()
+    {
+        piped_input = None;
     }
 
     if piped_input.is_none() && command_candidate.is_empty() {
@@ -83,7 +80,7 @@ pub(crate) async fn handle_intelligence(
         println!("🔧 Executing: {}", command_display);
         println!();
 
-        let output = Command::new("sh")
+        let output = Command::new(config.execution.shell.program())
             .arg("-c")
             .arg(&command_candidate)
             .output()
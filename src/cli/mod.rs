@@ -1,12 +1,21 @@
+mod approval;
 mod args;
 mod chat;
 mod commands;
 mod config_cmd;
+mod explain;
 mod intelligence;
 mod models;
+mod output;
 mod providers;
+mod rules;
 mod setup;
+mod step;
 mod task;
+mod undo;
 mod util;
 
 pub use args::Cli;
+
+#[cfg(test)]
+mod tests;
@@ -20,6 +20,7 @@ struct OpenRouterModelsResponse {
     data: Vec<OpenRouterModel>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct Pricing {
     prompt: String,
@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+use std::process;
+
+use crate::agent::metrics::totals;
+use crate::agent::{AgentEvent, AgentOutcome, AgentRun, ApprovalDecision, LoopStop, StageMetrics};
+
+/// Process exit codes used by `--output json`. These are part of the scripting interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExitStatus {
+    /// The plan ran and every command succeeded.
+    Success = 0,
+    /// A plan was produced but not executed (rejected, refused or blocked by validation).
+    NotExecuted = 3,
+    /// Execution started and a command failed.
+    ExecutionFailed = 4,
+    /// The planner asked a question that needs an answer.
+    NeedsClarification = 5,
+    /// A pipeline stage errored (e.g. the LLM request failed).
+    StageFailed = 6,
+    /// `--loop` stopped at the iteration limit or token budget before reaching the goal.
+    GoalNotReached = 7,
+    /// The run was stopped by Ctrl-C or SIGTERM.
+    Interrupted = 130,
+}
+
+impl ExitStatus {
+    pub(crate) fn for_run(run: &AgentRun) -> Self {
+        if run
+            .events
+            .iter()
+            .any(|event| matches!(event, AgentEvent::RunCancelled { .. }))
+        {
+            return ExitStatus::Interrupted;
+        }
+        let stop = run.goal_loop.as_ref().and_then(|summary| summary.stop);
+        match stop {
+            Some(LoopStop::MaxIterations | LoopStop::TokenBudget) => ExitStatus::GoalNotReached,
+            _ => Self::for_outcome(&run.outcome),
+        }
+    }
+
+    pub(crate) fn for_outcome(outcome: &AgentOutcome) -> Self {
+        match outcome {
+            AgentOutcome::Planned {
+                validation,
+                approval,
+                execution,
+                ..
+            } => {
+                let blocked = validation
+                    .as_ref()
+                    .is_some_and(|validation| !validation.plan_can_continue);
+                let rejected = matches!(approval, Some(ApprovalDecision::Rejected { .. }));
+                match execution {
+                    _ if blocked || rejected => ExitStatus::NotExecuted,
+                    Some(report) if report.success => ExitStatus::Success,
+                    Some(_) => ExitStatus::ExecutionFailed,
+                    None => ExitStatus::NotExecuted,
+                }
+            }
+            AgentOutcome::AwaitingClarification { .. } => ExitStatus::NeedsClarification,
+            AgentOutcome::Cancelled { .. } => ExitStatus::NotExecuted,
+            AgentOutcome::Failed { .. } => ExitStatus::StageFailed,
+        }
+    }
+}
+
+/// Print the run as a single JSON object and exit with its [`ExitStatus`].
+pub(crate) fn print_run_json(run: &AgentRun) -> ! {
+    let status = ExitStatus::for_run(run);
+    let mut stdout = io::stdout().lock();
+    let written = serde_json::to_writer(&mut stdout, run)
+        .map_err(io::Error::from)
+        .and_then(|()| writeln!(stdout))
+        .and_then(|()| stdout.flush());
+    if let Err(err) = written {
+        eprintln!("Failed to write JSON output: {err}");
+        process::exit(1);
+    }
+    process::exit(status as i32);
+}
+
+/// Print a per-stage timing table to stderr so it never mixes with JSON on stdout.
+pub(crate) fn print_timings(metrics: &[StageMetrics]) {
+    let row = |label: &str, ms: u64, calls: u64, tokens: u64, retries: u64| {
+        format!(
+            "{:<12} {:>9} {:>9} {:>8} {:>8}",
+            label,
+            format!("{:.2}s", ms as f64 / 1000.0),
+            calls,
+            tokens,
+            retries
+        )
+    };
+
+    eprintln!();
+    eprintln!(
+        "{:<12} {:>9} {:>9} {:>8} {:>8}",
+        "Stage", "Time", "LLM calls", "Tokens", "Retries"
+    );
+    for stage in metrics {
+        eprintln!(
+            "{}",
+            row(
+                &stage.stage.to_string(),
+                stage.duration_ms,
+                stage.llm_calls,
+                stage.tokens,
+                stage.retries
+            )
+        );
+    }
+    let (ms, calls, tokens, retries) = totals(metrics);
+    eprintln!("{}", row("total", ms, calls, tokens, retries));
+}
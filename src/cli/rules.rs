@@ -0,0 +1,32 @@
+use anyhow::Result;
+
+use crate::config::Config;
+use crate::planner::{self, PlannerGuidance, PlannerRules};
+
+pub(crate) fn handle_rules(config: &Config) -> Result<()> {
+    let rules = PlannerRules::load()?;
+
+    println!("📜 Planning rules");
+    match &rules.user {
+        Some(file) => println!("   User rules: {}", file.path.display()),
+        None => println!("   User rules: (none, create ~/.li/rules.md)"),
+    }
+    match &rules.project {
+        Some(file) => println!("   Project rules: {}", file.path.display()),
+        None => println!("   Project rules: (none, create .li/rules.md in your project)"),
+    }
+    println!("   Precedence: built-in safety rules > project rules > user rules");
+    println!("   Target shell: {}", config.execution.shell.display_name());
+
+    let guidance = PlannerGuidance {
+        rules,
+        shell: config.execution.shell,
+        persistent_shell: config.execution.persistent_shell,
+        ..PlannerGuidance::default()
+    };
+
+    println!("\n=== Effective Planner Prompt ===");
+    println!("{}", planner::planner_system_prompt(&guidance));
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crate::cancel::CancelToken;
+use crate::client::{AIClient, MeteredClient, UsageMeter};
+use crate::config::Config;
+use crate::exec::{PendingStep, StepChoice, StepGate};
+
+use super::explain::explain_command;
+
+/// Asks on the terminal before each command of a step-by-step run.
+pub(crate) struct TerminalStepGate {
+    config: Config,
+    usage: Arc<UsageMeter>,
+    cancel: CancelToken,
+}
+
+impl TerminalStepGate {
+    /// Explanations are counted in `usage` and stopped by `cancel`, like the
+    /// run's own LLM calls.
+    pub(crate) fn new(config: Config, usage: Arc<UsageMeter>, cancel: CancelToken) -> Self {
+        Self {
+            config,
+            usage,
+            cancel,
+        }
+    }
+
+    async fn explain(&self, command: &str) -> Result<()> {
+        let client = MeteredClient::new(
+            Arc::new(AIClient::new(&self.config.llm)?),
+            self.usage.clone(),
+        )
+        .with_cancel_token(self.cancel.clone());
+        explain_command(&client, &self.config, command).await
+    }
+}
+
+#[async_trait]
+impl StepGate for TerminalStepGate {
+    async fn decide(&self, step: PendingStep<'_>) -> Result<StepChoice> {
+        let mut command = step.command.to_string();
+        loop {
+            print!("[r]un, [s]kip, [e]dit, e[x]plain or [a]bort? ");
+            io::stdout().flush()?;
+            let Some(answer) = read_line().await? else {
+                return Ok(StepChoice::Abort);
+            };
+
+            match answer.trim().to_lowercase().as_str() {
+                "r" | "run" | "y" | "yes" if command == step.command => return Ok(StepChoice::Run),
+                "r" | "run" | "y" | "yes" => return Ok(StepChoice::Edit(command)),
+                "s" | "skip" => return Ok(StepChoice::Skip),
+                "a" | "abort" => return Ok(StepChoice::Abort),
+                "e" | "edit" => {
+                    println!("Current: {command}");
+                    print!("New command (empty keeps it): ");
+                    io::stdout().flush()?;
+                    let Some(edited) = read_line().await? else {
+                        return Ok(StepChoice::Abort);
+                    };
+                    if !edited.trim().is_empty() {
+                        command = edited.trim().to_string();
+                    }
+                    println!(
+                        "> {} step {}/{}: {command}",
+                        step.phase, step.index, step.total
+                    );
+                }
+                "x" | "explain" => {
+                    if let Err(err) = self.explain(&command).await {
+                        println!("⚠️  Could not explain the command: {err:#}");
+                    }
+                }
+                _ => println!("Please answer r, s, e, x or a."),
+            }
+        }
+    }
+}
+
+/// One line from stdin, or `None` once it is closed. The read runs on the
+/// blocking pool so the runtime stays free while the user thinks.
+async fn read_line() -> Result<Option<String>> {
+    tokio::task::spawn_blocking(|| {
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(input))
+    })
+    .await?
+}
@@ -1,16 +1,57 @@
-use anyhow::{Context, Result, bail};
-use std::io::{self, Write};
-
-use crate::agent::{AgentOrchestrator, AgentOutcome, AgentRequest, StageKind};
-use crate::client::AIClient;
-use crate::config::Config;
-use crate::planner;
-use crate::recovery::{RecoveryContext, RecoveryEngine, RecoveryResult, RecoveryStrategy};
+use std::io::{self, IsTerminal};
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::agent::{
+    AgentOrchestrator, AgentOutcome, AgentPipelineBuilder, AgentRequest, AgentRun,
+    ApprovalDecision, AutoApprover, Clarification, CommandValidationAdapter, DirectPlanningAdapter,
+    EngineRecoveryAdapter, ExecutionReport, JsonLinesObserver, LogObserver, LoopStop, LoopSummary,
+    MetricsLog, MetricsRecord, ModelGoalAdapter, ModelReviewAdapter, NoopRecoveryAdapter,
+    PlanExecutionAdapter, PluginStage, PolicyApprover, PreviewStage, ReviewStage,
+    SandboxPreviewAdapter, SessionRecorder, SessionStore, StageKind, TerminalProgressObserver,
+    chooser_for,
+};
+use crate::cancel::{self, CancelToken};
+use crate::client::{AIClient, UsageMeter};
+use crate::config::{ApprovalMode, Config, StageSpec};
+use crate::history::PlanHistory;
+use crate::planner::{self, SCRIPT_SHELL, ScriptFormat, ScriptHeader, shell_note};
+use crate::reviewer::PlanReview;
+use crate::undo::UndoStore;
 use crate::validator::ValidationResult;
 
+use super::approval::{InteractiveApprover, PlanOnlyApprover, render_plan};
+use super::args::{EmitFormat, OutputFormat, ResumeArgs};
 use super::intelligence::explain_plan_output;
+use super::output::{self, ExitStatus};
+use super::step::TerminalStepGate;
+
+/// Flags that shape a single task run.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TaskOptions {
+    pub force: bool,
+    pub assume_yes: bool,
+    pub goal_loop: bool,
+    pub preview: bool,
+    pub step: bool,
+    pub pty: bool,
+    /// Print the plan in this format instead of running it.
+    pub emit: Option<EmitFormat>,
+    pub timings: bool,
+    /// Pipeline profile from `pipeline.profiles` in the config.
+    pub profile: Option<String>,
+    pub events_fd: Option<u32>,
+    pub output: OutputFormat,
+}
 
-pub(crate) async fn handle_task(words: Vec<String>, config: &Config) -> Result<()> {
+pub(crate) async fn handle_task(
+    words: Vec<String>,
+    options: TaskOptions,
+    config: &Config,
+) -> Result<()> {
     let prompt = words.join(" ").trim().to_owned();
     if prompt.is_empty() {
         println!(
@@ -19,42 +60,252 @@ pub(crate) async fn handle_task(words: Vec<String>, config: &Config) -> Result<(
         return Ok(());
     }
 
-    let orchestrator = AgentOrchestrator::default();
-    let request = AgentRequest::new(prompt.clone());
+    let standard = StageSpec::standard();
+    let configured = config.pipeline.stages_for(options.profile.as_deref())?;
+    let mut stages = configured.unwrap_or(&standard);
+    let emitted;
+    let mut session = None;
+    let config = &match options.emit {
+        Some(format) => {
+            emitted = emit_stages(stages);
+            stages = &emitted;
+            emit_config(config, format.into())
+        }
+        None => {
+            session = start_session(stages, configured.is_some());
+            config.clone()
+        }
+    };
+    let session_id = session.as_ref().map(|session| session.id().to_string());
+    let orchestrator = build_orchestrator(
+        &options,
+        config,
+        stages,
+        configured.is_some(),
+        task_cancel_token(&options),
+        session,
+    )?;
+    let mut request = AgentRequest::new(prompt.clone());
+    request.force = options.force;
+    request.assume_yes = options.assume_yes;
+    request.interactive = options.output == OutputFormat::Text && options.emit.is_none();
+    request.goal_loop = options.goal_loop || stages.contains(&StageSpec::Assessment);
+    request.preview = options.preview;
+    request.step = options.step;
+    request.pty = options.pty;
 
     let run = orchestrator
         .run(config.clone(), request)
         .await
         .context("Agent pipeline failed")?;
 
+    finish_task(run, &prompt, session_id.as_deref(), &options, config).await
+}
+
+/// Continue a saved session, or list the saved sessions.
+pub(crate) async fn handle_resume(
+    args: &ResumeArgs,
+    mut options: TaskOptions,
+    config: &Config,
+) -> Result<()> {
+    if options.emit.is_some() {
+        bail!("--emit starts a new task; it cannot be used with resume");
+    }
+    let store = SessionStore::open_default()?;
+    if args.list {
+        list_sessions(&store)?;
+        return Ok(());
+    }
+
+    let mut session = match &args.id {
+        Some(id) => store.load(id)?,
+        None => store
+            .latest()?
+            .ok_or_else(|| anyhow!("No saved sessions to resume"))?,
+    };
+
+    if let Some(answer) = &args.answer {
+        let Some(AgentOutcome::AwaitingClarification { question, .. }) = &session.outcome else {
+            bail!(
+                "Session {} is not waiting for an answer ({})",
+                session.id,
+                session.status()
+            );
+        };
+        session.state.clarifications.push(Clarification {
+            question: question.clone(),
+            answer: answer.clone(),
+        });
+    }
+
+    std::env::set_current_dir(&session.cwd).with_context(|| {
+        format!(
+            "Session {} was started in {}, which is no longer available",
+            session.id,
+            session.cwd.display()
+        )
+    })?;
+
+    let request = &mut session.state.request;
+    request.interactive = options.output == OutputFormat::Text;
+    request.force |= options.force;
+    request.assume_yes |= options.assume_yes;
+    request.preview |= options.preview;
+    request.step |= options.step;
+    request.pty |= options.pty;
+    options.force = request.force;
+    options.assume_yes = request.assume_yes;
+    options.goal_loop = request.goal_loop;
+    let prompt = request.task.clone();
+
+    if options.output == OutputFormat::Text {
+        println!(
+            "Resuming session {} ({}): {}",
+            session.id,
+            session.status(),
+            prompt
+        );
+    }
+
+    let session_id = session.id.clone();
+    let recorder = SessionRecorder::resume(store, &session);
+    let orchestrator = build_orchestrator(
+        &options,
+        config,
+        &session.stages,
+        session.configured,
+        task_cancel_token(&options),
+        Some(recorder),
+    )?;
+    let run = orchestrator
+        .resume(config.clone(), session.state)
+        .await
+        .context("Agent pipeline failed")?;
+
+    finish_task(run, &prompt, Some(&session_id), &options, config).await
+}
+
+fn list_sessions(store: &SessionStore) -> Result<()> {
+    let sessions = store.list()?;
+    if sessions.is_empty() {
+        println!("No saved sessions.");
+        return Ok(());
+    }
+    for session in sessions {
+        println!(
+            "{}  {:<22}  {}",
+            session.id,
+            session.status(),
+            session.state.request.task
+        );
+        if let Some(AgentOutcome::AwaitingClarification { question, .. }) = &session.outcome {
+            println!("    question: {}", question);
+        }
+    }
+    Ok(())
+}
+
+/// Ctrl-C handling for a run; prompts only when a person is at the terminal.
+fn task_cancel_token(options: &TaskOptions) -> CancelToken {
+    let text = options.output == OutputFormat::Text;
+    let cancel = CancelToken::new().with_prompt(text && io::stdin().is_terminal());
+    cancel::install_signal_handlers(&cancel);
+    cancel
+}
+
+/// Start saving the run under `~/.li/sessions`; runs go unsaved when there is no home directory.
+fn start_session(stages: &[StageSpec], configured: bool) -> Option<SessionRecorder> {
+    let store = SessionStore::open_default().ok()?;
+    Some(SessionRecorder::start(store, stages.to_vec(), configured))
+}
+
+/// Report a finished run and turn its outcome into the process result.
+async fn finish_task(
+    run: AgentRun,
+    prompt: &str,
+    session_id: Option<&str>,
+    options: &TaskOptions,
+    config: &Config,
+) -> Result<()> {
+    record_metrics(config, &run);
+    if options.timings {
+        output::print_timings(&run.metrics);
+    }
+
+    if let Some(format) = options.emit {
+        match &run.outcome {
+            AgentOutcome::Planned {
+                plan: Some(plan),
+                validation,
+                review,
+                ..
+            } => {
+                let header = script_header(prompt, config, validation.as_ref(), review.as_ref());
+                return emit_plan(plan, format.into(), &header, validation.as_ref());
+            }
+            AgentOutcome::AwaitingClarification { question, .. } => bail!(
+                "The planner needs more information: {question} Add the answer to the task and run it again."
+            ),
+            _ => {}
+        }
+    }
+
+    if options.output == OutputFormat::Json {
+        remember_successful_run(config, prompt, &run);
+        output::print_run_json(&run);
+    }
+
+    if let Some(summary) = &run.goal_loop {
+        render_goal_loop(summary)?;
+        if summary.stop.is_some_and(LoopStop::goal_achieved) {
+            return Ok(());
+        }
+    }
+
+    let status = ExitStatus::for_run(&run);
     match run.outcome {
         AgentOutcome::Planned {
             plan: Some(plan),
             validation,
+            review,
+            approval,
+            execution,
             ..
         } => {
-            if let Some(validation) = validation.clone() {
-                let proceed =
-                    resolve_validation_issues(&validation, &plan, config, &prompt).await?;
-                if !proceed {
-                    return Ok(());
-                }
+            if let Some(validation) = &validation
+                && !validation.plan_can_continue
+            {
+                render_syntax_issues(validation, config);
+                return Ok(());
             }
 
-            render_plan(&plan, config);
+            if let Some(ApprovalDecision::Rejected { reason }) = approval {
+                println!("\nPlan execution cancelled: {}", reason);
+                return Ok(());
+            }
 
-            match prompt_for_approval()? {
-                ApprovalResponse::Yes => {
-                    crate::exec::execute_plan(&plan).await?;
-                }
-                ApprovalResponse::YesWithIntelligence => {
-                    let output = crate::exec::execute_plan_with_capture(&plan).await?;
-                    let client = AIClient::new(&config.llm)?;
-                    explain_plan_output(&client, config, &plan, &output).await?;
-                }
-                ApprovalResponse::No => {
-                    println!("\nPlan execution cancelled.");
+            let Some(report) = execution else {
+                // Pipelines without approval or execution stages still show what was planned.
+                if approval.is_none() {
+                    render_plan(&plan, review.as_ref(), config);
                 }
+                return Ok(());
+            };
+
+            if let Some(id) = &report.undo {
+                println!("Undo with: li undo {}", id);
+            }
+            if !report.success {
+                bail!("Plan execution failed: {}", report.notes.join("; "));
+            }
+
+            if !options.goal_loop {
+                remember_plan(config, prompt, &plan, &report);
+            }
+            if approval == Some(ApprovalDecision::ApprovedWithExplanation) {
+                let output = report.transcript();
+                let client = AIClient::new(&config.llm)?;
+                explain_plan_output(&client, config, &plan, &output).await?;
             }
 
             Ok(())
@@ -68,10 +319,29 @@ pub(crate) async fn handle_task(words: Vec<String>, config: &Config) -> Result<(
             if !context.trim().is_empty() {
                 println!("Context: {}", context);
             }
+            if let Some(id) = session_id {
+                println!("Answer with: li resume {} --answer \"...\"", id);
+            }
             Ok(())
         }
-        AgentOutcome::Cancelled { reason } => {
-            println!("Agent cancelled the request: {}", reason);
+        AgentOutcome::Cancelled {
+            reason, execution, ..
+        } => {
+            println!("\nPlan execution cancelled: {}", reason);
+            if let Some(report) = execution {
+                for note in &report.notes {
+                    println!("  {}", note);
+                }
+                if let Some(id) = &report.undo {
+                    println!("Undo with: li undo {}", id);
+                }
+            }
+            if let Some(id) = session_id {
+                println!("Continue with: li resume {}", id);
+            }
+            if status == ExitStatus::Interrupted {
+                process::exit(status as i32);
+            }
             Ok(())
         }
         AgentOutcome::Failed { stage, error } => {
@@ -81,208 +351,337 @@ pub(crate) async fn handle_task(words: Vec<String>, config: &Config) -> Result<(
                     config.llm.provider.display_name(),
                     config.llm.provider.api_key_env_var()
                 ),
-                StageKind::Validation => "Inspect the validator warnings above for missing tools before rerunning the command.".to_string(),
+                StageKind::Validation => "Inspect the validator warnings above for missing tools or shell syntax errors before rerunning the command.".to_string(),
+                StageKind::Review => format!(
+                    "Check that the reviewer model '{}' is available, or disable review in your config.",
+                    config.review.model_or(&config.models.planner)
+                ),
+                StageKind::Preview => {
+                    "The plan could not be previewed. Re-run without --preview to skip the preview.".to_string()
+                }
+                StageKind::Approval => {
+                    "The plan could not be approved. Re-run the command to be prompted again.".to_string()
+                }
                 StageKind::Execution => {
                     "Review the command output above for failures before retrying.".to_string()
                 }
                 StageKind::Recovery => {
                     "Recovery cancelled. Resolve tool installation manually or re-run with recovery enabled.".to_string()
                 }
+                StageKind::Assessment => {
+                    "The goal could not be assessed. Check the command output above and re-run the task to continue.".to_string()
+                }
+                StageKind::Plugin => {
+                    "Check the plugin's own output above and its command in the pipeline config.".to_string()
+                }
             };
-            bail!("Agent stage {} failed: {}. {}", stage, error, guidance);
+            let resume = session_id
+                .map(|id| format!(" Then continue with: li resume {id}"))
+                .unwrap_or_default();
+            bail!(
+                "Agent stage {} failed: {}. {}{}",
+                stage,
+                error,
+                guidance,
+                resume
+            );
         }
     }
 }
 
-async fn resolve_validation_issues(
-    validation: &ValidationResult,
-    plan: &planner::Plan,
+/// Compose the pipeline from `stages`; `configured` is set when they come from the config file.
+fn build_orchestrator(
+    options: &TaskOptions,
     config: &Config,
-    goal: &str,
-) -> Result<bool> {
-    if validation.missing_commands.is_empty() {
-        return Ok(true);
-    }
-
-    let count = validation.missing_commands.len();
-    println!(
-        "⚠️  Validator identified {} missing command{}:",
-        count,
-        if count == 1 { "" } else { "s" }
-    );
-    for missing in &validation.missing_commands {
-        let phase = if missing.is_dry_run {
-            "dry-run"
-        } else {
-            "execute"
-        };
-        println!(
-            "   • {} (step {}: {})",
-            missing.command,
-            missing.plan_step + 1,
-            phase
-        );
+    stages: &[StageSpec],
+    configured: bool,
+    cancel: CancelToken,
+    session: Option<SessionRecorder>,
+) -> Result<AgentOrchestrator> {
+    let text = options.output == OutputFormat::Text;
+    let usage = Arc::new(UsageMeter::default());
+    let mut builder = AgentPipelineBuilder::new()
+        .with_cancel_token(cancel.clone())
+        .with_usage_meter(usage.clone());
+    if let Some(session) = session {
+        builder = builder.with_session(session);
     }
-
-    if validation.plan_can_continue {
-        println!(
-            "Plan can continue, but results may be degraded until the missing tool{} {} installed.",
-            if count == 1 { "" } else { "s are" },
-            if count == 1 { "is" } else { "are" }
-        );
-        return Ok(true);
+    if text {
+        builder = builder.with_observer(LogObserver);
+        if let Some(progress) = TerminalProgressObserver::for_stderr() {
+            builder = builder.with_observer(progress);
+        }
     }
-
-    println!("Plan cannot continue until the missing commands are addressed.");
-
-    if !config.recovery.enabled {
-        println!("Recovery is disabled in your configuration. Enable it to receive guided fixes.");
-        return Ok(false);
+    if let Some(fd) = options.events_fd {
+        builder = builder.with_observer(JsonLinesObserver::open_fd(fd)?);
     }
 
-    let mut engine = RecoveryEngine::new(config)?;
-    engine.set_available_tools().await?;
-    let mut any_success = false;
-
-    for missing in &validation.missing_commands {
-        loop {
-            let strategy = prompt_recovery_strategy()?;
-            if matches!(strategy, RecoveryStrategy::NeverRecover) {
-                println!(
-                    "Recovery cancelled. Resolve missing tools manually and rerun the command."
-                );
-                return Ok(false);
+    let mut builder = builder;
+    for stage in stages {
+        builder = match stage {
+            StageSpec::Planning => builder.with_planning_adapter(DirectPlanningAdapter::default()),
+            StageSpec::Validation => builder.with_validation_adapter(CommandValidationAdapter),
+            StageSpec::Review { model } => {
+                let review =
+                    ReviewStage::new(ModelReviewAdapter::default().with_model(model.clone()));
+                // A pipeline from the config file lists review on purpose.
+                builder.add_stage(if configured { review.always() } else { review })
             }
-
-            let options = engine
-                .generate_recovery_options(strategy, missing, plan, goal)
-                .await?;
-
-            if options.command_alternatives.is_empty()
-                && options.installation_instructions.is_empty()
-                && !options.can_skip_step
-            {
-                println!(
-                    "No automated recovery options available for '{}'.",
-                    missing.command
-                );
-                continue;
+            StageSpec::Preview => {
+                let preview = PreviewStage::new(SandboxPreviewAdapter);
+                builder.add_stage(if configured {
+                    preview.always()
+                } else {
+                    preview
+                })
             }
-
-            let choice = engine.present_recovery_menu(&options, missing).await?;
-            let context = RecoveryContext {
-                missing_command: missing.clone(),
-                original_plan: plan.clone(),
-                original_goal: goal.to_string(),
-            };
-
-            match engine.execute_recovery(choice, &context, &options).await? {
-                RecoveryResult::AlternativeSucceeded(alt) => {
-                    println!("✅ Alternative executed: {}", alt.command);
-                    any_success = true;
-                    break;
-                }
-                RecoveryResult::InstallationSucceeded(inst) => {
-                    println!("✅ Installation succeeded: {}", inst.command);
-                    any_success = true;
-                    break;
-                }
-                RecoveryResult::InstallationCancelled => {
-                    println!("Installation cancelled. Re-run the command when ready.");
-                    return Ok(false);
-                }
-                RecoveryResult::PlanAborted(reason) => {
-                    println!("Plan aborted: {}", reason);
-                    return Ok(false);
-                }
-                RecoveryResult::AlternativeFailed(_) | RecoveryResult::InstallationFailed(_) => {
-                    println!("Recovery attempt did not succeed. Try another option.");
+            StageSpec::Approval { mode } => {
+                let mode = if options.assume_yes {
+                    ApprovalMode::Auto
+                } else {
+                    mode.unwrap_or(config.approval.mode)
+                };
+                match mode {
+                    ApprovalMode::Interactive if text => builder.with_approver(InteractiveApprover),
+                    ApprovalMode::Interactive => builder.with_approver(PlanOnlyApprover),
+                    ApprovalMode::Auto => builder.with_approver(AutoApprover),
+                    ApprovalMode::Policy => builder.with_approver(PolicyApprover),
                 }
-                RecoveryResult::StepSkipped => {
-                    println!("Recovery step skipped.");
-                    break;
+            }
+            StageSpec::Execution { assume_yes } => {
+                let mut execution = PlanExecutionAdapter::default()
+                    .with_assume_yes(*assume_yes)
+                    .with_echo(text)
+                    .with_terminal(text && io::stdin().is_terminal() && io::stdout().is_terminal());
+                if text && io::stdin().is_terminal() {
+                    execution = execution.with_step_gate(TerminalStepGate::new(
+                        config.clone(),
+                        usage.clone(),
+                        cancel.clone(),
+                    ));
                 }
-                RecoveryResult::RetryRequested | RecoveryResult::RetryWithDifferentApproach => {
-                    println!("Retry requested. Re-run the command after addressing the prompt.");
-                    return Ok(false);
+                if let Some(store) = undo_store(config) {
+                    execution = execution.with_undo(store);
                 }
+                builder.with_execution_adapter(execution)
             }
-        }
+            StageSpec::Recovery { chooser } if text => {
+                let chooser = chooser.unwrap_or(config.recovery.chooser);
+                builder.with_recovery_adapter(EngineRecoveryAdapter::new(chooser_for(chooser)))
+            }
+            StageSpec::Recovery { .. } => builder.with_recovery_adapter(NoopRecoveryAdapter),
+            StageSpec::Assessment => builder.with_goal_adapter(ModelGoalAdapter::default()),
+            StageSpec::Plugin {
+                name,
+                command,
+                args,
+                timeout_secs,
+            } => builder.add_stage(
+                PluginStage::new(name, command, args.clone())
+                    .with_timeout((*timeout_secs > 0).then(|| Duration::from_secs(*timeout_secs))),
+            ),
+        };
     }
 
-    if any_success {
-        println!("Re-run your original command to take advantage of the recovery steps.");
+    if options.goal_loop && !stages.contains(&StageSpec::Assessment) {
+        builder = builder.with_goal_adapter(ModelGoalAdapter::default());
     }
+    Ok(builder.build())
+}
 
-    Ok(false)
+/// The stages of an `--emit` run: everything that shapes the plan, nothing that
+/// approves or runs it.
+fn emit_stages(stages: &[StageSpec]) -> Vec<StageSpec> {
+    stages
+        .iter()
+        .filter(|stage| {
+            matches!(
+                stage,
+                StageSpec::Planning
+                    | StageSpec::Validation
+                    | StageSpec::Review { .. }
+                    | StageSpec::Plugin { .. }
+            )
+        })
+        .cloned()
+        .collect()
 }
 
-fn render_plan(plan: &planner::Plan, config: &Config) {
-    println!("\n=== Proposed Plan ===");
-    println!("Provider: {}", config.llm.provider.display_name());
-    println!("Planner Model: {}", config.models.planner);
-    println!("Plan confidence: {:.2}", plan.confidence);
+/// Plan for bash, with state shared between commands only where the file runs
+/// them in one shell.
+fn emit_config(config: &Config, format: ScriptFormat) -> Config {
+    if let Some(note) = shell_note(config.execution.shell) {
+        eprintln!("{note}");
+    }
+    let mut config = config.clone();
+    config.execution.shell = SCRIPT_SHELL;
+    config.execution.persistent_shell = format == ScriptFormat::Bash;
+    config
+}
 
-    if !plan.dry_run_commands.is_empty() {
-        println!("\nDry-run Commands:");
-        for (idx, cmd) in plan.dry_run_commands.iter().enumerate() {
-            println!("  {}. {}", idx + 1, cmd);
+fn script_header(
+    task: &str,
+    config: &Config,
+    validation: Option<&ValidationResult>,
+    review: Option<&PlanReview>,
+) -> ScriptHeader {
+    let mut remarks = Vec::new();
+    for missing in validation.map_or(&[][..], |validation| &validation.missing_commands) {
+        remarks.push(format!(
+            "Requires `{}`, which was not installed where the plan was made.",
+            missing.command
+        ));
+    }
+    if let Some(review) = review {
+        if !review.summary.trim().is_empty() {
+            remarks.push(format!("Review: {}", review.summary.trim()));
+        }
+        for finding in &review.findings {
+            remarks.push(format!(
+                "Review finding ({}, {}): {}",
+                finding.severity, finding.category, finding.message
+            ));
         }
     }
+    ScriptHeader {
+        task: task.to_string(),
+        model: config.models.planner.clone(),
+        remarks,
+    }
+}
 
-    if !plan.execute_commands.is_empty() {
-        println!("\nExecute Commands:");
-        for (idx, cmd) in plan.execute_commands.iter().enumerate() {
-            println!("  {}. {}", idx + 1, cmd);
-        }
+/// Print the plan as a file; plans that are not valid bash are refused.
+fn emit_plan(
+    plan: &planner::Plan,
+    format: ScriptFormat,
+    header: &ScriptHeader,
+    validation: Option<&ValidationResult>,
+) -> Result<()> {
+    if let Some(validation) = validation
+        && !validation.syntax_issues.is_empty()
+    {
+        let issues = validation
+            .syntax_issues
+            .iter()
+            .map(|issue| format!("{} ({})", issue.command_line, issue.message))
+            .collect::<Vec<_>>();
+        bail!(
+            "The plan contains commands that are not valid bash syntax: {}",
+            issues.join("; ")
+        );
     }
+    print!("{}", planner::render_script(plan, format, header));
+    Ok(())
+}
 
-    if !plan.notes.trim().is_empty() {
-        println!("\nNotes: {}", plan.notes.trim());
+/// Snapshot store for `li undo`; runs go without snapshots when it is disabled or there is no home directory.
+fn undo_store(config: &Config) -> Option<UndoStore> {
+    if !config.undo.enabled {
+        return None;
     }
+    let store = UndoStore::open_default().ok()?;
+    Some(store.with_max_bytes(config.undo.max_snapshot_mb.saturating_mul(1024 * 1024)))
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ApprovalResponse {
-    Yes,
-    YesWithIntelligence,
-    No,
+/// Print each iteration's verdict and fail when the loop ran out of iterations or tokens.
+fn render_goal_loop(summary: &LoopSummary) -> Result<()> {
+    if summary.iterations.is_empty() {
+        return Ok(());
+    }
+
+    println!("\n=== Goal Loop ===");
+    for record in &summary.iterations {
+        let marker = if record.assessment.achieved {
+            "✓"
+        } else {
+            "…"
+        };
+        println!(
+            "  {} iteration {}: {}",
+            marker, record.iteration, record.assessment.summary
+        );
+    }
+    println!(
+        "{} LLM call(s), {} token(s) used",
+        summary.llm_calls, summary.tokens_used
+    );
+
+    match summary.stop {
+        Some(stop @ (LoopStop::MaxIterations | LoopStop::TokenBudget)) => bail!(
+            "Goal not reached: {} after {} iteration(s)",
+            stop.describe(),
+            summary.iterations.len()
+        ),
+        _ => Ok(()),
+    }
 }
 
-fn prompt_for_approval() -> Result<ApprovalResponse> {
-    print!("\nExecute this plan? [y/N/i]: ");
-    io::stdout().flush()?;
+/// Append the run's stage metrics to the local metrics file.
+fn record_metrics(config: &Config, run: &AgentRun) {
+    if !config.metrics.enabled {
+        return;
+    }
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+    let record = MetricsRecord::new(config, run.outcome.status(), &run.metrics);
+    let result = MetricsLog::open_default().and_then(|log| log.append(&record));
+    if let Err(err) = result {
+        eprintln!("⚠️  Could not save run metrics: {err}");
+    }
+}
 
-    let answer = input.trim().to_lowercase();
-    Ok(match answer.as_str() {
-        "y" | "yes" => ApprovalResponse::Yes,
-        "i" | "intelligence" => ApprovalResponse::YesWithIntelligence,
-        _ => ApprovalResponse::No,
-    })
+fn remember_successful_run(config: &Config, task: &str, run: &AgentRun) {
+    if run.goal_loop.is_some() {
+        return;
+    }
+    if let AgentOutcome::Planned {
+        plan: Some(plan),
+        execution: Some(report),
+        ..
+    } = &run.outcome
+        && report.success
+    {
+        remember_plan(config, task, plan, report);
+    }
 }
 
-fn prompt_recovery_strategy() -> Result<RecoveryStrategy> {
-    println!("\nChoose a recovery approach:");
-    println!("  1) Look at alternate commands / install missing command");
-    println!("  2) Skip this missing step and continue");
-    println!("  3) Cancel recovery and exit");
-
-    loop {
-        print!("Selection [1-3]: ");
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let trimmed = input.trim();
-
-        match trimmed {
-            "1" => return Ok(RecoveryStrategy::InstallationFirst),
-            "2" => return Ok(RecoveryStrategy::SkipOnError),
-            "3" => return Ok(RecoveryStrategy::NeverRecover),
-            _ => println!("❌ Please enter a number between 1 and 3."),
-        }
+fn render_syntax_issues(validation: &ValidationResult, config: &Config) {
+    if validation.syntax_issues.is_empty() {
+        return;
+    }
+
+    println!(
+        "⚠️  The plan contains commands that are not valid {} syntax:",
+        config.execution.shell.display_name()
+    );
+    for issue in &validation.syntax_issues {
+        let phase = if issue.is_dry_run {
+            "dry-run"
+        } else {
+            "execute"
+        };
+        println!(
+            "   • step {} ({}): {}",
+            issue.plan_step + 1,
+            phase,
+            issue.command_line
+        );
+        println!("     {}", issue.message);
+    }
+    println!(
+        "Plan cannot run with the configured shell. Rephrase the task or change execution.shell in your config."
+    );
+}
+
+/// Store an approved, successfully executed plan for future few-shot retrieval.
+/// Plans whose steps were skipped or edited are not stored, since the planned
+/// commands are not what completed the task.
+fn remember_plan(config: &Config, task: &str, plan: &planner::Plan, report: &ExecutionReport) {
+    if !config.history.enabled || !report.ran_as_planned() {
+        return;
+    }
+
+    let result = PlanHistory::open_default().and_then(|history| history.record(task, plan));
+    if let Err(err) = result {
+        eprintln!("⚠️  Could not save plan to history: {err}");
     }
 }
@@ -0,0 +1,103 @@
+use httpmock::prelude::*;
+use serde_json::json;
+
+use super::explain::{annotate_parts, render_breakdown};
+use crate::client::AIClient;
+use crate::config::Config;
+use crate::validator::CommandValidator;
+
+fn explain_config(base_url: String) -> Config {
+    let mut config = Config::builder().build().unwrap();
+    config.llm.api_key = "test-key".to_string();
+    config.llm.base_url = base_url;
+    config.models.planner = "planner/model".to_string();
+    config
+}
+
+fn completion(content: &str) -> serde_json::Value {
+    json!({
+        "choices": [
+            {
+                "index": 0,
+                "finish_reason": "stop",
+                "message": { "role": "assistant", "content": content }
+            }
+        ]
+    })
+}
+
+#[tokio::test]
+async fn explain_annotates_each_numbered_part() {
+    let server = MockServer::start_async().await;
+    let content = "{\"summary\":\"Lists files by size.\",\"annotations\":[\"list directory contents\",\"long format with sizes\",\"the directory to list\",\"pipe into the next command\",\"sort lines\",\"numerically, largest first\"]}";
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/v1/chat/completions")
+                .json_body_partial(json!({ "model": "planner/model" }).to_string())
+                .body_contains("1. [program] ls")
+                .body_contains("6. [flag] -rn");
+            then.status(200).json_body(completion(content));
+        })
+        .await;
+
+    let config = explain_config(server.url("/v1"));
+    let client = AIClient::new(&config.llm).unwrap();
+    let command = "ls -l /tmp | sort -rn";
+    let parts = CommandValidator::split_command(command);
+    let explanation = annotate_parts(&client, &config, command, &parts)
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(explanation.summary, "Lists files by size.");
+    assert_eq!(
+        render_breakdown(&parts, &explanation.annotations)
+            .lines()
+            .collect::<Vec<_>>(),
+        [
+            "ls      program   list directory contents",
+            "  -l    flag      long format with sizes",
+            "  /tmp  argument  the directory to list",
+            "|       operator  pipe into the next command",
+            "sort    program   sort lines",
+            "  -rn   flag      numerically, largest first",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn explain_renders_every_part_when_annotations_do_not_match() {
+    let server = MockServer::start_async().await;
+    let content = "Here you go: {\"summary\":\"\",\"annotations\":[\"remove files\"]}";
+    server
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/chat/completions");
+            then.status(200).json_body(completion(content));
+        })
+        .await;
+
+    let config = explain_config(server.url("/v1"));
+    let client = AIClient::new(&config.llm).unwrap();
+    let command = "rm -rf build";
+    let parts = CommandValidator::split_command(command);
+    let explanation = annotate_parts(&client, &config, command, &parts)
+        .await
+        .unwrap();
+    assert_eq!(explanation.annotations.len(), 1);
+
+    // Parts past the last annotation are still listed, just without one.
+    let rendered = render_breakdown(&parts, &explanation.annotations);
+    assert_eq!(
+        rendered.lines().collect::<Vec<_>>(),
+        [
+            "rm       program   remove files",
+            "  -rf    flag",
+            "  build  argument"
+        ]
+    );
+
+    // Extra annotations are ignored rather than shifted onto other parts.
+    let extra = ["a", "b", "c", "d"].map(String::from);
+    assert_eq!(render_breakdown(&parts, &extra).lines().count(), 3);
+}
@@ -0,0 +1,105 @@
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::undo::{EntryState, UndoSnapshot, UndoStore};
+
+use super::args::UndoArgs;
+
+/// Restore a run's snapshot, or list the saved snapshots.
+pub(crate) fn handle_undo(args: &UndoArgs) -> Result<()> {
+    let store = UndoStore::open_default()?;
+    if args.list {
+        return list_snapshots(&store);
+    }
+
+    let snapshot = match &args.id {
+        Some(id) => store.load(id)?,
+        None => store.latest()?.ok_or_else(|| anyhow!("No runs to undo"))?,
+    };
+
+    println!("Undo snapshot {}: {}", snapshot.id, snapshot.task);
+    if snapshot.restored_at.is_some() {
+        println!(
+            "⚠️  This snapshot was already restored once; restoring it again reverts any changes made since."
+        );
+    }
+    render_snapshot(&snapshot);
+
+    if snapshot.entries.is_empty() {
+        println!("No files were recorded, so there is nothing to restore.");
+        return Ok(());
+    }
+
+    if !args.yes {
+        if !io::stdin().is_terminal() {
+            bail!("Refusing to restore files without confirmation; pass --yes to restore anyway");
+        }
+        print!("Restore these paths? [y/N]: ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().to_lowercase() != "y" {
+            println!("Nothing was restored.");
+            return Ok(());
+        }
+    }
+
+    let report = store.restore(&snapshot.id)?;
+    for path in &report.restored {
+        println!("  ✓ restored {}", path.display());
+    }
+    for path in &report.removed {
+        println!("  ✓ removed {}", path.display());
+    }
+    for (path, reason) in &report.failed {
+        println!("  ✗ {}: {}", path.display(), reason);
+    }
+    if !report.failed.is_empty() {
+        bail!("{} path(s) could not be restored", report.failed.len());
+    }
+    Ok(())
+}
+
+/// What restoring would do, and what it cannot revert.
+fn render_snapshot(snapshot: &UndoSnapshot) {
+    for entry in &snapshot.entries {
+        match &entry.state {
+            EntryState::Saved { .. } => println!("  ~ {}", entry.path.display()),
+            EntryState::Absent => println!("  - {} (created by the plan)", entry.path.display()),
+            EntryState::NotSaved { reason } => {
+                println!("  ! {} (not saved: {})", entry.path.display(), reason)
+            }
+        }
+    }
+
+    if !snapshot.irreversible.is_empty() {
+        println!("Undo cannot revert:");
+        for step in &snapshot.irreversible {
+            println!("  • {}: {}", step.command, step.reason);
+        }
+    }
+}
+
+fn list_snapshots(store: &UndoStore) -> Result<()> {
+    let snapshots = store.list()?;
+    if snapshots.is_empty() {
+        println!("No undo snapshots.");
+        return Ok(());
+    }
+    for snapshot in snapshots {
+        let status = if snapshot.restored_at.is_some() {
+            "restored"
+        } else {
+            "available"
+        };
+        println!(
+            "{}  {:<9}  {:>3} path(s)  {}",
+            snapshot.id,
+            status,
+            snapshot.entries.len(),
+            snapshot.task
+        );
+    }
+    Ok(())
+}
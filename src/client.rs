@@ -7,10 +7,12 @@ use serde::de::{self, Deserializer};
 use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tokio::time::{Duration, sleep};
 
+use crate::cancel::CancelToken;
 use crate::config::{LlmProvider, LlmSettings};
+use crate::tokens::estimate_prompt_tokens;
 
 const MAX_RETRIES: usize = 3;
 
@@ -163,7 +165,10 @@ impl LlmClient for ProviderClient {
         loop {
             attempt += 1;
             match self.execute_once(&request).await? {
-                ResponseOutcome::Success(response) => return Ok(response),
+                ResponseOutcome::Success(mut response) => {
+                    response.retries = (attempt - 1) as u32;
+                    return Ok(response);
+                }
                 ResponseOutcome::Retry(delay, message) => {
                     if attempt > MAX_RETRIES {
                         return Err(anyhow!(
@@ -200,6 +205,92 @@ fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
 
 pub type AIClient = ProviderClient;
 
+/// Running totals of LLM calls, tokens and retries shared by every client in a run.
+#[derive(Debug, Default)]
+pub struct UsageMeter {
+    calls: AtomicU64,
+    tokens: AtomicU64,
+    retries: AtomicU64,
+}
+
+impl UsageMeter {
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    pub fn tokens(&self) -> u64 {
+        self.tokens.load(Ordering::Relaxed)
+    }
+
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, tokens: u64, retries: u32) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.tokens.fetch_add(tokens, Ordering::Relaxed);
+        self.retries
+            .fetch_add(u64::from(retries), Ordering::Relaxed);
+    }
+}
+
+/// Client wrapper that records every completion in a [`UsageMeter`].
+///
+/// Falls back to the local token estimate when the provider omits `usage`.
+pub struct MeteredClient {
+    inner: Arc<DynLlmClient>,
+    meter: Arc<UsageMeter>,
+    cancel: CancelToken,
+}
+
+impl MeteredClient {
+    pub fn new(inner: Arc<DynLlmClient>, meter: Arc<UsageMeter>) -> Self {
+        Self {
+            inner,
+            meter,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// Abandon calls, including their retry back-off, when the run is interrupted.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+}
+
+#[async_trait]
+impl LlmClient for MeteredClient {
+    async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
+        let prompt_estimate = u64::from(estimate_prompt_tokens(&request.messages));
+        let response = tokio::select! {
+            response = self.inner.chat_completion(request) => response?,
+            () = self.cancel.interrupted() => {
+                self.cancel.cancel_if_interrupted();
+                return Err(anyhow!("LLM request cancelled"));
+            }
+        };
+
+        let reported = response.usage.map(|usage| usage.total_tokens).unwrap_or(0);
+        let tokens = if reported > 0 {
+            reported
+        } else {
+            let replies: Vec<ChatMessage> = response
+                .choices
+                .iter()
+                .map(|choice| choice.message.clone())
+                .collect();
+            prompt_estimate + u64::from(estimate_prompt_tokens(&replies))
+        };
+        self.meter.record(tokens, response.retries);
+
+        Ok(response)
+    }
+}
+
 pub trait LlmClientFactory: Send + Sync {
     fn build(&self, settings: &LlmSettings) -> Result<Arc<DynLlmClient>>;
 }
@@ -329,6 +420,18 @@ fn flatten_message_content(value: Value) -> String {
 #[derive(Debug, Clone, Deserialize)]
 pub struct ChatCompletionResponse {
     pub choices: Vec<ChatChoice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    /// Retries the client needed before this response arrived.
+    #[serde(skip)]
+    pub retries: u32,
+}
+
+/// Token counts reported by the provider, when it reports them.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub total_tokens: u64,
 }
 
 #[derive(Debug, Clone, Deserialize)]
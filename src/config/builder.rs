@@ -1,11 +1,29 @@
 use anyhow::Result;
-use super::types::{Config, LlmSettings, ModelSettings, RecoverySettings};
+use super::types::{
+    Config, ExecutionSettings, HistorySettings, LlmSettings, ModelSettings, RecoverySettings,
+    ReviewSettings, ConfidenceSettings, ApprovalSettings,
+    HooksSettings,
+    PipelineSettings,
+    MetricsSettings,
+    IterationSettings,
+    UndoSettings,
+};
 
 #[derive(Debug)]
 pub struct ConfigBuilder {
     pub(super) llm: LlmSettings,
     pub(super) models: ModelSettings,
     pub(super) recovery: RecoverySettings,
+    pub(super) history: HistorySettings,
+    pub(super) execution: ExecutionSettings,
+    pub(super) review: ReviewSettings,
+    pub(super) confidence: ConfidenceSettings,
+    pub(super) approval: ApprovalSettings,
+    pub(super) iteration: IterationSettings,
+    pub(super) metrics: MetricsSettings,
+    pub(super) undo: UndoSettings,
+    pub(super) pipeline: PipelineSettings,
+    pub(super) hooks: HooksSettings,
 }
 
 impl ConfigBuilder {
@@ -14,6 +32,16 @@ impl ConfigBuilder {
             llm: LlmSettings::default(),
             models: ModelSettings::default(),
             recovery: RecoverySettings::default(),
+            history: HistorySettings::default(),
+            execution: ExecutionSettings::default(),
+            review: ReviewSettings::default(),
+            confidence: ConfidenceSettings::default(),
+            approval: ApprovalSettings::default(),
+            iteration: IterationSettings::default(),
+            metrics: MetricsSettings::default(),
+            undo: UndoSettings::default(),
+            pipeline: PipelineSettings::default(),
+            hooks: HooksSettings::default(),
         }
     }
 
@@ -41,11 +69,101 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn with_history<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut HistorySettings),
+    {
+        update(&mut self.history);
+        self
+    }
+
+    pub fn with_execution<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut ExecutionSettings),
+    {
+        update(&mut self.execution);
+        self
+    }
+
+    pub fn with_review<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut ReviewSettings),
+    {
+        update(&mut self.review);
+        self
+    }
+
+    pub fn with_confidence<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut ConfidenceSettings),
+    {
+        update(&mut self.confidence);
+        self
+    }
+
+    pub fn with_approval<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut ApprovalSettings),
+    {
+        update(&mut self.approval);
+        self
+    }
+
+    pub fn with_iteration<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut IterationSettings),
+    {
+        update(&mut self.iteration);
+        self
+    }
+
+    pub fn with_metrics<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut MetricsSettings),
+    {
+        update(&mut self.metrics);
+        self
+    }
+
+    pub fn with_undo<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut UndoSettings),
+    {
+        update(&mut self.undo);
+        self
+    }
+
+    pub fn with_pipeline<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut PipelineSettings),
+    {
+        update(&mut self.pipeline);
+        self
+    }
+
+    pub fn with_hooks<F>(mut self, update: F) -> Self
+    where
+        F: FnOnce(&mut HooksSettings),
+    {
+        update(&mut self.hooks);
+        self
+    }
+
     pub fn build(self) -> Result<Config> {
         Ok(Config {
             llm: self.llm,
             models: self.models,
             recovery: self.recovery,
+            history: self.history,
+            execution: self.execution,
+            review: self.review,
+            confidence: self.confidence,
+            approval: self.approval,
+            iteration: self.iteration,
+            metrics: self.metrics,
+            undo: self.undo,
+            pipeline: self.pipeline,
+            hooks: self.hooks,
         })
     }
 }
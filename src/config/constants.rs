@@ -3,3 +3,15 @@ pub const DEFAULT_MAX_TOKENS: u32 = 2048;
 pub const DEFAULT_PLANNER_MODEL: &str = "minimax/minimax-m2:free";
 pub const DEFAULT_OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
 pub const DEFAULT_CEREBRAS_BASE_URL: &str = "https://api.cerebras.ai/v1";
+pub const DEFAULT_HISTORY_MAX_EXAMPLES: usize = 3;
+pub const DEFAULT_REPLAN_BELOW: f32 = 0.5;
+pub const DEFAULT_REFUSE_BELOW: f32 = 0.25;
+pub const DEFAULT_MAX_REPLANS: usize = 1;
+pub const DEFAULT_LOOP_MAX_ITERATIONS: usize = 5;
+pub const DEFAULT_LOOP_TOKEN_BUDGET: u64 = 50_000;
+pub const DEFAULT_STEP_TIMEOUT_SECS: u64 = 600;
+pub const DEFAULT_PLAN_TIMEOUT_SECS: u64 = 3600;
+pub const DEFAULT_IDLE_WARNING_SECS: u64 = 60;
+pub const DEFAULT_UNDO_MAX_SNAPSHOT_MB: u64 = 100;
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_PLUGIN_TIMEOUT_SECS: u64 = 60;
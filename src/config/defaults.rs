@@ -1,5 +1,7 @@
 use super::constants::*;
-use super::types::{LlmSettings, ModelSettings, RecoverySettings, LlmProvider};
+use super::types::{
+    MetricsSettings, UndoSettings, HooksSettings, IterationSettings, ExecutionSettings, ConfidenceSettings, HistorySettings, LlmSettings, ModelSettings, RecoverySettings, RecoveryChooserKind, LlmProvider, ShellDialect,
+};
 
 pub fn default_user_agent() -> String {
     format!("li/{}", env!("CARGO_PKG_VERSION"))
@@ -28,7 +30,81 @@ impl Default for ModelSettings {
 }
 
 impl Default for RecoverySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            chooser: RecoveryChooserKind::default(),
+        }
+    }
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_examples: DEFAULT_HISTORY_MAX_EXAMPLES,
+        }
+    }
+}
+
+impl Default for ConfidenceSettings {
+    fn default() -> Self {
+        Self {
+            replan_below: DEFAULT_REPLAN_BELOW,
+            refuse_below: DEFAULT_REFUSE_BELOW,
+            skip_dry_run_at: None,
+            max_replans: DEFAULT_MAX_REPLANS,
+        }
+    }
+}
+
+impl Default for ExecutionSettings {
+    fn default() -> Self {
+        Self {
+            shell: ShellDialect::default(),
+            step_timeout_secs: DEFAULT_STEP_TIMEOUT_SECS,
+            plan_timeout_secs: DEFAULT_PLAN_TIMEOUT_SECS,
+            idle_warning_secs: DEFAULT_IDLE_WARNING_SECS,
+            preview: false,
+            persistent_shell: false,
+            pty: false,
+        }
+    }
+}
+
+impl Default for IterationSettings {
+    fn default() -> Self {
+        Self {
+            max_iterations: DEFAULT_LOOP_MAX_ITERATIONS,
+            token_budget: DEFAULT_LOOP_TOKEN_BUDGET,
+        }
+    }
+}
+
+impl Default for MetricsSettings {
     fn default() -> Self {
         Self { enabled: true }
     }
 }
+
+impl Default for UndoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_snapshot_mb: DEFAULT_UNDO_MAX_SNAPSHOT_MB,
+        }
+    }
+}
+
+impl Default for HooksSettings {
+    fn default() -> Self {
+        Self {
+            pre_plan: None,
+            post_plan: None,
+            pre_execute: None,
+            post_execute: None,
+            on_failure: None,
+            timeout_secs: DEFAULT_HOOK_TIMEOUT_SECS,
+        }
+    }
+}
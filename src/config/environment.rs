@@ -2,7 +2,7 @@ use anyhow::{anyhow, Result, Context};
 use std::env;
 
 use super::builder::ConfigBuilder;
-use super::types::LlmProvider;
+use super::types::{LlmProvider, ShellDialect};
 
 pub fn apply_env_overrides(mut builder: ConfigBuilder) -> Result<ConfigBuilder> {
     if let Some(provider_raw) = env_string("LI_PROVIDER")? {
@@ -49,6 +49,17 @@ pub fn apply_env_overrides(mut builder: ConfigBuilder) -> Result<ConfigBuilder>
         builder = builder.with_models(|models| models.planner = planner);
     }
 
+    if let Some(reviewer) = env_string("LI_REVIEWER_MODEL")? {
+        builder = builder.with_review(|review| review.model = Some(reviewer));
+    }
+
+    if let Some(shell_raw) = env_string("LI_SHELL")? {
+        let shell = shell_raw
+            .parse::<ShellDialect>()
+            .with_context(|| format!("Failed to parse LI_SHELL value '{shell_raw}'"))?;
+        builder = builder.with_execution(|execution| execution.shell = shell);
+    }
+
     Ok(builder)
 }
 
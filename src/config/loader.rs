@@ -101,13 +101,12 @@ impl FileConfigV1 {
 impl FileConfigV2 {
     pub fn apply(self, builder: ConfigBuilder) -> ConfigBuilder {
         let builder = builder.with_llm(|llm| {
-            if let Some(provider) = self.llm.provider.clone() {
-                if let Ok(parsed) = provider.parse::<super::types::LlmProvider>() {
-                    if llm.provider != parsed {
-                        llm.provider = parsed;
-                        llm.base_url = parsed.default_base_url().to_string();
-                    }
-                }
+            if let Some(provider) = self.llm.provider.clone()
+                && let Ok(parsed) = provider.parse::<super::types::LlmProvider>()
+                && llm.provider != parsed
+            {
+                llm.provider = parsed;
+                llm.base_url = parsed.default_base_url().to_string();
             }
             if let Some(api_key) = self.llm.api_key.clone() {
                 llm.api_key = api_key;
@@ -132,11 +131,180 @@ impl FileConfigV2 {
             }
         });
 
-        if let Some(recovery) = self.recovery {
+        let builder = if let Some(recovery) = self.recovery {
             builder.with_recovery(|settings| {
                 if let Some(enabled) = recovery.enabled {
                     settings.enabled = enabled;
                 }
+                if let Some(chooser) = recovery.chooser
+                    && let Ok(parsed) = chooser.parse::<super::types::RecoveryChooserKind>()
+                {
+                    settings.chooser = parsed;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(history) = self.history {
+            builder.with_history(|settings| {
+                if let Some(enabled) = history.enabled {
+                    settings.enabled = enabled;
+                }
+                if let Some(max_examples) = history.max_examples {
+                    settings.max_examples = max_examples;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(execution) = self.execution {
+            builder.with_execution(|settings| {
+                if let Some(shell) = execution.shell
+                    && let Ok(parsed) = shell.parse::<super::types::ShellDialect>()
+                {
+                    settings.shell = parsed;
+                }
+                if let Some(secs) = execution.step_timeout_secs {
+                    settings.step_timeout_secs = secs;
+                }
+                if let Some(secs) = execution.plan_timeout_secs {
+                    settings.plan_timeout_secs = secs;
+                }
+                if let Some(secs) = execution.idle_warning_secs {
+                    settings.idle_warning_secs = secs;
+                }
+                if let Some(preview) = execution.preview {
+                    settings.preview = preview;
+                }
+                if let Some(persistent) = execution.persistent_shell {
+                    settings.persistent_shell = persistent;
+                }
+                if let Some(pty) = execution.pty {
+                    settings.pty = pty;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(review) = self.review {
+            builder.with_review(|settings| {
+                if let Some(enabled) = review.enabled {
+                    settings.enabled = enabled;
+                }
+                if let Some(model) = review.model {
+                    settings.model = Some(model);
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(approval) = self.approval {
+            builder.with_approval(|settings| {
+                if let Some(mode) = approval.mode
+                    && let Ok(parsed) = mode.parse::<super::types::ApprovalMode>()
+                {
+                    settings.mode = parsed;
+                }
+                if let Some(allowed_commands) = approval.allowed_commands {
+                    settings.allowed_commands = allowed_commands;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(confidence) = self.confidence {
+            builder.with_confidence(|settings| {
+                if let Some(replan_below) = confidence.replan_below {
+                    settings.replan_below = replan_below;
+                }
+                if let Some(refuse_below) = confidence.refuse_below {
+                    settings.refuse_below = refuse_below;
+                }
+                if let Some(skip_dry_run_at) = confidence.skip_dry_run_at {
+                    settings.skip_dry_run_at = Some(skip_dry_run_at);
+                }
+                if let Some(max_replans) = confidence.max_replans {
+                    settings.max_replans = max_replans;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(iteration) = self.iteration {
+            builder.with_iteration(|settings| {
+                if let Some(max_iterations) = iteration.max_iterations {
+                    settings.max_iterations = max_iterations;
+                }
+                if let Some(token_budget) = iteration.token_budget {
+                    settings.token_budget = token_budget;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(metrics) = self.metrics {
+            builder.with_metrics(|settings| {
+                if let Some(enabled) = metrics.enabled {
+                    settings.enabled = enabled;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(undo) = self.undo {
+            builder.with_undo(|settings| {
+                if let Some(enabled) = undo.enabled {
+                    settings.enabled = enabled;
+                }
+                if let Some(max_snapshot_mb) = undo.max_snapshot_mb {
+                    settings.max_snapshot_mb = max_snapshot_mb;
+                }
+            })
+        } else {
+            builder
+        };
+
+        let builder = if let Some(pipeline) = self.pipeline {
+            builder.with_pipeline(|settings| {
+                if let Some(stages) = pipeline.stages {
+                    settings.stages = Some(stages);
+                }
+                if let Some(profiles) = pipeline.profiles {
+                    settings.profiles = profiles;
+                }
+            })
+        } else {
+            builder
+        };
+
+        if let Some(hooks) = self.hooks {
+            builder.with_hooks(|settings| {
+                if let Some(command) = hooks.pre_plan {
+                    settings.pre_plan = Some(command);
+                }
+                if let Some(command) = hooks.post_plan {
+                    settings.post_plan = Some(command);
+                }
+                if let Some(command) = hooks.pre_execute {
+                    settings.pre_execute = Some(command);
+                }
+                if let Some(command) = hooks.post_execute {
+                    settings.post_execute = Some(command);
+                }
+                if let Some(command) = hooks.on_failure {
+                    settings.on_failure = Some(command);
+                }
+                if let Some(secs) = hooks.timeout_secs {
+                    settings.timeout_secs = secs;
+                }
             })
         } else {
             builder
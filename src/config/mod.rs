@@ -15,11 +15,13 @@ mod types;
 mod validation;
 
 // Re-export the main types for convenience
+#[allow(unused_imports)]
 pub use types::{
-    Config, LlmSettings, LlmProvider
+    ApprovalMode, ApprovalSettings, ConfidenceSettings, Config, HooksSettings, PipelineSettings, MetricsSettings, UndoSettings, IterationSettings, ExecutionSettings, HistorySettings, LlmProvider, LlmSettings, ModelSettings,
+    RecoveryChooserKind, RecoverySettings, ReviewSettings, ShellDialect, StageSpec,
 };
 
-pub use constants::DEFAULT_MAX_TOKENS;
+pub use constants::{DEFAULT_MAX_TOKENS, DEFAULT_PLUGIN_TIMEOUT_SECS};
 
 #[cfg(test)]
 mod tests;
@@ -1,10 +1,11 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use std::sync::{Mutex, OnceLock};
     use tempfile::TempDir;
 
-    use crate::config::{Config, LlmProvider, DEFAULT_CEREBRAS_BASE_URL};
-    use crate::config::builder::ConfigBuilder;
+    use crate::config::constants::DEFAULT_CEREBRAS_BASE_URL;
+    use crate::config::{ApprovalMode, Config, HooksSettings, LlmProvider, StageSpec};
     use crate::config::environment::{env_string, env_u64, env_u32};
 
     fn env_lock<'a>() -> std::sync::MutexGuard<'a, ()> {
@@ -156,6 +157,260 @@ mod tests {
         assert_eq!(json["models"]["planner"], "custom/planner");
         assert_eq!(json["models"]["max_tokens"], 999);
         assert_eq!(json["recovery"]["enabled"], false);
+        assert_eq!(json["history"]["enabled"], true);
+        assert_eq!(json["history"]["max_examples"], 3);
+    }
+
+    #[test]
+    fn load_reads_history_settings() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": {},
+                "history": { "enabled": false, "max_examples": 5 }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+        ]);
+
+        let config = Config::load().unwrap();
+        assert!(!config.history.enabled);
+        assert_eq!(config.history.max_examples, 5);
+    }
+
+    #[test]
+    fn load_reads_undo_settings() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": {},
+                "undo": { "max_snapshot_mb": 20 }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+        ]);
+
+        let config = Config::load().unwrap();
+        assert!(config.undo.enabled);
+        assert_eq!(config.undo.max_snapshot_mb, 20);
+    }
+
+    #[test]
+    fn load_reads_review_settings() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": { "planner": "small/planner" },
+                "review": { "enabled": true }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+            ("LI_REVIEWER_MODEL", Some("large/reviewer")),
+        ]);
+
+        let config = Config::load().unwrap();
+        assert!(config.review.enabled);
+        assert_eq!(
+            config.review.model_or(&config.models.planner),
+            "large/reviewer"
+        );
+    }
+
+    #[test]
+    fn load_reads_execution_limits() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": {},
+                "execution": { "step_timeout_secs": 120, "idle_warning_secs": 0, "persistent_shell": true, "pty": true }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+        ]);
+
+        let config = Config::load().unwrap();
+        assert_eq!(config.execution.step_timeout_secs, 120);
+        assert_eq!(config.execution.plan_timeout_secs, 3600);
+        assert_eq!(config.execution.idle_warning_secs, 0);
+        assert!(config.execution.persistent_shell);
+        assert!(config.execution.pty);
+        assert!(!config.execution.preview);
+    }
+
+    #[test]
+    fn load_reads_hook_settings() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": {},
+                "hooks": { "post_plan": "./check-plan", "timeout_secs": 5 }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+        ]);
+
+        let config = Config::load().unwrap();
+        assert_eq!(config.hooks.post_plan.as_deref(), Some("./check-plan"));
+        assert_eq!(config.hooks.pre_execute, None);
+        assert_eq!(config.hooks.timeout_secs, 5);
+        assert_eq!(HooksSettings::default().timeout_secs, 60);
+    }
+
+    #[test]
+    fn load_rejects_inverted_confidence_thresholds() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": {},
+                "confidence": { "replan_below": 0.4, "refuse_below": 0.6, "skip_dry_run_at": 0.95 }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+        ]);
+
+        let err = Config::load().unwrap_err();
+        assert!(err.to_string().contains("confidence.refuse_below"));
+    }
+
+    #[test]
+    fn load_reads_pipeline_profiles() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": {},
+                "pipeline": {
+                    "profiles": {
+                        "explain": [{ "stage": "planning" }, { "stage": "review", "model": "big/reviewer" }],
+                        "autonomous": [
+                            { "stage": "planning" },
+                            { "stage": "approval", "mode": "auto" },
+                            { "stage": "execution" }
+                        ]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+        ]);
+
+        let config = Config::load().unwrap();
+        assert_eq!(config.pipeline.stages_for(None).unwrap(), None);
+        assert_eq!(
+            config.pipeline.stages_for(Some("explain")).unwrap(),
+            Some(
+                &[
+                    StageSpec::Planning,
+                    StageSpec::Review {
+                        model: Some("big/reviewer".to_string())
+                    }
+                ][..]
+            )
+        );
+        let autonomous = config.pipeline.stages_for(Some("autonomous")).unwrap().unwrap();
+        assert_eq!(
+            autonomous[1],
+            StageSpec::Approval {
+                mode: Some(ApprovalMode::Auto)
+            }
+        );
+        assert_eq!(autonomous[2], StageSpec::Execution { assume_yes: false });
+        assert!(config.pipeline.stages_for(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn load_rejects_pipelines_that_do_not_start_with_planning() {
+        let _lock = env_lock();
+        let temp_home = TempDir::new().unwrap();
+        let home = temp_home.path().to_str().unwrap().to_string();
+        let config_dir = temp_home.path().join(".li");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config"),
+            r#"{
+                "llm": { "api_key": "file-key" },
+                "models": {},
+                "pipeline": { "stages": [{ "stage": "execution" }, { "stage": "planning" }] }
+            }"#,
+        )
+        .unwrap();
+
+        let _env = EnvGuard::new(&[
+            ("HOME", Some(home.as_str())),
+            ("OPENROUTER_API_KEY", None),
+        ]);
+
+        let err = Config::load().unwrap_err();
+        assert!(err.to_string().contains("pipeline.stages"));
     }
 
     #[test]
@@ -1,15 +1,28 @@
 use anyhow::anyhow;
+use std::collections::BTreeMap;
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use super::constants::{DEFAULT_CEREBRAS_BASE_URL, DEFAULT_OPENROUTER_BASE_URL};
+use super::constants::{
+    DEFAULT_CEREBRAS_BASE_URL, DEFAULT_OPENROUTER_BASE_URL, DEFAULT_PLUGIN_TIMEOUT_SECS,
+};
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub llm: LlmSettings,
     pub models: ModelSettings,
     pub recovery: RecoverySettings,
+    pub history: HistorySettings,
+    pub execution: ExecutionSettings,
+    pub review: ReviewSettings,
+    pub confidence: ConfidenceSettings,
+    pub approval: ApprovalSettings,
+    pub iteration: IterationSettings,
+    pub metrics: MetricsSettings,
+    pub undo: UndoSettings,
+    pub pipeline: PipelineSettings,
+    pub hooks: HooksSettings,
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +85,134 @@ impl LlmProvider {
     }
 }
 
+/// Shell dialect used to run plan commands and targeted by the planner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellDialect {
+    #[default]
+    Sh,
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl fmt::Display for ShellDialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program())
+    }
+}
+
+impl std::str::FromStr for ShellDialect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sh" => Ok(ShellDialect::Sh),
+            "bash" => Ok(ShellDialect::Bash),
+            "zsh" => Ok(ShellDialect::Zsh),
+            "fish" => Ok(ShellDialect::Fish),
+            other => Err(anyhow!("Unknown shell '{other}' (expected sh, bash, zsh or fish)")),
+        }
+    }
+}
+
+impl ShellDialect {
+    /// Executable invoked as `<program> -c <command>`.
+    pub fn program(self) -> &'static str {
+        match self {
+            ShellDialect::Sh => "sh",
+            ShellDialect::Bash => "bash",
+            ShellDialect::Zsh => "zsh",
+            ShellDialect::Fish => "fish",
+        }
+    }
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            ShellDialect::Sh => "POSIX sh",
+            ShellDialect::Bash => "Bash",
+            ShellDialect::Zsh => "Zsh",
+            ShellDialect::Fish => "fish",
+        }
+    }
+}
+
+/// Who approves a plan before it is executed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalMode {
+    /// Show the plan and ask on the terminal.
+    #[default]
+    Interactive,
+    /// Approve every plan that is not blocked by the reviewer or confidence policy.
+    Auto,
+    /// Approve only plans whose programs are all in `approval.allowed_commands`.
+    Policy,
+}
+
+impl fmt::Display for ApprovalMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApprovalMode::Interactive => write!(f, "interactive"),
+            ApprovalMode::Auto => write!(f, "auto"),
+            ApprovalMode::Policy => write!(f, "policy"),
+        }
+    }
+}
+
+impl std::str::FromStr for ApprovalMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interactive" => Ok(ApprovalMode::Interactive),
+            "auto" => Ok(ApprovalMode::Auto),
+            "policy" => Ok(ApprovalMode::Policy),
+            other => Err(anyhow!(
+                "Unknown approval mode '{other}' (expected interactive, auto or policy)"
+            )),
+        }
+    }
+}
+
+/// Who picks among the recovery options offered for a missing command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryChooserKind {
+    /// Show the recovery menu and ask on the terminal.
+    #[default]
+    Interactive,
+    /// Use only options whose programs are all in `approval.allowed_commands`.
+    Policy,
+    /// Take the option the model is most confident in.
+    BestConfidence,
+}
+
+impl fmt::Display for RecoveryChooserKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecoveryChooserKind::Interactive => write!(f, "interactive"),
+            RecoveryChooserKind::Policy => write!(f, "policy"),
+            RecoveryChooserKind::BestConfidence => write!(f, "best_confidence"),
+        }
+    }
+}
+
+impl std::str::FromStr for RecoveryChooserKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interactive" => Ok(RecoveryChooserKind::Interactive),
+            "policy" => Ok(RecoveryChooserKind::Policy),
+            "best_confidence" => Ok(RecoveryChooserKind::BestConfidence),
+            other => Err(anyhow!(
+                "Unknown recovery chooser '{other}' (expected interactive, policy or best_confidence)"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ModelSettings {
     pub planner: String,
@@ -81,11 +222,202 @@ pub struct ModelSettings {
 #[derive(Debug, Clone)]
 pub struct RecoverySettings {
     pub enabled: bool,
+    pub chooser: RecoveryChooserKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct HistorySettings {
+    pub enabled: bool,
+    pub max_examples: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutionSettings {
+    pub shell: ShellDialect,
+    /// Seconds a plan command may run unless the plan sets its own limit. 0 disables the limit.
+    pub step_timeout_secs: u64,
+    /// Seconds the whole plan may run. 0 disables the deadline.
+    pub plan_timeout_secs: u64,
+    /// Warn about a command that has printed nothing for this many seconds. 0 disables the warning.
+    pub idle_warning_secs: u64,
+    /// Preview the plan's file changes in a sandbox before asking for approval.
+    pub preview: bool,
+    /// Run all of a plan's commands in one shell, so `cd` and exported variables carry over.
+    pub persistent_shell: bool,
+    /// Run commands on a pseudo-terminal when li itself runs in one. Off by default,
+    /// since a terminal merges the command's stderr into its stdout.
+    pub pty: bool,
+}
+
+/// Independent review of produced plans by a second model.
+#[derive(Debug, Clone, Default)]
+pub struct ReviewSettings {
+    pub enabled: bool,
+    pub model: Option<String>,
+}
+
+/// Thresholds that decide what happens to a plan based on its confidence.
+#[derive(Debug, Clone)]
+pub struct ConfidenceSettings {
+    /// Plans below this confidence are re-planned with more context.
+    pub replan_below: f32,
+    /// Plans below this confidence are refused unless `--force` is given.
+    pub refuse_below: f32,
+    /// Plans at or above this confidence skip the dry-run phase. Disabled when unset.
+    pub skip_dry_run_at: Option<f32>,
+    /// Maximum number of automatic re-plans per task.
+    pub max_replans: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ApprovalSettings {
+    pub mode: ApprovalMode,
+    /// Programs the policy approver may run without asking.
+    pub allowed_commands: Vec<String>,
+}
+
+/// Bounds for the goal-driven loop (`li --loop`).
+#[derive(Debug, Clone)]
+pub struct IterationSettings {
+    /// Maximum plan/execute/assess rounds per task.
+    pub max_iterations: usize,
+    /// Stop once LLM calls in the run have used this many tokens.
+    pub token_budget: u64,
+}
+
+/// Local per-run timing and token metrics (`~/.li/metrics.jsonl`).
+#[derive(Debug, Clone)]
+pub struct MetricsSettings {
+    pub enabled: bool,
+}
+
+/// Snapshots of the files a plan changes, for `li undo` (`~/.li/undo`).
+#[derive(Debug, Clone)]
+pub struct UndoSettings {
+    pub enabled: bool,
+    /// Largest snapshot per run; files beyond it are listed as not saved.
+    pub max_snapshot_mb: u64,
+}
+
+/// One stage of a configured agent pipeline, with its adapter options.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum StageSpec {
+    Planning,
+    Validation,
+    Review {
+        /// Reviewer model for this pipeline, overriding `review.model`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model: Option<String>,
+    },
+    Preview,
+    Approval {
+        /// Approver for this pipeline, overriding `approval.mode`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<ApprovalMode>,
+    },
+    Execution {
+        /// Run plans that no approval stage has approved.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        assume_yes: bool,
+    },
+    Recovery {
+        /// Recovery chooser for this pipeline, overriding `recovery.chooser`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chooser: Option<RecoveryChooserKind>,
+    },
+    Assessment,
+    /// An external executable speaking the plugin stage protocol.
+    Plugin {
+        /// Label used in events and error messages.
+        name: String,
+        command: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        args: Vec<String>,
+        /// Seconds the plugin may run before it is killed, which fails the stage.
+        /// 0 disables the limit.
+        #[serde(
+            default = "default_plugin_timeout_secs",
+            skip_serializing_if = "is_default_plugin_timeout"
+        )]
+        timeout_secs: u64,
+    },
+}
+
+fn default_plugin_timeout_secs() -> u64 {
+    DEFAULT_PLUGIN_TIMEOUT_SECS
+}
+
+fn is_default_plugin_timeout(secs: &u64) -> bool {
+    *secs == DEFAULT_PLUGIN_TIMEOUT_SECS
+}
+
+impl StageSpec {
+    /// The pipeline li runs when none is configured.
+    pub fn standard() -> Vec<StageSpec> {
+        vec![
+            StageSpec::Planning,
+            StageSpec::Validation,
+            StageSpec::Review { model: None },
+            StageSpec::Preview,
+            StageSpec::Approval { mode: None },
+            StageSpec::Execution { assume_yes: false },
+            StageSpec::Recovery { chooser: None },
+        ]
+    }
+}
+
+/// Stage composition declared in the config file.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineSettings {
+    /// Stages run when no profile is selected; the built-in pipeline when unset.
+    pub stages: Option<Vec<StageSpec>>,
+    /// Named pipelines selected with `--profile`.
+    pub profiles: BTreeMap<String, Vec<StageSpec>>,
+}
+
+impl PipelineSettings {
+    /// Configured stages for `profile`, or the default pipeline when no profile is given.
+    /// `None` means the built-in pipeline.
+    pub fn stages_for(&self, profile: Option<&str>) -> Result<Option<&[StageSpec]>, anyhow::Error> {
+        match profile {
+            Some(name) => match self.profiles.get(name) {
+                Some(stages) => Ok(Some(stages)),
+                None => Err(anyhow!(
+                    "Unknown pipeline profile '{name}'. Define it under pipeline.profiles in your config."
+                )),
+            },
+            None => Ok(self.stages.as_deref()),
+        }
+    }
+}
+
+/// Shell commands run around the agent stages. Each receives the run so far as
+/// JSON on stdin; a non-zero exit vetoes the run.
+#[derive(Debug, Clone)]
+pub struct HooksSettings {
+    pub pre_plan: Option<String>,
+    /// May print replacement plan JSON on stdout, which is then reviewed and approved.
+    pub post_plan: Option<String>,
+    pub pre_execute: Option<String>,
+    pub post_execute: Option<String>,
+    /// Runs when a stage fails or the plan does not execute successfully; cannot veto.
+    pub on_failure: Option<String>,
+    /// Seconds a hook may run before it is killed, which vetoes the run. 0 disables the limit.
+    pub timeout_secs: u64,
+}
+
+impl ReviewSettings {
+    /// Reviewer model, falling back to the planner model when none is configured.
+    pub fn model_or<'a>(&'a self, planner: &'a str) -> &'a str {
+        self.model.as_deref().unwrap_or(planner)
+    }
 }
 
 // File configuration types
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub(super) enum RawConfig {
     Nested(FileConfigV2),
     Legacy(FileConfigV1),
@@ -105,6 +437,26 @@ pub(super) struct FileConfigV2 {
     pub models: FileModelSettings,
     #[serde(default)]
     pub recovery: Option<FileRecoverySettings>,
+    #[serde(default)]
+    pub history: Option<FileHistorySettings>,
+    #[serde(default)]
+    pub execution: Option<FileExecutionSettings>,
+    #[serde(default)]
+    pub review: Option<FileReviewSettings>,
+    #[serde(default)]
+    pub confidence: Option<FileConfidenceSettings>,
+    #[serde(default)]
+    pub approval: Option<FileApprovalSettings>,
+    #[serde(default)]
+    pub iteration: Option<FileIterationSettings>,
+    #[serde(default)]
+    pub metrics: Option<FileMetricsSettings>,
+    #[serde(default)]
+    pub undo: Option<FileUndoSettings>,
+    #[serde(default)]
+    pub pipeline: Option<FilePipelineSettings>,
+    #[serde(default)]
+    pub hooks: Option<FileHooksSettings>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -125,6 +477,77 @@ pub(super) struct FileModelSettings {
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub(super) struct FileRecoverySettings {
     pub enabled: Option<bool>,
+    pub chooser: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileHistorySettings {
+    pub enabled: Option<bool>,
+    pub max_examples: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileExecutionSettings {
+    pub shell: Option<String>,
+    pub step_timeout_secs: Option<u64>,
+    pub plan_timeout_secs: Option<u64>,
+    pub idle_warning_secs: Option<u64>,
+    pub preview: Option<bool>,
+    pub persistent_shell: Option<bool>,
+    pub pty: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileReviewSettings {
+    pub enabled: Option<bool>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileApprovalSettings {
+    pub mode: Option<String>,
+    pub allowed_commands: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileConfidenceSettings {
+    pub replan_below: Option<f32>,
+    pub refuse_below: Option<f32>,
+    pub skip_dry_run_at: Option<f32>,
+    pub max_replans: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileIterationSettings {
+    pub max_iterations: Option<usize>,
+    pub token_budget: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileMetricsSettings {
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileUndoSettings {
+    pub enabled: Option<bool>,
+    pub max_snapshot_mb: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FilePipelineSettings {
+    pub stages: Option<Vec<StageSpec>>,
+    pub profiles: Option<BTreeMap<String, Vec<StageSpec>>>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(super) struct FileHooksSettings {
+    pub pre_plan: Option<String>,
+    pub post_plan: Option<String>,
+    pub pre_execute: Option<String>,
+    pub post_execute: Option<String>,
+    pub on_failure: Option<String>,
+    pub timeout_secs: Option<u64>,
 }
 
 // Serialization helpers
@@ -133,6 +556,16 @@ pub(super) struct PersistedConfig<'a> {
     pub llm: PersistedLlm<'a>,
     pub models: PersistedModels<'a>,
     pub recovery: PersistedRecovery,
+    pub history: PersistedHistory,
+    pub execution: PersistedExecution,
+    pub review: PersistedReview<'a>,
+    pub confidence: PersistedConfidence,
+    pub approval: PersistedApproval<'a>,
+    pub iteration: PersistedIteration,
+    pub metrics: PersistedMetrics,
+    pub undo: PersistedUndo,
+    pub pipeline: PersistedPipeline<'a>,
+    pub hooks: PersistedHooks<'a>,
 }
 
 #[derive(Serialize)]
@@ -153,6 +586,86 @@ pub(super) struct PersistedModels<'a> {
 #[derive(Serialize)]
 pub(super) struct PersistedRecovery {
     pub enabled: bool,
+    pub chooser: RecoveryChooserKind,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedHistory {
+    pub enabled: bool,
+    pub max_examples: usize,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedExecution {
+    pub shell: ShellDialect,
+    pub step_timeout_secs: u64,
+    pub plan_timeout_secs: u64,
+    pub idle_warning_secs: u64,
+    pub preview: bool,
+    pub persistent_shell: bool,
+    pub pty: bool,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedReview<'a> {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedConfidence {
+    pub replan_below: f32,
+    pub refuse_below: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_dry_run_at: Option<f32>,
+    pub max_replans: usize,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedApproval<'a> {
+    pub mode: ApprovalMode,
+    pub allowed_commands: &'a [String],
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedIteration {
+    pub max_iterations: usize,
+    pub token_budget: u64,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedMetrics {
+    pub enabled: bool,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedUndo {
+    pub enabled: bool,
+    pub max_snapshot_mb: u64,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedPipeline<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stages: Option<&'a [StageSpec]>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: &'a BTreeMap<String, Vec<StageSpec>>,
+}
+
+#[derive(Serialize)]
+pub(super) struct PersistedHooks<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_plan: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_plan: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_execute: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_execute: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<&'a str>,
+    pub timeout_secs: u64,
 }
 
 impl<'a> From<&'a Config> for PersistedConfig<'a> {
@@ -171,6 +684,57 @@ impl<'a> From<&'a Config> for PersistedConfig<'a> {
             },
             recovery: PersistedRecovery {
                 enabled: config.recovery.enabled,
+                chooser: config.recovery.chooser,
+            },
+            history: PersistedHistory {
+                enabled: config.history.enabled,
+                max_examples: config.history.max_examples,
+            },
+            execution: PersistedExecution {
+                shell: config.execution.shell,
+                step_timeout_secs: config.execution.step_timeout_secs,
+                plan_timeout_secs: config.execution.plan_timeout_secs,
+                idle_warning_secs: config.execution.idle_warning_secs,
+                preview: config.execution.preview,
+                persistent_shell: config.execution.persistent_shell,
+                pty: config.execution.pty,
+            },
+            review: PersistedReview {
+                enabled: config.review.enabled,
+                model: config.review.model.as_deref(),
+            },
+            confidence: PersistedConfidence {
+                replan_below: config.confidence.replan_below,
+                refuse_below: config.confidence.refuse_below,
+                skip_dry_run_at: config.confidence.skip_dry_run_at,
+                max_replans: config.confidence.max_replans,
+            },
+            approval: PersistedApproval {
+                mode: config.approval.mode,
+                allowed_commands: &config.approval.allowed_commands,
+            },
+            iteration: PersistedIteration {
+                max_iterations: config.iteration.max_iterations,
+                token_budget: config.iteration.token_budget,
+            },
+            metrics: PersistedMetrics {
+                enabled: config.metrics.enabled,
+            },
+            undo: PersistedUndo {
+                enabled: config.undo.enabled,
+                max_snapshot_mb: config.undo.max_snapshot_mb,
+            },
+            pipeline: PersistedPipeline {
+                stages: config.pipeline.stages.as_deref(),
+                profiles: &config.pipeline.profiles,
+            },
+            hooks: PersistedHooks {
+                pre_plan: config.hooks.pre_plan.as_deref(),
+                post_plan: config.hooks.post_plan.as_deref(),
+                pre_execute: config.hooks.pre_execute.as_deref(),
+                post_execute: config.hooks.post_execute.as_deref(),
+                on_failure: config.hooks.on_failure.as_deref(),
+                timeout_secs: config.hooks.timeout_secs,
             },
         }
     }
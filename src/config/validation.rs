@@ -1,18 +1,70 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 
-use super::types::Config;
+use super::types::{Config, StageSpec};
 
 pub fn validate(config: &Config) -> Result<()> {
     if config.llm.api_key.trim().is_empty() {
         let provider = config.llm.provider;
         let env_var = provider.api_key_env_var();
-        Err(anyhow!(
+        return Err(anyhow!(
             "{} API key not found. Set {} or add it to {}",
             provider.display_name(),
             env_var,
             Config::config_path()?.display()
-        ))
-    } else {
-        Ok(())
+        ));
     }
+
+    let confidence = &config.confidence;
+    let thresholds = [
+        ("confidence.replan_below", Some(confidence.replan_below)),
+        ("confidence.refuse_below", Some(confidence.refuse_below)),
+        ("confidence.skip_dry_run_at", confidence.skip_dry_run_at),
+    ];
+    for (name, value) in thresholds {
+        if let Some(value) = value
+            && !(0.0..=1.0).contains(&value)
+        {
+            bail!("{name} must be between 0 and 1, got {value}");
+        }
+    }
+    if confidence.refuse_below > confidence.replan_below {
+        bail!(
+            "confidence.refuse_below ({}) must not exceed confidence.replan_below ({})",
+            confidence.refuse_below,
+            confidence.replan_below
+        );
+    }
+
+    if config.iteration.max_iterations == 0 {
+        bail!("iteration.max_iterations must be at least 1");
+    }
+
+    if let Some(stages) = &config.pipeline.stages {
+        validate_pipeline("pipeline.stages", stages)?;
+    }
+    for (name, stages) in &config.pipeline.profiles {
+        validate_pipeline(&format!("pipeline.profiles.{name}"), stages)?;
+    }
+
+    Ok(())
+}
+
+fn validate_pipeline(name: &str, stages: &[StageSpec]) -> Result<()> {
+    if stages.first() != Some(&StageSpec::Planning) {
+        bail!("{name} must start with the planning stage");
+    }
+    if stages.iter().filter(|stage| **stage == StageSpec::Planning).count() > 1 {
+        bail!("{name} may contain only one planning stage");
+    }
+    for stage in stages {
+        if let StageSpec::Plugin { name: plugin, command, .. } = stage {
+            if plugin.trim().is_empty() {
+                bail!("{name} has a plugin stage without a name");
+            }
+            if command.trim().is_empty() {
+                bail!("{name} plugin '{plugin}' must set command");
+            }
+        }
+    }
+    Ok(())
 }
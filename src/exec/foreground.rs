@@ -0,0 +1,74 @@
+//! Lends the user's terminal to a plan command. Commands run in process groups
+//! of their own, and only the terminal's foreground group may read from it, so
+//! each command is made the foreground while it runs and li takes the terminal
+//! back afterwards. Ctrl-C then reaches the command, as it would in a shell.
+
+use std::io;
+
+use tokio::process::Command as TokioCommand;
+
+/// The terminal on stdin, lent to a command's process group until dropped.
+pub(super) struct Foreground {
+    /// li's process group, which gets the terminal back.
+    home: libc::pid_t,
+    /// SIGTTOU disposition to restore.
+    ttou: libc::sighandler_t,
+}
+
+impl Foreground {
+    /// Whether stdin is a terminal on which li is the foreground process group.
+    pub(super) fn available() -> bool {
+        // SAFETY: these calls only query the terminal and the process.
+        unsafe {
+            libc::isatty(libc::STDIN_FILENO) == 1
+                && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+        }
+    }
+
+    /// Have `command` take the terminal for its own process group before it
+    /// runs, so it cannot read from the background and be stopped before
+    /// [`Foreground::lend`] runs in li.
+    pub(super) fn install(command: &mut TokioCommand) {
+        // SAFETY: only async-signal-safe calls on stack data, between fork and exec.
+        unsafe {
+            command.pre_exec(|| {
+                // A background group changing the foreground would get SIGTTOU.
+                let mut block: libc::sigset_t = std::mem::zeroed();
+                let mut previous: libc::sigset_t = std::mem::zeroed();
+                libc::sigemptyset(&mut block);
+                libc::sigaddset(&mut block, libc::SIGTTOU);
+                libc::sigprocmask(libc::SIG_BLOCK, &block, &mut previous);
+                let result = libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+                let error = io::Error::last_os_error();
+                libc::sigprocmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+                if result < 0 {
+                    return Err(error);
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Make `group` the terminal's foreground process group.
+    pub(super) fn lend(group: Option<u32>) -> Self {
+        // SAFETY: these calls pass no memory. SIGTTOU is ignored while li is
+        // in the background, so neither its output nor taking the terminal
+        // back stops it.
+        let (home, ttou) = unsafe { (libc::getpgrp(), libc::signal(libc::SIGTTOU, libc::SIG_IGN)) };
+        if let Some(group) = group.and_then(|group| libc::pid_t::try_from(group).ok()) {
+            // SAFETY: as above; a group that has already exited makes this fail harmlessly.
+            unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, group) };
+        }
+        Self { home, ttou }
+    }
+}
+
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        // SAFETY: these calls pass no memory.
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, self.home);
+            libc::signal(libc::SIGTTOU, self.ttou);
+        }
+    }
+}
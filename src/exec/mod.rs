@@ -1,261 +1,1054 @@
-use anyhow::{Context, Result, anyhow, bail};
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command as TokioCommand;
+mod foreground;
+mod preview;
+mod pty;
+mod sandbox;
+mod session;
+mod step;
+
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncBufReadExt;
+use tokio::process::{Child, Command as TokioCommand};
+use tokio::task::JoinHandle;
 
 use crate::agent::ExecutionReport;
+use crate::cancel::{CancelToken, INTERRUPTED, KILL_GRACE, Signal, signal_group};
+use crate::config::ShellDialect;
 use crate::planner::Plan;
 
-/// Execute all dry-run and execute commands in the plan, streaming output to stdout/stderr.
-pub async fn execute_plan(plan: &Plan) -> Result<()> {
-    println!("\n=== Executing Plan ===");
-
-    if !plan.dry_run_commands.is_empty() {
-        println!("\n[Dry-run Phase]");
-        for (idx, cmd) in plan.dry_run_commands.iter().enumerate() {
-            println!(
-                "\n> Running check {}/{}: {}",
-                idx + 1,
-                plan.dry_run_commands.len(),
-                cmd
-            );
-            let success = run_command(cmd).await?;
-            if !success {
-                bail!("Dry-run check failed: {}", cmd);
-            }
+use foreground::Foreground;
+#[allow(unused_imports)]
+pub use preview::{ChangeKind, FileChange, PreviewMode, PreviewReport, preview};
+use pty::{Passthrough, Pty};
+use sandbox::Sandbox;
+use session::{Markers, Pipe, Sent, Session, Until, pipe};
+use step::OutputCapture;
+pub use step::{StepDecision, StepResult, StepStatus};
+
+/// How long to keep reading a finished command's output pipes.
+const PIPE_DRAIN: Duration = Duration::from_millis(500);
+
+/// Plan phase a command belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepPhase {
+    DryRun,
+    Execute,
+}
+
+impl fmt::Display for StepPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepPhase::DryRun => write!(f, "dry-run"),
+            StepPhase::Execute => write!(f, "execute"),
         }
-        println!("\n✓ All dry-run checks passed.");
-    }
-
-    if !plan.execute_commands.is_empty() {
-        println!("\n[Execute Phase]");
-        for (idx, cmd) in plan.execute_commands.iter().enumerate() {
-            println!(
-                "\n> Executing {}/{}: {}",
-                idx + 1,
-                plan.execute_commands.len(),
-                cmd
-            );
-            let success = run_command(cmd).await?;
-            if !success {
-                bail!("Command failed: {}", cmd);
-            }
+    }
+}
+
+/// Progress notification emitted around every plan command.
+#[derive(Debug, Clone, Copy)]
+pub enum StepEvent<'a> {
+    Started {
+        phase: StepPhase,
+        index: usize,
+        total: usize,
+        command: &'a str,
+    },
+    Finished {
+        phase: StepPhase,
+        index: usize,
+        success: bool,
+    },
+    /// The running command has printed nothing for `seconds`.
+    Idle {
+        phase: StepPhase,
+        index: usize,
+        seconds: u64,
+    },
+}
+
+impl StepEvent<'_> {
+    pub fn phase(&self) -> StepPhase {
+        match self {
+            StepEvent::Started { phase, .. }
+            | StepEvent::Finished { phase, .. }
+            | StepEvent::Idle { phase, .. } => *phase,
         }
-        println!("\n✓ Plan execution completed.");
     }
 
-    Ok(())
+    /// The same event for a phase whose first `skipped` commands did not run.
+    pub fn offset(self, skipped: usize) -> Self {
+        match self {
+            StepEvent::Started {
+                phase,
+                index,
+                total,
+                command,
+            } => StepEvent::Started {
+                phase,
+                index: index + skipped,
+                total: total + skipped,
+                command,
+            },
+            StepEvent::Finished {
+                phase,
+                index,
+                success,
+            } => StepEvent::Finished {
+                phase,
+                index: index + skipped,
+                success,
+            },
+            StepEvent::Idle {
+                phase,
+                index,
+                seconds,
+            } => StepEvent::Idle {
+                phase,
+                index: index + skipped,
+                seconds,
+            },
+        }
+    }
 }
 
-/// Execute the plan and capture combined output for downstream explanation.
-pub async fn execute_plan_with_capture(plan: &Plan) -> Result<String> {
-    use std::process::Command;
-
-    println!("\n=== Executing Plan ===");
-    let mut all_output = String::new();
-
-    if !plan.dry_run_commands.is_empty() {
-        println!("\n[Dry-run Phase]");
-        all_output.push_str("[Dry-run Phase]\n");
-
-        for (idx, cmd) in plan.dry_run_commands.iter().enumerate() {
-            println!(
-                "\n> Running check {}/{}: {}",
-                idx + 1,
-                plan.dry_run_commands.len(),
-                cmd
-            );
-            all_output.push_str(&format!("\nCommand: {}\n", cmd));
-
-            let output = Command::new("sh")
-                .arg("-c")
-                .arg(cmd)
-                .output()
-                .context("Failed to execute dry-run command")?;
-
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-
-            if !stdout.trim().is_empty() {
-                println!("\n┌─ COMMAND OUTPUT: {}", cmd);
-                println!("│");
-                for line in stdout.lines() {
-                    println!("│ {}", line);
-                }
-                println!("│");
-                all_output.push_str(&stdout);
+/// Callback invoked with step progress while a plan runs.
+pub type StepCallback<'a> = dyn FnMut(StepEvent<'_>) + Send + 'a;
+
+/// A command about to run in step-by-step mode.
+#[derive(Debug, Clone, Copy)]
+pub struct PendingStep<'a> {
+    pub phase: StepPhase,
+    pub index: usize,
+    pub total: usize,
+    pub command: &'a str,
+}
+
+/// How to go on with a [`PendingStep`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepChoice {
+    Run,
+    Skip,
+    /// Run this command instead of the planned one.
+    Edit(String),
+    Abort,
+}
+
+/// Asked before every command when a plan runs step by step.
+#[async_trait]
+pub trait StepGate: Send + Sync {
+    async fn decide(&self, step: PendingStep<'_>) -> Result<StepChoice>;
+}
+
+/// Runs plan commands one at a time in the configured shell.
+///
+/// Each command's stdout and stderr are streamed to the terminal as they arrive
+/// (when echo is on) and captured separately into a [`StepResult`]. Commands
+/// that outlive their time limit get SIGTERM, then SIGKILL after [`KILL_GRACE`].
+/// Commands run in a fresh `<shell> -c` each, on a pseudo-terminal with
+/// [`Executor::with_pty`], or one after another in a single shell session with
+/// [`Executor::with_persistent_shell`].
+#[derive(Clone)]
+pub struct Executor {
+    shell: ShellDialect,
+    echo: bool,
+    terminal: bool,
+    pty: bool,
+    step_timeout: Option<Duration>,
+    plan_timeout: Option<Duration>,
+    idle_warning: Option<Duration>,
+    sandbox: Option<Arc<Sandbox>>,
+    gate: Option<Arc<dyn StepGate>>,
+    persistent_shell: bool,
+}
+
+impl Executor {
+    pub fn new(shell: ShellDialect) -> Self {
+        Self {
+            shell,
+            echo: true,
+            terminal: false,
+            pty: false,
+            step_timeout: None,
+            plan_timeout: None,
+            idle_warning: None,
+            sandbox: None,
+            gate: None,
+            persistent_shell: false,
+        }
+    }
+
+    /// Print progress and command output while the plan runs. Without it nothing
+    /// is printed, so callers can own stdout (e.g. `--output json`).
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Let commands read the user's terminal: each one that runs in a fresh shell
+    /// is made the terminal's foreground process group while it runs, so it can
+    /// ask questions, and Ctrl-C reaches it as it would in a shell. Without it,
+    /// or when li is not in the foreground of a terminal, stdin is empty.
+    pub fn with_terminal(mut self, terminal: bool) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    /// Run each command on a pseudo-terminal, so it colors its output and can
+    /// prompt as it would outside li. The report keeps a plain-text transcript,
+    /// with stderr merged into stdout. Not used with a persistent shell.
+    pub fn with_pty(mut self, pty: bool) -> Self {
+        self.pty = pty;
+        self
+    }
+
+    /// Time limit for commands the plan did not give one in `Plan::timeouts`.
+    pub fn with_step_timeout(mut self, limit: Option<Duration>) -> Self {
+        self.step_timeout = limit;
+        self
+    }
+
+    /// Time limit for the whole plan, dry-run checks included.
+    pub fn with_plan_timeout(mut self, limit: Option<Duration>) -> Self {
+        self.plan_timeout = limit;
+        self
+    }
+
+    /// Flag commands that print nothing for this long.
+    pub fn with_idle_warning(mut self, after: Option<Duration>) -> Self {
+        self.idle_warning = after;
+        self
+    }
+
+    /// Ask `gate` before every command whether to run, skip or edit it.
+    pub fn with_step_gate(mut self, gate: Option<Arc<dyn StepGate>>) -> Self {
+        self.gate = gate;
+        self
+    }
+
+    /// Run the plan's commands in one shell, so a `cd`, `export` or `source` in one
+    /// step applies to the following ones. A step that is stopped, or that exits
+    /// the shell, ends the session and the next step starts a new one.
+    pub fn with_persistent_shell(mut self, persistent: bool) -> Self {
+        self.persistent_shell = persistent;
+        self
+    }
+
+    /// Start every command inside `sandbox`.
+    fn with_sandbox(mut self, sandbox: Option<Arc<Sandbox>>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Run the dry-run checks, then the execute commands, stopping at the first
+    /// step that fails or is cancelled.
+    pub async fn run(
+        &self,
+        plan: &Plan,
+        cancel: &CancelToken,
+        on_step: &mut StepCallback<'_>,
+    ) -> ExecutionReport {
+        let mut session = None;
+        let report = self.run_phases(plan, cancel, on_step, &mut session).await;
+        if let Some(session) = session {
+            session.close().await;
+        }
+        report
+    }
+
+    async fn run_phases(
+        &self,
+        plan: &Plan,
+        cancel: &CancelToken,
+        on_step: &mut StepCallback<'_>,
+        session: &mut Option<Session>,
+    ) -> ExecutionReport {
+        self.say("\n=== Executing Plan ===");
+        let deadline = self.plan_timeout.map(|limit| Limit {
+            at: Instant::now() + limit,
+            status: StepStatus::DeadlineExceeded {
+                limit_secs: limit.as_secs(),
+            },
+        });
+        let mut steps = Vec::new();
+        let phases = [
+            (StepPhase::DryRun, &plan.dry_run_commands),
+            (StepPhase::Execute, &plan.execute_commands),
+        ];
+
+        for (phase, commands) in phases {
+            if commands.is_empty() {
+                continue;
             }
+            let (heading, label, done) = match phase {
+                StepPhase::DryRun => (
+                    "[Dry-run Phase]",
+                    "Running check",
+                    "✓ All dry-run checks passed.",
+                ),
+                StepPhase::Execute => (
+                    "[Execute Phase]",
+                    "Executing",
+                    "✓ Plan execution completed.",
+                ),
+            };
+            self.say(&format!("\n{heading}"));
+
+            for (idx, planned) in commands.iter().enumerate() {
+                let index = idx + 1;
+                self.say(&format!(
+                    "\n> {label} {index}/{}: {planned}",
+                    commands.len()
+                ));
+                let pending = PendingStep {
+                    phase,
+                    index,
+                    total: commands.len(),
+                    command: planned,
+                };
+                let (cmd, decision) = self.ask_gate(pending).await;
+                on_step(StepEvent::Started {
+                    phase,
+                    index,
+                    total: commands.len(),
+                    command: &cmd,
+                });
+                let limit = self.step_limit(plan, planned, deadline);
+                let mut step = match (decision, limit.filter(|limit| limit.at <= Instant::now())) {
+                    (Some(StepDecision::Skipped), _) => {
+                        self.say("└─ Step skipped");
+                        StepResult::not_run(phase, index, &cmd, StepStatus::Skipped)
+                    }
+                    (Some(StepDecision::Aborted), _) => {
+                        self.say("└─ Plan aborted");
+                        cancel.cancel("plan aborted by user");
+                        StepResult::not_run(phase, index, &cmd, StepStatus::Cancelled)
+                    }
+                    (_, Some(expired)) => {
+                        self.say("└─ Plan time limit reached; command not started");
+                        StepResult::not_run(phase, index, &cmd, expired.status)
+                    }
+                    (_, None) => {
+                        let step = PendingStep {
+                            command: &cmd,
+                            ..pending
+                        };
+                        self.run_step(step, limit, session, cancel, on_step).await
+                    }
+                };
+                step.decision = decision;
+                if cmd != *planned {
+                    step.original_command = Some(planned.clone());
+                }
+                on_step(StepEvent::Finished {
+                    phase,
+                    index,
+                    success: step.succeeded(),
+                });
 
-            if !stderr.trim().is_empty() {
-                eprintln!("│");
-                for line in stderr.lines() {
-                    eprintln!("│ {}", line);
+                let failure = (!step.allows_next()).then(|| failure_note(&step, cancel));
+                steps.push(step);
+                if let Some(note) = failure {
+                    return ExecutionReport::from_steps(
+                        plan.execute_commands.clone(),
+                        steps,
+                        vec![note],
+                    );
                 }
-                all_output.push_str(&stderr);
             }
+            self.say(&format!("\n{done}"));
+        }
 
-            if output.status.success() {
-                println!("└─ Command completed successfully");
-            } else {
-                println!(
-                    "└─ Command failed with exit code {:?}",
-                    output.status.code()
-                );
-                bail!("Dry-run check failed: {}", cmd);
+        ExecutionReport::from_steps(plan.execute_commands.clone(), steps, Vec::new())
+    }
+
+    /// The command to run for `step` and the user's decision, when a gate is set.
+    /// A gate that fails, e.g. because stdin was closed, aborts the plan.
+    async fn ask_gate(&self, step: PendingStep<'_>) -> (String, Option<StepDecision>) {
+        let Some(gate) = &self.gate else {
+            return (step.command.to_string(), None);
+        };
+        let choice = match gate.decide(step).await {
+            Ok(choice) => choice,
+            Err(error) => {
+                self.say(&format!("└─ {error:#}"));
+                StepChoice::Abort
+            }
+        };
+        let planned = step.command.to_string();
+        match choice {
+            StepChoice::Run => (planned, Some(StepDecision::Run)),
+            StepChoice::Edit(edited) if edited.trim() == planned.trim() => {
+                (planned, Some(StepDecision::Run))
             }
+            StepChoice::Edit(edited) => {
+                self.say(&format!("> Running edited command: {edited}"));
+                (edited, Some(StepDecision::Edited))
+            }
+            StepChoice::Skip => (planned, Some(StepDecision::Skipped)),
+            StepChoice::Abort => (planned, Some(StepDecision::Aborted)),
+        }
+    }
+
+    /// When the step for `planned` has to be stopped: its own limit or the plan
+    /// deadline, whichever comes first. The plan sets limits by the planned
+    /// command line, so an edited command keeps the limit of the step it replaces.
+    fn step_limit(&self, plan: &Plan, planned: &str, deadline: Option<Limit>) -> Option<Limit> {
+        let own = plan
+            .timeout_for(planned)
+            .map(Duration::from_secs)
+            .or(self.step_timeout)
+            .filter(|limit| !limit.is_zero())
+            .map(|limit| Limit {
+                at: Instant::now() + limit,
+                status: StepStatus::TimedOut {
+                    limit_secs: limit.as_secs(),
+                },
+            });
+        match (own, deadline) {
+            (Some(own), Some(deadline)) if deadline.at < own.at => Some(deadline),
+            (Some(own), _) => Some(own),
+            (None, deadline) => deadline,
+        }
+    }
+
+    async fn run_step(
+        &self,
+        step: PendingStep<'_>,
+        limit: Option<Limit>,
+        session: &mut Option<Session>,
+        cancel: &CancelToken,
+        on_step: &mut StepCallback<'_>,
+    ) -> StepResult {
+        let PendingStep {
+            phase,
+            index,
+            command: cmd,
+            ..
+        } = step;
+        let started = Instant::now();
+        if self.echo {
+            println!("\n┌─ COMMAND OUTPUT: {}", cmd);
+            println!("│");
         }
-        println!("\n✓ All dry-run checks passed.");
-        all_output.push_str("\n✓ All dry-run checks passed.\n");
-    }
-
-    if !plan.execute_commands.is_empty() {
-        println!("\n[Execute Phase]");
-        all_output.push_str("\n[Execute Phase]\n");
-
-        for (idx, cmd) in plan.execute_commands.iter().enumerate() {
-            println!(
-                "\n> Executing {}/{}: {}",
-                idx + 1,
-                plan.execute_commands.len(),
-                cmd
-            );
-            all_output.push_str(&format!("\nCommand: {}\n", cmd));
-
-            let output = Command::new("sh")
-                .arg("-c")
-                .arg(cmd)
-                .output()
-                .context("Failed to execute command")?;
-
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-
-            if !stdout.trim().is_empty() {
-                println!("\n┌─ COMMAND OUTPUT: {}", cmd);
-                println!("│");
-                for line in stdout.lines() {
-                    println!("│ {}", line);
+
+        let activity = Arc::new(Mutex::new(Instant::now()));
+        let stream = |pipe: Option<Pipe>, echo: Echo, until: Option<Until>| {
+            Stream::start(pipe, self.echo.then_some(echo), activity.clone(), until)
+        };
+        let (launched, parked) = if self.persistent_shell {
+            match self.send(session, cmd).await {
+                Ok(Sent {
+                    child,
+                    stdout: (stdout, stdout_until),
+                    stderr: (stderr, stderr_until),
+                    markers,
+                    parked,
+                }) => (
+                    Ok(Launched {
+                        child,
+                        stdout: stream(Some(stdout), Echo::Stdout, Some(stdout_until)),
+                        stderr: stream(Some(stderr), Echo::Stderr, Some(stderr_until)),
+                        markers: Some(markers),
+                        input: None,
+                        foreground: None,
+                    }),
+                    Some(parked),
+                ),
+                Err(error) => (Err(error), None),
+            }
+        } else if let Some(pty) = self.pty.then(Pty::open).and_then(Result::ok) {
+            (self.launch_on_pty(pty, cmd, activity.clone()), None)
+        } else {
+            let mut command = self.command(cmd);
+            let lend = self.terminal && Foreground::available();
+            let stdin = if lend {
+                Foreground::install(&mut command);
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            };
+            let launched = spawn_in_group(&mut command, self.shell, stdin).map(|mut child| {
+                let foreground = lend.then(|| Foreground::lend(child.id()));
+                Launched {
+                    stdout: stream(child.stdout.take().map(pipe), Echo::Stdout, None),
+                    stderr: stream(child.stderr.take().map(pipe), Echo::Stderr, None),
+                    child,
+                    markers: None,
+                    input: None,
+                    foreground,
                 }
-                println!("│");
-                all_output.push_str(&stdout);
+            });
+            (launched, None)
+        };
+
+        let mut stalled = false;
+        let (status, stdout, stderr) = match launched {
+            Ok(mut launched) => {
+                let watch = Watch {
+                    limit,
+                    idle: self.idle_warning.map(|after| (after, activity)),
+                    input: launched.input.as_ref(),
+                };
+                let mut on_idle = |quiet: Duration| {
+                    stalled = true;
+                    if self.echo {
+                        eprintln!(
+                            "│ ⚠ No output for {}s; still running (Ctrl-C to skip or abort)",
+                            quiet.as_secs()
+                        );
+                    }
+                    on_step(StepEvent::Idle {
+                        phase,
+                        index,
+                        seconds: quiet.as_secs(),
+                    });
+                };
+                let child = &mut launched.child;
+                let waited = supervise(
+                    child,
+                    launched.markers.as_mut(),
+                    cmd,
+                    cancel,
+                    watch,
+                    &mut on_idle,
+                )
+                .await;
+                let mut status = match waited {
+                    Ok(Waited::Exited(status)) => status,
+                    Ok(Waited::Skipped) => StepStatus::Skipped,
+                    Ok(Waited::Aborted) => StepStatus::Cancelled,
+                    Ok(Waited::TimedOut(status)) => status,
+                    Err(error) => {
+                        let group = child.id();
+                        stop(child, group, Signal::Kill).await;
+                        launched.stderr.note(&format!("{error:#}"));
+                        StepStatus::Cancelled
+                    }
+                };
+                // Give the terminal back before anything else is printed.
+                drop(launched.input.take());
+                let lent = launched.foreground.take().is_some();
+                if lent && interrupted_on_terminal(status) {
+                    // The command had the terminal, so Ctrl-C went to it instead
+                    // of li. It has ended already, so it can only be skipped.
+                    status = match ask_interrupt_choice(cmd, cancel, false).await {
+                        InterruptChoice::SkipStep => StepStatus::Skipped,
+                        _ => {
+                            cancel.cancel(if cancel.prompts() {
+                                "plan aborted by user"
+                            } else {
+                                INTERRUPTED
+                            });
+                            StepStatus::Cancelled
+                        }
+                    };
+                }
+                let (stdout, stdout_pipe) = launched.stdout.finish().await;
+                let (stderr, stderr_pipe) = launched.stderr.finish().await;
+                if let (Some(parked), Some(markers)) = (parked, &launched.markers) {
+                    *session = parked
+                        .resume(launched.child, markers, stdout_pipe, stderr_pipe)
+                        .await;
+                    if session.is_none() && self.echo {
+                        println!("│ (shell session ended; the next command starts a new one)");
+                    }
+                }
+                (status, stdout, stderr)
             }
+            Err(error) => {
+                let mut stderr = OutputCapture::default();
+                stderr.push_line(&format!("{error:#}"));
+                (
+                    StepStatus::NotStarted,
+                    OutputCapture::default().finish(),
+                    stderr.finish(),
+                )
+            }
+        };
 
-            if !stderr.trim().is_empty() {
-                eprintln!("│");
-                for line in stderr.lines() {
-                    eprintln!("│ {}", line);
+        if self.echo {
+            println!("│");
+            match status {
+                StepStatus::Exited { code: 0 } => println!("└─ Command completed successfully"),
+                StepStatus::Exited { code } => {
+                    println!("└─ Command failed with exit code {}", code)
+                }
+                StepStatus::Signaled { .. } => println!("└─ Command was terminated by signal"),
+                StepStatus::Skipped => println!("└─ Step skipped"),
+                StepStatus::Cancelled => println!("└─ Command cancelled"),
+                StepStatus::NotStarted => println!("└─ Command could not be started"),
+                StepStatus::TimedOut { limit_secs } => {
+                    println!("└─ Command stopped after its {limit_secs}s time limit")
+                }
+                StepStatus::DeadlineExceeded { limit_secs } => {
+                    println!("└─ Command stopped: plan time limit of {limit_secs}s reached")
                 }
-                all_output.push_str(&stderr);
             }
+        }
 
-            if output.status.success() {
-                println!("└─ Command completed successfully");
-            } else {
-                println!(
-                    "└─ Command failed with exit code {:?}",
-                    output.status.code()
-                );
-                bail!("Command failed: {}", cmd);
+        let (stdout, stdout_truncated) = stdout;
+        let (stderr, stderr_truncated) = stderr;
+        StepResult {
+            phase,
+            index,
+            command: cmd.to_string(),
+            status,
+            duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            stdout,
+            stderr,
+            stdout_truncated,
+            stderr_truncated,
+            stalled,
+            decision: None,
+            original_command: None,
+        }
+    }
+
+    /// Send `cmd` to the plan's shell session, starting the session if needed.
+    async fn send(&self, session: &mut Option<Session>, cmd: &str) -> Result<Sent> {
+        let shell = match session.take() {
+            Some(shell) => shell,
+            None => Session::start(self.shell_command(), self.shell)?,
+        };
+        shell.send(cmd).await
+    }
+
+    /// Start `cmd` on `pty`, in a session of its own.
+    fn launch_on_pty(
+        &self,
+        pty: Pty,
+        cmd: &str,
+        activity: Arc<Mutex<Instant>>,
+    ) -> Result<Launched> {
+        let mut command = self.command(cmd);
+        pty.attach(&mut command)?;
+        let child = command.spawn().map_err(|e| spawn_error(e, self.shell))?;
+        // The command holds copies of the terminal's child end; only the child may keep them.
+        drop(command);
+        let interactive = io::stdin().is_terminal();
+        let (stdout, input) = match pty.start(self.echo, interactive, activity.clone()) {
+            Ok(started) => started,
+            Err(error) => {
+                if let Some(group) = child.id() {
+                    signal_group(group, Signal::Kill);
+                }
+                return Err(error.into());
             }
+        };
+        Ok(Launched {
+            child,
+            stdout,
+            stderr: Stream::start(None, None, activity, None),
+            markers: None,
+            input,
+            foreground: None,
+        })
+    }
+
+    fn command(&self, cmd: &str) -> TokioCommand {
+        let mut command = self.shell_command();
+        command.arg("-c").arg(cmd);
+        command
+    }
+
+    /// The configured shell, inside the sandbox when there is one.
+    fn shell_command(&self) -> TokioCommand {
+        let mut command = TokioCommand::new(self.shell.program());
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.install(&mut command);
         }
-        println!("\n✓ Plan execution completed.");
-        all_output.push_str("\n✓ Plan execution completed.\n");
+        command
     }
 
-    Ok(all_output)
+    fn say(&self, line: &str) {
+        if self.echo {
+            println!("{line}");
+        }
+    }
 }
 
-/// Execute the plan and return a structured report without emitting additional notes.
-pub async fn execution_report(plan: &Plan) -> Result<ExecutionReport> {
-    let output = execute_plan_with_capture(plan).await?;
-    Ok(ExecutionReport {
-        commands: plan.execute_commands.clone(),
-        success: true,
-        stdout: if output.trim().is_empty() {
-            None
-        } else {
-            Some(output)
-        },
-        stderr: None,
-        notes: Vec::new(),
-    })
+fn exit_status(status: ExitStatus) -> StepStatus {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => StepStatus::Exited { code },
+        (None, Some(signal)) => StepStatus::Signaled { signal },
+        (None, None) => StepStatus::Exited { code: -1 },
+    }
 }
 
-/// Run a shell command, streaming output to stdout/stderr.
-pub async fn run_command(cmd: &str) -> Result<bool> {
-    let modified_cmd = if cmd.starts_with("ls ") || cmd == "ls" {
-        cmd.replace("ls", "ls --color=always")
-    } else {
-        cmd.to_string()
+/// Report note for the step that stopped the plan.
+fn failure_note(step: &StepResult, cancel: &CancelToken) -> String {
+    let failed = match step.phase {
+        StepPhase::DryRun => "Dry-run check failed",
+        StepPhase::Execute => "Command failed",
     };
+    match step.status {
+        StepStatus::Cancelled if step.decision == Some(StepDecision::Aborted) => format!(
+            "Aborted before {} step {}: {}",
+            step.phase, step.index, step.command
+        ),
+        StepStatus::Cancelled => format!(
+            "Cancelled ({}) while running: {}",
+            cancel.reason().unwrap_or_default(),
+            step.command
+        ),
+        StepStatus::NotStarted => format!(
+            "Failed to start {} command: {}",
+            step.phase,
+            step.stderr.trim()
+        ),
+        StepStatus::TimedOut { limit_secs } => format!(
+            "Stopped {} command after its {limit_secs}s time limit: {}",
+            step.phase, step.command
+        ),
+        StepStatus::DeadlineExceeded { limit_secs } => format!(
+            "Plan time limit of {limit_secs}s reached while running: {}",
+            step.command
+        ),
+        status => format!("{failed} ({status}): {}", step.command),
+    }
+}
 
-    println!("\n┌─ COMMAND OUTPUT: {}", cmd);
-    println!("│");
-
-    let mut child = TokioCommand::new("sh")
-        .arg("-c")
-        .arg(&modified_cmd)
-        .env("FORCE_COLOR", "1")
-        .env("CLICOLOR_FORCE", "1")
-        .env("COLORTERM", "truecolor")
-        .env("TERM", "xterm-256color")
-        .env("GIT_CONFIG_PARAMETERS", "'color.ui=always'")
-        .env("LS_COLORS", "di=1;34:fi=0:ln=1;36:pi=40;33:so=1;35:do=1;35:bd=40;33;01:cd=40;33;01:or=40;31;01:ex=1;32:*.tar=1;31:*.tgz=1;31:*.zip=1;31:*.gz=1;31:*.bz2=1;31:*.deb=1;31:*.rpm=1;31:*.jpg=1;35:*.png=1;35:*.gif=1;35:*.bmp=1;35:*.ppm=1;35:*.tga=1;35:*.xbm=1;35:*.xpm=1;35:*.tif=1;35:*.mpg=1;37:*.avi=1;37:*.gl=1;37:*.dl=1;37:*.jpg=1;35:*.png=1;35:*.gif=1;35:*.bmp=1;35:*.ppm=1;35:*.tga=1;35:*.xbm=1;35:*.xpm=1;35:*.tif=1;35:")
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                anyhow!(
-                    "Command not found: {}. Please ensure the command exists in your PATH.",
-                    cmd
-                )
-            } else {
-                anyhow!("Failed to execute command '{}': {}", cmd, e)
+/// Where a stream's lines are echoed while the command runs.
+#[derive(Debug, Clone, Copy)]
+enum Echo {
+    Stdout,
+    Stderr,
+}
+
+/// A started command and the readers of its output.
+struct Launched {
+    child: Child,
+    stdout: Stream,
+    stderr: Stream,
+    /// Sentinels that end the step when it runs in a shell session.
+    markers: Option<Markers>,
+    /// The user's terminal, while lent to a command on a pseudo-terminal.
+    input: Option<Passthrough>,
+    /// The user's terminal, while lent to a command that reads it directly.
+    foreground: Option<Foreground>,
+}
+
+/// Reads one of a command's output pipes line by line in the background.
+struct Stream {
+    capture: Arc<Mutex<OutputCapture>>,
+    reader: JoinHandle<Option<Pipe>>,
+}
+
+impl Stream {
+    /// `activity` is set to the time each line arrives. Reading stops at the end
+    /// of the pipe, or at the `until` sentinel, which hands the pipe back.
+    fn start(
+        pipe: Option<Pipe>,
+        echo: Option<Echo>,
+        activity: Arc<Mutex<Instant>>,
+        mut until: Option<Until>,
+    ) -> Self {
+        let capture = Arc::new(Mutex::new(OutputCapture::default()));
+        let shared = capture.clone();
+        let reader = tokio::spawn(async move {
+            let mut pipe = pipe?;
+            let mut buffer = Vec::new();
+            loop {
+                buffer.clear();
+                match pipe.read_until(b'\n', &mut buffer).await {
+                    Ok(0) | Err(_) => return None,
+                    Ok(_) => {}
+                }
+                *activity.lock().unwrap() = Instant::now();
+                let line = String::from_utf8_lossy(&buffer);
+                let mut line = line.trim_end_matches(['\n', '\r']);
+                let mut rest = None;
+                if let Some(sentinel) = &until
+                    && let Some(at) = line.find(&sentinel.marker)
+                {
+                    rest = Some(line[at + sentinel.marker.len()..].to_string());
+                    // Output without a trailing newline ends up in front of the sentinel.
+                    line = &line[..at];
+                }
+                if rest.is_none() || !line.is_empty() {
+                    match echo {
+                        Some(Echo::Stdout) => println!("│ {}", line),
+                        Some(Echo::Stderr) => eprintln!("│ {}", line),
+                        None => {}
+                    }
+                    shared.lock().unwrap().push_line(line);
+                }
+                if let Some(rest) = rest
+                    && let Some(sentinel) = until.take()
+                {
+                    let _ = sentinel.done.send(rest);
+                    return Some(pipe);
+                }
             }
-        })?;
+        });
+        Self { capture, reader }
+    }
 
-    let stdout = child.stdout.take().expect("Failed to capture stdout");
-    let stderr = child.stderr.take().expect("Failed to capture stderr");
+    fn note(&self, line: &str) {
+        self.capture.lock().unwrap().push_line(line);
+    }
 
-    let mut stdout_reader = BufReader::new(stdout).lines();
-    let mut stderr_reader = BufReader::new(stderr).lines();
+    /// Wait for the pipe to close or reach its sentinel, giving up shortly after
+    /// the command exited: background jobs it started may hold the pipe open
+    /// indefinitely. Returns the output and the pipe, when it was handed back.
+    async fn finish(self) -> ((String, bool), Option<Pipe>) {
+        let mut reader = self.reader;
+        let pipe = match tokio::time::timeout(PIPE_DRAIN, &mut reader).await {
+            Ok(pipe) => pipe.ok().flatten(),
+            Err(_) => {
+                reader.abort();
+                None
+            }
+        };
+        let output = std::mem::take(&mut *self.capture.lock().unwrap()).finish();
+        (output, pipe)
+    }
+}
 
-    let stdout_handle = tokio::spawn(async move {
-        while let Ok(Some(line)) = stdout_reader.next_line().await {
-            println!("│ {}", line);
+/// Start a plan command in its own process group with piped output.
+///
+/// Stdin may only be the terminal when the group is made its foreground (see
+/// [`Foreground`]): a background process group that reads it would be stopped
+/// by the kernel.
+fn spawn_in_group(command: &mut TokioCommand, shell: ShellDialect, stdin: Stdio) -> Result<Child> {
+    command
+        .process_group(0)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| spawn_error(e, shell))
+}
+
+fn spawn_error(error: io::Error, shell: ShellDialect) -> anyhow::Error {
+    if error.kind() == io::ErrorKind::NotFound {
+        anyhow!(
+            "Shell '{}' not found. Install it or change execution.shell in your config.",
+            shell.program()
+        )
+    } else {
+        anyhow!(error)
+    }
+}
+
+/// How a supervised command ended.
+enum Waited {
+    Exited(StepStatus),
+    Skipped,
+    /// The plan was aborted; the command's process group has been stopped.
+    Aborted,
+    /// A time limit ran out; the command's process group has been stopped.
+    TimedOut(StepStatus),
+}
+
+/// A point in time at which a command is stopped.
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    at: Instant,
+    /// How the step ends when the limit is reached.
+    status: StepStatus,
+}
+
+/// Limits checked while a command runs.
+struct Watch<'a> {
+    limit: Option<Limit>,
+    /// Quiet period after which the command is flagged, and the time of its last output.
+    idle: Option<(Duration, Arc<Mutex<Instant>>)>,
+    /// Terminal to take back from the command while asking about an interrupt.
+    input: Option<&'a Passthrough>,
+}
+
+/// What to do with a command the user interrupted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InterruptChoice {
+    SkipStep,
+    AbortPlan,
+    Continue,
+}
+
+/// Wait for `child`, or for the `markers` of a step in a shell session, handling
+/// interrupts, cancellation and time limits while it runs. `on_idle` is called
+/// once if the command goes quiet for too long.
+async fn supervise(
+    child: &mut Child,
+    mut markers: Option<&mut Markers>,
+    cmd: &str,
+    cancel: &CancelToken,
+    watch: Watch<'_>,
+    on_idle: &mut (dyn FnMut(Duration) + Send),
+) -> Result<Waited> {
+    let group = child.id();
+    let _tracked = group.map(|pgid| cancel.track_group(pgid));
+    let mut idle = watch.idle;
+
+    loop {
+        let quiet_at = idle
+            .as_ref()
+            .map(|(after, activity)| *activity.lock().unwrap() + *after);
+        tokio::select! {
+            status = finished(child, markers.as_deref_mut()) => {
+                return Ok(Waited::Exited(status?));
+            }
+            () = cancel.interrupted() => {}
+            status = expire(watch.limit) => {
+                stop(child, group, Signal::Terminate).await;
+                return Ok(Waited::TimedOut(status));
+            }
+            () = sleep_until(quiet_at) => {
+                if let Some((after, activity)) = &idle {
+                    let quiet = activity.lock().unwrap().elapsed();
+                    if quiet >= *after {
+                        on_idle(quiet);
+                        idle = None;
+                    }
+                }
+                continue;
+            }
         }
-    });
 
-    let stderr_handle = tokio::spawn(async move {
-        while let Ok(Some(line)) = stderr_reader.next_line().await {
-            eprintln!("│ {}", line);
+        let choice = if cancel.is_cancelled() {
+            InterruptChoice::AbortPlan
+        } else {
+            cancel.take_interrupt();
+            if let Some(input) = watch.input {
+                input.pause();
+            }
+            let choice = ask_interrupt_choice(cmd, cancel, true).await;
+            if let (Some(input), InterruptChoice::Continue) = (watch.input, choice) {
+                input.resume();
+            }
+            choice
+        };
+
+        match choice {
+            InterruptChoice::Continue => continue,
+            InterruptChoice::SkipStep => {
+                stop(child, group, Signal::Interrupt).await;
+                return Ok(Waited::Skipped);
+            }
+            InterruptChoice::AbortPlan => {
+                cancel.cancel(if cancel.prompts() {
+                    "plan aborted by user"
+                } else {
+                    INTERRUPTED
+                });
+                stop(child, group, cancel.signal()).await;
+                return Ok(Waited::Aborted);
+            }
         }
-    });
+    }
+}
 
+/// How the step ended: the exit code on its sentinel, or the shell's own exit
+/// status when the shell went away first.
+async fn finished(child: &mut Child, markers: Option<&mut Markers>) -> Result<StepStatus> {
+    if let Some(markers) = markers
+        && let Some(code) = markers.wait().await
+    {
+        return Ok(StepStatus::Exited { code });
+    }
     let status = child
         .wait()
         .await
         .map_err(|e| anyhow!("Failed to wait for command completion: {}", e))?;
+    Ok(exit_status(status))
+}
 
-    stdout_handle
-        .await
-        .map_err(|e| anyhow!("Failed to read command output: {}", e))?;
-    stderr_handle
+/// Wait for `limit` to run out and return the status the step then ends with.
+async fn expire(limit: Option<Limit>) -> StepStatus {
+    match limit {
+        Some(limit) => {
+            tokio::time::sleep_until(limit.at.into()).await;
+            limit.status
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Sleep until `at`, or forever without it.
+async fn sleep_until(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Forward `signal` to the command's process group, killing it after [`KILL_GRACE`].
+async fn stop(child: &mut Child, group: Option<u32>, signal: Signal) {
+    let Some(group) = group else {
+        let _ = child.kill().await;
+        return;
+    };
+    signal_group(group, signal);
+    if tokio::time::timeout(KILL_GRACE, child.wait())
         .await
-        .map_err(|e| anyhow!("Failed to read command errors: {}", e))?;
+        .is_err()
+    {
+        signal_group(group, Signal::Kill);
+        let _ = child.wait().await;
+    }
+    // Background jobs the command started share its group and must not outlive it.
+    signal_group(group, Signal::Kill);
+}
+
+/// Whether a command that had the terminal ended because of Ctrl-C: killed by
+/// SIGINT, or exiting with 130 as shells and most CLIs do when they trap it.
+fn interrupted_on_terminal(status: StepStatus) -> bool {
+    match status {
+        StepStatus::Signaled { signal } => signal == libc::SIGINT,
+        StepStatus::Exited { code } => code == 128 + libc::SIGINT,
+        _ => false,
+    }
+}
 
-    if status.success() {
-        println!("│");
-        println!("└─ Command completed successfully");
-        Ok(true)
+/// Ask on the terminal how to handle an interrupted command, offering to let
+/// it continue when it is still running. Another Ctrl-C while asking aborts
+/// the plan.
+async fn ask_interrupt_choice(cmd: &str, cancel: &CancelToken, running: bool) -> InterruptChoice {
+    if !cancel.prompts() {
+        return InterruptChoice::AbortPlan;
+    }
+
+    eprintln!("\n⚠ Interrupted: {cmd}");
+    if running {
+        eprint!("[s]kip step, [a]bort plan, [c]ontinue? ");
     } else {
-        println!("│");
-        if let Some(code) = status.code() {
-            println!("└─ Command failed with exit code {}", code);
-            Err(anyhow!("Command failed with exit code {}: {}", code, cmd))
-        } else {
-            println!("└─ Command was terminated by signal");
-            Err(anyhow!("Command was terminated by signal: {}", cmd))
+        eprint!("[s]kip step, [a]bort plan? ");
+    }
+    let _ = std::io::stderr().flush();
+
+    // The reader is stopped and joined before returning, so an abandoned read
+    // cannot take the next line typed for something else.
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut reader = tokio::task::spawn_blocking({
+        let stop = Arc::clone(&stop);
+        move || pty::read_line_until(&stop)
+    });
+
+    let answer = tokio::select! {
+        answer = &mut reader => answer.ok().flatten(),
+        () = cancel.interrupted() => {
+            stop.store(true, Ordering::SeqCst);
+            let _ = reader.await;
+            None
         }
+    };
+    match answer.unwrap_or_default().trim().to_lowercase().as_str() {
+        "s" | "skip" => InterruptChoice::SkipStep,
+        "c" | "continue" if running => InterruptChoice::Continue,
+        _ => InterruptChoice::AbortPlan,
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,472 @@
+//! Filesystem preview: run a plan's execute commands in a [`Sandbox`] and
+//! report which paths under the working directory they add, modify or delete.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::ops::Bound;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command as TokioCommand;
+
+use crate::agent::ExecutionReport;
+use crate::cancel::CancelToken;
+use crate::planner::Plan;
+use crate::undo::footprint;
+
+use super::Executor;
+use super::sandbox::{self, Layer, Sandbox};
+
+/// Largest directory tree, in entries, that is previewed.
+pub(crate) const PREVIEW_MAX_ENTRIES: usize = 100_000;
+
+/// How the working directory was isolated for the preview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewMode {
+    /// An overlayfs collected the writes.
+    Overlay,
+    /// The commands ran on a temporary copy of the directory.
+    Copy,
+}
+
+impl fmt::Display for PreviewMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreviewMode::Overlay => write!(f, "overlay"),
+            PreviewMode::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// One path the previewed commands changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    /// Relative to the working directory.
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dir: bool,
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = match self.kind {
+            ChangeKind::Added => '+',
+            ChangeKind::Modified => '~',
+            ChangeKind::Deleted => '-',
+        };
+        let slash = if self.dir { "/" } else { "" };
+        write!(f, "{marker} {}{slash}", self.path.display())
+    }
+}
+
+/// What running the execute commands in isolation did to the working directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreviewReport {
+    pub mode: PreviewMode,
+    /// Changed paths in path order.
+    pub changes: Vec<FileChange>,
+    /// How the commands fared in the preview.
+    pub execution: ExecutionReport,
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+impl PreviewReport {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == kind)
+            .count()
+    }
+}
+
+/// Run `plan`'s execute commands with `executor` in a mount namespace over
+/// `dir`, which the real commands would change, and diff the result.
+pub async fn preview(
+    executor: Executor,
+    dir: &Path,
+    plan: &Plan,
+    cancel: &CancelToken,
+) -> Result<PreviewReport> {
+    if let Some(reason) = sandbox::UNSUPPORTED {
+        bail!(reason);
+    }
+    // The sandbox has no network and its own processes, but a daemon's socket
+    // on disk stays reachable, and what the daemon does is not sandboxed.
+    for command in &plan.execute_commands {
+        if let Some(program) = footprint(command, dir).daemon_clients.first() {
+            bail!(
+                "`{command}` would act through a system service ({program}), which a preview cannot contain"
+            );
+        }
+    }
+    let before = Tree::scan(dir)?;
+    let scratch = Scratch::create()?;
+    let mut notes = Vec::new();
+
+    let overlay = Layer::Overlay {
+        upper: scratch.path.join("upper"),
+        work: scratch.path.join("work"),
+    };
+    let (mode, layer, sandbox) = match enter(executor.shell.program(), dir, &overlay).await {
+        Ok(sandbox) => (PreviewMode::Overlay, overlay, sandbox),
+        Err(err) => {
+            notes.push(format!(
+                "overlayfs unavailable ({err:#}); previewed on a copy"
+            ));
+            if scratch.path.starts_with(dir) {
+                bail!(
+                    "Cannot copy {} into {}, which is inside it",
+                    dir.display(),
+                    scratch.path.display()
+                );
+            }
+            let copy = scratch.path.join("copy");
+            copy_tree(dir, &copy, &before)?;
+            let layer = Layer::Copy { copy };
+            let sandbox = enter(executor.shell.program(), dir, &layer)
+                .await
+                .context("Could not isolate the working directory for the preview")?;
+            (PreviewMode::Copy, layer, sandbox)
+        }
+    };
+
+    let commands = Plan {
+        dry_run_commands: Vec::new(),
+        ..plan.clone()
+    };
+    let execution = executor
+        .with_echo(false)
+        .with_sandbox(Some(sandbox))
+        .run(&commands, cancel, &mut |_| {})
+        .await;
+
+    let after = match &layer {
+        Layer::Overlay { upper, .. } => before.clone().overlaid(upper)?,
+        Layer::Copy { copy } => Tree::scan(copy)?,
+    };
+    Ok(PreviewReport {
+        mode,
+        changes: before.diff(&after)?,
+        execution,
+        notes,
+    })
+}
+
+/// Create `layer`'s directories and check that a command can start inside it.
+async fn enter(shell: &str, dir: &Path, layer: &Layer) -> Result<std::sync::Arc<Sandbox>> {
+    if let Layer::Overlay { upper, work } = layer {
+        for path in [upper, work] {
+            fs::create_dir_all(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+        }
+    }
+    let sandbox = Sandbox::new(dir, layer)?;
+    let mut probe = TokioCommand::new(shell);
+    probe
+        .arg("-c")
+        .arg("true")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    sandbox.install(&mut probe);
+    let status = probe.status().await?;
+    if !status.success() {
+        bail!("sandboxed shell exited with {status}");
+    }
+    Ok(sandbox)
+}
+
+/// A directory tree by relative path.
+#[derive(Debug, Clone, Default)]
+struct Tree(BTreeMap<PathBuf, Node>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Dir,
+    File {
+        len: u64,
+        mode: u32,
+        /// Where the contents can be read for comparison.
+        source: PathBuf,
+    },
+    Symlink(PathBuf),
+}
+
+impl Node {
+    /// `None` for sockets, fifos and devices, which the preview ignores.
+    fn read(path: &Path, metadata: &fs::Metadata) -> Result<Option<Self>> {
+        let kind = metadata.file_type();
+        Ok(Some(if kind.is_dir() {
+            Node::Dir
+        } else if kind.is_file() {
+            Node::File {
+                len: metadata.len(),
+                mode: metadata.permissions().mode() & 0o7777,
+                source: path.to_path_buf(),
+            }
+        } else if kind.is_symlink() {
+            Node::Symlink(
+                fs::read_link(path)
+                    .with_context(|| format!("Failed to read link {}", path.display()))?,
+            )
+        } else {
+            return Ok(None);
+        }))
+    }
+
+    fn same_as(&self, other: &Node) -> Result<bool> {
+        match (self, other) {
+            (
+                Node::File { len, mode, source },
+                Node::File {
+                    len: other_len,
+                    mode: other_mode,
+                    source: other_source,
+                },
+            ) => Ok(len == other_len
+                && mode == other_mode
+                && (source == other_source || same_contents(source, other_source)?)),
+            _ => Ok(self == other),
+        }
+    }
+}
+
+impl Tree {
+    /// Every entry below `root`, without following symlinks.
+    fn scan(root: &Path) -> Result<Self> {
+        let mut tree = Tree::default();
+        tree.add_dir(root, Path::new(""))?;
+        Ok(tree)
+    }
+
+    fn add_dir(&mut self, root: &Path, relative: &Path) -> Result<()> {
+        let dir = root.join(relative);
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+            let path = relative.join(entry.file_name());
+            let metadata = entry
+                .metadata()
+                .with_context(|| format!("Failed to inspect {}", entry.path().display()))?;
+            let Some(node) = Node::read(&entry.path(), &metadata)? else {
+                continue;
+            };
+            let is_dir = node == Node::Dir;
+            self.0.insert(path.clone(), node);
+            if self.0.len() > PREVIEW_MAX_ENTRIES {
+                bail!(
+                    "{} has more than {PREVIEW_MAX_ENTRIES} entries; too many to preview",
+                    root.display()
+                );
+            }
+            if is_dir {
+                self.add_dir(root, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The tree as seen through an overlay whose upper directory is `upper`.
+    fn overlaid(mut self, upper: &Path) -> Result<Self> {
+        self.apply_upper(upper, Path::new(""))?;
+        Ok(self)
+    }
+
+    fn apply_upper(&mut self, upper: &Path, relative: &Path) -> Result<()> {
+        let dir = upper.join(relative);
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+            let path = relative.join(entry.file_name());
+            let metadata = fs::symlink_metadata(entry.path())
+                .with_context(|| format!("Failed to inspect {}", entry.path().display()))?;
+
+            // A 0/0 character device marks a deleted path.
+            if metadata.file_type().is_char_device() && metadata.rdev() == 0 {
+                self.remove_below(&path);
+                self.0.remove(&path);
+                continue;
+            }
+
+            let Some(node) = Node::read(&entry.path(), &metadata)? else {
+                continue;
+            };
+            let merged = node == Node::Dir
+                && self.0.get(&path) == Some(&Node::Dir)
+                && !is_opaque(&entry.path());
+            if !merged {
+                self.remove_below(&path);
+            }
+            let is_dir = node == Node::Dir;
+            self.0.insert(path.clone(), node);
+            if is_dir {
+                self.apply_upper(upper, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_below(&mut self, path: &Path) {
+        let below = self
+            .0
+            .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+            .map(|(entry, _)| entry)
+            .take_while(|entry| entry.starts_with(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for entry in below {
+            self.0.remove(&entry);
+        }
+    }
+
+    fn diff(&self, after: &Tree) -> Result<Vec<FileChange>> {
+        let mut changes = Vec::new();
+        for (path, node) in &self.0 {
+            let kind = match after.0.get(path) {
+                None => ChangeKind::Deleted,
+                Some(other) if !node.same_as(other)? => ChangeKind::Modified,
+                Some(_) => continue,
+            };
+            changes.push(FileChange {
+                path: path.clone(),
+                kind,
+                dir: *after.0.get(path).unwrap_or(node) == Node::Dir,
+            });
+        }
+        for (path, node) in &after.0 {
+            if !self.0.contains_key(path) {
+                changes.push(FileChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Added,
+                    dir: *node == Node::Dir,
+                });
+            }
+        }
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(changes)
+    }
+}
+
+fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let (mut a, mut b) = (open(a)?, open(b)?);
+    let (mut left, mut right) = ([0u8; 8192], [0u8; 8192]);
+    loop {
+        let read = read_full(&mut a, &mut left)?;
+        if read != read_full(&mut b, &mut right)? || left[..read] != right[..read] {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fill `buffer` unless the reader ends first.
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+/// Whether an overlay upper directory hides the lower directory's contents.
+#[cfg(target_os = "linux")]
+fn is_opaque(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut value = [0u8; 1];
+    [c"user.overlay.opaque", c"trusted.overlay.opaque"]
+        .iter()
+        .any(|name| {
+            // SAFETY: both strings are NUL-terminated and `value` outlives the call.
+            let len = unsafe {
+                libc::lgetxattr(
+                    path.as_ptr(),
+                    name.as_ptr(),
+                    value.as_mut_ptr().cast(),
+                    value.len(),
+                )
+            };
+            len == 1 && value[0] == b'y'
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_opaque(_path: &Path) -> bool {
+    false
+}
+
+/// Copy the entries of `tree`, scanned from `from`, to `to`.
+fn copy_tree(from: &Path, to: &Path, tree: &Tree) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("Failed to create {}", to.display()))?;
+    for (path, node) in &tree.0 {
+        let target = to.join(path);
+        let result = match node {
+            Node::Dir => fs::create_dir(&target),
+            Node::File { .. } => fs::copy(from.join(path), &target).map(|_| ()),
+            Node::Symlink(link) => std::os::unix::fs::symlink(link, &target),
+        };
+        result.with_context(|| format!("Failed to copy {} for the preview", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Temporary directory holding the overlay layers or the copy.
+struct Scratch {
+    path: PathBuf,
+}
+
+impl Scratch {
+    fn create() -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        let path = std::env::temp_dir().join(format!("li-preview-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        // overlayfs leaves an unreadable directory in its work directory.
+        let _ = fs::set_permissions(
+            self.path.join("work/work"),
+            fs::Permissions::from_mode(0o700),
+        );
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
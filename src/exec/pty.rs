@@ -0,0 +1,444 @@
+//! Runs plan commands on a pseudo-terminal, so they behave as they would in the
+//! user's terminal: they color their output, draw progress bars and can ask for
+//! passwords or confirmations. Keystrokes are passed through to the command,
+//! the terminal follows the size of the user's window, and a plain-text
+//! transcript of the output is kept for the report.
+
+use std::io::{self, Write};
+#[cfg(target_os = "linux")]
+use std::os::fd::FromRawFd;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+use tokio::process::Command as TokioCommand;
+use tokio::signal::unix::{SignalKind, signal};
+
+use super::Stream;
+use super::step::OutputCapture;
+
+/// How often the input thread checks whether the command has finished.
+const INPUT_POLL: Duration = Duration::from_millis(100);
+
+/// A pseudo-terminal for one command.
+pub(super) struct Pty {
+    master: OwnedFd,
+    slave: OwnedFd,
+}
+
+impl Pty {
+    /// Open a pseudo-terminal the size of the user's terminal.
+    #[cfg(target_os = "linux")]
+    pub(super) fn open() -> io::Result<Self> {
+        let mut master = -1;
+        let mut slave = -1;
+        let size = window_size();
+        // SAFETY: openpty only writes the two descriptors; the name and termios
+        // arguments may be null and `size` outlives the call.
+        check(unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                size.as_ref()
+                    .map_or(std::ptr::null(), |size| size as *const libc::winsize),
+            )
+        })?;
+        // SAFETY: openpty returned two open descriptors nothing else owns.
+        let (master, slave) =
+            unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+        for fd in [&master, &slave] {
+            // SAFETY: fcntl on a descriptor owned above.
+            check(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+        }
+        Ok(Self { master, slave })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn open() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "pseudo-terminals are only used on Linux",
+        ))
+    }
+
+    /// Make the terminal `command`'s stdin, stdout, stderr and controlling
+    /// terminal. The command starts a session of its own, so its process group
+    /// id is its pid, as with a plain command.
+    pub(super) fn attach(&self, command: &mut TokioCommand) -> io::Result<()> {
+        command
+            .stdin(Stdio::from(self.slave.try_clone()?))
+            .stdout(Stdio::from(self.slave.try_clone()?))
+            .stderr(Stdio::from(self.slave.try_clone()?));
+        // SAFETY: setsid and ioctl are async-signal-safe and touch no memory
+        // shared with the parent.
+        unsafe {
+            command.pre_exec(|| {
+                check(libc::setsid())?;
+                // The request is a c_int on some platforms and a c_ulong on others.
+                check(libc::ioctl(0, libc::TIOCSCTTY as _, 0))
+            });
+        }
+        Ok(())
+    }
+
+    /// Start reading the command's output, and hand it the user's keyboard and
+    /// window size when `interactive`. Closes the parent's end of the terminal
+    /// the command now owns.
+    pub(super) fn start(
+        self,
+        echo: bool,
+        interactive: bool,
+        activity: Arc<Mutex<Instant>>,
+    ) -> io::Result<(Stream, Option<Passthrough>)> {
+        let Self { master, slave } = self;
+        drop(slave);
+        let passthrough = if interactive {
+            Some(Passthrough::start(&master)?)
+        } else {
+            None
+        };
+        set_nonblocking(&master)?;
+        let master = AsyncFd::with_interest(master, Interest::READABLE)?;
+        Ok((read_output(master, echo, activity), passthrough))
+    }
+}
+
+/// Copy the command's output to the terminal as is and into a plain-text
+/// transcript. Reading ends when every process holding the terminal is gone.
+fn read_output(master: AsyncFd<OwnedFd>, echo: bool, activity: Arc<Mutex<Instant>>) -> Stream {
+    let capture = Arc::new(Mutex::new(OutputCapture::default()));
+    let shared = capture.clone();
+    let reader = tokio::spawn(async move {
+        let mut transcript = Transcript::default();
+        let mut buffer = [0u8; 4096];
+        let mut at_line_start = true;
+        loop {
+            let Ok(mut ready) = master.readable().await else {
+                break;
+            };
+            let read = match ready.try_io(|fd| read_fd(fd.get_ref().as_raw_fd(), &mut buffer)) {
+                Ok(read) => read,
+                Err(_would_block) => continue,
+            };
+            // Linux reports EIO once the last process holding the terminal exits.
+            let Ok(count @ 1..) = read else {
+                break;
+            };
+            let bytes = &buffer[..count];
+            *activity.lock().unwrap() = Instant::now();
+            if echo {
+                let mut stdout = io::stdout().lock();
+                let _ = stdout.write_all(bytes);
+                let _ = stdout.flush();
+                at_line_start = bytes.ends_with(b"\n");
+            }
+            transcript.feed(bytes, &mut shared.lock().unwrap());
+        }
+        if echo && !at_line_start {
+            println!();
+        }
+        transcript.finish(&mut shared.lock().unwrap());
+        None
+    });
+    Stream { capture, reader }
+}
+
+/// The user's terminal, lent to a command on a pseudo-terminal until dropped.
+///
+/// Keystrokes are forwarded as typed, with echo and line editing left to the
+/// command's terminal. Ctrl-C still reaches li, which asks whether to skip the
+/// step or abort the plan; [`Passthrough::pause`] gives the terminal back for
+/// that question.
+pub(super) struct Passthrough {
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    saved: Option<libc::termios>,
+    raw: Option<libc::termios>,
+    input: Option<thread::JoinHandle<()>>,
+    resize: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Passthrough {
+    fn start(master: &OwnedFd) -> io::Result<Self> {
+        let saved = terminal_mode(libc::STDIN_FILENO);
+        let raw = saved.map(|saved| {
+            let mut raw = saved;
+            raw.c_iflag &= !(libc::IXON | libc::ICRNL | libc::INLCR | libc::IGNCR);
+            // ISIG stays on, so Ctrl-C interrupts li rather than the command.
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            raw
+        });
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        let input = {
+            let target = master.try_clone()?;
+            let (stop, paused) = (stop.clone(), paused.clone());
+            thread::spawn(move || forward_input(&target, &stop, &paused))
+        };
+        let resize = match signal(SignalKind::window_change()) {
+            Ok(mut resized) => {
+                let target = master.try_clone()?;
+                Some(tokio::spawn(async move {
+                    while resized.recv().await.is_some() {
+                        if let Some(size) = window_size() {
+                            // SAFETY: TIOCSWINSZ reads `size`, which outlives the call.
+                            unsafe { libc::ioctl(target.as_raw_fd(), libc::TIOCSWINSZ, &size) };
+                        }
+                    }
+                }))
+            }
+            Err(_) => None,
+        };
+
+        let passthrough = Self {
+            stop,
+            paused,
+            saved,
+            raw,
+            input: Some(input),
+            resize,
+        };
+        passthrough.resume();
+        Ok(passthrough)
+    }
+
+    /// Stop forwarding keystrokes and restore the terminal, e.g. to ask a question.
+    pub(super) fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        if let Some(saved) = &self.saved {
+            set_terminal_mode(libc::STDIN_FILENO, saved);
+        }
+    }
+
+    /// Forward keystrokes to the command again.
+    pub(super) fn resume(&self) {
+        if let Some(raw) = &self.raw {
+            set_terminal_mode(libc::STDIN_FILENO, raw);
+        }
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Passthrough {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(input) = self.input.take() {
+            let _ = input.join();
+        }
+        if let Some(resize) = self.resize.take() {
+            resize.abort();
+        }
+        if let Some(saved) = &self.saved {
+            set_terminal_mode(libc::STDIN_FILENO, saved);
+        }
+    }
+}
+
+/// Copy stdin to the command's terminal until `stop` is set. Waits in short
+/// polls, so no read is left pending to swallow input meant for li.
+fn forward_input(target: &OwnedFd, stop: &AtomicBool, paused: &AtomicBool) {
+    let mut buffer = [0u8; 1024];
+    while !stop.load(Ordering::SeqCst) {
+        if paused.load(Ordering::SeqCst) {
+            thread::sleep(INPUT_POLL);
+            continue;
+        }
+        if !stdin_ready() || stop.load(Ordering::SeqCst) || paused.load(Ordering::SeqCst) {
+            continue;
+        }
+        let Ok(count @ 1..) = read_fd(libc::STDIN_FILENO, &mut buffer) else {
+            return;
+        };
+        if write_all_fd(target.as_raw_fd(), &buffer[..count]).is_err() {
+            return;
+        }
+    }
+}
+
+/// Read one line from stdin, giving up with `None` once `stop` is set. Reads a
+/// byte at a time after a short poll, so nothing past the line is consumed and
+/// no read is left pending once this returns.
+pub(super) fn read_line_until(stop: &AtomicBool) -> Option<String> {
+    let mut line = Vec::new();
+    while !stop.load(Ordering::SeqCst) {
+        if !stdin_ready() {
+            continue;
+        }
+        let mut byte = [0u8; 1];
+        match read_fd(libc::STDIN_FILENO, &mut byte) {
+            Ok(1..) if byte[0] != b'\n' => line.push(byte[0]),
+            Ok(_) => return Some(String::from_utf8_lossy(&line).into_owned()),
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Wait up to [`INPUT_POLL`] for stdin to become readable.
+fn stdin_ready() -> bool {
+    let mut poll = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `poll` is a single valid pollfd.
+    let ready = unsafe { libc::poll(&mut poll, 1, INPUT_POLL.as_millis() as libc::c_int) };
+    ready > 0
+}
+
+/// Turns terminal output into plain-text lines for the report: escape
+/// sequences are dropped, and a carriage return starts the line over, so a
+/// progress bar leaves only its final state.
+#[derive(Debug, Default)]
+pub(super) struct Transcript {
+    line: Vec<u8>,
+    escape: Escape,
+    carriage: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    #[default]
+    None,
+    /// After ESC.
+    Start,
+    /// A control sequence, ended by a byte in `@`..=`~`.
+    Csi,
+    /// An operating system command, ended by BEL or ESC `\`.
+    Osc,
+    /// ESC inside an operating system command, normally followed by `\`.
+    OscEnd,
+    /// A character set selection, one byte long.
+    Charset,
+}
+
+impl Transcript {
+    pub(super) fn feed(&mut self, bytes: &[u8], capture: &mut OutputCapture) {
+        for &byte in bytes {
+            if self.escape != Escape::None {
+                self.escape = Self::next_escape(self.escape, byte);
+                continue;
+            }
+            if self.carriage && byte != b'\n' {
+                self.line.clear();
+            }
+            self.carriage = false;
+            match byte {
+                0x1b => self.escape = Escape::Start,
+                b'\n' => {
+                    capture.push_line(&String::from_utf8_lossy(&self.line));
+                    self.line.clear();
+                }
+                b'\r' => self.carriage = true,
+                0x08 => {
+                    self.line.pop();
+                }
+                b'\t' => self.line.push(byte),
+                0x00..=0x1f | 0x7f => {}
+                _ => self.line.push(byte),
+            }
+        }
+    }
+
+    /// Keep the last line when the output did not end with a newline.
+    pub(super) fn finish(&mut self, capture: &mut OutputCapture) {
+        if !self.line.is_empty() {
+            capture.push_line(&String::from_utf8_lossy(&self.line));
+            self.line.clear();
+        }
+    }
+
+    /// The state after `byte` inside an escape sequence.
+    fn next_escape(escape: Escape, byte: u8) -> Escape {
+        match (escape, byte) {
+            (Escape::Start, b'[') => Escape::Csi,
+            (Escape::Start, b']') => Escape::Osc,
+            (Escape::Start, b'(' | b')') => Escape::Charset,
+            (Escape::Csi, b'@'..=b'~') | (Escape::Osc, 0x07) => Escape::None,
+            (Escape::Csi, _) => Escape::Csi,
+            (Escape::Osc, 0x1b) => Escape::OscEnd,
+            (Escape::Osc, _) => Escape::Osc,
+            _ => Escape::None,
+        }
+    }
+}
+
+/// The size of the user's terminal, if stdout is one.
+fn window_size() -> Option<libc::winsize> {
+    // SAFETY: winsize is plain data, and TIOCGWINSZ only writes to it.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size)
+}
+
+fn terminal_mode(fd: RawFd) -> Option<libc::termios> {
+    // SAFETY: termios is plain data, and tcgetattr only writes to it.
+    let mut mode: libc::termios = unsafe { std::mem::zeroed() };
+    (unsafe { libc::tcgetattr(fd, &mut mode) } == 0).then_some(mode)
+}
+
+fn set_terminal_mode(fd: RawFd, mode: &libc::termios) {
+    // SAFETY: tcsetattr only reads `mode`.
+    unsafe {
+        libc::tcsetattr(fd, libc::TCSADRAIN, mode);
+    }
+}
+
+fn set_nonblocking(fd: &OwnedFd) -> io::Result<()> {
+    // SAFETY: fcntl on a descriptor owned by the caller.
+    unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFL);
+        check(flags)?;
+        check(libc::fcntl(
+            fd.as_raw_fd(),
+            libc::F_SETFL,
+            flags | libc::O_NONBLOCK,
+        ))
+    }
+}
+
+fn read_fd(fd: RawFd, buffer: &mut [u8]) -> io::Result<usize> {
+    // SAFETY: `buffer` is valid for writes of its length.
+    let count = unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+    if count < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(count as usize)
+}
+
+/// Write all of `bytes`, waiting briefly while a non-blocking descriptor is full.
+fn write_all_fd(fd: RawFd, mut bytes: &[u8]) -> io::Result<()> {
+    while !bytes.is_empty() {
+        // SAFETY: `bytes` is valid for reads of its length.
+        let count = unsafe { libc::write(fd, bytes.as_ptr().cast(), bytes.len()) };
+        if count < 0 {
+            let error = io::Error::last_os_error();
+            match error.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+                _ => return Err(error),
+            }
+        }
+        bytes = &bytes[count as usize..];
+    }
+    Ok(())
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,318 @@
+//! Runs plan commands in private namespaces: a mount namespace in which only the
+//! working directory is writable, and writes to it land somewhere li can inspect
+//! afterwards instead of on disk, plus network, PID and IPC namespaces so that
+//! commands cannot reach other hosts or signal processes outside the sandbox.
+
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+use anyhow::{Result, bail};
+use tokio::process::Command as TokioCommand;
+
+/// Why [`Sandbox::new`] fails on this platform, if it always does.
+#[cfg(not(target_os = "linux"))]
+pub(crate) const UNSUPPORTED: Option<&str> =
+    Some("file change preview needs Linux mount namespaces");
+#[cfg(target_os = "linux")]
+pub(crate) const UNSUPPORTED: Option<&str> = None;
+
+/// Where writes to the working directory go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Layer {
+    /// An overlayfs over the directory; `upper` collects every change.
+    Overlay { upper: PathBuf, work: PathBuf },
+    /// A copy of the directory bind-mounted over it.
+    Copy { copy: PathBuf },
+}
+
+/// Mount namespace setup applied to every command the executor starts.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub(crate) struct Sandbox {
+    target: CString,
+    source: CString,
+    fstype: CString,
+    flags: libc::c_ulong,
+    data: Option<CString>,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+/// Mount namespaces do not exist here, so no sandbox can be built.
+#[cfg(not(target_os = "linux"))]
+#[derive(Debug)]
+pub(crate) enum Sandbox {}
+
+#[cfg(not(target_os = "linux"))]
+impl Sandbox {
+    pub(crate) fn new(_dir: &Path, _layer: &Layer) -> Result<Arc<Self>> {
+        bail!(UNSUPPORTED.unwrap_or_default())
+    }
+
+    pub(crate) fn install(self: &Arc<Self>, _command: &mut TokioCommand) {
+        match **self {}
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Sandbox {
+    /// Cover `dir` with `layer` in each command's namespace.
+    pub(crate) fn new(dir: &Path, layer: &Layer) -> Result<Arc<Self>> {
+        let (source, fstype, flags, data) = match layer {
+            Layer::Overlay { upper, work } => {
+                for path in [dir, upper.as_path(), work.as_path()] {
+                    // overlayfs splits its options on these characters.
+                    if path
+                        .as_os_str()
+                        .as_bytes()
+                        .iter()
+                        .any(|b| b",:\\".contains(b))
+                    {
+                        bail!("{} cannot be used as an overlay layer", path.display());
+                    }
+                }
+                let options = format!(
+                    "lowerdir={},upperdir={},workdir={},userxattr",
+                    dir.display(),
+                    upper.display(),
+                    work.display()
+                );
+                (
+                    c_string("overlay")?,
+                    c_string("overlay")?,
+                    0,
+                    Some(c_string(options)?),
+                )
+            }
+            Layer::Copy { copy } => (c_path(copy)?, c_string("")?, libc::MS_BIND, None),
+        };
+
+        // SAFETY: getuid and getgid cannot fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Ok(Arc::new(Self {
+            target: c_path(dir)?,
+            source,
+            fstype,
+            flags,
+            data,
+            uid_map: format!("{uid} {uid} 1").into_bytes(),
+            gid_map: format!("{gid} {gid} 1").into_bytes(),
+        }))
+    }
+
+    /// Enter the sandbox in `command`'s child process before it runs.
+    pub(crate) fn install(self: &Arc<Self>, command: &mut TokioCommand) {
+        let sandbox = self.clone();
+        // SAFETY: `enter` only makes system calls on data prepared before the
+        // fork; it does not allocate or take locks.
+        unsafe {
+            command.pre_exec(move || sandbox.enter());
+        }
+    }
+
+    /// Runs in the child between fork and exec.
+    fn enter(&self) -> io::Result<()> {
+        // SAFETY: every pointer passed below is a NUL-terminated string or a
+        // struct owned by `self` or this stack frame.
+        unsafe {
+            check(libc::unshare(
+                libc::CLONE_NEWUSER
+                    | libc::CLONE_NEWNS
+                    | libc::CLONE_NEWNET
+                    | libc::CLONE_NEWPID
+                    | libc::CLONE_NEWIPC,
+            ))?;
+            write_proc(c"/proc/self/setgroups", b"deny")?;
+            write_proc(c"/proc/self/uid_map", &self.uid_map)?;
+            write_proc(c"/proc/self/gid_map", &self.gid_map)?;
+
+            // Keep the mounts below from propagating back to the real namespace.
+            check(libc::mount(
+                std::ptr::null(),
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ))?;
+            check(libc::mount(
+                self.source.as_ptr(),
+                self.target.as_ptr(),
+                self.fstype.as_ptr(),
+                self.flags,
+                self.data
+                    .as_ref()
+                    .map_or(std::ptr::null(), |data| data.as_ptr().cast()),
+            ))?;
+
+            // Everything but the working directory becomes read-only.
+            set_read_only(c"/", libc::AT_RECURSIVE, true)?;
+            set_read_only(&self.target, 0, false)?;
+            check(libc::chdir(self.target.as_ptr()))?;
+
+            fork_into_pid_namespace()?;
+            // A /proc of the new namespace hides the host's processes. Where
+            // the kernel refuses it, e.g. in a container that masks parts of
+            // /proc, host PIDs stay visible but cannot be signalled from here.
+            libc::mount(
+                c"proc".as_ptr(),
+                c"/proc".as_ptr(),
+                c"proc".as_ptr(),
+                libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                std::ptr::null(),
+            );
+            Ok(())
+        }
+    }
+}
+
+/// `unshare(CLONE_NEWPID)` only moves the caller's children into the new PID
+/// namespace, so fork twice: the process li started waits outside it and exits
+/// as the command did, the namespace's init only reaps, and the command runs
+/// below init, where signals to its process group reach it as they would
+/// outside the sandbox. Returns in the process that goes on to exec.
+#[cfg(target_os = "linux")]
+unsafe fn fork_into_pid_namespace() -> io::Result<()> {
+    // SAFETY: fork, signal, waitpid and _exit are async-signal-safe.
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            init => {
+                // Interrupts and timeouts are for the command; this process
+                // has to stay to report how it ended.
+                for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT] {
+                    libc::signal(signal, libc::SIG_IGN);
+                }
+                close_inherited();
+                exit_as(reap_until(init));
+            }
+        }
+        match libc::fork() {
+            -1 => libc::_exit(127),
+            0 => Ok(()),
+            command => {
+                close_inherited();
+                exit_as(reap_until(command))
+            }
+        }
+    }
+}
+
+/// Close every descriptor but stdio. One of them is the pipe on which the
+/// spawning process waits for the command's exec, which only the command may
+/// hold open.
+#[cfg(target_os = "linux")]
+unsafe fn close_inherited() {
+    // SAFETY: closing descriptors passes no memory.
+    unsafe {
+        if libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0) < 0 {
+            for fd in 3..1024 {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+/// Reap children until `child` exits and return its wait status.
+#[cfg(target_os = "linux")]
+unsafe fn reap_until(child: libc::pid_t) -> libc::c_int {
+    let mut status = 0;
+    loop {
+        // SAFETY: `status` outlives the call.
+        let pid = unsafe { libc::waitpid(-1, &mut status, 0) };
+        if pid == child {
+            return status;
+        }
+        if pid < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return 127 << 8;
+        }
+    }
+}
+
+/// Exit the way the process with wait status `status` did. A namespace's init
+/// cannot be killed by its own signal, so it exits with 128 plus the signal.
+#[cfg(target_os = "linux")]
+unsafe fn exit_as(status: libc::c_int) -> ! {
+    // SAFETY: signal, kill and _exit pass no memory.
+    unsafe {
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+/// Set or clear `MOUNT_ATTR_RDONLY` on the mount at `path`.
+#[cfg(target_os = "linux")]
+unsafe fn set_read_only(
+    path: &std::ffi::CStr,
+    flags: libc::c_int,
+    read_only: bool,
+) -> io::Result<()> {
+    // SAFETY: mount_attr is plain data.
+    let mut attr: libc::mount_attr = unsafe { std::mem::zeroed() };
+    if read_only {
+        attr.attr_set = libc::MOUNT_ATTR_RDONLY;
+    } else {
+        attr.attr_clr = libc::MOUNT_ATTR_RDONLY;
+    }
+    // SAFETY: `path` and `attr` outlive the call.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            flags,
+            &attr as *const libc::mount_attr,
+            std::mem::size_of::<libc::mount_attr>(),
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn write_proc(path: &std::ffi::CStr, contents: &[u8]) -> io::Result<()> {
+    // SAFETY: `path` is NUL-terminated and `contents` outlives the write.
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        libc::close(fd);
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn c_string(value: impl Into<Vec<u8>>) -> Result<CString> {
+    CString::new(value).context("Path contains a NUL byte")
+}
+
+#[cfg(target_os = "linux")]
+fn c_path(path: &Path) -> Result<CString> {
+    c_string(path.as_os_str().as_bytes())
+}
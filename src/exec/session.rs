@@ -0,0 +1,248 @@
+//! One long-lived shell that runs every command of a plan, so `cd`, exported
+//! variables and activated environments carry over from one step to the next.
+//!
+//! Each command is written to the shell's stdin followed by a sentinel line on
+//! stdout and one on stderr. The stdout sentinel carries the command's exit
+//! code; output up to the sentinels belongs to the step.
+
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command as TokioCommand};
+use tokio::sync::oneshot;
+
+use crate::cancel::{KILL_GRACE, Signal, signal_group};
+use crate::config::ShellDialect;
+
+use super::spawn_in_group;
+
+/// One of the shell's output pipes, handed from step to step.
+pub(super) type Pipe = BufReader<Box<dyn AsyncRead + Unpin + Send>>;
+
+pub(super) fn pipe<R>(reader: R) -> Pipe
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    BufReader::new(Box::new(reader))
+}
+
+/// A running shell waiting for its next command.
+pub(super) struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Pipe,
+    stderr: Pipe,
+    shell: ShellDialect,
+    token: String,
+    sent: usize,
+}
+
+/// A command sent to the session. The session's pipes are read up to the
+/// command's sentinels, then handed back with [`Parked::resume`].
+pub(super) struct Sent {
+    pub(super) child: Child,
+    pub(super) stdout: (Pipe, Until),
+    pub(super) stderr: (Pipe, Until),
+    pub(super) markers: Markers,
+    pub(super) parked: Parked,
+}
+
+/// The rest of a session while one of its commands runs.
+pub(super) struct Parked {
+    stdin: ChildStdin,
+    shell: ShellDialect,
+    token: String,
+    sent: usize,
+}
+
+/// The sentinel ending a step's output on one pipe. The text after it on the
+/// sentinel line is sent to `done`.
+pub(super) struct Until {
+    pub(super) marker: String,
+    pub(super) done: oneshot::Sender<String>,
+}
+
+/// Waits for both sentinels of a step.
+pub(super) struct Markers {
+    stdout: Option<oneshot::Receiver<String>>,
+    stderr: Option<oneshot::Receiver<String>>,
+    code: Option<i32>,
+    lost: bool,
+}
+
+impl Session {
+    /// Start `command`, a shell reading commands from its stdin.
+    pub(super) fn start(mut command: TokioCommand, shell: ShellDialect) -> Result<Self> {
+        let mut child = spawn_in_group(&mut command, shell, Stdio::piped())?;
+        let (Some(stdin), Some(stdout), Some(stderr)) =
+            (child.stdin.take(), child.stdout.take(), child.stderr.take())
+        else {
+            anyhow::bail!("Shell session was started without pipes");
+        };
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        Ok(Self {
+            child,
+            stdin,
+            stdout: pipe(stdout),
+            stderr: pipe(stderr),
+            shell,
+            token: format!("__li_step_{}_{nanos}_", std::process::id()),
+            sent: 0,
+        })
+    }
+
+    /// Write `cmd` and its sentinels to the shell.
+    pub(super) async fn send(mut self, cmd: &str) -> Result<Sent> {
+        self.sent += 1;
+        let marker = format!("{}{}__", self.token, self.sent);
+        let script = script(self.shell, cmd, &marker);
+        let written = async {
+            self.stdin.write_all(script.as_bytes()).await?;
+            self.stdin.flush().await
+        }
+        .await;
+        if let Err(error) = written {
+            kill(&mut self.child).await;
+            return Err(error).context("Shell session is no longer running");
+        }
+
+        let (stdout_done, stdout) = oneshot::channel();
+        let (stderr_done, stderr) = oneshot::channel();
+        Ok(Sent {
+            child: self.child,
+            stdout: (
+                self.stdout,
+                Until {
+                    marker: marker.clone(),
+                    done: stdout_done,
+                },
+            ),
+            stderr: (
+                self.stderr,
+                Until {
+                    marker,
+                    done: stderr_done,
+                },
+            ),
+            markers: Markers {
+                stdout: Some(stdout),
+                stderr: Some(stderr),
+                code: None,
+                lost: false,
+            },
+            parked: Parked {
+                stdin: self.stdin,
+                shell: self.shell,
+                token: self.token,
+                sent: self.sent,
+            },
+        })
+    }
+
+    /// Let the shell exit after its last command, killing it if it does not.
+    /// Background jobs the plan started keep running, as they would after `sh -c`.
+    pub(super) async fn close(self) {
+        let Self {
+            mut child, stdin, ..
+        } = self;
+        drop(stdin);
+        if tokio::time::timeout(KILL_GRACE, child.wait())
+            .await
+            .is_err()
+        {
+            kill(&mut child).await;
+        }
+    }
+}
+
+impl Parked {
+    /// The session back, once both sentinels were read and the pipes returned.
+    /// Otherwise the shell has exited or was stopped, and is killed if it is
+    /// still running.
+    pub(super) async fn resume(
+        self,
+        mut child: Child,
+        markers: &Markers,
+        stdout: Option<Pipe>,
+        stderr: Option<Pipe>,
+    ) -> Option<Session> {
+        match (markers.complete(), stdout, stderr) {
+            (true, Some(stdout), Some(stderr)) => Some(Session {
+                child,
+                stdin: self.stdin,
+                stdout,
+                stderr,
+                shell: self.shell,
+                token: self.token,
+                sent: self.sent,
+            }),
+            _ => {
+                if let Ok(None) = child.try_wait() {
+                    kill(&mut child).await;
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Markers {
+    /// The step's exit code once both sentinels arrived, or `None` when a pipe
+    /// closed first because the shell went away (e.g. the command ran `exit`).
+    pub(super) async fn wait(&mut self) -> Option<i32> {
+        if let Some(stdout) = self.stdout.as_mut() {
+            let tail = stdout.await.ok();
+            self.stdout = None;
+            self.code = tail.and_then(|tail| tail.trim().parse().ok());
+            self.lost |= self.code.is_none();
+        }
+        if let Some(stderr) = self.stderr.as_mut() {
+            self.lost |= stderr.await.is_err();
+            self.stderr = None;
+        }
+        if self.lost { None } else { self.code }
+    }
+
+    fn complete(&self) -> bool {
+        self.stdout.is_none() && self.stderr.is_none() && !self.lost
+    }
+}
+
+/// Kill the shell and everything it started.
+async fn kill(child: &mut Child) {
+    if let Some(group) = child.id() {
+        signal_group(group, Signal::Kill);
+    }
+    let _ = child.kill().await;
+}
+
+/// `cmd` followed by the commands that print its sentinels.
+///
+/// The command is run through `eval` with stdin closed, so a syntax error stays
+/// within the step and nothing it runs can read the rest of the session's input.
+fn script(shell: ShellDialect, cmd: &str, marker: &str) -> String {
+    let (eval, quoted, status) = match shell {
+        ShellDialect::Sh | ShellDialect::Bash => {
+            // `command` keeps a syntax error in eval from exiting the shell.
+            ("command eval", posix_quote(cmd), "\"$?\"")
+        }
+        ShellDialect::Zsh => ("eval", posix_quote(cmd), "\"$?\""),
+        ShellDialect::Fish => ("eval", fish_quote(cmd), "$status"),
+    };
+    format!(
+        "{eval} {quoted} </dev/null; printf '%s %s\\n' '{marker}' {status}; printf '%s\\n' '{marker}' >&2\n"
+    )
+}
+
+fn posix_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'"))
+}
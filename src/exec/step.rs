@@ -0,0 +1,164 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::StepPhase;
+
+/// Bytes of stdout and of stderr kept per step; older output is dropped first.
+pub(crate) const OUTPUT_CAPTURE_LIMIT: usize = 64 * 1024;
+
+/// How a plan step ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum StepStatus {
+    /// The command exited on its own with this code.
+    Exited { code: i32 },
+    /// The command was killed by this signal without `li` asking.
+    Signaled { signal: i32 },
+    /// The user interrupted the command and chose to skip it.
+    Skipped,
+    /// The plan was aborted while the command ran.
+    Cancelled,
+    /// The command could not be started.
+    NotStarted,
+    /// The command ran past its own time limit and was stopped.
+    TimedOut { limit_secs: u64 },
+    /// The plan's time limit ran out while the command ran, or before it started.
+    DeadlineExceeded { limit_secs: u64 },
+}
+
+impl fmt::Display for StepStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepStatus::Exited { code } => write!(f, "exit code {code}"),
+            StepStatus::Signaled { signal } => write!(f, "killed by signal {signal}"),
+            StepStatus::Skipped => write!(f, "skipped"),
+            StepStatus::Cancelled => write!(f, "cancelled"),
+            StepStatus::NotStarted => write!(f, "not started"),
+            StepStatus::TimedOut { limit_secs } => write!(f, "timed out after {limit_secs}s"),
+            StepStatus::DeadlineExceeded { limit_secs } => {
+                write!(f, "plan time limit of {limit_secs}s reached")
+            }
+        }
+    }
+}
+
+/// What the user chose for a step in step-by-step mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepDecision {
+    Run,
+    /// Run after replacing the planned command.
+    Edited,
+    Skipped,
+    /// The user stopped the plan at this step.
+    Aborted,
+}
+
+/// Result of one plan command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepResult {
+    pub phase: StepPhase,
+    /// One-based position within the phase.
+    pub index: usize,
+    pub command: String,
+    #[serde(flatten)]
+    pub status: StepStatus,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether the start of stdout was dropped to stay under [`OUTPUT_CAPTURE_LIMIT`].
+    #[serde(default)]
+    pub stdout_truncated: bool,
+    #[serde(default)]
+    pub stderr_truncated: bool,
+    /// Whether the command went `execution.idle_warning_secs` without printing anything.
+    #[serde(default)]
+    pub stalled: bool,
+    /// The user's choice when the plan ran step by step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<StepDecision>,
+    /// The planned command, when the user edited it before it ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_command: Option<String>,
+}
+
+impl StepResult {
+    /// A step that ended with `status` without its command being started.
+    pub(crate) fn not_run(
+        phase: StepPhase,
+        index: usize,
+        command: &str,
+        status: StepStatus,
+    ) -> Self {
+        Self {
+            phase,
+            index,
+            command: command.to_string(),
+            status,
+            duration_ms: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            stdout_truncated: false,
+            stderr_truncated: false,
+            stalled: false,
+            decision: None,
+            original_command: None,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.status == StepStatus::Exited { code: 0 }
+    }
+
+    /// Whether the plan may go on to the next step.
+    pub fn allows_next(&self) -> bool {
+        self.succeeded() || self.status == StepStatus::Skipped
+    }
+
+    /// One line naming the step and how it ended, e.g. for notes and prompts.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} step {} ({}): {}",
+            self.phase, self.index, self.status, self.command
+        )
+    }
+}
+
+/// Output of one stream, keeping the most recent [`OUTPUT_CAPTURE_LIMIT`] bytes.
+///
+/// The buffer is allowed to grow to twice the limit before its front is
+/// dropped, so a long-running command does not shift the whole buffer on
+/// every line.
+#[derive(Debug, Default)]
+pub(crate) struct OutputCapture {
+    text: String,
+    truncated: bool,
+}
+
+impl OutputCapture {
+    pub(crate) fn push_line(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+        if self.text.len() > 2 * OUTPUT_CAPTURE_LIMIT {
+            self.trim();
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> (String, bool) {
+        if self.text.len() > OUTPUT_CAPTURE_LIMIT {
+            self.trim();
+        }
+        (self.text, self.truncated)
+    }
+
+    /// Drop the front of the buffer down to the last [`OUTPUT_CAPTURE_LIMIT`] bytes.
+    fn trim(&mut self) {
+        let mut cut = self.text.len() - OUTPUT_CAPTURE_LIMIT;
+        while !self.text.is_char_boundary(cut) {
+            cut += 1;
+        }
+        self.text.drain(..cut);
+        self.truncated = true;
+    }
+}
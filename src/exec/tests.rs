@@ -0,0 +1,484 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use tempfile::TempDir;
+
+use crate::cancel::CancelToken;
+use crate::config::ShellDialect;
+use crate::planner::Plan;
+
+use super::pty::{Pty, Transcript};
+use super::step::{OUTPUT_CAPTURE_LIMIT, OutputCapture};
+use super::{
+    ChangeKind, Executor, FileChange, PendingStep, PreviewMode, PreviewReport, StepChoice,
+    StepDecision, StepGate, StepStatus, interrupted_on_terminal, preview,
+};
+
+fn plan(commands: &[&str]) -> Plan {
+    Plan {
+        confidence: 0.9,
+        dry_run_commands: vec!["echo checking".to_string()],
+        execute_commands: commands.iter().map(|cmd| cmd.to_string()).collect(),
+        notes: String::new(),
+        timeouts: Default::default(),
+    }
+}
+
+fn project(root: &Path) -> PathBuf {
+    fs::create_dir_all(root.join("old")).unwrap();
+    fs::write(root.join("keep.txt"), "keep\n").unwrap();
+    fs::write(root.join("edit.txt"), "before\n").unwrap();
+    fs::write(root.join("gone.txt"), "gone\n").unwrap();
+    fs::write(root.join("old/a.txt"), "a\n").unwrap();
+    root.to_path_buf()
+}
+
+/// Preview `commands` in `dir`, or `None` where this machine cannot create
+/// unprivileged mount namespaces.
+async fn try_preview(dir: &Path, commands: &[&str]) -> Option<PreviewReport> {
+    match preview(
+        Executor::new(ShellDialect::Sh),
+        dir,
+        &plan(commands),
+        &CancelToken::new(),
+    )
+    .await
+    {
+        Ok(report) => Some(report),
+        Err(err) if format!("{err:#}").contains("Could not isolate") => {
+            eprintln!("skipping preview test: {err:#}");
+            None
+        }
+        Err(err) => panic!("preview failed: {err:#}"),
+    }
+}
+
+const CHANGES: &[&str] = &[
+    "echo new > added.txt",
+    "echo after >> edit.txt",
+    "rm gone.txt old/a.txt && rmdir old",
+    "mkdir -p made && touch made/b.txt",
+];
+
+fn change(path: &str, kind: ChangeKind, dir: bool) -> FileChange {
+    FileChange {
+        path: PathBuf::from(path),
+        kind,
+        dir,
+    }
+}
+
+fn assert_untouched(dir: &Path) {
+    assert_eq!(
+        fs::read_to_string(dir.join("edit.txt")).unwrap(),
+        "before\n"
+    );
+    assert!(dir.join("gone.txt").exists());
+    assert!(dir.join("old/a.txt").exists());
+    assert!(!dir.join("added.txt").exists());
+    assert!(!dir.join("made").exists());
+}
+
+fn expected_changes() -> Vec<FileChange> {
+    vec![
+        change("added.txt", ChangeKind::Added, false),
+        change("edit.txt", ChangeKind::Modified, false),
+        change("gone.txt", ChangeKind::Deleted, false),
+        change("made", ChangeKind::Added, true),
+        change("made/b.txt", ChangeKind::Added, false),
+        change("old", ChangeKind::Deleted, true),
+        change("old/a.txt", ChangeKind::Deleted, false),
+    ]
+}
+
+#[tokio::test]
+async fn preview_lists_changes_without_touching_the_directory() {
+    let temp = TempDir::new().unwrap();
+    let dir = project(temp.path());
+    let Some(report) = try_preview(&dir, CHANGES).await else {
+        return;
+    };
+
+    assert!(report.execution.success, "{:?}", report.execution.notes);
+    assert_eq!(report.changes, expected_changes());
+    assert_eq!(report.execution.steps.len(), CHANGES.len());
+    assert_untouched(&dir);
+}
+
+#[tokio::test]
+async fn preview_falls_back_to_a_copy_when_overlay_cannot_be_used() {
+    let temp = TempDir::new().unwrap();
+    // overlayfs cannot take a lower directory with a comma in its path.
+    let dir = project(&temp.path().join("with,comma"));
+    let Some(report) = try_preview(&dir, CHANGES).await else {
+        return;
+    };
+
+    assert_eq!(report.mode, PreviewMode::Copy);
+    assert!(report.notes[0].contains("overlayfs unavailable"));
+    assert_eq!(report.changes, expected_changes());
+    assert_untouched(&dir);
+}
+
+#[tokio::test]
+async fn preview_keeps_the_rest_of_the_filesystem_read_only() {
+    let temp = TempDir::new().unwrap();
+    let dir = project(&temp.path().join("project"));
+    let outside = temp.path().join("outside.txt");
+    let write = format!("echo leaked > {}", outside.display());
+    let Some(report) = try_preview(&dir, &[&write]).await else {
+        return;
+    };
+
+    assert!(!report.execution.success);
+    assert!(report.changes.is_empty());
+    assert!(!outside.exists());
+}
+
+#[tokio::test]
+async fn preview_cannot_reach_the_network_or_processes_outside_it() {
+    let temp = TempDir::new().unwrap();
+    let dir = project(&temp.path().join("project"));
+    let signal = format!(
+        "kill -0 {} 2>/dev/null && echo reached || echo isolated",
+        std::process::id()
+    );
+    let commands = [
+        "echo $$",
+        signal.as_str(),
+        // Only the loopback interface exists, and it is down.
+        "grep -c : /proc/net/dev",
+    ];
+    let Some(report) = try_preview(&dir, &commands).await else {
+        return;
+    };
+
+    let stdout = report
+        .execution
+        .steps
+        .iter()
+        .map(|step| step.stdout.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(stdout, ["2\n", "isolated\n", "1\n"]);
+
+    let error = preview(
+        Executor::new(ShellDialect::Sh),
+        &dir,
+        &plan(&["sudo -n docker rm -f web"]),
+        &CancelToken::new(),
+    )
+    .await
+    .unwrap_err();
+    assert!(format!("{error:#}").contains("(docker)"));
+}
+
+/// Answers step prompts from a script and records the commands it was shown.
+#[derive(Default)]
+struct ScriptedGate {
+    answers: Mutex<VecDeque<StepChoice>>,
+    shown: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl StepGate for ScriptedGate {
+    async fn decide(&self, step: PendingStep<'_>) -> Result<StepChoice> {
+        self.shown.lock().unwrap().push(step.command.to_string());
+        Ok(self
+            .answers
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(StepChoice::Run))
+    }
+}
+
+#[tokio::test]
+async fn step_gate_decides_each_command_and_is_recorded_in_the_report() {
+    let gate = Arc::new(ScriptedGate::default());
+    gate.answers.lock().unwrap().extend([
+        StepChoice::Run,
+        StepChoice::Skip,
+        StepChoice::Edit("echo 2".to_string()),
+        StepChoice::Abort,
+    ]);
+    let executor = Executor::new(ShellDialect::Sh)
+        .with_echo(false)
+        .with_step_gate(Some(gate.clone()));
+    let cancel = CancelToken::new();
+
+    let report = executor
+        .run(
+            &plan(&["echo one", "echo two", "echo three", "echo four"]),
+            &cancel,
+            &mut |_| {},
+        )
+        .await;
+
+    assert_eq!(
+        *gate.shown.lock().unwrap(),
+        ["echo checking", "echo one", "echo two", "echo three"]
+    );
+    let decisions = report
+        .steps
+        .iter()
+        .map(|step| (step.command.as_str(), step.status, step.decision))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        decisions,
+        [
+            (
+                "echo checking",
+                StepStatus::Exited { code: 0 },
+                Some(StepDecision::Run)
+            ),
+            ("echo one", StepStatus::Skipped, Some(StepDecision::Skipped)),
+            (
+                "echo 2",
+                StepStatus::Exited { code: 0 },
+                Some(StepDecision::Edited)
+            ),
+            (
+                "echo three",
+                StepStatus::Cancelled,
+                Some(StepDecision::Aborted)
+            ),
+        ]
+    );
+    assert_eq!(report.steps[2].stdout, "2\n");
+    assert_eq!(
+        report.steps[2].original_command.as_deref(),
+        Some("echo two")
+    );
+    assert!(!report.success);
+    assert_eq!(report.notes, ["Aborted before execute step 3: echo three"]);
+    assert!(cancel.is_cancelled());
+}
+
+#[tokio::test]
+async fn persistent_shell_keeps_state_between_steps_and_splits_their_output() {
+    let dir = TempDir::new().unwrap();
+    let sub = dir.path().canonicalize().unwrap().join("sub");
+    fs::create_dir(&sub).unwrap();
+    let cd = format!("cd '{}'", sub.display());
+    let executor = Executor::new(ShellDialect::Sh)
+        .with_echo(false)
+        .with_persistent_shell(true);
+
+    let report = executor
+        .run(
+            &plan(&[
+                &cd,
+                "export GREETING=hi",
+                "printf %s \"$GREETING\"; echo warn >&2",
+                "pwd",
+                "exit 0",
+                "echo \"[$GREETING]\"",
+                "if",
+            ]),
+            &CancelToken::new(),
+            &mut |_| {},
+        )
+        .await;
+
+    let outputs = report
+        .steps
+        .iter()
+        .map(|step| (step.status, step.stdout.as_str(), step.stderr.as_str()))
+        .collect::<Vec<_>>();
+    let ok = StepStatus::Exited { code: 0 };
+    assert_eq!(
+        outputs[..7],
+        [
+            (ok, "checking\n", ""),
+            (ok, "", ""),
+            (ok, "", ""),
+            (ok, "hi\n", "warn\n"),
+            (ok, &format!("{}\n", sub.display()), ""),
+            (ok, "", ""),
+            // `exit` ended the session, so this step runs in a new shell.
+            (ok, "[]\n", ""),
+        ]
+    );
+    // A syntax error fails its own step without taking the session down with it.
+    assert_eq!(report.steps[7].status, StepStatus::Exited { code: 2 });
+    assert!(!report.steps[7].stderr.is_empty());
+    assert!(!report.success);
+}
+
+#[tokio::test]
+async fn steps_keep_their_streams_apart_and_a_failing_step_stops_the_plan() {
+    let executor = Executor::new(ShellDialect::Sh).with_echo(false);
+
+    let report = executor
+        .run(
+            &plan(&[
+                "echo out; echo err >&2",
+                "echo partial; exit 3",
+                "echo never",
+            ]),
+            &CancelToken::new(),
+            &mut |_| {},
+        )
+        .await;
+
+    let steps = report
+        .steps
+        .iter()
+        .map(|step| {
+            (
+                step.command.as_str(),
+                step.status,
+                step.stdout.as_str(),
+                step.stderr.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        steps,
+        [
+            (
+                "echo checking",
+                StepStatus::Exited { code: 0 },
+                "checking\n",
+                ""
+            ),
+            (
+                "echo out; echo err >&2",
+                StepStatus::Exited { code: 0 },
+                "out\n",
+                "err\n"
+            ),
+            (
+                "echo partial; exit 3",
+                StepStatus::Exited { code: 3 },
+                "partial\n",
+                ""
+            ),
+        ]
+    );
+    assert!(!report.success);
+}
+
+#[tokio::test]
+async fn steps_past_their_time_limit_are_stopped_and_recorded() {
+    let mut timed = plan(&["sleep 30", "echo never"]);
+    timed.timeouts.insert("sleep 30".to_string(), 1);
+    let executor = Executor::new(ShellDialect::Sh)
+        .with_echo(false)
+        .with_step_timeout(Some(Duration::from_secs(60)));
+
+    let report = executor.run(&timed, &CancelToken::new(), &mut |_| {}).await;
+
+    assert_eq!(report.steps.len(), 2);
+    let step = &report.steps[1];
+    assert_eq!(step.command, "sleep 30");
+    assert_eq!(step.status, StepStatus::TimedOut { limit_secs: 1 });
+    assert!(step.duration_ms >= 1000 && step.duration_ms < 10_000);
+    assert!(!report.success);
+}
+
+#[tokio::test]
+async fn edited_steps_keep_the_time_limit_of_the_planned_step() {
+    let mut timed = plan(&["sleep 30"]);
+    timed.timeouts.insert("sleep 30".to_string(), 1);
+    let gate = Arc::new(ScriptedGate::default());
+    gate.answers
+        .lock()
+        .unwrap()
+        .extend([StepChoice::Run, StepChoice::Edit("sleep 31".to_string())]);
+    let executor = Executor::new(ShellDialect::Sh)
+        .with_echo(false)
+        .with_step_timeout(Some(Duration::from_secs(60)))
+        .with_step_gate(Some(gate));
+
+    let report = executor.run(&timed, &CancelToken::new(), &mut |_| {}).await;
+
+    let step = &report.steps[1];
+    assert_eq!(step.command, "sleep 31");
+    assert_eq!(step.status, StepStatus::TimedOut { limit_secs: 1 });
+}
+
+#[test]
+fn ctrl_c_on_a_lent_terminal_is_recognised_by_signal_or_exit_code() {
+    assert!(interrupted_on_terminal(StepStatus::Signaled {
+        signal: libc::SIGINT
+    }));
+    // bash scripts, git and most CLIs trap SIGINT and exit with 130.
+    assert!(interrupted_on_terminal(StepStatus::Exited { code: 130 }));
+    assert!(!interrupted_on_terminal(StepStatus::Exited { code: 1 }));
+    assert!(!interrupted_on_terminal(StepStatus::Signaled {
+        signal: libc::SIGTERM
+    }));
+}
+
+#[test]
+fn output_capture_keeps_the_last_bytes_of_long_output() {
+    let mut capture = OutputCapture::default();
+    for line in 0..100_000 {
+        capture.push_line(&format!("line {line:06} é"));
+    }
+    let (text, truncated) = capture.finish();
+    assert!(truncated);
+    assert!(text.len() <= OUTPUT_CAPTURE_LIMIT);
+    assert!(text.len() > OUTPUT_CAPTURE_LIMIT - 4);
+    assert!(text.ends_with("line 099999 é\n"));
+
+    let mut capture = OutputCapture::default();
+    capture.push_line("short");
+    assert_eq!(capture.finish(), ("short\n".to_string(), false));
+}
+
+#[test]
+fn transcript_keeps_plain_text_and_the_last_state_of_rewritten_lines() {
+    let mut transcript = Transcript::default();
+    let mut capture = OutputCapture::default();
+    let output: &[&[u8]] = &[
+        b"\x1b[1;32mcompiled\x1b[0m 3 crates\r\n",
+        b"downloading  10%\r",
+        b"downloading 100%\r\n\x1b]0;window title\x07",
+        b"Password: \x1b[?25l\x1b(Bdone\x08\x08\x08\x08ok",
+    ];
+    for chunk in output {
+        transcript.feed(chunk, &mut capture);
+    }
+    transcript.finish(&mut capture);
+
+    assert_eq!(
+        capture.finish().0,
+        "compiled 3 crates\ndownloading 100%\nPassword: ok\n"
+    );
+}
+
+#[tokio::test]
+async fn pty_gives_commands_a_terminal_and_records_plain_output() {
+    if let Err(err) = Pty::open() {
+        eprintln!("skipping pty test: {err}");
+        return;
+    }
+    let executor = Executor::new(ShellDialect::Sh)
+        .with_echo(false)
+        .with_pty(true);
+
+    let report = executor
+        .run(
+            &plan(&[
+                "test -t 0 && test -t 1 && printf '\\033[32mready\\033[0m\\n'; echo warn >&2",
+                "stty size",
+            ]),
+            &CancelToken::new(),
+            &mut |_| {},
+        )
+        .await;
+
+    assert!(report.success, "{report:?}");
+    let step = &report.steps[1];
+    assert_eq!(step.stdout, "ready\nwarn\n");
+    assert!(step.stderr.is_empty());
+    assert!(report.steps[2].stdout.ends_with('\n'));
+}
@@ -0,0 +1,13 @@
+//! Local history of approved plans used as few-shot planner examples.
+//!
+//! Plans that the user approved and that executed successfully are appended to
+//! `~/.li/plans.jsonl`. When planning a new task, the most lexically similar
+//! past tasks are retrieved and shown to the planner as examples.
+
+mod scoring;
+mod store;
+
+pub use store::PlanHistory;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,58 @@
+use std::collections::{HashMap, HashSet};
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "into", "is", "it", "me",
+    "my", "of", "on", "or", "please", "that", "the", "this", "to", "with",
+];
+
+/// Split text into lowercase alphanumeric terms, dropping stop words.
+pub(super) fn tokenize(text: &str) -> Vec<String> {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .filter(|token| !STOP_WORDS.contains(&token.as_str()))
+        .collect()
+}
+
+/// Score each document against the query using Okapi BM25.
+pub(super) fn bm25_scores(query: &str, documents: &[&str]) -> Vec<f64> {
+    let query_terms: HashSet<String> = tokenize(query).into_iter().collect();
+    if query_terms.is_empty() || documents.is_empty() {
+        return vec![0.0; documents.len()];
+    }
+
+    let tokenized: Vec<Vec<String>> = documents.iter().map(|doc| tokenize(doc)).collect();
+    let doc_count = tokenized.len() as f64;
+    let avg_len = tokenized.iter().map(Vec::len).sum::<usize>() as f64 / doc_count;
+
+    let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+    for terms in &tokenized {
+        let unique: HashSet<&str> = terms.iter().map(String::as_str).collect();
+        for term in unique {
+            *doc_freq.entry(term).or_default() += 1;
+        }
+    }
+
+    tokenized
+        .iter()
+        .map(|terms| {
+            let len = terms.len() as f64;
+            query_terms
+                .iter()
+                .map(|term| {
+                    let tf = terms.iter().filter(|t| *t == term).count() as f64;
+                    if tf == 0.0 {
+                        return 0.0;
+                    }
+                    let df = doc_freq.get(term.as_str()).copied().unwrap_or(0) as f64;
+                    let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                    let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len.max(1.0));
+                    idf * tf * (BM25_K1 + 1.0) / (tf + norm)
+                })
+                .sum()
+        })
+        .collect()
+}
@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::planner::{Plan, PlanExample};
+
+use super::scoring::bm25_scores;
+
+/// Upper bound on stored plans; the oldest entries are dropped beyond this.
+const MAX_RECORDS: usize = 500;
+
+/// A single approved and successfully executed plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanRecord {
+    pub task: String,
+    pub plan: Plan,
+    pub recorded_at: u64,
+}
+
+/// JSON-lines store of approved plans.
+#[derive(Debug, Clone)]
+pub struct PlanHistory {
+    path: PathBuf,
+}
+
+impl PlanHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn default_path() -> Result<PathBuf> {
+        let mut path = home_dir().context("Could not determine home directory")?;
+        path.push(".li/plans.jsonl");
+        Ok(path)
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(Self::default_path()?))
+    }
+
+    /// Load every readable record, skipping lines that fail to parse.
+    pub fn load(&self) -> Result<Vec<PlanRecord>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let contents = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed reading plan history at {}", self.path.display()))?;
+
+        Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Append an approved plan, replacing any earlier record for the same task and commands.
+    pub fn record(&self, task: &str, plan: &Plan) -> Result<()> {
+        let task = task.trim();
+        if task.is_empty() || plan.execute_commands.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Unable to create history directory {}", parent.display())
+            })?;
+        }
+
+        let record = PlanRecord {
+            task: task.to_string(),
+            plan: plan.clone(),
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+        };
+
+        let mut records = self.load()?;
+        let before = records.len();
+        records.retain(|existing| !is_same_plan(existing, &record));
+
+        if records.len() == before && records.len() < MAX_RECORDS {
+            let line = serde_json::to_string(&record).context("Failed to serialize plan record")?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .with_context(|| format!("Failed to open {}", self.path.display()))?;
+            writeln!(file, "{line}")
+                .with_context(|| format!("Failed to write {}", self.path.display()))?;
+            return Ok(());
+        }
+
+        records.push(record);
+        let skip = records.len().saturating_sub(MAX_RECORDS);
+        self.rewrite(&records[skip..])
+    }
+
+    /// Return up to `limit` past plans whose tasks best match `task`, most similar first.
+    pub fn similar(&self, task: &str, limit: usize) -> Result<Vec<PlanExample>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let records = self.load()?;
+        let documents: Vec<&str> = records.iter().map(|record| record.task.as_str()).collect();
+        let scores = bm25_scores(task, &documents);
+
+        let mut ranked: Vec<(f64, &PlanRecord)> = scores
+            .into_iter()
+            .zip(records.iter())
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.0.total_cmp(&a.0)
+                .then_with(|| b.1.recorded_at.cmp(&a.1.recorded_at))
+        });
+
+        Ok(ranked
+            .into_iter()
+            .take(limit)
+            .map(|(_, record)| PlanExample {
+                task: record.task.clone(),
+                plan: record.plan.clone(),
+            })
+            .collect())
+    }
+
+    fn rewrite(&self, records: &[PlanRecord]) -> Result<()> {
+        let mut payload = String::new();
+        for record in records {
+            payload.push_str(
+                &serde_json::to_string(record).context("Failed to serialize plan record")?,
+            );
+            payload.push('\n');
+        }
+        fs::write(&self.path, payload)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+fn is_same_plan(a: &PlanRecord, b: &PlanRecord) -> bool {
+    a.task.eq_ignore_ascii_case(&b.task)
+        && a.plan.dry_run_commands == b.plan.dry_run_commands
+        && a.plan.execute_commands == b.plan.execute_commands
+}
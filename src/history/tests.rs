@@ -0,0 +1,116 @@
+use tempfile::TempDir;
+
+use crate::planner::Plan;
+
+use super::PlanHistory;
+use super::scoring::{bm25_scores, tokenize};
+
+fn sample_plan(command: &str) -> Plan {
+    Plan {
+        confidence: 0.9,
+        dry_run_commands: vec![],
+        execute_commands: vec![command.to_string()],
+        notes: String::new(),
+        timeouts: Default::default(),
+    }
+}
+
+#[test]
+fn tokenize_lowercases_and_drops_stop_words() {
+    assert_eq!(
+        tokenize("Find the LARGEST files in ~/Downloads"),
+        vec!["find", "largest", "files", "downloads"]
+    );
+}
+
+#[test]
+fn bm25_prefers_documents_sharing_rare_terms() {
+    let documents = [
+        "list docker containers",
+        "stop the process listening on port 8080",
+        "list files by size",
+    ];
+
+    let scores = bm25_scores("what is listening on port 3000", &documents);
+
+    assert!(scores[1] > scores[0]);
+    assert!(scores[1] > scores[2]);
+    assert_eq!(scores[0], 0.0);
+}
+
+#[test]
+fn record_and_retrieve_similar_plans() {
+    let dir = TempDir::new().unwrap();
+    let history = PlanHistory::new(dir.path().join("plans.jsonl"));
+
+    history
+        .record("list running docker containers", &sample_plan("docker ps"))
+        .unwrap();
+    history
+        .record("show disk usage of home", &sample_plan("du -sh ~"))
+        .unwrap();
+    history
+        .record(
+            "remove stopped docker containers",
+            &sample_plan("docker container prune"),
+        )
+        .unwrap();
+
+    let examples = history.similar("list docker containers", 2).unwrap();
+
+    assert_eq!(examples.len(), 2);
+    assert_eq!(examples[0].task, "list running docker containers");
+    assert_eq!(examples[1].task, "remove stopped docker containers");
+}
+
+#[test]
+fn similar_returns_nothing_without_overlap() {
+    let dir = TempDir::new().unwrap();
+    let history = PlanHistory::new(dir.path().join("plans.jsonl"));
+    history
+        .record("show disk usage", &sample_plan("df -h"))
+        .unwrap();
+
+    assert!(history.similar("restart nginx", 3).unwrap().is_empty());
+    assert!(history.similar("show disk usage", 0).unwrap().is_empty());
+}
+
+#[test]
+fn record_replaces_duplicate_plans() {
+    let dir = TempDir::new().unwrap();
+    let history = PlanHistory::new(dir.path().join("plans.jsonl"));
+
+    history
+        .record("show disk usage", &sample_plan("df -h"))
+        .unwrap();
+    history
+        .record("Show disk usage", &sample_plan("df -h"))
+        .unwrap();
+    history
+        .record("show disk usage", &sample_plan("du -sh ."))
+        .unwrap();
+
+    let records = history.load().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].task, "Show disk usage");
+    assert_eq!(
+        records[1].plan.execute_commands,
+        vec!["du -sh .".to_string()]
+    );
+}
+
+#[test]
+fn load_skips_corrupt_lines() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("plans.jsonl");
+    let history = PlanHistory::new(&path);
+    history
+        .record("show disk usage", &sample_plan("df -h"))
+        .unwrap();
+
+    let mut contents = std::fs::read_to_string(&path).unwrap();
+    contents.push_str("{not json}\n");
+    std::fs::write(&path, contents).unwrap();
+
+    assert_eq!(history.load().unwrap().len(), 1);
+}
@@ -1,11 +1,15 @@
 mod agent;
+mod cancel;
 mod cli;
 mod client;
 mod config;
 mod exec;
+mod history;
 mod planner;
 mod recovery;
+mod reviewer;
 mod tokens;
+mod undo;
 mod validator;
 
 use anyhow::Result;
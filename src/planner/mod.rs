@@ -1,30 +1,60 @@
 mod parsing;
 mod prompt;
+mod rules;
+mod script;
 mod session;
 mod transport;
 mod types;
 
-pub use types::Plan;
+pub use rules::PlannerRules;
+pub use script::{SCRIPT_SHELL, ScriptFormat, ScriptHeader, render_script, shell_note};
+pub use types::{ClarificationNeeded, Plan, PlanExample, PlannerGuidance};
+
+pub(crate) use parsing::extract_json_object;
+pub(crate) use prompt::planner_system_prompt;
 
 use crate::client::DynLlmClient;
 use anyhow::Result;
-use session::{default_question_resolver, interactive_plan_with_resolver};
+use session::{
+    default_question_resolver, interactive_plan_with_resolver, unanswered_question_resolver,
+};
 
 pub async fn plan(
     client: &DynLlmClient,
     request: &str,
     model: &str,
     max_tokens: u32,
+    guidance: &PlannerGuidance,
 ) -> Result<Plan> {
     interactive_plan_with_resolver(
         client,
         request,
         model,
         max_tokens,
+        guidance,
         &default_question_resolver,
     )
     .await
 }
 
+/// Plan without prompting; a planner question fails with [`ClarificationNeeded`].
+pub async fn plan_unattended(
+    client: &DynLlmClient,
+    request: &str,
+    model: &str,
+    max_tokens: u32,
+    guidance: &PlannerGuidance,
+) -> Result<Plan> {
+    interactive_plan_with_resolver(
+        client,
+        request,
+        model,
+        max_tokens,
+        guidance,
+        &unanswered_question_resolver,
+    )
+    .await
+}
+
 #[cfg(test)]
 mod tests;
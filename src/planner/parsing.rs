@@ -1,16 +1,12 @@
 pub(crate) fn extract_json_object(input: &str) -> Option<String> {
     let mut cleaned = input.to_string();
 
-    loop {
-        if let Some(think_start) = cleaned.find("<think>") {
-            if let Some(think_end_pos) = cleaned[think_start..].find("</think>") {
-                let absolute_end = think_start + think_end_pos + "</think>".len();
-                cleaned.replace_range(think_start..absolute_end, "");
-            } else {
-                cleaned.replace_range(think_start.., "");
-                break;
-            }
+    while let Some(think_start) = cleaned.find("<think>") {
+        if let Some(think_end_pos) = cleaned[think_start..].find("</think>") {
+            let absolute_end = think_start + think_end_pos + "</think>".len();
+            cleaned.replace_range(think_start..absolute_end, "");
         } else {
+            cleaned.replace_range(think_start.., "");
             break;
         }
     }
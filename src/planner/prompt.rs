@@ -1,3 +1,7 @@
+use crate::config::ShellDialect;
+
+use super::types::PlannerGuidance;
+
 pub(crate) const PLANNER_SYSTEM_PROMPT: &str = r#"You are a STRICT JSON planner that converts a natural-language goal into a safe, minimal shell plan.
 
 OBJECTIVE
@@ -25,7 +29,8 @@ For a complete plan:
   "confidence": <number between 0 and 1 inclusive>,
   "dry_run_commands": [<string>, ...],
   "execute_commands": [<string>, ...],
-  "notes": "<string>"
+  "notes": "<string>",
+  "timeouts": {"<command>": <seconds>, ...}
 }
 
 For a clarifying question:
@@ -40,6 +45,7 @@ ADDITIONAL CONSTRAINTS
 - `confidence` MUST be a number (not a string).
 - `dry_run_commands` and `execute_commands` MUST be arrays of strings (can be empty).
 - `notes` MUST be a string (use "" if nothing to add).
+- `timeouts` is optional. Add an entry, keyed by the exact command string, only for a command expected to run longer than 10 minutes (large builds, downloads, full-disk searches); the value is a whole number of seconds. Every other command is stopped after 10 minutes.
 - No additional keys are allowed. No nulls. No trailing commas.
 
 NEGATIVE EXAMPLES (DO NOT DO)
@@ -61,3 +67,77 @@ ALLOWED OUTPUT SHAPES (the only two shapes):
 {"type":"plan","confidence":0.0,"dry_run_commands":[],"execute_commands":[],"notes":""}
 {"type":"question","text":"What server should I use?","context":"Creating a remote git repository"}
 "#;
+
+/// Build the planner system prompt for the target shell, appending any user and project rules.
+pub(crate) fn planner_system_prompt(guidance: &PlannerGuidance) -> String {
+    let mut prompt = PLANNER_SYSTEM_PROMPT.to_string();
+    let shell = guidance.shell;
+    if guidance.persistent_shell {
+        prompt.push_str(&format!(
+            "\nTARGET SHELL\n\
+             - Commands run one after another in a single `{}` session, so they MUST be valid {} syntax.\n\
+             - `cd`, exported variables and sourced environments carry over to later commands in the same list, so put them in their own step instead of repeating them with `&&`.\n\
+             - Do not rely on state set by `dry_run_commands` in `execute_commands`; the dry-run phase may be skipped.\n\
+             - {}\n",
+            shell.program(),
+            shell.display_name(),
+            shell_guidance(shell)
+        ));
+    } else {
+        prompt.push_str(&format!(
+            "\nTARGET SHELL\n\
+             - Every command runs as `{} -c <command>`, so it MUST be valid {} syntax.\n\
+             - {}\n",
+            shell.program(),
+            shell.display_name(),
+            shell_guidance(shell)
+        ));
+    }
+
+    let rules = &guidance.rules;
+    if rules.is_empty() {
+        return prompt;
+    }
+
+    prompt.push_str(
+        "\nCUSTOM RULES\n\
+         - The rules below come from the user's and project's rules files. Follow them when choosing commands.\n\
+         - They never override the SAFETY & PORTABILITY RULES or the OUTPUT FORMAT above.\n\
+         - When a project rule conflicts with a user rule, the project rule wins.\n",
+    );
+
+    if let Some(user) = &rules.user {
+        prompt.push_str(&format!(
+            "\nUSER RULES ({})\n{}\n",
+            user.path.display(),
+            user.contents
+        ));
+    }
+
+    if let Some(project) = &rules.project {
+        prompt.push_str(&format!(
+            "\nPROJECT RULES ({})\n{}\n",
+            project.path.display(),
+            project.contents
+        ));
+    }
+
+    prompt
+}
+
+fn shell_guidance(shell: ShellDialect) -> &'static str {
+    match shell {
+        ShellDialect::Sh => {
+            "Use only POSIX sh features: no `[[ ]]`, arrays, here-strings (`<<<`), `function` keyword, `source`, or `&>` redirects."
+        }
+        ShellDialect::Bash => {
+            "Bash features such as `[[ ]]`, arrays, brace expansion and `set -o pipefail` are available; avoid POSIX-only workarounds."
+        }
+        ShellDialect::Zsh => {
+            "Zsh features are available; quote glob patterns passed to tools like find or grep, because unmatched globs are errors in zsh."
+        }
+        ShellDialect::Fish => {
+            "Use fish syntax: `set VAR value` (`set -x` to export) instead of `VAR=value`, `(cmd)` instead of `$(cmd)` or backticks, `math` instead of `$(( ))`, `test` instead of `[[ ]]`, and `if ...; ...; end` / `for ...; ...; end` blocks. Heredocs are not supported. This overrides the POSIX preference in rule 4."
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const RULES_DIR: &str = ".li";
+const RULES_FILE: &str = "rules.md";
+
+/// A rules file discovered on disk together with its trimmed contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RulesFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// User and project planning rules appended to the planner system prompt.
+///
+/// Project rules take precedence over user rules; neither may override the
+/// built-in safety and output format rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlannerRules {
+    pub user: Option<RulesFile>,
+    pub project: Option<RulesFile>,
+}
+
+impl PlannerRules {
+    /// Load `~/.li/rules.md` and the nearest `.li/rules.md` above the current directory.
+    pub fn load() -> Result<Self> {
+        let home = dirs::home_dir();
+        let cwd = std::env::current_dir().context("Could not determine current directory")?;
+        Self::load_from(home.as_deref(), &cwd)
+    }
+
+    pub fn load_from(home: Option<&Path>, cwd: &Path) -> Result<Self> {
+        let user_path = home.map(rules_path);
+
+        let user = match &user_path {
+            Some(path) => read_rules_file(path)?,
+            None => None,
+        };
+
+        let mut project = None;
+        for dir in cwd.ancestors() {
+            let candidate = rules_path(dir);
+            if Some(&candidate) == user_path.as_ref() {
+                continue;
+            }
+            if let Some(file) = read_rules_file(&candidate)? {
+                project = Some(file);
+                break;
+            }
+        }
+
+        Ok(Self { user, project })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.user.is_none() && self.project.is_none()
+    }
+}
+
+fn rules_path(dir: &Path) -> PathBuf {
+    dir.join(RULES_DIR).join(RULES_FILE)
+}
+
+fn read_rules_file(path: &Path) -> Result<Option<RulesFile>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed reading rules at {}", path.display()))?;
+    let contents = contents.trim();
+    if contents.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(RulesFile {
+        path: path.to_path_buf(),
+        contents: contents.to_string(),
+    }))
+}
@@ -0,0 +1,214 @@
+//! Render a [`Plan`] as a file to commit and review instead of running it.
+//!
+//! Dry-run commands become preconditions that stop the script when they fail,
+//! and every execute command becomes one step.
+
+use crate::config::ShellDialect;
+
+use super::Plan;
+
+/// The kind of file [`render_script`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptFormat {
+    /// A bash script with `set -euo pipefail`.
+    Bash,
+    /// A Makefile with one target per step.
+    Make,
+    /// A GitHub Actions style list of steps.
+    Yaml,
+}
+
+/// Every format runs its commands with bash, so plans are made for bash.
+pub const SCRIPT_SHELL: ShellDialect = ShellDialect::Bash;
+
+/// A note for the user when `configured` is not the shell the file runs with.
+pub fn shell_note(configured: ShellDialect) -> Option<String> {
+    (configured != SCRIPT_SHELL).then(|| {
+        format!(
+            "Note: the emitted file runs its commands with {SCRIPT_SHELL}, so the plan is made for {SCRIPT_SHELL} instead of the configured {configured}."
+        )
+    })
+}
+
+/// What the comment at the top of the file records about the plan.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptHeader {
+    pub task: String,
+    pub model: String,
+    /// Further lines such as reviewer findings.
+    pub remarks: Vec<String>,
+}
+
+/// Write `plan` as a standalone file in `format`.
+pub fn render_script(plan: &Plan, format: ScriptFormat, header: &ScriptHeader) -> String {
+    let mut out = String::new();
+    match format {
+        ScriptFormat::Bash => {
+            out.push_str("#!/usr/bin/env bash\n");
+            push_header(&mut out, plan, header);
+            render_bash(&mut out, plan);
+        }
+        ScriptFormat::Make => {
+            push_header(&mut out, plan, header);
+            render_make(&mut out, plan);
+        }
+        ScriptFormat::Yaml => {
+            push_header(&mut out, plan, header);
+            render_yaml(&mut out, plan);
+        }
+    }
+    out
+}
+
+fn push_header(out: &mut String, plan: &Plan, header: &ScriptHeader) {
+    out.push_str("# Generated by li for the task:\n");
+    push_comment(out, "  ", &header.task);
+    push_comment(out, "", &format!("Model: {}", header.model));
+    if !plan.notes.trim().is_empty() {
+        push_comment(out, "", &format!("Notes: {}", plan.notes.trim()));
+    }
+    for remark in &header.remarks {
+        push_comment(out, "", remark);
+    }
+    out.push('\n');
+}
+
+fn push_comment(out: &mut String, indent: &str, text: &str) {
+    for line in text.trim().lines() {
+        out.push_str(format!("# {indent}{line}").trim_end());
+        out.push('\n');
+    }
+}
+
+fn render_bash(out: &mut String, plan: &Plan) {
+    out.push_str("set -euo pipefail\n");
+
+    // Inside an `if` condition bash ignores `set -e`, even in a subshell, so
+    // each check runs in its own shell where a failing line still stops it.
+    for check in &plan.dry_run_commands {
+        let failed = quote(&format!("Precondition failed: {}", first_line(check)));
+        out.push_str(&format!(
+            "\nif ! bash -euo pipefail -c {}; then\n  echo {failed} >&2\n  exit 1\nfi\n",
+            quote(check.trim())
+        ));
+    }
+
+    let total = plan.execute_commands.len();
+    for (index, command) in plan.execute_commands.iter().enumerate() {
+        let banner = quote(&step_banner(index, total, command));
+        out.push_str(&format!("\necho {banner}\n{}\n", command.trim()));
+    }
+}
+
+fn render_make(out: &mut String, plan: &Plan) {
+    let checks = (1..=plan.dry_run_commands.len())
+        .map(|index| format!("check-{index}"))
+        .collect::<Vec<_>>();
+    let steps = (1..=plan.execute_commands.len())
+        .map(|index| format!("step-{index}"))
+        .collect::<Vec<_>>();
+
+    // Each recipe runs in one bash process, so multi-line commands keep working.
+    out.push_str("SHELL := /bin/bash\n.SHELLFLAGS := -euo pipefail -c\n.ONESHELL:\n\n");
+    let mut phony = vec!["all".to_string(), "check".to_string()];
+    phony.extend(checks.iter().cloned());
+    phony.extend(steps.iter().cloned());
+    out.push_str(&format!(".PHONY: {}\n\n", phony.join(" ")));
+    // Each step depends on the one before it, so the last step alone brings in
+    // every step in order, and `make step-N` runs the plan up to step N.
+    out.push_str(&format!(
+        "all: {}\n\n",
+        steps.last().map_or("check", String::as_str)
+    ));
+    out.push_str("check:");
+    for check in &checks {
+        out.push_str(&format!(" {check}"));
+    }
+    out.push('\n');
+
+    for (target, check) in checks.iter().zip(&plan.dry_run_commands) {
+        out.push_str(&format!("\n{target}:\n{}\n", recipe(check)));
+    }
+
+    let total = steps.len();
+    for (index, command) in plan.execute_commands.iter().enumerate() {
+        let prerequisite = match index {
+            0 => "check".to_string(),
+            _ => steps[index - 1].clone(),
+        };
+        let banner = quote(&step_banner(index, total, command)).replace('$', "$$");
+        out.push_str(&format!(
+            "\n{}: {prerequisite}\n\t@echo {banner}\n{}\n",
+            steps[index],
+            recipe(command)
+        ));
+    }
+}
+
+fn render_yaml(out: &mut String, plan: &Plan) {
+    if plan.dry_run_commands.is_empty() && plan.execute_commands.is_empty() {
+        out.push_str("steps: []\n");
+        return;
+    }
+    out.push_str("steps:\n");
+
+    let checks = plan
+        .dry_run_commands
+        .iter()
+        .map(|check| (format!("Check: {}", first_line(check)), check));
+    let steps = plan
+        .execute_commands
+        .iter()
+        .enumerate()
+        .map(|(index, command)| {
+            (
+                format!("Step {}: {}", index + 1, first_line(command)),
+                command,
+            )
+        });
+    for (name, command) in checks.chain(steps) {
+        // A JSON string is also a valid double-quoted YAML scalar.
+        let name = serde_json::to_string(&name).unwrap_or_default();
+        out.push_str(&format!("  - name: {name}\n    shell: bash\n"));
+        if let Some(secs) = plan.timeout_for(command) {
+            out.push_str(&format!(
+                "    timeout-minutes: {}\n",
+                secs.div_ceil(60).max(1)
+            ));
+        }
+        out.push_str(&format!("    run: |\n{}\n", indent(command, "      ")));
+    }
+}
+
+fn step_banner(index: usize, total: usize, command: &str) -> String {
+    format!("==> [{}/{total}] {}", index + 1, first_line(command))
+}
+
+fn first_line(command: &str) -> &str {
+    command.trim().lines().next().unwrap_or_default()
+}
+
+fn indent(command: &str, prefix: &str) -> String {
+    command
+        .trim()
+        .lines()
+        .map(|line| format!("{prefix}{line}").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `command` as recipe lines, with `$` escaped from make. Blank lines keep
+/// their tab so make passes them on instead of ignoring them.
+fn recipe(command: &str) -> String {
+    command
+        .trim()
+        .replace('$', "$$")
+        .lines()
+        .map(|line| format!("\t{line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
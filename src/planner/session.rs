@@ -4,7 +4,7 @@ use std::io::{self, Write};
 use crate::client::DynLlmClient;
 
 use super::transport::call_planner_with_context;
-use super::types::{Plan, PlannerResponse, QuestionResolver};
+use super::types::{ClarificationNeeded, Plan, PlannerGuidance, PlannerResponse, QuestionResolver};
 
 pub(crate) fn default_question_resolver(question: &str, context: &str) -> Result<String> {
     println!("\n🤔 Planner asks: {}", question);
@@ -19,11 +19,21 @@ pub(crate) fn default_question_resolver(question: &str, context: &str) -> Result
     Ok(answer.trim().to_string())
 }
 
+/// Resolver for unattended runs: surfaces the question to the caller instead of prompting.
+pub(crate) fn unanswered_question_resolver(question: &str, context: &str) -> Result<String> {
+    Err(ClarificationNeeded {
+        question: question.to_string(),
+        context: context.to_string(),
+    }
+    .into())
+}
+
 pub(crate) async fn interactive_plan_with_resolver(
     client: &DynLlmClient,
     initial_request: &str,
     model: &str,
     max_tokens: u32,
+    guidance: &PlannerGuidance,
     resolver: &QuestionResolver,
 ) -> Result<Plan> {
     let mut context = initial_request.to_string();
@@ -31,7 +41,8 @@ pub(crate) async fn interactive_plan_with_resolver(
 
     loop {
         let response =
-            call_planner_with_context(client, &context, &conversation, model, max_tokens).await?;
+            call_planner_with_context(client, &context, &conversation, model, max_tokens, guidance)
+                .await?;
 
         match response {
             PlannerResponse::Plan {
@@ -39,12 +50,14 @@ pub(crate) async fn interactive_plan_with_resolver(
                 dry_run_commands,
                 execute_commands,
                 notes,
+                timeouts,
             } => {
                 return Ok(Plan {
                     confidence,
                     dry_run_commands,
                     execute_commands,
                     notes,
+                    timeouts,
                 });
             }
             PlannerResponse::Question { text, context: ctx } => {
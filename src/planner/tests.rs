@@ -1,16 +1,26 @@
-use super::Plan;
 use super::plan;
-use super::prompt::PLANNER_SYSTEM_PROMPT;
+use super::prompt::{PLANNER_SYSTEM_PROMPT, planner_system_prompt};
+use super::rules::RulesFile;
 use super::session::interactive_plan_with_resolver;
 use super::types::QuestionResolver;
+use super::types::{PlanExample, PlannerGuidance};
+use super::{Plan, PlannerRules, ScriptFormat, ScriptHeader, render_script, shell_note};
+
+use std::fs;
+use std::process::Command;
 
 use anyhow::Result;
 use httpmock::prelude::*;
 use serde_json::json;
+use tempfile::TempDir;
 
 use crate::{
     client::{AIClient, ChatMessage, ChatMessageRole},
-    config::{Config, LlmProvider, LlmSettings, ModelSettings, RecoverySettings},
+    config::{
+        ApprovalSettings, ConfidenceSettings, Config, ExecutionSettings, HistorySettings,
+        HooksSettings, IterationSettings, LlmProvider, LlmSettings, MetricsSettings, ModelSettings,
+        PipelineSettings, RecoverySettings, ReviewSettings, ShellDialect, UndoSettings,
+    },
     tokens::compute_completion_token_budget,
 };
 
@@ -28,14 +38,25 @@ fn sample_config() -> Config {
             max_tokens: 512,
         },
         recovery: RecoverySettings::default(),
+        history: HistorySettings::default(),
+        execution: ExecutionSettings::default(),
+        review: ReviewSettings::default(),
+        confidence: ConfidenceSettings::default(),
+        approval: ApprovalSettings::default(),
+        hooks: HooksSettings::default(),
+        pipeline: PipelineSettings::default(),
+        metrics: MetricsSettings::default(),
+        undo: UndoSettings::default(),
+        iteration: IterationSettings::default(),
     }
 }
 
 fn expected_request_body(user_input: &str) -> serde_json::Value {
+    let system_prompt = planner_system_prompt(&PlannerGuidance::default());
     let messages = vec![
         ChatMessage {
             role: ChatMessageRole::System,
-            content: PLANNER_SYSTEM_PROMPT.to_string(),
+            content: system_prompt.clone(),
         },
         ChatMessage {
             role: ChatMessageRole::User,
@@ -49,7 +70,7 @@ fn expected_request_body(user_input: &str) -> serde_json::Value {
         "messages": [
             {
                 "role": "system",
-                "content": PLANNER_SYSTEM_PROMPT
+                "content": system_prompt
             },
             {
                 "role": "user",
@@ -68,7 +89,15 @@ async fn plan_with_resolver(
     max_tokens: u32,
     resolver: &QuestionResolver,
 ) -> Result<Plan> {
-    interactive_plan_with_resolver(client, request, model, max_tokens, resolver).await
+    interactive_plan_with_resolver(
+        client,
+        request,
+        model,
+        max_tokens,
+        &PlannerGuidance::default(),
+        resolver,
+    )
+    .await
 }
 
 #[tokio::test]
@@ -106,6 +135,7 @@ async fn plan_parses_valid_response() {
         "make a new git repo",
         &config.models.planner,
         config.models.max_tokens,
+        &PlannerGuidance::default(),
     )
     .await
     .unwrap();
@@ -213,6 +243,7 @@ async fn plan_errors_on_missing_fields() {
         "make a new git repo",
         &config.models.planner,
         config.models.max_tokens,
+        &PlannerGuidance::default(),
     )
     .await
     .unwrap_err();
@@ -220,3 +251,339 @@ async fn plan_errors_on_missing_fields() {
     assert!(err.to_string().contains("Failed to parse planner JSON"));
     _mock.assert_async().await;
 }
+
+#[test]
+fn system_prompt_without_rules_only_adds_target_shell() {
+    let prompt = planner_system_prompt(&PlannerGuidance::default());
+    assert!(prompt.starts_with(PLANNER_SYSTEM_PROMPT));
+    assert!(prompt.contains("TARGET SHELL"));
+    assert!(prompt.contains("`sh -c <command>`"));
+    assert!(!prompt.contains("CUSTOM RULES"));
+}
+
+#[test]
+fn system_prompt_targets_fish_syntax() {
+    let guidance = PlannerGuidance {
+        shell: ShellDialect::Fish,
+        ..PlannerGuidance::default()
+    };
+
+    let prompt = planner_system_prompt(&guidance);
+    assert!(prompt.contains("`fish -c <command>`"));
+    assert!(prompt.contains("valid fish syntax"));
+    assert!(prompt.contains("`set VAR value`"));
+}
+
+#[test]
+fn system_prompt_describes_a_persistent_shell_session() {
+    let guidance = PlannerGuidance {
+        shell: ShellDialect::Bash,
+        persistent_shell: true,
+        ..PlannerGuidance::default()
+    };
+
+    let prompt = planner_system_prompt(&guidance);
+    assert!(prompt.contains("a single `bash` session"));
+    assert!(prompt.contains("carry over to later commands"));
+    assert!(!prompt.contains("-c <command>"));
+}
+
+#[test]
+fn system_prompt_appends_user_then_project_rules() {
+    let rules = PlannerRules {
+        user: Some(RulesFile {
+            path: "/home/me/.li/rules.md".into(),
+            contents: "- never run sudo".to_string(),
+        }),
+        project: Some(RulesFile {
+            path: "/work/app/.li/rules.md".into(),
+            contents: "- always use podman not docker".to_string(),
+        }),
+    };
+
+    let guidance = PlannerGuidance {
+        rules,
+        ..PlannerGuidance::default()
+    };
+
+    let prompt = planner_system_prompt(&guidance);
+    assert!(prompt.starts_with(PLANNER_SYSTEM_PROMPT));
+    assert!(prompt.contains("the project rule wins"));
+
+    let user_idx = prompt.find("- never run sudo").unwrap();
+    let project_idx = prompt.find("- always use podman not docker").unwrap();
+    assert!(user_idx < project_idx);
+}
+
+#[test]
+fn rules_load_from_home_and_nearest_project() {
+    let home = TempDir::new().unwrap();
+    let workspace = TempDir::new().unwrap();
+    let nested = workspace.path().join("service/src");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::create_dir_all(home.path().join(".li")).unwrap();
+    std::fs::create_dir_all(workspace.path().join(".li")).unwrap();
+    std::fs::write(home.path().join(".li/rules.md"), "prefer fd over find\n").unwrap();
+    std::fs::write(workspace.path().join(".li/rules.md"), "  \n").unwrap();
+    std::fs::create_dir_all(workspace.path().join("service/.li")).unwrap();
+    std::fs::write(
+        workspace.path().join("service/.li/rules.md"),
+        "always use podman not docker",
+    )
+    .unwrap();
+
+    let rules = PlannerRules::load_from(Some(home.path()), &nested).unwrap();
+
+    assert_eq!(rules.user.unwrap().contents, "prefer fd over find");
+    let project = rules.project.unwrap();
+    assert_eq!(project.contents, "always use podman not docker");
+    assert_eq!(project.path, workspace.path().join("service/.li/rules.md"));
+}
+
+#[test]
+fn rules_do_not_treat_home_file_as_project_rules() {
+    let home = TempDir::new().unwrap();
+    let project_dir = home.path().join("projects/app");
+    std::fs::create_dir_all(&project_dir).unwrap();
+    std::fs::create_dir_all(home.path().join(".li")).unwrap();
+    std::fs::write(home.path().join(".li/rules.md"), "never run sudo").unwrap();
+
+    let rules = PlannerRules::load_from(Some(home.path()), &project_dir).unwrap();
+
+    assert!(rules.user.is_some());
+    assert!(rules.project.is_none());
+}
+
+#[tokio::test]
+async fn plan_includes_examples_before_request() {
+    let server = MockServer::start_async().await;
+    let system_prompt = planner_system_prompt(&PlannerGuidance::default());
+
+    let example_answer = "{\"type\":\"plan\",\"confidence\":0.9,\"dry_run_commands\":[],\"execute_commands\":[\"docker ps\"],\"notes\":\"\"}";
+
+    let _mock = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/v1/chat/completions")
+                .json_body_partial(
+                    json!({
+                        "messages": [
+                            { "role": "system", "content": system_prompt },
+                            { "role": "user", "content": "list running docker containers" },
+                            { "role": "assistant", "content": example_answer },
+                            { "role": "user", "content": "list docker containers" }
+                        ]
+                    })
+                    .to_string(),
+                );
+
+            then.status(200).json_body(json!({
+                "choices": [
+                    {
+                        "index": 0,
+                        "finish_reason": "stop",
+                        "message": {
+                            "role": "assistant",
+                            "content": "{\"type\":\"plan\",\"confidence\":0.9,\"dry_run_commands\":[],\"execute_commands\":[\"docker ps\"],\"notes\":\"\"}"
+                        }
+                    }
+                ]
+            }));
+        })
+        .await;
+
+    let mut config = sample_config();
+    config.llm.base_url = server.url("/v1");
+    let client = AIClient::new(&config.llm).unwrap();
+
+    let guidance = PlannerGuidance {
+        examples: vec![PlanExample {
+            task: "list running docker containers".to_string(),
+            plan: Plan {
+                confidence: 0.9,
+                dry_run_commands: vec![],
+                execute_commands: vec!["docker ps".to_string()],
+                notes: String::new(),
+                timeouts: Default::default(),
+            },
+        }],
+        ..PlannerGuidance::default()
+    };
+
+    let plan = plan(
+        &client,
+        "list docker containers",
+        &config.models.planner,
+        config.models.max_tokens,
+        &guidance,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(plan.execute_commands, vec!["docker ps".to_string()]);
+    _mock.assert_async().await;
+}
+
+fn script_plan() -> Plan {
+    Plan {
+        confidence: 0.9,
+        dry_run_commands: vec!["test -d data".to_string()],
+        execute_commands: vec![
+            "cd data".to_string(),
+            "name='it'\"'\"'s here'; echo \"$name\" > \"$PWD/out.txt\"".to_string(),
+        ],
+        notes: "Writes out.txt inside data.".to_string(),
+        timeouts: [("cd data".to_string(), 90)].into(),
+    }
+}
+
+fn script_header() -> ScriptHeader {
+    ScriptHeader {
+        task: "write a note into data".to_string(),
+        model: "test-model".to_string(),
+        remarks: vec!["Review: looks fine".to_string()],
+    }
+}
+
+#[test]
+fn bash_script_checks_preconditions_and_keeps_state_between_steps() {
+    let script = render_script(&script_plan(), ScriptFormat::Bash, &script_header());
+    assert!(script.starts_with(
+        "#!/usr/bin/env bash\n\
+         # Generated by li for the task:\n\
+         #   write a note into data\n\
+         # Model: test-model\n\
+         # Notes: Writes out.txt inside data.\n\
+         # Review: looks fine\n\
+         \n\
+         set -euo pipefail\n"
+    ));
+    assert!(script.contains("echo '==> [1/2] cd data'\ncd data\n"));
+
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("plan.sh"), &script).unwrap();
+    let run = || {
+        Command::new("bash")
+            .arg("plan.sh")
+            .current_dir(dir.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = run();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Precondition failed: test -d data\n"
+    );
+
+    fs::create_dir(dir.path().join("data")).unwrap();
+    let output = run();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "==> [1/2] cd data\n==> [2/2] name='it'\"'\"'s here'; echo \"$name\" > \"$PWD/out.txt\"\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("data/out.txt")).unwrap(),
+        "it's here\n"
+    );
+}
+
+#[test]
+fn bash_preconditions_fail_on_any_failing_line() {
+    let plan = Plan {
+        dry_run_commands: vec!["test -d data\necho 'data is there'".to_string()],
+        ..script_plan()
+    };
+    let script = render_script(&plan, ScriptFormat::Bash, &script_header());
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("plan.sh"), &script).unwrap();
+
+    let output = Command::new("bash")
+        .arg("plan.sh")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Precondition failed: test -d data\n"
+    );
+    assert!(!dir.path().join("data/out.txt").exists());
+}
+
+#[test]
+fn makefile_has_one_target_per_step_and_escapes_variables() {
+    let makefile = render_script(&script_plan(), ScriptFormat::Make, &script_header());
+    assert!(
+        makefile
+            .contains(".PHONY: all check check-1 step-1 step-2\n\nall: step-2\n\ncheck: check-1\n")
+    );
+    assert!(makefile.contains("\ncheck-1:\n\ttest -d data\n"));
+    assert!(makefile.contains("\nstep-1: check\n\t@echo '==> [1/2] cd data'\n\tcd data\n"));
+    assert!(makefile.contains("\nstep-2: step-1\n"));
+    assert!(makefile.contains("echo \"$$name\" > \"$$PWD/out.txt\""));
+
+    let has_make = Command::new("make").arg("--version").output().is_ok();
+    if !has_make {
+        return;
+    }
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("data")).unwrap();
+    fs::write(dir.path().join("Makefile"), &makefile).unwrap();
+    let output = Command::new("make")
+        .arg("step-1")
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "test -d data\n==> [1/2] cd data\n"
+    );
+}
+
+#[test]
+fn emitted_files_note_when_the_configured_shell_is_not_bash() {
+    assert_eq!(shell_note(ShellDialect::Bash), None);
+    assert_eq!(
+        shell_note(ShellDialect::Zsh).as_deref(),
+        Some(
+            "Note: the emitted file runs its commands with bash, so the plan is made for bash instead of the configured zsh."
+        )
+    );
+    for format in [ScriptFormat::Bash, ScriptFormat::Make, ScriptFormat::Yaml] {
+        let script = render_script(&script_plan(), format, &script_header());
+        assert!(
+            script.contains("#!/usr/bin/env bash")
+                || script.contains("SHELL := /bin/bash")
+                || script.contains("shell: bash"),
+            "{format:?} does not run with bash"
+        );
+    }
+}
+
+#[test]
+fn yaml_lists_checks_then_steps_with_their_time_limits() {
+    let yaml = render_script(&script_plan(), ScriptFormat::Yaml, &script_header());
+    let steps = yaml.split_once("\n\n").unwrap().1;
+    assert_eq!(
+        steps,
+        "steps:\n\
+         \x20 - name: \"Check: test -d data\"\n\
+         \x20   shell: bash\n\
+         \x20   run: |\n\
+         \x20     test -d data\n\
+         \x20 - name: \"Step 1: cd data\"\n\
+         \x20   shell: bash\n\
+         \x20   timeout-minutes: 2\n\
+         \x20   run: |\n\
+         \x20     cd data\n\
+         \x20 - name: \"Step 2: name='it'\\\"'\\\"'s here'; echo \\\"$name\\\" > \\\"$PWD/out.txt\\\"\"\n\
+         \x20   shell: bash\n\
+         \x20   run: |\n\
+         \x20     name='it'\"'\"'s here'; echo \"$name\" > \"$PWD/out.txt\"\n"
+    );
+}
@@ -1,11 +1,12 @@
 use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
 
 use crate::client::{ChatCompletionRequest, ChatMessage, ChatMessageRole, DynLlmClient};
 use crate::tokens::compute_completion_token_budget;
 
 use super::parsing::extract_json_object;
-use super::prompt::PLANNER_SYSTEM_PROMPT;
-use super::types::PlannerResponse;
+use super::prompt::planner_system_prompt;
+use super::types::{PlanExample, PlannerGuidance, PlannerResponse};
 
 pub(crate) async fn call_planner_with_context(
     client: &DynLlmClient,
@@ -13,12 +14,17 @@ pub(crate) async fn call_planner_with_context(
     conversation: &[(String, String)],
     model: &str,
     max_tokens: u32,
+    guidance: &PlannerGuidance,
 ) -> Result<PlannerResponse> {
     let mut messages = vec![ChatMessage {
         role: ChatMessageRole::System,
-        content: PLANNER_SYSTEM_PROMPT.to_string(),
+        content: planner_system_prompt(guidance),
     }];
 
+    for example in &guidance.examples {
+        messages.extend(example_messages(example)?);
+    }
+
     for (role, content) in conversation {
         let message_role = if role == "question" {
             ChatMessageRole::Assistant
@@ -69,3 +75,37 @@ pub(crate) async fn call_planner_with_context(
 
     Ok(response)
 }
+
+/// Planner answer in the strict output format, used to replay past plans as examples.
+#[derive(Serialize)]
+struct ExampleAnswer<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    confidence: f32,
+    dry_run_commands: &'a [String],
+    execute_commands: &'a [String],
+    notes: &'a str,
+}
+
+/// Render a past plan as a user request followed by the planner's JSON answer.
+fn example_messages(example: &PlanExample) -> Result<[ChatMessage; 2]> {
+    let answer = serde_json::to_string(&ExampleAnswer {
+        kind: "plan",
+        confidence: example.plan.confidence,
+        dry_run_commands: &example.plan.dry_run_commands,
+        execute_commands: &example.plan.execute_commands,
+        notes: &example.plan.notes,
+    })
+    .context("Failed to serialize planner example")?;
+
+    Ok([
+        ChatMessage {
+            role: ChatMessageRole::User,
+            content: example.task.clone(),
+        },
+        ChatMessage {
+            role: ChatMessageRole::Assistant,
+            content: answer,
+        },
+    ])
+}
@@ -1,12 +1,46 @@
+use std::collections::BTreeMap;
+
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::config::ShellDialect;
+
+use super::rules::PlannerRules;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Plan {
     pub confidence: f32,
     pub dry_run_commands: Vec<String>,
     pub execute_commands: Vec<String>,
     pub notes: String,
+    /// Seconds a command may run, keyed by the command line. Commands without an
+    /// entry use `execution.step_timeout_secs`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub timeouts: BTreeMap<String, u64>,
+}
+
+impl Plan {
+    /// The plan's own time limit for `command`, if it set one.
+    pub fn timeout_for(&self, command: &str) -> Option<u64> {
+        self.timeouts.get(command).copied()
+    }
+}
+
+/// A previously approved task and plan shown to the planner as a few-shot example.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanExample {
+    pub task: String,
+    pub plan: Plan,
+}
+
+/// Prompt customisations applied to every planner call in a session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlannerGuidance {
+    pub rules: PlannerRules,
+    pub examples: Vec<PlanExample>,
+    pub shell: ShellDialect,
+    /// Commands share one shell session instead of starting a new shell each.
+    pub persistent_shell: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -17,6 +51,8 @@ pub(crate) enum PlannerResponse {
         dry_run_commands: Vec<String>,
         execute_commands: Vec<String>,
         notes: String,
+        #[serde(default)]
+        timeouts: BTreeMap<String, u64>,
     },
     Question {
         text: String,
@@ -25,3 +61,18 @@ pub(crate) enum PlannerResponse {
 }
 
 pub(crate) type QuestionResolver = dyn Fn(&str, &str) -> Result<String> + Send + Sync;
+
+/// Returned when the planner asks a question but nobody is available to answer it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClarificationNeeded {
+    pub question: String,
+    pub context: String,
+}
+
+impl std::fmt::Display for ClarificationNeeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "planner needs clarification: {}", self.question)
+    }
+}
+
+impl std::error::Error for ClarificationNeeded {}
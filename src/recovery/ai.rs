@@ -1,7 +1,6 @@
 use anyhow::{Context, Result, anyhow};
-use serde_json;
 
-use crate::client::{ChatCompletionRequest, ChatMessage, ChatMessageRole, LlmClient};
+use crate::client::{ChatCompletionRequest, ChatMessage, ChatMessageRole};
 use crate::planner::Plan;
 use crate::validator::MissingCommand;
 
@@ -12,21 +11,21 @@ impl RecoveryEngine {
     /// Extract JSON content from markdown code blocks
     pub fn extract_json_from_markdown(content: &str) -> String {
         // First try to extract from ```json code blocks
-        if let Some(start) = content.find("```json") {
-            if let Some(end) = content[start + 7..].find("```") {
-                let json_start = start + 7; // "```json".len()
-                let json_end = start + 7 + end;
-                return content[json_start..json_end].trim().to_string();
-            }
+        if let Some(start) = content.find("```json")
+            && let Some(end) = content[start + 7..].find("```")
+        {
+            let json_start = start + 7; // "```json".len()
+            let json_end = start + 7 + end;
+            return content[json_start..json_end].trim().to_string();
         }
 
         // Try to extract from any ``` code blocks
-        if let Some(start) = content.find("```") {
-            if let Some(end) = content[start + 3..].find("```") {
-                let json_start = start + 3;
-                let json_end = start + 3 + end;
-                return content[json_start..json_end].trim().to_string();
-            }
+        if let Some(start) = content.find("```")
+            && let Some(end) = content[start + 3..].find("```")
+        {
+            let json_start = start + 3;
+            let json_end = start + 3 + end;
+            return content[json_start..json_end].trim().to_string();
         }
 
         // If no code blocks, return the content as-is (trimmed)
@@ -3,18 +3,22 @@
 //! This module offers AI-powered recovery suggestions when commands fail during execution,
 //! including alternative commands, installation instructions, and user interaction.
 
+use std::sync::Arc;
+
 use anyhow::Result;
 
-use crate::client::AIClient;
+use crate::client::{AIClient, DynLlmClient};
 use crate::config::Config;
 use crate::planner::Plan;
 use crate::validator::MissingCommand;
 
 // Re-export all public types
+#[allow(unused_imports)]
 pub use types::{
     CommandAlternative, InstallationInstruction, RecoveryChoice, RecoveryContext, RecoveryEngine,
     RecoveryOptions, RecoveryResult, RecoveryStrategy,
 };
+pub use ui::{print_missing_commands, prompt_recovery_strategy};
 
 // Module declarations
 mod ai;
@@ -24,13 +28,20 @@ mod utils;
 
 impl RecoveryEngine {
     /// Create a new recovery engine with the given configuration
+    #[allow(dead_code)]
     pub fn new(config: &Config) -> Result<Self> {
         let client = AIClient::new(&config.llm)?;
-        Ok(Self {
+        Ok(Self::with_client(config, Arc::new(client)))
+    }
+
+    /// Create a recovery engine that sends its requests through `client`
+    pub fn with_client(config: &Config, client: Arc<DynLlmClient>) -> Self {
+        Self {
             client,
             config: config.clone(),
             available_tools: Vec::new(),
-        })
+            confirm_installs: true,
+        }
     }
 
     /// Set the list of available tools for context
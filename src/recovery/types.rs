@@ -6,9 +6,11 @@ use crate::validator::MissingCommand;
 
 /// Main recovery engine structure
 pub struct RecoveryEngine {
-    pub client: crate::client::AIClient,
+    pub client: std::sync::Arc<crate::client::DynLlmClient>,
     pub config: crate::config::Config,
     pub available_tools: Vec<String>,
+    /// Ask on the terminal before running an installation command.
+    pub confirm_installs: bool,
 }
 
 /// Recovery options presented to the user
@@ -35,6 +37,7 @@ impl fmt::Display for CommandAlternative {
 }
 
 /// Installation instruction for missing commands
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct InstallationInstruction {
     pub command: String,
@@ -60,6 +63,7 @@ pub enum RecoveryChoice {
 }
 
 /// Recovery execution result
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum RecoveryResult {
     AlternativeSucceeded(CommandAlternative),
@@ -74,6 +78,7 @@ pub enum RecoveryResult {
 }
 
 /// Context for recovery operations
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct RecoveryContext {
     pub missing_command: MissingCommand,
@@ -99,6 +104,7 @@ pub struct AlternativeResponse {
 }
 
 /// Installation instruction from AI
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct InstallResponse {
     pub command: String,
@@ -4,7 +4,7 @@ use std::io::{self, Write};
 
 use crate::validator::MissingCommand;
 
-use super::types::{RecoveryChoice, RecoveryEngine, RecoveryOptions};
+use super::types::{RecoveryChoice, RecoveryEngine, RecoveryOptions, RecoveryStrategy};
 
 /// Present recovery options to the user and get their choice
 pub async fn present_recovery_menu(
@@ -33,7 +33,7 @@ pub async fn present_recovery_menu(
                 alt.command.green(),
                 alt.confidence
             );
-            println!("      {}", alt.to_string());
+            println!("      {}", alt);
             println!();
         }
     }
@@ -52,7 +52,7 @@ pub async fn present_recovery_menu(
                     .unwrap_or(&"unknown".to_string())
                     .dimmed()
             );
-            println!("      {}", inst.to_string());
+            println!("      {}", inst);
             println!();
         }
     }
@@ -114,8 +114,8 @@ fn parse_user_choice(
     }
 
     // Handle installation commands like "i1", "i2", etc.
-    if choice_lower.starts_with('i') {
-        let inst_num = choice_lower[1..]
+    if let Some(inst_digits) = choice_lower.strip_prefix('i') {
+        let inst_num = inst_digits
             .parse::<usize>()
             .context("Please enter a valid installation number (e.g., i1, i2)")?;
         if inst_num > 0 && inst_num <= options.installation_instructions.len() {
@@ -168,6 +168,7 @@ fn parse_user_choice(
     ))
 }
 
+#[allow(dead_code)]
 impl RecoveryEngine {
     /// Display error message for recovery failures
     pub(super) fn display_recovery_error(&self, error: &str) {
@@ -184,7 +185,7 @@ impl RecoveryEngine {
     }
 
     /// Display confirmation prompt before executing recovery action
-    pub(super) fn confirm_action(&self, action: &str) -> Result<bool> {
+    pub(super) fn confirm_action(&self, _action: &str) -> Result<bool> {
         print!("Execute this action? [y/N]: ");
         io::stdout().flush()?;
 
@@ -210,3 +211,50 @@ impl RecoveryEngine {
         println!();
     }
 }
+
+/// Print the commands the validator could not find, grouped by plan step
+pub fn print_missing_commands(missing: &[MissingCommand]) {
+    let count = missing.len();
+    println!(
+        "⚠️  Validator identified {} missing command{}:",
+        count,
+        if count == 1 { "" } else { "s" }
+    );
+    for command in missing {
+        let phase = if command.is_dry_run {
+            "dry-run"
+        } else {
+            "execute"
+        };
+        println!(
+            "   • {} (step {}: {})",
+            command.command,
+            command.plan_step + 1,
+            phase
+        );
+    }
+}
+
+/// Ask the user how to approach recovery for a missing command
+pub fn prompt_recovery_strategy() -> Result<RecoveryStrategy> {
+    println!("\nChoose a recovery approach:");
+    println!("  1) Look at alternate commands / install missing command");
+    println!("  2) Skip this missing step and continue");
+    println!("  3) Cancel recovery and exit");
+
+    loop {
+        print!("Selection [1-3]: ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let trimmed = input.trim();
+
+        match trimmed {
+            "1" => return Ok(RecoveryStrategy::InstallationFirst),
+            "2" => return Ok(RecoveryStrategy::SkipOnError),
+            "3" => return Ok(RecoveryStrategy::NeverRecover),
+            _ => println!("❌ Please enter a number between 1 and 3."),
+        }
+    }
+}
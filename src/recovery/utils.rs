@@ -157,10 +157,10 @@ pub async fn execute_installation(
         context.missing_command.command.bold()
     );
     println!("Command: {}", instruction.command.cyan());
-    println!("Description: {}", instruction.to_string());
+    println!("Description: {}", instruction);
 
     let confirmation_label = format!("install {}", instruction.command);
-    if !engine.confirm_action(&confirmation_label)? {
+    if engine.confirm_installs && !engine.confirm_action(&confirmation_label)? {
         return Ok(RecoveryResult::InstallationCancelled);
     }
 
@@ -0,0 +1,94 @@
+//! Independent review of planner output by a second model.
+//!
+//! The reviewer sees the user's goal and the proposed plan and returns structured
+//! findings about safety, whether the plan achieves the goal, and suggested edits.
+
+mod prompt;
+mod types;
+
+#[allow(unused_imports)]
+pub use types::{FindingCategory, FindingSeverity, PlanReview, ReviewFinding};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::client::{ChatCompletionRequest, ChatMessage, ChatMessageRole, DynLlmClient};
+use crate::config::ShellDialect;
+use crate::planner::{Plan, extract_json_object};
+use crate::tokens::compute_completion_token_budget;
+
+use prompt::REVIEWER_SYSTEM_PROMPT;
+
+/// Ask the reviewer model to assess `plan` against the user's `task`.
+pub async fn review(
+    client: &DynLlmClient,
+    task: &str,
+    plan: &Plan,
+    model: &str,
+    max_tokens: u32,
+    shell: ShellDialect,
+) -> Result<PlanReview> {
+    let messages = vec![
+        ChatMessage {
+            role: ChatMessageRole::System,
+            content: REVIEWER_SYSTEM_PROMPT.to_string(),
+        },
+        ChatMessage {
+            role: ChatMessageRole::User,
+            content: review_request(task, plan, shell),
+        },
+    ];
+
+    let completion_budget = compute_completion_token_budget(max_tokens, &messages);
+
+    let request = ChatCompletionRequest {
+        model: model.to_string(),
+        messages,
+        max_tokens: Some(completion_budget),
+        temperature: Some(0.0),
+    };
+
+    let response = client
+        .chat_completion(request)
+        .await
+        .context("Reviewer call failed")?;
+
+    let choice = response
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Reviewer returned no choices"))?;
+
+    let content = choice.message.content.trim();
+    let json_fragment = extract_json_object(content)
+        .ok_or_else(|| anyhow!("Reviewer response did not contain JSON object"))?;
+
+    serde_json::from_str(&json_fragment)
+        .with_context(|| format!("Failed to parse reviewer JSON: {content}"))
+}
+
+fn review_request(task: &str, plan: &Plan, shell: ShellDialect) -> String {
+    let mut request = format!("Goal: {task}\nTarget shell: {}\n", shell.display_name());
+
+    request.push_str("\nDry-run commands:\n");
+    push_commands(&mut request, &plan.dry_run_commands);
+    request.push_str("\nExecute commands:\n");
+    push_commands(&mut request, &plan.execute_commands);
+
+    if !plan.notes.trim().is_empty() {
+        request.push_str(&format!("\nPlanner notes: {}\n", plan.notes.trim()));
+    }
+
+    request
+}
+
+fn push_commands(request: &mut String, commands: &[String]) {
+    if commands.is_empty() {
+        request.push_str("  (none)\n");
+    }
+    for (idx, cmd) in commands.iter().enumerate() {
+        request.push_str(&format!("  {}. {}\n", idx + 1, cmd));
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,136 @@
+use httpmock::prelude::*;
+use serde_json::json;
+
+use super::{FindingCategory, FindingSeverity, review};
+use crate::client::AIClient;
+use crate::config::{LlmProvider, LlmSettings, ShellDialect};
+use crate::planner::Plan;
+
+fn llm_settings(base_url: String) -> LlmSettings {
+    LlmSettings {
+        provider: LlmProvider::OpenRouter,
+        api_key: "test-key".to_string(),
+        timeout_secs: 30,
+        base_url,
+        user_agent: "li/test".to_string(),
+    }
+}
+
+fn cleanup_plan() -> Plan {
+    Plan {
+        confidence: 0.7,
+        dry_run_commands: vec!["ls build".to_string()],
+        execute_commands: vec!["rm -rf build/*".to_string()],
+        notes: String::new(),
+        timeouts: Default::default(),
+    }
+}
+
+fn completion(content: &str) -> serde_json::Value {
+    json!({
+        "choices": [
+            {
+                "index": 0,
+                "finish_reason": "stop",
+                "message": { "role": "assistant", "content": content }
+            }
+        ]
+    })
+}
+
+#[tokio::test]
+async fn review_parses_findings_and_blocks_on_high_severity() {
+    let server = MockServer::start_async().await;
+    let content = "{\"achieves_goal\":true,\"summary\":\"Works but deletes broadly.\",\"findings\":[{\"severity\":\"high\",\"category\":\"safety\",\"command\":\"rm -rf build/*\",\"message\":\"Deletes everything under build without confirmation.\",\"suggested_edit\":\"rm -rf build/tmp\"},{\"severity\":\"low\",\"category\":\"correctness\",\"command\":null,\"message\":\"Dry-run only lists files.\",\"suggested_edit\":null}]}";
+
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(POST)
+                .path("/v1/chat/completions")
+                .json_body_partial(json!({ "model": "reviewer/model" }).to_string())
+                .body_contains("Goal: clean the build directory")
+                .body_contains("rm -rf build/*");
+            then.status(200).json_body(completion(content));
+        })
+        .await;
+
+    let client = AIClient::new(&llm_settings(server.url("/v1"))).unwrap();
+    let review = review(
+        &client,
+        "clean the build directory",
+        &cleanup_plan(),
+        "reviewer/model",
+        512,
+        ShellDialect::Sh,
+    )
+    .await
+    .unwrap();
+
+    mock.assert_async().await;
+    assert!(review.achieves_goal);
+    assert_eq!(review.findings.len(), 2);
+    assert_eq!(review.findings[0].severity, FindingSeverity::High);
+    assert_eq!(review.findings[0].category, FindingCategory::Safety);
+    assert_eq!(
+        review.findings[0].suggested_edit.as_deref(),
+        Some("rm -rf build/tmp")
+    );
+    assert_eq!(review.findings[1].command, None);
+    assert!(review.blocks_execution());
+}
+
+#[tokio::test]
+async fn review_without_findings_does_not_block() {
+    let server = MockServer::start_async().await;
+    let content =
+        "<think>looks fine</think>{\"achieves_goal\":true,\"summary\":\"Safe.\",\"findings\":[]}";
+
+    let _mock = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/chat/completions");
+            then.status(200).json_body(completion(content));
+        })
+        .await;
+
+    let client = AIClient::new(&llm_settings(server.url("/v1"))).unwrap();
+    let review = review(
+        &client,
+        "clean the build directory",
+        &cleanup_plan(),
+        "reviewer/model",
+        512,
+        ShellDialect::Sh,
+    )
+    .await
+    .unwrap();
+
+    assert!(review.findings.is_empty());
+    assert!(!review.blocks_execution());
+}
+
+#[tokio::test]
+async fn review_rejects_non_json_response() {
+    let server = MockServer::start_async().await;
+
+    let _mock = server
+        .mock_async(|when, then| {
+            when.method(POST).path("/v1/chat/completions");
+            then.status(200)
+                .json_body(completion("The plan looks fine to me."));
+        })
+        .await;
+
+    let client = AIClient::new(&llm_settings(server.url("/v1"))).unwrap();
+    let err = review(
+        &client,
+        "clean the build directory",
+        &cleanup_plan(),
+        "reviewer/model",
+        512,
+        ShellDialect::Sh,
+    )
+    .await
+    .unwrap_err();
+
+    assert!(err.to_string().contains("did not contain JSON"));
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How serious a reviewer finding is. High-severity findings block automatic execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for FindingSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            FindingSeverity::Low => "low",
+            FindingSeverity::Medium => "medium",
+            FindingSeverity::High => "high",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// What a reviewer finding is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingCategory {
+    Safety,
+    Goal,
+    Correctness,
+}
+
+impl fmt::Display for FindingCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            FindingCategory::Safety => "safety",
+            FindingCategory::Goal => "goal",
+            FindingCategory::Correctness => "correctness",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// A single concern raised by the reviewer, optionally with a replacement command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewFinding {
+    pub severity: FindingSeverity,
+    pub category: FindingCategory,
+    #[serde(default)]
+    pub command: Option<String>,
+    pub message: String,
+    #[serde(default)]
+    pub suggested_edit: Option<String>,
+}
+
+/// Structured verdict returned by the reviewer model for a plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanReview {
+    pub achieves_goal: bool,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub findings: Vec<ReviewFinding>,
+}
+
+impl PlanReview {
+    /// Whether any finding is severe enough to require explicit approval.
+    pub fn blocks_execution(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|finding| finding.severity == FindingSeverity::High)
+    }
+}
@@ -10,7 +10,7 @@ fn estimate_token_count(text: &str) -> u32 {
     let word_count = text.split_whitespace().count();
 
     // Use both approximations and pick the larger one to stay conservative.
-    let approx_from_chars = (char_count + 2) / 3; // ~3 chars per token, rounded up.
+    let approx_from_chars = char_count.div_ceil(3); // ~3 chars per token, rounded up.
     let approx_from_words = word_count;
 
     approx_from_chars.max(approx_from_words) as u32
@@ -0,0 +1,21 @@
+//! Snapshots of the files a plan is about to change, restored by `li undo`.
+//!
+//! Before the execute phase, each command is read for the paths it is likely to
+//! modify (operands of `rm`, `mv`, `cp`, `sed -i`, `chmod` and the like, and
+//! output redirections) and those paths are copied to `~/.li/undo/<run-id>`.
+//! Paths that did not exist yet are recorded too, so undo can remove what the
+//! plan created. Effects a file copy cannot revert, such as package installs or
+//! network requests, are listed in the snapshot instead.
+
+mod store;
+mod targets;
+
+#[allow(unused_imports)]
+pub use store::{
+    EntryState, IrreversibleStep, RestoreReport, SnapshotEntry, UndoSnapshot, UndoStore, new_run_id,
+};
+#[allow(unused_imports)]
+pub use targets::{Footprint, footprint};
+
+#[cfg(test)]
+mod tests;
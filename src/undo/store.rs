@@ -0,0 +1,409 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+use super::targets::footprint;
+
+/// How a recorded path looked before the plan ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum EntryState {
+    /// A copy is kept in the snapshot's `files` directory under this name.
+    Saved { file: String },
+    /// Nothing was there; undo removes whatever the plan created.
+    Absent,
+    /// The path existed but was not copied.
+    NotSaved { reason: String },
+}
+
+/// One path recorded before the plan ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub state: EntryState,
+}
+
+/// A plan command with effects that restoring files cannot revert.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IrreversibleStep {
+    pub command: String,
+    pub reason: String,
+}
+
+/// The files one run was about to change, as `~/.li/undo/<run-id>/snapshot.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UndoSnapshot {
+    pub id: String,
+    pub created_at: u64,
+    pub task: String,
+    pub cwd: PathBuf,
+    pub entries: Vec<SnapshotEntry>,
+    #[serde(default)]
+    pub irreversible: Vec<IrreversibleStep>,
+    /// When `li undo` last restored this snapshot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored_at: Option<u64>,
+}
+
+impl UndoSnapshot {
+    /// Whether the plan was found to change nothing worth recording.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.irreversible.is_empty()
+    }
+}
+
+/// What `li undo` did to each recorded path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Paths put back to their saved contents.
+    pub restored: Vec<PathBuf>,
+    /// Paths the plan created, now removed.
+    pub removed: Vec<PathBuf>,
+    /// Paths that could not be put back, with the reason.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Directory of undo snapshots, one subdirectory per run.
+#[derive(Debug, Clone)]
+pub struct UndoStore {
+    dir: PathBuf,
+    max_bytes: u64,
+    persistent_shell: bool,
+}
+
+impl UndoStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: u64::MAX,
+            persistent_shell: false,
+        }
+    }
+
+    /// Stop copying files once a snapshot holds this many bytes.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The commands run in one shell, so a `cd` in one moves the ones after it.
+    pub fn with_persistent_shell(mut self, persistent_shell: bool) -> Self {
+        self.persistent_shell = persistent_shell;
+        self
+    }
+
+    pub fn default_dir() -> Result<PathBuf> {
+        let mut path = home_dir().context("Could not determine home directory")?;
+        path.push(".li/undo");
+        Ok(path)
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(Self::default_dir()?))
+    }
+
+    fn run_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    /// Record what `commands` are about to change when run from `cwd`.
+    ///
+    /// In a persistent shell each command runs where the previous one left the
+    /// shell. Commands after a `cd` li cannot resolve are recorded as
+    /// irreversible, since their targets cannot be located.
+    ///
+    /// When a snapshot with this id exists, as for a resumed run, only paths it
+    /// does not cover yet are added, so it keeps the state from before the first
+    /// attempt. Empty snapshots are not written.
+    pub fn capture(
+        &self,
+        id: &str,
+        task: &str,
+        cwd: &Path,
+        commands: &[String],
+    ) -> Result<UndoSnapshot> {
+        let mut snapshot = match self.load(id) {
+            Ok(snapshot) => snapshot,
+            Err(_) => UndoSnapshot {
+                id: id.to_string(),
+                created_at: now(),
+                task: task.to_string(),
+                cwd: cwd.to_path_buf(),
+                entries: Vec::new(),
+                irreversible: Vec::new(),
+                restored_at: None,
+            },
+        };
+
+        let files = self.run_dir(id).join("files");
+        let mut budget = self
+            .max_bytes
+            .saturating_sub(dir_size(&files, self.max_bytes));
+        let mut workdir = Some(cwd.to_path_buf());
+        for command in commands {
+            let Some(dir) = &workdir else {
+                let step = IrreversibleStep {
+                    command: command.clone(),
+                    reason: "runs after a cd li cannot resolve".to_string(),
+                };
+                if !snapshot.irreversible.contains(&step) {
+                    snapshot.irreversible.push(step);
+                }
+                continue;
+            };
+            let footprint = footprint(command, dir);
+            if self.persistent_shell {
+                workdir = footprint.cwd;
+            }
+            for reason in footprint.irreversible {
+                let step = IrreversibleStep {
+                    command: command.clone(),
+                    reason,
+                };
+                if !snapshot.irreversible.contains(&step) {
+                    snapshot.irreversible.push(step);
+                }
+            }
+            for path in footprint.paths {
+                if path.starts_with(&self.dir)
+                    || snapshot.entries.iter().any(|entry| entry.path == path)
+                {
+                    continue;
+                }
+                let name = snapshot.entries.len().to_string();
+                let state = save(&path, &files.join(&name), &mut budget, self.max_bytes)
+                    .with_context(|| format!("Failed to snapshot {}", path.display()))?;
+                let state = match state {
+                    Saved::Copied => EntryState::Saved { file: name },
+                    Saved::Absent => EntryState::Absent,
+                    Saved::Skipped(reason) => EntryState::NotSaved { reason },
+                };
+                snapshot.entries.push(SnapshotEntry { path, state });
+            }
+        }
+
+        if !snapshot.is_empty() {
+            self.save(&snapshot)?;
+        }
+        Ok(snapshot)
+    }
+
+    fn save(&self, snapshot: &UndoSnapshot) -> Result<()> {
+        let dir = self.run_dir(&snapshot.id);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Unable to create undo directory {}", dir.display()))?;
+        let json = serde_json::to_vec_pretty(snapshot).context("Failed to serialize snapshot")?;
+        let path = dir.join("snapshot.json");
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, json)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn load(&self, id: &str) -> Result<UndoSnapshot> {
+        let path = self.run_dir(id).join("snapshot.json");
+        if !path.exists() {
+            bail!("No undo snapshot '{id}' in {}", self.dir.display());
+        }
+        let contents =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("Undo snapshot {} is not valid", path.display()))
+    }
+
+    /// Saved snapshots, newest first. Unreadable ones are skipped.
+    pub fn list(&self) -> Result<Vec<UndoSnapshot>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", self.dir.display()));
+            }
+        };
+
+        let mut snapshots = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| self.load(&entry.file_name().to_string_lossy()).ok())
+            .collect::<Vec<_>>();
+        snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        Ok(snapshots)
+    }
+
+    /// The newest snapshot that has not been restored yet.
+    pub fn latest(&self) -> Result<Option<UndoSnapshot>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|snapshot| snapshot.restored_at.is_none()))
+    }
+
+    /// Put every path recorded in snapshot `id` back as it was before the run.
+    pub fn restore(&self, id: &str) -> Result<RestoreReport> {
+        let mut snapshot = self.load(id)?;
+        let files = self.run_dir(id).join("files");
+        let mut report = RestoreReport::default();
+
+        // Parents before children, so a restored directory does not undo a file inside it.
+        let mut entries = snapshot.entries.clone();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        for entry in entries {
+            let result = match &entry.state {
+                EntryState::Saved { file } => {
+                    restore(&files.join(file), &entry.path).map(|()| &mut report.restored)
+                }
+                EntryState::Absent => match remove(&entry.path) {
+                    Ok(true) => Ok(&mut report.removed),
+                    Ok(false) => continue,
+                    Err(err) => Err(err),
+                },
+                EntryState::NotSaved { reason } => {
+                    report.failed.push((entry.path, reason.clone()));
+                    continue;
+                }
+            };
+            match result {
+                Ok(list) => list.push(entry.path),
+                Err(err) => report.failed.push((entry.path, err.to_string())),
+            }
+        }
+
+        snapshot.restored_at = Some(now());
+        self.save(&snapshot)?;
+        Ok(report)
+    }
+}
+
+/// A fresh run id, in the same form as session ids.
+pub fn new_run_id() -> String {
+    format!("{}-{}", now(), process::id())
+}
+
+enum Saved {
+    Copied,
+    Absent,
+    Skipped(String),
+}
+
+fn save(path: &Path, copy: &Path, budget: &mut u64, max_bytes: u64) -> Result<Saved> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Saved::Absent),
+        Err(err) => return Ok(Saved::Skipped(err.to_string())),
+    };
+    if path.starts_with("/proc") || path.starts_with("/sys") || path.starts_with("/dev") {
+        return Ok(Saved::Skipped("not a regular file".to_string()));
+    }
+
+    let size = if metadata.is_dir() {
+        // Stops early, so `rm -rf /var` is turned down without walking all of it.
+        dir_size(path, *budget)
+    } else {
+        metadata.len()
+    };
+    if size > *budget {
+        return Ok(Saved::Skipped(format!(
+            "larger than the {} MiB snapshot limit",
+            max_bytes / (1024 * 1024)
+        )));
+    }
+
+    if let Some(parent) = copy.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create {}", parent.display()))?;
+    }
+    match copy_tree(path, copy) {
+        Ok(()) => {
+            *budget -= size;
+            Ok(Saved::Copied)
+        }
+        Err(err) => {
+            // A partial copy would restore the wrong contents.
+            let _ = remove(copy);
+            Ok(Saved::Skipped(err.to_string()))
+        }
+    }
+}
+
+fn restore(copy: &Path, path: &Path) -> io::Result<()> {
+    remove(path)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    copy_tree(copy, path)
+}
+
+/// Remove whatever is at `path`; `false` when there was nothing.
+fn remove(path: &Path) -> io::Result<bool> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => Err(err),
+    };
+    result.map(|()| true)
+}
+
+/// Copy files, directories and symlinks with their permissions.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    let kind = metadata.file_type();
+    if kind.is_symlink() {
+        unix_fs::symlink(fs::read_link(from)?, to)
+    } else if kind.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        // Set last, in case the directory is read-only.
+        fs::set_permissions(to, metadata.permissions())
+    } else if kind.is_file() {
+        fs::copy(from, to).map(|_| ())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "not a regular file, directory or symlink",
+        ))
+    }
+}
+
+/// Bytes of regular files under `path`, not following symlinks. Counting stops
+/// as soon as the total exceeds `limit`, so larger trees report some size above it.
+fn dir_size(path: &Path, limit: u64) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    let mut total = 0u64;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        let size = if kind.is_dir() {
+            dir_size(&entry.path(), limit - total)
+        } else if kind.is_file() {
+            fs::metadata(entry.path()).map_or(0, |metadata| metadata.len())
+        } else {
+            0
+        };
+        total = total.saturating_add(size);
+        if total > limit {
+            break;
+        }
+    }
+    total
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
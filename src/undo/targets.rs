@@ -0,0 +1,588 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use dirs::home_dir;
+
+use crate::validator::{CommandPart, CommandValidator, PartKind};
+
+/// What one command is expected to change.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Footprint {
+    /// Absolute paths the command may create, modify or remove.
+    pub paths: Vec<PathBuf>,
+    /// Effects that a copy of those paths cannot revert, one reason each.
+    pub irreversible: Vec<String>,
+    /// Directory a shell is left in after running the command: the starting
+    /// one unless it runs `cd`, and `None` when li cannot resolve the `cd`.
+    pub cwd: Option<PathBuf>,
+    /// Programs that act through a system daemon, which they reach over a unix
+    /// socket on disk rather than the network.
+    pub daemon_clients: Vec<String>,
+}
+
+impl Footprint {
+    fn path(&mut self, path: PathBuf) {
+        if !self.paths.contains(&path) {
+            self.paths.push(path);
+        }
+    }
+
+    fn irreversible(&mut self, reason: impl Into<String>) {
+        let reason = reason.into();
+        if !self.irreversible.contains(&reason) {
+            self.irreversible.push(reason);
+        }
+    }
+}
+
+/// Programs that run the command named in their arguments.
+const WRAPPERS: [&str; 6] = ["sudo", "command", "nohup", "time", "nice", "env"];
+
+/// `sudo` options that take the next word as their value, as in `sudo -u root`.
+const SUDO_VALUE_OPTIONS: [&str; 21] = [
+    "-u",
+    "-g",
+    "-C",
+    "-D",
+    "-p",
+    "-R",
+    "-r",
+    "-T",
+    "-t",
+    "-U",
+    "--user",
+    "--group",
+    "--close-from",
+    "--chdir",
+    "--host",
+    "--prompt",
+    "--chroot",
+    "--role",
+    "--type",
+    "--command-timeout",
+    "--other-user",
+];
+
+/// Whether `option` of `wrapper` takes the next word as its value.
+fn takes_value(wrapper: &str, option: &str) -> bool {
+    match wrapper {
+        "sudo" => SUDO_VALUE_OPTIONS.contains(&option),
+        "env" => matches!(option, "-u" | "-C" | "--unset" | "--chdir"),
+        "nice" => matches!(option, "-n" | "--adjustment"),
+        "time" => matches!(option, "-f" | "-o" | "--format" | "--output"),
+        _ => false,
+    }
+}
+
+/// Programs whose file operands are removed, modified or created.
+const MUTATING: [&str; 17] = [
+    "rm", "rmdir", "unlink", "shred", "truncate", "touch", "mkdir", "tee", "mv", "cp", "ln",
+    "install", "sed", "perl", "chmod", "chown", "chgrp",
+];
+
+const PACKAGE_MANAGERS: [&str; 21] = [
+    "apt", "apt-get", "aptitude", "dnf", "yum", "zypper", "pacman", "apk", "brew", "port", "snap",
+    "flatpak", "pip", "pip3", "pipx", "npm", "pnpm", "yarn", "gem", "cargo", "conda",
+];
+
+/// Package manager subcommands that install, remove or upgrade packages.
+const PACKAGE_CHANGES: [&str; 13] = [
+    "install",
+    "uninstall",
+    "reinstall",
+    "remove",
+    "erase",
+    "purge",
+    "autoremove",
+    "add",
+    "upgrade",
+    "update",
+    "i",
+    "rm",
+    "un",
+];
+
+const NETWORK: [&str; 11] = [
+    "curl", "wget", "ssh", "scp", "sftp", "rsync", "ftp", "nc", "telnet", "http", "https",
+];
+
+const SERVICES: [&str; 8] = [
+    "systemctl",
+    "service",
+    "launchctl",
+    "kill",
+    "pkill",
+    "killall",
+    "shutdown",
+    "reboot",
+];
+
+/// Programs that hand their work to a system daemon.
+const DAEMON_CLIENTS: [&str; 7] = [
+    "systemctl",
+    "service",
+    "launchctl",
+    "shutdown",
+    "reboot",
+    "docker",
+    "podman",
+];
+
+/// Git subcommands that rewrite the working tree or history.
+const GIT_REWRITES: [&str; 10] = [
+    "checkout", "switch", "reset", "restore", "clean", "stash", "merge", "rebase", "pull", "am",
+];
+
+/// Infer the paths `command` targets when run from `cwd`.
+///
+/// Like [`CommandValidator::split_command`] this reads the command lexically:
+/// wildcards are expanded against the filesystem as it is now, while targets
+/// that depend on variables or command substitution are reported as
+/// irreversible rather than guessed.
+pub fn footprint(command: &str, cwd: &Path) -> Footprint {
+    let mut footprint = Footprint::default();
+    let mut base = cwd.to_path_buf();
+    let mut lost = false;
+    let parts = CommandValidator::split_command(command);
+    let stages = parts.last().map_or(0, |part| part.stage + 1);
+
+    for stage in 0..stages {
+        if lost {
+            // Targets of the remaining stages would be resolved against the wrong directory.
+            break;
+        }
+        let parts: Vec<&CommandPart> = parts.iter().filter(|part| part.stage == stage).collect();
+        for part in parts
+            .iter()
+            .filter(|part| part.kind == PartKind::Redirection)
+        {
+            if let Some(target) = redirection_target(&part.text) {
+                add_operand(&mut footprint, &base, target);
+            }
+        }
+
+        let mut words = parts
+            .iter()
+            .skip_while(|part| part.kind != PartKind::Program)
+            .filter(|part| {
+                matches!(
+                    part.kind,
+                    PartKind::Program | PartKind::Flag | PartKind::Argument
+                )
+            })
+            .map(|part| part.text.as_str())
+            .collect::<Vec<_>>();
+        while let Some(wrapper) = words.first().map(|word| program_name(word))
+            && WRAPPERS.contains(&wrapper)
+        {
+            words.remove(0);
+            while let Some(option) = words.first().copied()
+                && (option.starts_with('-') || option.contains('='))
+            {
+                words.remove(0);
+                if takes_value(wrapper, option) && !words.is_empty() {
+                    words.remove(0);
+                }
+            }
+        }
+        let Some((program, args)) = words.split_first() else {
+            continue;
+        };
+        let program = program_name(program);
+
+        if program == "cd" {
+            // Later stages of the same command run in the new directory.
+            let dir = args
+                .iter()
+                .find(|arg| !matches!(**arg, "-L" | "-P" | "-e" | "-@"));
+            match dir.map(|dir| Word::parse(dir)) {
+                Some(Ok(dir)) if !dir.glob && dir.text != "-" => base = resolve(&base, &dir.text),
+                Some(_) => {
+                    footprint.irreversible("changes into a directory li cannot resolve");
+                    lost = true;
+                }
+                None => base = home_dir().unwrap_or(base),
+            }
+            continue;
+        }
+        command_targets(&mut footprint, &base, program, args);
+    }
+
+    footprint.cwd = (!lost).then_some(base);
+    footprint
+}
+
+fn command_targets(footprint: &mut Footprint, base: &Path, program: &str, args: &[&str]) {
+    if DAEMON_CLIENTS.contains(&program)
+        && !footprint
+            .daemon_clients
+            .iter()
+            .any(|known| known == program)
+    {
+        footprint.daemon_clients.push(program.to_string());
+    }
+    match program {
+        "rm" | "rmdir" | "unlink" | "shred" | "tee" => {
+            for operand in operands(args, &[]) {
+                add_operand(footprint, base, operand);
+            }
+        }
+        "truncate" | "touch" | "mkdir" => {
+            for operand in operands(args, &["-s", "-r", "-d", "-t", "-m"]) {
+                add_operand(footprint, base, operand);
+            }
+        }
+        "mv" | "cp" | "ln" | "install" => copy_targets(footprint, base, program, args),
+        "sed" | "perl" => {
+            if !args.iter().any(|arg| edits_in_place(arg)) {
+                return;
+            }
+            let scripted = args.iter().any(|arg| {
+                matches!(*arg, "-e" | "-f")
+                    || arg.starts_with("--expression")
+                    || arg.starts_with("--file")
+            });
+            let operands = operands(args, &["-e", "-f", "-l"]);
+            let files = if scripted {
+                &operands[..]
+            } else {
+                operands.get(1..).unwrap_or_default()
+            };
+            for file in files {
+                add_operand(footprint, base, file);
+            }
+        }
+        "chmod" | "chown" | "chgrp" => {
+            // `chmod -x file` gives the mode in flag position.
+            let mode_as_flag = program == "chmod"
+                && args.iter().any(|arg| {
+                    arg.len() > 1
+                        && arg.starts_with('-')
+                        && arg[1..].chars().all(|c| "rwxXst".contains(c))
+                });
+            let referenced = args.iter().any(|arg| arg.starts_with("--reference"));
+            let operands = operands(args, &[]);
+            let skip = usize::from(!mode_as_flag && !referenced);
+            for file in operands.iter().skip(skip) {
+                add_operand(footprint, base, file);
+            }
+        }
+        "dd" => {
+            if let Some(output) = args.iter().find_map(|arg| arg.strip_prefix("of=")) {
+                add_operand(footprint, base, output);
+            }
+        }
+        "find"
+            if args
+                .iter()
+                .any(|arg| matches!(*arg, "-delete" | "-exec" | "-execdir")) =>
+        {
+            footprint.irreversible("changes files chosen by find");
+        }
+        "xargs" if args.iter().any(|arg| MUTATING.contains(&program_name(arg))) => {
+            footprint.irreversible("changes files named on standard input");
+        }
+        "git" => match operands(args, &["-C", "-c"]).first().copied() {
+            Some("push") => footprint.irreversible("network operation (git push)"),
+            Some(subcommand) if GIT_REWRITES.contains(&subcommand) => {
+                footprint.irreversible(format!(
+                    "rewrites the git working tree or history (git {subcommand})"
+                ));
+            }
+            _ => {}
+        },
+        "go" if matches!(operands(args, &[]).first(), Some(&("install" | "get"))) => {
+            footprint.irreversible("package operation (go)");
+        }
+        program if PACKAGE_MANAGERS.contains(&program) => {
+            let subcommand = operands(args, &[]).first().copied().unwrap_or_default();
+            let changes = match program {
+                "cargo" => matches!(subcommand, "install" | "uninstall"),
+                "pacman" => args
+                    .iter()
+                    .any(|arg| ["-S", "-R", "-U"].iter().any(|flag| arg.starts_with(flag))),
+                _ => PACKAGE_CHANGES.contains(&subcommand),
+            };
+            if changes {
+                footprint.irreversible(format!("package operation ({program})"));
+            }
+        }
+        program if NETWORK.contains(&program) => {
+            footprint.irreversible(format!("network operation ({program})"));
+            let output_flags: &[&str] = match program {
+                "curl" => &["-o", "--output"],
+                "wget" => &["-O", "--output-document"],
+                _ => &[],
+            };
+            if let Some(output) = args
+                .windows(2)
+                .find(|pair| output_flags.contains(&pair[0]))
+                .map(|pair| pair[1])
+            {
+                add_operand(footprint, base, output);
+            }
+        }
+        program if SERVICES.contains(&program) => {
+            footprint.irreversible(format!("changes running services or processes ({program})"));
+        }
+        _ => {}
+    }
+}
+
+/// Targets of `mv`, `cp`, `ln` and `install`: the destination, plus the sources for `mv`.
+fn copy_targets(footprint: &mut Footprint, base: &Path, program: &str, args: &[&str]) {
+    let target_dir = args
+        .windows(2)
+        .find(|pair| pair[0] == "-t")
+        .map(|pair| pair[1])
+        .or_else(|| {
+            args.iter()
+                .find_map(|arg| arg.strip_prefix("--target-directory="))
+        });
+    let mut operands = operands(args, &["-t", "-S", "-m", "-o", "-g"]);
+    let destination = match target_dir {
+        Some(dir) => dir,
+        None if operands.len() >= 2 => operands.pop().unwrap_or_default(),
+        None => return,
+    };
+
+    let destination = match Word::parse(destination) {
+        Ok(word) if !word.glob => resolve(base, &word.text),
+        _ => {
+            footprint.irreversible(format!("destination {destination} cannot be resolved"));
+            return;
+        }
+    };
+    let mut sources = Vec::new();
+    for operand in operands {
+        match expand(base, operand) {
+            Ok(paths) => sources.extend(paths),
+            Err(reason) => footprint.irreversible(reason),
+        }
+    }
+
+    // A destination that is already a directory receives each source under its own name.
+    if destination.is_dir() {
+        for source in &sources {
+            if let Some(name) = source.file_name() {
+                footprint.path(destination.join(name));
+            }
+        }
+    } else {
+        footprint.path(destination);
+    }
+    if program == "mv" {
+        for source in sources {
+            footprint.path(source);
+        }
+    }
+}
+
+/// Non-flag arguments, skipping the values of the flags in `with_value`.
+fn operands<'a>(args: &[&'a str], with_value: &[&str]) -> Vec<&'a str> {
+    let mut operands = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if *arg == "--" {
+            operands.extend(args.by_ref());
+            break;
+        }
+        if with_value.contains(arg) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            operands.push(*arg);
+        }
+    }
+    operands
+}
+
+fn edits_in_place(arg: &str) -> bool {
+    if arg.starts_with("--") {
+        return arg.starts_with("--in-place");
+    }
+    // `-i`, `-i.bak`, `-ni`, `-pi`: a short flag group with `i` before any suffix.
+    arg.strip_prefix('-').is_some_and(|flags| {
+        flags
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .any(|c| c == 'i')
+    })
+}
+
+fn add_operand(footprint: &mut Footprint, base: &Path, operand: &str) {
+    match expand(base, operand) {
+        Ok(paths) => {
+            for path in paths {
+                footprint.path(path);
+            }
+        }
+        Err(reason) => footprint.irreversible(reason),
+    }
+}
+
+/// The file a redirection writes to; `None` for input and descriptor duplication.
+fn redirection_target(text: &str) -> Option<&str> {
+    let rest = text.trim_start_matches(|c: char| c.is_ascii_digit());
+    let target = ["&>>", "&>", ">>", ">|", ">"]
+        .into_iter()
+        .find_map(|op| rest.strip_prefix(op))?;
+    let target = target.trim();
+    (!target.is_empty() && !target.starts_with('&') && !target.starts_with("/dev/"))
+        .then_some(target)
+}
+
+/// Base name of a program word, e.g. `rm` for `/bin/rm`.
+fn program_name(word: &str) -> &str {
+    word.rsplit('/').next().unwrap_or(word)
+}
+
+/// A shell word with its quoting removed.
+struct Word {
+    text: String,
+    /// Whether it holds unquoted wildcards.
+    glob: bool,
+}
+
+impl Word {
+    /// Fails when the word depends on expansions li does not perform.
+    fn parse(token: &str) -> Result<Self, String> {
+        let mut text = String::new();
+        let mut glob = false;
+        let mut quote: Option<char> = None;
+        let mut chars = token.chars();
+
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') => text.extend(chars.next()),
+                (Some('"'), '$' | '`') => return Err(unresolved(token)),
+                (Some(_), c) => text.push(c),
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '\\') => text.extend(chars.next()),
+                (None, '$' | '`' | '{') => return Err(unresolved(token)),
+                (None, '*' | '?' | '[') => {
+                    glob = true;
+                    text.push(c);
+                }
+                (None, c) => text.push(c),
+            }
+        }
+
+        if let Some(rest) = text.strip_prefix('~')
+            && (rest.is_empty() || rest.starts_with('/'))
+            && !token.starts_with(['\'', '"'])
+        {
+            let home = home_dir().ok_or_else(|| unresolved(token))?;
+            text = format!("{}{rest}", home.display());
+        }
+        Ok(Self { text, glob })
+    }
+}
+
+fn unresolved(token: &str) -> String {
+    format!("{token}: target depends on shell expansion")
+}
+
+/// Paths an operand names, with wildcards matched against the filesystem.
+fn expand(base: &Path, operand: &str) -> Result<Vec<PathBuf>, String> {
+    let word = Word::parse(operand)?;
+    let path = resolve(base, &word.text);
+    if !word.glob {
+        return Ok(vec![path]);
+    }
+
+    let mut matches = vec![PathBuf::new()];
+    for component in path.components() {
+        let name = component.as_os_str().to_string_lossy();
+        if !name.contains(['*', '?', '[']) {
+            for found in &mut matches {
+                found.push(component);
+            }
+            continue;
+        }
+        matches = matches
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok().map(|entries| (dir, entries)))
+            .flat_map(|(dir, entries)| {
+                let mut names = entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .filter(|entry| !entry.starts_with('.') || name.starts_with('.'))
+                    .filter(|entry| wildcard_match(&name, entry))
+                    .collect::<Vec<_>>();
+                names.sort();
+                names
+                    .into_iter()
+                    .map(|entry| dir.join(entry))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+
+    // Like the shell, a pattern without matches stands for itself.
+    if matches.is_empty() {
+        matches.push(path);
+    }
+    Ok(matches)
+}
+
+/// Match `name` against a pattern of `*`, `?` and `[...]` classes.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(end) = pattern
+                .iter()
+                .skip(2)
+                .position(|&c| c == ']')
+                .map(|end| end + 2)
+            else {
+                return name.first() == Some(&'[') && matches_from(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let class = &pattern[1..end];
+            let (negated, class) = match class.first() {
+                Some('!' | '^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut hit = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    hit |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    hit |= class[i] == c;
+                    i += 1;
+                }
+            }
+            hit != negated && matches_from(&pattern[end + 1..], &name[1..])
+        }
+        Some(&literal) => name.first() == Some(&literal) && matches_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// `path` relative to `base`, with `.` and `..` removed lexically.
+fn resolve(base: &Path, path: &str) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
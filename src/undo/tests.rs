@@ -0,0 +1,243 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use tempfile::TempDir;
+
+use super::{EntryState, UndoStore, footprint};
+
+fn run(dir: &Path, command: &str) {
+    let status = Command::new("sh")
+        .args(["-c", command])
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{command} failed");
+}
+
+fn commands(commands: &[&str]) -> Vec<String> {
+    commands.iter().map(|cmd| cmd.to_string()).collect()
+}
+
+#[test]
+fn footprint_finds_the_paths_mutating_commands_target() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    fs::create_dir(root.join("archive")).unwrap();
+    fs::write(root.join("a.log"), "").unwrap();
+    fs::write(root.join("b.log"), "").unwrap();
+
+    let paths = |command: &str| footprint(command, root).paths;
+
+    assert_eq!(
+        paths("rm -f *.log"),
+        [root.join("a.log"), root.join("b.log")]
+    );
+    assert_eq!(
+        paths("mv a.log archive/"),
+        [root.join("archive/a.log"), root.join("a.log")]
+    );
+    assert_eq!(paths("cp a.log copy.log"), [root.join("copy.log")]);
+    assert_eq!(
+        paths("sed -i.bak 's/x/y/' 'my notes.txt'"),
+        [root.join("my notes.txt")]
+    );
+    assert_eq!(paths("chmod -R 755 archive"), [root.join("archive")]);
+    assert_eq!(
+        paths("sort a.log 2>&1 > sorted.txt | tee -a ../out.txt"),
+        [
+            root.join("sorted.txt"),
+            dir.path().parent().unwrap().join("out.txt")
+        ]
+    );
+    assert_eq!(paths("cd archive && rm old"), [root.join("archive/old")]);
+    assert_eq!(
+        paths("sudo rm -rf /tmp/../srv/cache"),
+        [Path::new("/srv/cache")]
+    );
+    assert_eq!(paths("sudo -u root rm -rf old"), [root.join("old")]);
+    assert_eq!(
+        paths("sudo -g staff env -u HOME LANG=C nice -n 10 rm a.log"),
+        [root.join("a.log")]
+    );
+    assert_eq!(
+        paths("time -o timing.txt sudo --user deploy touch stamp"),
+        [root.join("stamp")]
+    );
+
+    assert!(paths("sed 's/x/y/' a.log > /dev/null").is_empty());
+    assert!(paths("ls -la | grep log").is_empty());
+}
+
+#[test]
+fn footprint_lists_effects_a_snapshot_cannot_revert() {
+    let dir = TempDir::new().unwrap();
+    let irreversible = |command: &str| footprint(command, dir.path()).irreversible;
+
+    assert_eq!(
+        irreversible("sudo apt-get install -y ripgrep"),
+        ["package operation (apt-get)"]
+    );
+    assert_eq!(
+        irreversible("curl -fsSL https://example.com -o page.html"),
+        ["network operation (curl)"]
+    );
+    assert_eq!(
+        irreversible("git push origin main"),
+        ["network operation (git push)"]
+    );
+    assert_eq!(
+        irreversible("rm -rf \"$BUILD_DIR\""),
+        ["\"$BUILD_DIR\": target depends on shell expansion"]
+    );
+    assert!(irreversible("npm test").is_empty());
+    assert!(irreversible("git status").is_empty());
+}
+
+#[test]
+fn restore_puts_changed_files_back_and_removes_created_ones() {
+    let home = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let root = work.path();
+    fs::write(root.join("config.ini"), "port=80\n").unwrap();
+    fs::write(root.join("run.sh"), "echo hi\n").unwrap();
+    fs::create_dir(root.join("data")).unwrap();
+    fs::write(root.join("data/rows.csv"), "1,2\n").unwrap();
+
+    let plan = commands(&[
+        "sed -i 's/80/8080/' config.ini",
+        "chmod +x run.sh",
+        "rm -r data",
+        "echo done > result.txt",
+        "pip install requests",
+    ]);
+    let store = UndoStore::new(home.path());
+    let snapshot = store.capture("run-1", "reconfigure", root, &plan).unwrap();
+    assert_eq!(snapshot.entries.len(), 4);
+    assert_eq!(snapshot.irreversible.len(), 1);
+    assert_eq!(snapshot.irreversible[0].command, "pip install requests");
+
+    for command in &plan[..4] {
+        run(root, command);
+    }
+    let report = store.restore("run-1").unwrap();
+
+    assert_eq!(report.removed, [root.join("result.txt")]);
+    assert_eq!(report.restored.len(), 3);
+    assert!(report.failed.is_empty());
+    assert_eq!(
+        fs::read_to_string(root.join("config.ini")).unwrap(),
+        "port=80\n"
+    );
+    let mode = fs::metadata(root.join("run.sh"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o111, 0);
+    assert_eq!(
+        fs::read_to_string(root.join("data/rows.csv")).unwrap(),
+        "1,2\n"
+    );
+    assert!(!root.join("result.txt").exists());
+    assert!(store.load("run-1").unwrap().restored_at.is_some());
+    assert!(store.latest().unwrap().is_none());
+}
+
+#[test]
+fn capture_keeps_the_earliest_state_and_respects_the_size_limit() {
+    let home = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let root = work.path();
+    fs::write(root.join("notes.txt"), "first\n").unwrap();
+    fs::write(root.join("big.bin"), vec![0u8; 4096]).unwrap();
+
+    let store = UndoStore::new(home.path()).with_max_bytes(1024);
+    store
+        .capture("run-2", "edit", root, &commands(&["rm notes.txt big.bin"]))
+        .unwrap();
+    fs::write(root.join("notes.txt"), "second\n").unwrap();
+
+    // A resumed run adds new paths but keeps what it recorded first.
+    let snapshot = store
+        .capture(
+            "run-2",
+            "edit",
+            root,
+            &commands(&["rm notes.txt other.txt"]),
+        )
+        .unwrap();
+    let states = snapshot
+        .entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.path.file_name().unwrap().to_str().unwrap(),
+                &entry.state,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(matches!(states[0], ("notes.txt", EntryState::Saved { .. })));
+    assert!(matches!(
+        states[1],
+        ("big.bin", EntryState::NotSaved { .. })
+    ));
+    assert!(matches!(states[2], ("other.txt", EntryState::Absent)));
+
+    // A tree over the limit is turned down without being copied.
+    fs::create_dir_all(root.join("cache/deep")).unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(root.join("cache/deep").join(name), vec![0u8; 512]).unwrap();
+    }
+    let snapshot = store
+        .capture("run-2", "edit", root, &commands(&["rm -rf cache"]))
+        .unwrap();
+    assert!(matches!(
+        snapshot.entries.last().unwrap().state,
+        EntryState::NotSaved { .. }
+    ));
+
+    store.restore("run-2").unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        "first\n"
+    );
+}
+
+#[test]
+fn capture_follows_cd_across_commands_in_a_persistent_shell() {
+    let home = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let root = work.path();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("file"), "top\n").unwrap();
+    fs::write(root.join("sub/file"), "nested\n").unwrap();
+    let plan = commands(&["cd sub", "rm file"]);
+
+    let store = UndoStore::new(home.path()).with_persistent_shell(true);
+    let snapshot = store.capture("run-3", "clean", root, &plan).unwrap();
+    let paths = snapshot
+        .entries
+        .iter()
+        .map(|entry| entry.path.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, [root.join("sub/file")]);
+
+    // Without a shared shell, each command starts where li was started.
+    let snapshot = UndoStore::new(home.path())
+        .capture("run-4", "clean", root, &plan)
+        .unwrap();
+    assert_eq!(snapshot.entries[0].path, root.join("file"));
+
+    let snapshot = store
+        .capture(
+            "run-5",
+            "clean",
+            root,
+            &commands(&["cd \"$BUILD_DIR\"", "rm file"]),
+        )
+        .unwrap();
+    assert!(snapshot.entries.is_empty());
+    assert_eq!(snapshot.irreversible.len(), 2);
+    assert_eq!(snapshot.irreversible[1].command, "rm file");
+}
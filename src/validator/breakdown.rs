@@ -0,0 +1,174 @@
+use std::fmt;
+
+use super::checker::is_assignment;
+
+/// Role of a single piece of a command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartKind {
+    Program,
+    Flag,
+    Argument,
+    Redirection,
+    Operator,
+}
+
+impl fmt::Display for PartKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            PartKind::Program => "program",
+            PartKind::Flag => "flag",
+            PartKind::Argument => "argument",
+            PartKind::Redirection => "redirect",
+            PartKind::Operator => "operator",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// A piece of a command line together with the pipeline stage it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPart {
+    pub kind: PartKind,
+    pub text: String,
+    pub stage: usize,
+}
+
+const OPERATORS: [&str; 5] = ["&&", "||", "|", ";", "&"];
+
+/// Split a command line into pipeline stages, operators, redirections, flags and arguments.
+///
+/// This is a lexical split for display purposes; it does not expand variables or globs.
+pub(super) fn split_command(cmd: &str) -> Vec<CommandPart> {
+    let mut parts = Vec::new();
+    let mut stage = 0;
+    let mut expect_program = true;
+    let mut tokens = tokenize(cmd).into_iter();
+
+    while let Some(token) = tokens.next() {
+        if OPERATORS.contains(&token.as_str()) {
+            parts.push(CommandPart {
+                kind: PartKind::Operator,
+                text: token,
+                stage,
+            });
+            stage += 1;
+            expect_program = true;
+            continue;
+        }
+
+        if let Some(operator_len) = redirection_len(&token) {
+            let text = if operator_len == token.len() && !token.ends_with('&') {
+                match tokens.next() {
+                    Some(target) => format!("{token} {target}"),
+                    None => token,
+                }
+            } else {
+                token
+            };
+            parts.push(CommandPart {
+                kind: PartKind::Redirection,
+                text,
+                stage,
+            });
+            continue;
+        }
+
+        let kind = if expect_program && !is_assignment(&token) {
+            expect_program = false;
+            PartKind::Program
+        } else if expect_program {
+            PartKind::Argument
+        } else if token.starts_with('-') && token.len() > 1 {
+            PartKind::Flag
+        } else {
+            PartKind::Argument
+        };
+
+        parts.push(CommandPart {
+            kind,
+            text: token,
+            stage,
+        });
+    }
+
+    parts
+}
+
+/// Split on whitespace and shell operators while keeping quoted text intact.
+fn tokenize(cmd: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = cmd.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            current.push(c);
+            if c == q {
+                quote = None;
+            } else if c == '\\'
+                && q == '"'
+                && let Some(next) = chars.next()
+            {
+                current.push(next);
+            }
+            continue;
+        }
+
+        match c {
+            '\'' | '"' => {
+                quote = Some(c);
+                current.push(c);
+            }
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            // A line break separates commands just like `;`.
+            '\n' => {
+                flush(&mut tokens, &mut current);
+                tokens.push(";".to_string());
+            }
+            c if c.is_whitespace() => flush(&mut tokens, &mut current),
+            '|' | '&' | ';' => {
+                // `2>&1` and `&>` belong to a redirection, not an operator.
+                if c == '&' && (current.ends_with('>') || chars.peek() == Some(&'>')) {
+                    current.push(c);
+                    continue;
+                }
+                flush(&mut tokens, &mut current);
+                let mut operator = c.to_string();
+                if c != ';' && chars.peek() == Some(&c) {
+                    operator.push(c);
+                    chars.next();
+                }
+                tokens.push(operator);
+            }
+            _ => current.push(c),
+        }
+    }
+
+    flush(&mut tokens, &mut current);
+    tokens
+}
+
+fn flush(tokens: &mut Vec<String>, current: &mut String) {
+    if !current.is_empty() {
+        tokens.push(std::mem::take(current));
+    }
+}
+
+/// Length of the redirection operator at the start of `token`, if it is one.
+fn redirection_len(token: &str) -> Option<usize> {
+    let digits = token.chars().take_while(|c| c.is_ascii_digit()).count();
+    let rest = &token[digits..];
+    let operator = ["&>>", "&>", ">>", ">&", "<<<", "<<", ">", "<"]
+        .into_iter()
+        .find(|op| rest.starts_with(op))?;
+    if digits > 0 && operator.starts_with('&') {
+        return None;
+    }
+    Some(digits + operator.len())
+}
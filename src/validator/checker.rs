@@ -28,7 +28,7 @@ impl CommandValidator {
         // For regular commands, use 'which' or 'command -v'
         let result = TokioCommand::new("sh")
             .arg("-c")
-            .arg(&format!("command -v {}", cmd))
+            .arg(format!("command -v {}", cmd))
             .output()
             .await;
 
@@ -38,7 +38,7 @@ impl CommandValidator {
                 // Fallback: try to get command help/version
                 let fallback_result = TokioCommand::new("sh")
                     .arg("-c")
-                    .arg(&format!("{} --version >/dev/null 2>&1", cmd))
+                    .arg(format!("{} --version >/dev/null 2>&1", cmd))
                     .output()
                     .await;
 
@@ -205,3 +205,15 @@ pub(super) fn extract_command(cmd: &str) -> Option<String> {
 
     Some(cleaned_cmd.to_string())
 }
+
+/// Whether a word is a `NAME=value` variable assignment
+pub(crate) fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
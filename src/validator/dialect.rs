@@ -0,0 +1,138 @@
+use tokio::process::Command as TokioCommand;
+
+use crate::config::ShellDialect;
+
+use super::checker::is_assignment;
+
+/// Constructs that the target shell cannot parse, detected without running the command.
+pub(super) fn dialect_issues(cmd: &str, shell: ShellDialect) -> Vec<String> {
+    let code = strip_quoted(cmd);
+    let words: Vec<&str> = code
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut issues = Vec::new();
+
+    match shell {
+        ShellDialect::Sh => {
+            if code.contains("[[") {
+                issues.push("`[[ ]]` is not POSIX sh; use `[ ]` or `test`".to_string());
+            }
+            if code.contains("<<<") {
+                issues.push("here-strings (`<<<`) are not POSIX sh".to_string());
+            }
+            if code.contains("&>") {
+                issues.push("`&>` is not POSIX sh; use `>file 2>&1`".to_string());
+            }
+            if words.contains(&"function") {
+                issues.push(
+                    "the `function` keyword is not POSIX sh; use `name() { ... }`".to_string(),
+                );
+            }
+            if words.first() == Some(&"source") {
+                issues.push("`source` is not POSIX sh; use `.`".to_string());
+            }
+            if code.contains("=(") {
+                issues.push("arrays are not supported in POSIX sh".to_string());
+            }
+        }
+        ShellDialect::Fish => {
+            if code.contains("$((") {
+                issues.push("fish has no `$(( ))` arithmetic; use `math`".to_string());
+            }
+            if code.contains('`') {
+                issues.push("fish does not support backtick command substitution".to_string());
+            }
+            if code.contains("[[") {
+                issues.push("fish has no `[[ ]]`; use `test`".to_string());
+            }
+            if code.contains("${") {
+                issues.push(
+                    "fish does not support `${VAR}` expansion; use `$VAR` or `{$VAR}`".to_string(),
+                );
+            }
+            if code.contains("<<") && !code.contains("<<<") {
+                issues.push("fish does not support heredocs".to_string());
+            }
+            if words
+                .iter()
+                .any(|word| matches!(*word, "then" | "fi" | "do" | "done" | "esac"))
+            {
+                issues.push(
+                    "fish blocks end with `end`, not `then`/`fi`/`do`/`done`/`esac`".to_string(),
+                );
+            }
+            if words.first().is_some_and(|word| is_assignment(word)) {
+                issues
+                    .push("fish sets variables with `set VAR value`, not `VAR=value`".to_string());
+            }
+        }
+        ShellDialect::Bash | ShellDialect::Zsh => {}
+    }
+
+    issues
+}
+
+/// Ask the target shell to parse the command without executing it.
+///
+/// Returns `None` when the command parses or when the shell is not installed.
+pub(super) async fn parse_error(cmd: &str, shell: ShellDialect) -> Option<String> {
+    let output = TokioCommand::new(shell.program())
+        .arg("-n")
+        .arg("-c")
+        .arg(cmd)
+        .output()
+        .await
+        .ok()?;
+
+    if output.status.success() {
+        return None;
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("syntax error");
+    Some(format!(
+        "{} cannot parse this command: {}",
+        shell.display_name(),
+        message
+    ))
+}
+
+/// Blank out quoted text so that literal strings are not mistaken for syntax.
+fn strip_quoted(cmd: &str) -> String {
+    let mut result = String::with_capacity(cmd.len());
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in cmd.chars() {
+        if escaped {
+            escaped = false;
+            result.push(' ');
+            continue;
+        }
+        match quote {
+            Some(q) if c == q => {
+                quote = None;
+                result.push(c);
+            }
+            Some(_) => result.push(' '),
+            None => {
+                if c == '\\' {
+                    escaped = true;
+                    result.push(' ');
+                } else {
+                    if c == '\'' || c == '"' {
+                        quote = Some(c);
+                    }
+                    result.push(c);
+                }
+            }
+        }
+    }
+
+    result
+}
@@ -1,17 +1,23 @@
 //! Validator module providing command validation functionality
 //!
 //! This module checks whether commands in execution plans are available on the system
-//! before attempting to run them, preventing execution failures due to missing tools.
+//! and valid syntax for the target shell before attempting to run them, preventing
+//! execution failures due to missing tools or dialect mismatches.
 
 use anyhow::{Result, anyhow};
 
+use crate::config::ShellDialect;
 use crate::planner::Plan;
 
 // Re-export all public types
-pub use types::{CommandValidator, MissingCommand, ValidationResult};
+pub use breakdown::{CommandPart, PartKind};
+pub(crate) use checker::is_assignment;
+pub use types::{CommandValidator, MissingCommand, SyntaxIssue, ValidationResult};
 
 // Module declarations
+mod breakdown;
 mod checker;
+mod dialect;
 mod types;
 
 impl CommandValidator {
@@ -20,11 +26,22 @@ impl CommandValidator {
         Default::default()
     }
 
+    /// Validate commands against the given target shell
+    pub fn with_shell(mut self, shell: ShellDialect) -> Self {
+        self.shell = shell;
+        self
+    }
+
     /// Extract the primary command from a complex command line
     pub fn extract_command(cmd: &str) -> Option<String> {
         checker::extract_command(cmd)
     }
 
+    /// Split a command line into its pipeline stages, operators, redirections and flags
+    pub fn split_command(cmd: &str) -> Vec<CommandPart> {
+        breakdown::split_command(cmd)
+    }
+
     /// Check if a command exists in the system PATH
     pub async fn command_exists(&mut self, cmd: &str) -> bool {
         // Check cache first
@@ -43,45 +60,78 @@ impl CommandValidator {
     /// Validate all commands in a plan
     pub async fn validate_plan(&mut self, plan: &Plan) -> Result<ValidationResult> {
         let mut missing_commands = Vec::new();
+        let mut syntax_issues = Vec::new();
 
         // Check dry-run commands
         for (idx, cmd) in plan.dry_run_commands.iter().enumerate() {
-            if let Some(command_name) = Self::extract_command(cmd) {
-                if !self.command_exists(&command_name).await {
-                    missing_commands.push(MissingCommand {
-                        command: command_name,
-                        failed_command_line: cmd.clone(),
-                        plan_step: idx,
-                        is_dry_run: true,
-                    });
-                }
+            if let Some(message) = self.check_syntax(cmd).await {
+                syntax_issues.push(SyntaxIssue {
+                    command_line: cmd.clone(),
+                    plan_step: idx,
+                    is_dry_run: true,
+                    message,
+                });
+            }
+            if let Some(command_name) = Self::extract_command(cmd)
+                && !self.command_exists(&command_name).await
+            {
+                missing_commands.push(MissingCommand {
+                    command: command_name,
+                    failed_command_line: cmd.clone(),
+                    plan_step: idx,
+                    is_dry_run: true,
+                });
             }
         }
 
         // Check execute commands
         for (idx, cmd) in plan.execute_commands.iter().enumerate() {
-            if let Some(command_name) = Self::extract_command(cmd) {
-                if !self.command_exists(&command_name).await {
-                    missing_commands.push(MissingCommand {
-                        command: command_name,
-                        failed_command_line: cmd.clone(),
-                        plan_step: idx,
-                        is_dry_run: false,
-                    });
-                }
+            if let Some(message) = self.check_syntax(cmd).await {
+                syntax_issues.push(SyntaxIssue {
+                    command_line: cmd.clone(),
+                    plan_step: idx,
+                    is_dry_run: false,
+                    message,
+                });
+            }
+            if let Some(command_name) = Self::extract_command(cmd)
+                && !self.command_exists(&command_name).await
+            {
+                missing_commands.push(MissingCommand {
+                    command: command_name,
+                    failed_command_line: cmd.clone(),
+                    plan_step: idx,
+                    is_dry_run: false,
+                });
             }
         }
 
-        let plan_can_continue =
-            missing_commands.is_empty() || missing_commands.iter().all(|cmd| cmd.is_dry_run);
+        let plan_can_continue = syntax_issues.is_empty()
+            && (missing_commands.is_empty() || missing_commands.iter().all(|cmd| cmd.is_dry_run));
 
         Ok(ValidationResult {
             missing_commands,
+            syntax_issues,
             plan_can_continue,
         })
     }
 
+    /// Check that a command line is valid syntax for the target shell
+    pub async fn check_syntax(&self, cmd_line: &str) -> Option<String> {
+        let issues = dialect::dialect_issues(cmd_line, self.shell);
+        if !issues.is_empty() {
+            return Some(format!(
+                "Not valid {} syntax: {}",
+                self.shell.display_name(),
+                issues.join("; ")
+            ));
+        }
+
+        dialect::parse_error(cmd_line, self.shell).await
+    }
+
     /// Check a single command for existence
+    #[allow(dead_code)]
     pub async fn check_single_command(&mut self, cmd_line: &str) -> Result<bool> {
         let command_name = Self::extract_command(cmd_line)
             .ok_or_else(|| anyhow!("Could not extract command from: {}", cmd_line))?;
@@ -106,11 +156,13 @@ impl CommandValidator {
     }
 
     /// Clear the validation cache
+    #[allow(dead_code)]
     pub fn clear_cache(&mut self) {
         self.cache.clear();
     }
 
     /// Get cache statistics
+    #[allow(dead_code)]
     pub fn cache_stats(&self) -> (usize, usize) {
         let total = self.cache.len();
         let found = self.cache.values().filter(|&&exists| exists).count();
@@ -1,7 +1,9 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
+    use crate::config::ShellDialect;
     use crate::planner;
-    use crate::validator::{CommandValidator, MissingCommand, ValidationResult};
+    use crate::validator::{CommandValidator, MissingCommand, PartKind, ValidationResult};
 
     #[test]
     fn test_extract_command() {
@@ -142,6 +144,7 @@ mod tests {
             execute_commands: vec!["git status".to_string(), "anotherfakecmd".to_string()],
             confidence: 0.8,
             notes: "Test plan".to_string(),
+            timeouts: Default::default(),
         };
 
         let result = validator.validate_plan(&plan).await.unwrap();
@@ -191,6 +194,7 @@ mod tests {
 
         let result = ValidationResult {
             missing_commands: vec![missing_cmd],
+            syntax_issues: Vec::new(),
             plan_can_continue: false,
         };
 
@@ -285,4 +289,138 @@ mod tests {
         // Test commands with variables
         // Note: should_validate_command method doesn't exist
     }
+
+    #[tokio::test]
+    async fn test_sh_rejects_bashisms() {
+        let validator = CommandValidator::new().with_shell(ShellDialect::Sh);
+
+        assert!(
+            validator
+                .check_syntax("[[ -f Cargo.toml ]] && echo yes")
+                .await
+                .is_some()
+        );
+        assert!(validator.check_syntax("cat <<< 'hello'").await.is_some());
+        assert!(
+            validator
+                .check_syntax("[ -f Cargo.toml ] && echo yes")
+                .await
+                .is_none()
+        );
+        // Quoted text is not treated as syntax
+        assert!(
+            validator
+                .check_syntax("echo '[[ not a test ]]'")
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fish_rejects_posix_constructs() {
+        let validator = CommandValidator::new().with_shell(ShellDialect::Fish);
+
+        let issue = validator
+            .check_syntax("for f in *.log; do gzip \"$f\"; done")
+            .await
+            .expect("posix loop should be rejected");
+        assert!(issue.contains("fish"));
+        assert!(validator.check_syntax("FOO=bar make build").await.is_some());
+        assert!(validator.check_syntax("echo $((1 + 2))").await.is_some());
+        assert!(validator.check_syntax("echo `date`").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_validate_plan_blocks_on_syntax_issues() {
+        let mut validator = CommandValidator::new().with_shell(ShellDialect::Sh);
+
+        let plan = planner::Plan {
+            dry_run_commands: vec!["ls -la".to_string()],
+            execute_commands: vec!["source ./env.sh".to_string()],
+            confidence: 0.8,
+            notes: String::new(),
+            timeouts: Default::default(),
+        };
+
+        let result = validator.validate_plan(&plan).await.unwrap();
+
+        assert_eq!(result.syntax_issues.len(), 1);
+        assert_eq!(result.syntax_issues[0].plan_step, 0);
+        assert!(!result.syntax_issues[0].is_dry_run);
+        assert!(!result.plan_can_continue);
+    }
+
+    #[test]
+    fn test_split_command_pipeline() {
+        let parts = CommandValidator::split_command(
+            "find . -name '*.log' -mtime +7 2>/dev/null | xargs rm -f > removed.txt",
+        );
+
+        let summary: Vec<(PartKind, &str, usize)> = parts
+            .iter()
+            .map(|part| (part.kind, part.text.as_str(), part.stage))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (PartKind::Program, "find", 0),
+                (PartKind::Argument, ".", 0),
+                (PartKind::Flag, "-name", 0),
+                (PartKind::Argument, "'*.log'", 0),
+                (PartKind::Flag, "-mtime", 0),
+                (PartKind::Argument, "+7", 0),
+                (PartKind::Redirection, "2>/dev/null", 0),
+                (PartKind::Operator, "|", 0),
+                (PartKind::Program, "xargs", 1),
+                (PartKind::Argument, "rm", 1),
+                (PartKind::Flag, "-f", 1),
+                (PartKind::Redirection, "> removed.txt", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_command_operators_and_quotes() {
+        let parts =
+            CommandValidator::split_command("FOO=1 make build 2>&1 && echo \"done | ok\";ls");
+
+        let kinds: Vec<PartKind> = parts.iter().map(|part| part.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                PartKind::Argument,
+                PartKind::Program,
+                PartKind::Argument,
+                PartKind::Redirection,
+                PartKind::Operator,
+                PartKind::Program,
+                PartKind::Argument,
+                PartKind::Operator,
+                PartKind::Program,
+            ]
+        );
+        assert_eq!(parts[6].text, "\"done | ok\"");
+        assert_eq!(parts[8].stage, 2);
+    }
+
+    #[test]
+    fn test_split_command_treats_line_breaks_as_separators() {
+        let parts = CommandValidator::split_command("ls\nrm -rf ~ 'a\nb'");
+
+        let summary: Vec<(PartKind, &str, usize)> = parts
+            .iter()
+            .map(|part| (part.kind, part.text.as_str(), part.stage))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (PartKind::Program, "ls", 0),
+                (PartKind::Operator, ";", 0),
+                (PartKind::Program, "rm", 1),
+                (PartKind::Flag, "-rf", 1),
+                (PartKind::Argument, "~", 1),
+                (PartKind::Argument, "'a\nb'", 1),
+            ]
+        );
+    }
 }
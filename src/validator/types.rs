@@ -1,19 +1,26 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
+use crate::config::ShellDialect;
+
 /// Main command validator structure
+#[derive(Default)]
 pub struct CommandValidator {
     pub cache: HashMap<String, bool>,
+    pub shell: ShellDialect,
 }
 
 /// Result of command validation
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub missing_commands: Vec<MissingCommand>,
+    pub syntax_issues: Vec<SyntaxIssue>,
     pub plan_can_continue: bool,
 }
 
 /// Information about a missing command
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MissingCommand {
     pub command: String,
     pub failed_command_line: String,
@@ -21,10 +28,11 @@ pub struct MissingCommand {
     pub is_dry_run: bool,
 }
 
-impl Default for CommandValidator {
-    fn default() -> Self {
-        Self {
-            cache: HashMap::new(),
-        }
-    }
+/// A command that is not valid syntax for the target shell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyntaxIssue {
+    pub command_line: String,
+    pub plan_step: usize,
+    pub is_dry_run: bool,
+    pub message: String,
 }